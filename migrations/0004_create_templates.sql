@@ -0,0 +1,10 @@
+-- migrations/0004_create_templates.sql
+
+CREATE TABLE IF NOT EXISTS templates (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,       -- "invoice", "receipt", "statement", ...
+    description TEXT,
+    content TEXT NOT NULL,           -- HTML con sintaxis Handlebars
+    created_at TEXT NOT NULL,        -- ISO timestamp
+    updated_at TEXT NOT NULL         -- ISO timestamp
+);
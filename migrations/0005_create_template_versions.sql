@@ -0,0 +1,19 @@
+-- migrations/0005_create_template_versions.sql
+
+-- Cada cambio de contenido crea una versión inmutable
+CREATE TABLE IF NOT EXISTS template_versions (
+    id TEXT PRIMARY KEY,
+    template_id TEXT NOT NULL,
+    version INTEGER NOT NULL,        -- 1, 2, 3, ...
+    content TEXT NOT NULL,           -- HTML Handlebars de esta versión
+    created_at TEXT NOT NULL,
+    UNIQUE (template_id, version),
+    FOREIGN KEY (template_id) REFERENCES templates (id) ON DELETE CASCADE
+);
+
+ALTER TABLE templates ADD COLUMN current_version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE templates ADD COLUMN sample_data TEXT; -- JSON de ejemplo para /preview
+
+-- Las plantillas existentes quedan como versión 1
+INSERT INTO template_versions (id, template_id, version, content, created_at)
+SELECT lower(hex(randomblob(16))), id, 1, content, created_at FROM templates;
@@ -0,0 +1,17 @@
+-- migrations/0006_create_batch_items.sql
+
+-- Cada documento de un lote (operación "batch_pdf") con su propio estado
+CREATE TABLE IF NOT EXISTS batch_items (
+    id TEXT PRIMARY KEY,
+    operation_id TEXT NOT NULL,
+    item_index INTEGER NOT NULL,     -- posición en el request (0, 1, 2, ...)
+    file_name TEXT NOT NULL,
+    status TEXT NOT NULL,            -- "pending", "running", "done", "failed"
+    error_message TEXT,
+    file_path TEXT,                  -- PDF generado en disco
+    size_bytes INTEGER,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    UNIQUE (operation_id, item_index),
+    FOREIGN KEY (operation_id) REFERENCES operations (id) ON DELETE CASCADE
+);
@@ -0,0 +1,16 @@
+-- migrations/0007_create_signing_profiles.sql
+
+-- Certificados (PKCS#12) con los que el servidor firma PDFs.
+-- Se suben una vez y los requests los referencian por id o nombre.
+CREATE TABLE IF NOT EXISTS signing_profiles (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    pkcs12 BLOB NOT NULL,            -- archivo .p12/.pfx tal como se subió
+    password TEXT NOT NULL,          -- contraseña del PKCS#12 (nunca se expone por la API)
+    subject TEXT NOT NULL,           -- sujeto del certificado firmante
+    issuer TEXT NOT NULL,
+    serial_number TEXT NOT NULL,
+    not_before TEXT NOT NULL,        -- ISO timestamp
+    not_after TEXT NOT NULL,         -- ISO timestamp
+    created_at TEXT NOT NULL         -- ISO timestamp
+);
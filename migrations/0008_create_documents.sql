@@ -0,0 +1,19 @@
+-- migrations/0008_create_documents.sql
+
+-- PDFs (o imágenes) guardados por el servicio, ligados a la operación que los generó.
+-- El archivo vive en ./files/pdfs/<storage_key>; la fila guarda sus datos y la expiración.
+CREATE TABLE IF NOT EXISTS documents (
+    id TEXT PRIMARY KEY,
+    operation_id TEXT NOT NULL,
+    file_name TEXT NOT NULL,         -- nombre original del request
+    storage_key TEXT NOT NULL,       -- nombre del archivo en disco
+    content_type TEXT NOT NULL,
+    size_bytes INTEGER NOT NULL,
+    sha256 TEXT NOT NULL,            -- hash del contenido en hex
+    created_at TEXT NOT NULL,        -- ISO timestamp
+    expires_at TEXT,                 -- ISO timestamp (UTC, "Z"); NULL = no expira
+    FOREIGN KEY (operation_id) REFERENCES operations (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_documents_operation_id ON documents (operation_id);
+CREATE INDEX IF NOT EXISTS idx_documents_expires_at ON documents (expires_at);
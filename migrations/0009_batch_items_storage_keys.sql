@@ -0,0 +1,7 @@
+-- migrations/0009_batch_items_storage_keys.sql
+
+-- `batch_items.file_path` pasa a ser la clave en el Storage ("batches/<op>/<i>_<nombre>"),
+-- relativa a su raíz, en lugar de una ruta en disco ("./files/batches/...").
+UPDATE batch_items
+SET file_path = substr(file_path, length('./files/') + 1)
+WHERE file_path LIKE './files/%';
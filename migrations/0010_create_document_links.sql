@@ -0,0 +1,14 @@
+-- migrations/0010_create_document_links.sql
+
+-- Enlaces públicos de descarga de documentos. La URL lleva la expiración y una
+-- firma HMAC; la fila permite revocar el enlace antes de que expire.
+CREATE TABLE IF NOT EXISTS document_links (
+    id TEXT PRIMARY KEY,
+    document_id TEXT NOT NULL,
+    expires_at TEXT NOT NULL,        -- ISO timestamp (UTC, "Z")
+    revoked_at TEXT,                 -- ISO timestamp; NULL = vigente
+    created_at TEXT NOT NULL,        -- ISO timestamp
+    FOREIGN KEY (document_id) REFERENCES documents (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_document_links_document_id ON document_links (document_id);
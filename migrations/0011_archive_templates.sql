@@ -0,0 +1,5 @@
+-- migrations/0011_archive_templates.sql
+
+-- Borrar una plantilla la archiva: deja de listarse y de usarse como vigente,
+-- pero sus versiones se conservan para repetir renders ya hechos
+ALTER TABLE templates ADD COLUMN archived_at TEXT; -- ISO timestamp, NULL si está activa
@@ -0,0 +1,5 @@
+-- migrations/0012_batch_items_expiry.sql
+
+-- Los PDFs de lotes asíncronos expiran como los documentos (`DOCUMENT_TTL_SECONDS`):
+-- al vencer se borra el archivo y el item queda como "expired"
+ALTER TABLE batch_items ADD COLUMN expires_at TEXT; -- ISO timestamp, NULL si no expira
@@ -1,13 +1,146 @@
 use actix_web::web;
 
-use crate::handlers::{email_handler, notification_handler, operation_handler, pdf_handler};
+use crate::handlers::{
+    admin_handler, batch_handler, document_handler, email_handler, notification_handler,
+    operation_handler, pdf_handler, signing_handler, template_handler,
+};
 
 pub fn init_app(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/api")
             // Rutas PDF
             .service(
-                web::scope("/pdf").route("", web::post().to(pdf_handler::generate_pdf_endpoint)),
+                web::scope("/pdf")
+                    .route("", web::post().to(pdf_handler::generate_pdf_endpoint))
+                    .route(
+                        "/defaults",
+                        web::get().to(pdf_handler::pdf_defaults_endpoint),
+                    )
+                    .route(
+                        "/bundle",
+                        web::post().to(pdf_handler::generate_pdf_from_bundle_endpoint),
+                    )
+                    .route("/merge", web::post().to(pdf_handler::merge_pdf_endpoint))
+                    .route("/split", web::post().to(pdf_handler::split_pdf_endpoint))
+                    .route(
+                        "/local/{filename}",
+                        web::get().to(pdf_handler::serve_local_pdf),
+                    )
+                    .route(
+                        "/thumbnail",
+                        web::post().to(pdf_handler::thumbnail_pdf_endpoint),
+                    )
+                    .route(
+                        "/verify",
+                        web::post().to(signing_handler::verify_pdf_endpoint),
+                    )
+                    .route(
+                        "/jobs/{op_id}/result",
+                        web::get().to(pdf_handler::download_pdf_job_result_endpoint),
+                    )
+                    .route(
+                        "/batch",
+                        web::post().to(batch_handler::generate_batch_endpoint),
+                    )
+                    .route(
+                        "/batch/{op_id}",
+                        web::get().to(batch_handler::get_batch_endpoint),
+                    )
+                    .route(
+                        "/batch/{op_id}/items/{index}",
+                        web::get().to(batch_handler::download_batch_item_endpoint),
+                    ),
+            )
+            // Rutas de documentos guardados
+            .service(
+                web::scope("/documents")
+                    .route("", web::get().to(document_handler::list_documents_endpoint))
+                    .route(
+                        "/{id}",
+                        web::get().to(document_handler::get_document_endpoint),
+                    )
+                    .route(
+                        "/{id}",
+                        web::delete().to(document_handler::delete_document_endpoint),
+                    )
+                    .route(
+                        "/{id}/content",
+                        web::get().to(document_handler::download_document_endpoint),
+                    )
+                    .route(
+                        "/{id}/links",
+                        web::post().to(document_handler::create_document_link_endpoint),
+                    )
+                    .route(
+                        "/{id}/links",
+                        web::get().to(document_handler::list_document_links_endpoint),
+                    )
+                    .route(
+                        "/{id}/links/{link_id}",
+                        web::delete().to(document_handler::revoke_document_link_endpoint),
+                    ),
+            )
+            // Rutas públicas (sin API key): enlaces de descarga firmados
+            .service(web::scope("/public").route(
+                "/links/{link_id}",
+                web::get().to(document_handler::public_link_download_endpoint),
+            ))
+            // Rutas de plantillas
+            .service(
+                web::scope("/templates")
+                    .route(
+                        "",
+                        web::post().to(template_handler::create_template_endpoint),
+                    )
+                    .route("", web::get().to(template_handler::list_templates_endpoint))
+                    .route(
+                        "/{id}",
+                        web::get().to(template_handler::get_template_endpoint),
+                    )
+                    .route(
+                        "/{id}",
+                        web::put().to(template_handler::update_template_endpoint),
+                    )
+                    .route(
+                        "/{id}",
+                        web::delete().to(template_handler::delete_template_endpoint),
+                    )
+                    .route(
+                        "/{id}/versions",
+                        web::get().to(template_handler::list_template_versions_endpoint),
+                    )
+                    .route(
+                        "/{id}/versions/{version}",
+                        web::get().to(template_handler::get_template_version_endpoint),
+                    )
+                    .route(
+                        "/{id}/rollback",
+                        web::post().to(template_handler::rollback_template_endpoint),
+                    )
+                    .route(
+                        "/{id}/preview",
+                        web::post().to(template_handler::preview_template_endpoint),
+                    ),
+            )
+            // Rutas de perfiles de firma
+            .service(
+                web::scope("/signing")
+                    .route(
+                        "/profiles",
+                        web::post().to(signing_handler::create_signing_profile_endpoint),
+                    )
+                    .route(
+                        "/profiles",
+                        web::get().to(signing_handler::list_signing_profiles_endpoint),
+                    )
+                    .route(
+                        "/profiles/{id}",
+                        web::get().to(signing_handler::get_signing_profile_endpoint),
+                    )
+                    .route(
+                        "/profiles/{id}",
+                        web::delete().to(signing_handler::delete_signing_profile_endpoint),
+                    ),
             )
             // Rutas de operaciones
             .service(
@@ -37,6 +170,11 @@ pub fn init_app(cfg: &mut web::ServiceConfig) {
                         web::get().to(email_handler::email_status_endpoint),
                     ),
             )
+            // Rutas de administración
+            .service(web::scope("/admin").route(
+                "/cache",
+                web::delete().to(admin_handler::purge_render_cache_endpoint),
+            ))
             // Rutas de notificaciones unificadas
             .service(web::scope("/notifications").route(
                 "/send",
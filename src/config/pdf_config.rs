@@ -1,31 +1,303 @@
 //! config/pdf_config.rs
-//! Estructuras globales para configuración de PDFs (dimensiones por defecto, etc.)
+//! Valores por defecto de los PDFs (orientación, página, márgenes, escala y motor):
+//! los de fábrica, los del archivo `PDF_CONFIG_FILE` (TOML), los de las variables
+//! `PDF_DEFAULT_*` y, encima, los de cada tenant. Un request solo hereda lo que no define.
+//!
+//! ```toml
+//! [defaults]
+//! page_size_preset = "A4"
+//! margins = { top = "10mm", bottom = "10mm", left = "10mm", right = "10mm" }
+//!
+//! [tenants.acme]
+//! page_size_preset = "LETTER"
+//! margins = { top = "0.5in", bottom = "0.5in", left = "0.5in", right = "0.5in" }
+//! ```
 
-use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, path::Path};
 
-/// Configuración global de PDF, con valores por defecto
-/// (podría venir de un .toml, .env, etc.)
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PdfGlobalConfig {
-    pub default_orientation: String, // "portrait" o "landscape"
-    pub default_width: f64,          // ancho por defecto (pulgadas)
-    pub default_height: f64,         // alto por defecto
-    pub default_margin_top: f64,
-    pub default_margin_bottom: f64,
-    pub default_margin_left: f64,
-    pub default_margin_right: f64,
+use anyhow::{anyhow, Context, Result};
+use serde::{de::IntoDeserializer, Deserialize, Serialize};
+
+use crate::models::{
+    pdf_model::{
+        PaperSize, PdfBackend, PdfMargins, PdfOrientation, PdfPagePreset, PdfRequest,
+        MAX_PAGE_SIDE_MM,
+    },
+    units::parse_length_mm,
+    validation::{FieldErrorCode, ValidationErrors},
+};
+
+/// Tamaño de página de fábrica
+pub const BUILTIN_PAGE_SIZE: PdfPagePreset = PdfPagePreset::A4;
+/// Margen de fábrica (mm), en los cuatro lados
+pub const BUILTIN_MARGIN_MM: f64 = 10.0;
+/// Archivo que se lee si existe y no se define `PDF_CONFIG_FILE`
+const DEFAULT_CONFIG_FILE: &str = "pdf_config.toml";
+
+/// Una capa de valores por defecto; lo que no define se hereda de la anterior.
+/// `page_size_preset` y `custom_page_size` se excluyen: la capa que define uno
+/// reemplaza al otro.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PdfDefaultsLayer {
+    pub orientation: Option<PdfOrientation>,
+    pub page_size_preset: Option<PdfPagePreset>,
+    pub custom_page_size: Option<PaperSize>,
+    pub margins: Option<PdfMargins>,
+    pub scale: Option<f64>,
+    pub backend: Option<PdfBackend>,
 }
 
-impl Default for PdfGlobalConfig {
+/// Valores por defecto ya resueltos: lo que recibe un request que no los define
+#[derive(Debug, Clone, Serialize)]
+pub struct PdfDefaults {
+    /// Tenant cuyos ajustes se aplicaron; None para los del servicio
+    pub tenant: Option<String>,
+    pub orientation: PdfOrientation,
+    /// Uno de los dos: preset o tamaño personalizado
+    pub page_size_preset: Option<PdfPagePreset>,
+    pub custom_page_size: Option<PaperSize>,
+    pub margins: PdfMargins,
+    pub scale: f64,
+    /// None hasta que PdfService fija el motor global (el primero instalado)
+    pub backend: Option<PdfBackend>,
+}
+
+impl Default for PdfDefaults {
     fn default() -> Self {
-        PdfGlobalConfig {
-            default_orientation: "portrait".to_string(),
-            default_width: 8.5,
-            default_height: 11.0,
-            default_margin_top: 0.5,
-            default_margin_bottom: 0.5,
-            default_margin_left: 0.5,
-            default_margin_right: 0.5,
+        PdfDefaults {
+            tenant: None,
+            orientation: PdfOrientation::Portrait,
+            page_size_preset: Some(BUILTIN_PAGE_SIZE),
+            custom_page_size: None,
+            margins: PdfMargins {
+                top: BUILTIN_MARGIN_MM,
+                bottom: BUILTIN_MARGIN_MM,
+                left: BUILTIN_MARGIN_MM,
+                right: BUILTIN_MARGIN_MM,
+            },
+            scale: 1.0,
+            backend: None,
+        }
+    }
+}
+
+impl PdfDefaults {
+    fn merge(&mut self, layer: &PdfDefaultsLayer) {
+        if let Some(orientation) = &layer.orientation {
+            self.orientation = orientation.clone();
+        }
+        if let Some(preset) = &layer.page_size_preset {
+            self.page_size_preset = Some(preset.clone());
+            self.custom_page_size = None;
+        } else if let Some(size) = &layer.custom_page_size {
+            self.page_size_preset = None;
+            self.custom_page_size = Some(size.clone());
+        }
+        if let Some(margins) = &layer.margins {
+            self.margins = margins.clone();
+        }
+        if let Some(scale) = layer.scale {
+            self.scale = scale;
+        }
+        if layer.backend.is_some() {
+            self.backend = layer.backend;
+        }
+    }
+
+    /// Completa los campos de página que el request no define
+    pub fn apply(&self, req: &mut PdfRequest) {
+        req.orientation
+            .get_or_insert_with(|| self.orientation.clone());
+        if req.page_size_preset.is_none() && req.custom_page_size.is_none() {
+            req.page_size_preset = self.page_size_preset.clone();
+            req.custom_page_size = self.custom_page_size.clone();
+        }
+        req.margins.get_or_insert_with(|| self.margins.clone());
+        req.scale.get_or_insert(self.scale);
+        if req.backend.is_none() {
+            req.backend = self.backend;
+        }
+    }
+}
+
+/// Configuración de PDFs del servicio
+#[derive(Debug, Clone, Default)]
+pub struct PdfGlobalConfig {
+    /// Valores de todo el servicio
+    pub defaults: PdfDefaults,
+    /// Ajustes por tenant (header `X-Tenant-Id`) sobre `defaults`
+    pub tenants: HashMap<String, PdfDefaultsLayer>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PdfConfigFile {
+    #[serde(default)]
+    defaults: PdfDefaultsLayer,
+    #[serde(default)]
+    tenants: HashMap<String, PdfDefaultsLayer>,
+}
+
+impl PdfGlobalConfig {
+    /// Lee `PDF_CONFIG_FILE` (o `pdf_config.toml` si existe) y encima las variables
+    /// `PDF_DEFAULT_ORIENTATION`, `PDF_DEFAULT_PAGE_SIZE`, `PDF_DEFAULT_MARGIN`,
+    /// `PDF_DEFAULT_SCALE` y `PDF_BACKEND`.
+    pub fn from_env() -> Result<Self> {
+        let file = match std::env::var("PDF_CONFIG_FILE") {
+            Ok(path) if !path.is_empty() => Some(Self::read_file(Path::new(&path))?),
+            _ if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Some(Self::read_file(Path::new(DEFAULT_CONFIG_FILE))?)
+            }
+            _ => None,
+        };
+        let file = file.unwrap_or_default();
+
+        let mut defaults = PdfDefaults::default();
+        validate_layer("defaults", &file.defaults)?;
+        defaults.merge(&file.defaults);
+        let env_layer = env_layer()?;
+        validate_layer("PDF_DEFAULT_*", &env_layer)?;
+        defaults.merge(&env_layer);
+
+        for (tenant, layer) in &file.tenants {
+            validate_layer(&format!("tenants.{}", tenant), layer)?;
+        }
+        Ok(PdfGlobalConfig {
+            defaults,
+            tenants: file.tenants,
+        })
+    }
+
+    fn read_file(path: &Path) -> Result<PdfConfigFile> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("No se pudo leer la configuración de PDF {:?}", path))?;
+        let file: PdfConfigFile = toml::from_str(&text)
+            .with_context(|| format!("Configuración de PDF inválida en {:?}", path))?;
+        log::info!(
+            "Configuración de PDF cargada de {:?} ({} tenants)",
+            path,
+            file.tenants.len()
+        );
+        Ok(file)
+    }
+
+    /// Valores del servicio con los ajustes de `tenant` encima.
+    /// Error si el tenant no está configurado.
+    pub fn resolve(&self, tenant: Option<&str>) -> Result<PdfDefaults> {
+        let mut defaults = self.defaults.clone();
+        if let Some(tenant) = tenant {
+            let layer = self
+                .tenants
+                .get(tenant)
+                .ok_or_else(|| anyhow!("Tenant desconocido: {}", tenant))?;
+            defaults.merge(layer);
+            defaults.tenant = Some(tenant.to_string());
+        }
+        Ok(defaults)
+    }
+
+    /// Motores que pide la configuración (servicio y tenants), para comprobar al
+    /// arrancar que están instalados
+    pub fn backends(&self) -> impl Iterator<Item = PdfBackend> + '_ {
+        self.defaults
+            .backend
+            .into_iter()
+            .chain(self.tenants.values().filter_map(|layer| layer.backend))
+    }
+}
+
+/// Capa con las variables `PDF_DEFAULT_*` (y `PDF_BACKEND`) definidas
+fn env_layer() -> Result<PdfDefaultsLayer> {
+    let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    let mut layer = PdfDefaultsLayer::default();
+    if let Some(value) = var("PDF_DEFAULT_ORIENTATION") {
+        layer.orientation = Some(parse_enum(
+            "PDF_DEFAULT_ORIENTATION",
+            &value.to_lowercase(),
+        )?);
+    }
+    if let Some(value) = var("PDF_DEFAULT_PAGE_SIZE") {
+        layer.page_size_preset = Some(parse_enum("PDF_DEFAULT_PAGE_SIZE", &value.to_uppercase())?);
+    }
+    if let Some(value) = var("PDF_DEFAULT_MARGIN") {
+        let margin = parse_length_mm(&value).context("PDF_DEFAULT_MARGIN inválida")?;
+        layer.margins = Some(PdfMargins {
+            top: margin,
+            bottom: margin,
+            left: margin,
+            right: margin,
+        });
+    }
+    if let Some(value) = var("PDF_DEFAULT_SCALE") {
+        layer.scale = Some(
+            value
+                .parse()
+                .map_err(|_| anyhow!("PDF_DEFAULT_SCALE inválida: {}", value))?,
+        );
+    }
+    if let Some(value) = var("PDF_BACKEND") {
+        layer.backend = Some(value.parse()?);
+    }
+    Ok(layer)
+}
+
+/// Valor de un enum con los mismos nombres que en el JSON
+fn parse_enum<'de, T: Deserialize<'de>>(name: &str, value: &'de str) -> Result<T> {
+    T::deserialize(value.into_deserializer())
+        .map_err(|e: serde::de::value::Error| anyhow!("{} inválida: {}", name, e))
+}
+
+/// Los mismos rangos que el preflight de los requests
+fn validate_layer(source: &str, layer: &PdfDefaultsLayer) -> Result<()> {
+    let mut errors = ValidationErrors::default();
+    if layer.page_size_preset.is_some() && layer.custom_page_size.is_some() {
+        errors.add(
+            "custom_page_size",
+            FieldErrorCode::Conflict,
+            "no se puede combinar con `page_size_preset`",
+        );
+    }
+    if let Some(size) = &layer.custom_page_size {
+        errors.check_range(
+            "custom_page_size.width",
+            Some(size.width),
+            1.0,
+            MAX_PAGE_SIDE_MM,
+        );
+        errors.check_range(
+            "custom_page_size.height",
+            Some(size.height),
+            1.0,
+            MAX_PAGE_SIDE_MM,
+        );
+    }
+    if let Some(margins) = &layer.margins {
+        for (side, value) in [
+            ("top", margins.top),
+            ("bottom", margins.bottom),
+            ("left", margins.left),
+            ("right", margins.right),
+        ] {
+            errors.check_range(
+                &format!("margins.{}", side),
+                Some(value),
+                0.0,
+                MAX_PAGE_SIDE_MM,
+            );
         }
     }
+    errors.check_range("scale", layer.scale, 0.1, 2.0);
+    if errors.errors.is_empty() {
+        return Ok(());
+    }
+    let messages: Vec<String> = errors
+        .errors
+        .iter()
+        .map(|error| format!("{}.{}: {}", source, error.field, error.message))
+        .collect();
+    Err(anyhow!(
+        "Configuración de PDF inválida: {}",
+        messages.join("; ")
+    ))
 }
@@ -0,0 +1,29 @@
+//! handlers/admin_handler.rs
+//! Tareas de administración del servicio.
+
+use actix_web::{web, HttpResponse};
+use serde_json::json;
+
+use crate::services::pdf_service::PdfService;
+
+/// DELETE /api/admin/cache
+/// Vacía la caché de renders y responde cuántas entradas y bytes se liberaron.
+pub async fn purge_render_cache_endpoint(pdf_service: web::Data<PdfService>) -> HttpResponse {
+    match pdf_service.purge_cache() {
+        Some(purge) => {
+            log::info!(
+                "Caché de renders purgada: {} entradas, {} bytes",
+                purge.entries,
+                purge.bytes
+            );
+            HttpResponse::Ok().json(json!({
+                "purged_entries": purge.entries,
+                "purged_bytes": purge.bytes
+            }))
+        }
+        None => HttpResponse::Conflict().json(json!({
+            "error": "Render cache disabled",
+            "details": "Defina PDF_CACHE_MAX_BYTES para activarla"
+        })),
+    }
+}
@@ -0,0 +1,139 @@
+//! handlers/batch_handler.rs
+//! Generación de PDFs en lote.
+
+use actix_files::{file_extension_to_mime, NamedFile};
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use log::error;
+use serde_json::json;
+
+use crate::handlers::tenant_id;
+use crate::models::batch_model::{BatchOutput, BatchPdfRequest};
+use crate::services::batch_service::BatchService;
+use crate::services::error::is_not_found;
+use crate::services::pdf_service::PdfService;
+
+/// POST /api/pdf/batch
+/// Con `output = "zip"` (default) responde el ZIP con todos los PDFs y un
+/// `manifest.json`; con `output = "operation"` responde 202 con el id de la
+/// operación a consultar en `GET /api/pdf/batch/{op_id}`.
+pub async fn generate_batch_endpoint(
+    req: HttpRequest,
+    batch_service: web::Data<BatchService>,
+    pdf_service: web::Data<PdfService>,
+    body: web::Json<BatchPdfRequest>,
+) -> HttpResponse {
+    // Todos los documentos del lote usan los valores por defecto del mismo tenant
+    let tenant = tenant_id(&req);
+    if let Err(e) = pdf_service.defaults(tenant.as_deref()) {
+        return HttpResponse::NotFound().json(json!({
+            "success": false,
+            "error": e.to_string()
+        }));
+    }
+    let body = body.into_inner();
+    let output = body.output;
+    let zip_name = body
+        .zip_name
+        .clone()
+        .unwrap_or_else(|| "batch.zip".to_string());
+
+    let mut items = match batch_service.expand_items(body).await {
+        Ok(items) => items,
+        Err(e) => {
+            let mut response = if is_not_found(&e) {
+                HttpResponse::NotFound()
+            } else {
+                HttpResponse::BadRequest()
+            };
+            return response.json(json!({
+                "success": false,
+                "error": e.to_string()
+            }));
+        }
+    };
+    for item in &mut items {
+        item.tenant = tenant.clone();
+    }
+
+    match output {
+        BatchOutput::Operation => match batch_service.start_batch_operation(items).await {
+            Ok(op_id) => HttpResponse::Accepted().json(json!({
+                "success": true,
+                "operation_id": op_id,
+                "status_url": format!("/api/pdf/batch/{}", op_id),
+                "message": "Batch queued for async processing"
+            })),
+            Err(e) => HttpResponse::InternalServerError().json(json!({
+                "success": false,
+                "error": format!("Operation creation failed: {}", e)
+            })),
+        },
+        BatchOutput::Zip => {
+            let file = match batch_service.render_zip(items).await {
+                Ok(file) => file,
+                Err(e) => {
+                    error!("Error generando lote: {:?}", e);
+                    return HttpResponse::InternalServerError().json(json!({
+                        "success": false,
+                        "error": format!("Failed to generate batch: {}", e)
+                    }));
+                }
+            };
+            // Se sirve desde el archivo temporal, sin cargar el ZIP completo en memoria
+            match NamedFile::from_file(file, &zip_name) {
+                Ok(named) => named
+                    .set_content_type(file_extension_to_mime("zip"))
+                    .set_content_disposition(header::ContentDisposition::attachment(zip_name))
+                    .into_response(&req),
+                Err(e) => HttpResponse::InternalServerError().json(json!({
+                    "success": false,
+                    "error": format!("Failed to send batch: {}", e)
+                })),
+            }
+        }
+    }
+}
+
+/// GET /api/pdf/batch/{op_id}
+pub async fn get_batch_endpoint(
+    batch_service: web::Data<BatchService>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    match batch_service.get_batch(&path.into_inner()).await {
+        Ok(batch) => HttpResponse::Ok().json(batch),
+        Err(e) if is_not_found(&e) => HttpResponse::NotFound().json(json!({
+            "error": "Batch not found",
+            "details": e.to_string()
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": "Internal server error",
+            "details": e.to_string()
+        })),
+    }
+}
+
+/// GET /api/pdf/batch/{op_id}/items/{index}
+pub async fn download_batch_item_endpoint(
+    batch_service: web::Data<BatchService>,
+    path: web::Path<(String, i64)>,
+) -> HttpResponse {
+    let (op_id, index) = path.into_inner();
+    match batch_service.get_item_file(&op_id, index).await {
+        Ok(Some((file_name, bytes))) => HttpResponse::Ok()
+            .content_type(file_extension_to_mime("pdf"))
+            .insert_header(header::ContentDisposition::attachment(file_name))
+            .body(bytes),
+        Ok(None) => HttpResponse::NotFound().json(json!({ "error": "Batch item not found" })),
+        Err(e) if is_not_found(&e) => HttpResponse::NotFound().json(json!({
+            "error": "File not found",
+            "details": e.to_string()
+        })),
+        Err(e) => {
+            error!("{:?}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Internal server error",
+                "details": e.to_string()
+            }))
+        }
+    }
+}
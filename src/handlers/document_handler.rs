@@ -0,0 +1,202 @@
+//! handlers/document_handler.rs
+//! Documentos guardados: listado, metadatos, descarga y borrado.
+
+use actix_web::{http::header, web, HttpResponse};
+use log::error;
+use serde_json::json;
+
+use crate::handlers::validation_error;
+use crate::models::document_model::{
+    CreateDocumentLinkRequest, DocumentLinkQuery, ListDocumentsQuery,
+};
+use crate::services::document_service::DocumentService;
+use crate::services::download_link_service::{DownloadLinkService, LinkResolution};
+use crate::services::error::is_not_found;
+
+/// GET /api/documents
+/// Paginado (`page`, `page_size`) y opcionalmente filtrado por `operation_id`.
+pub async fn list_documents_endpoint(
+    document_service: web::Data<DocumentService>,
+    query: web::Query<ListDocumentsQuery>,
+) -> HttpResponse {
+    let query = query.into_inner();
+    let page = query.page.unwrap_or(1);
+    let page_size = query.page_size.unwrap_or(10);
+
+    match document_service
+        .list_documents(page, page_size, query.operation_id.as_deref())
+        .await
+    {
+        Ok(list) => HttpResponse::Ok().json(list),
+        Err(e) => {
+            error!("{:?}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Internal server error",
+                "details": e.to_string()
+            }))
+        }
+    }
+}
+
+/// GET /api/documents/{id}
+pub async fn get_document_endpoint(
+    document_service: web::Data<DocumentService>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    match document_service.get_document(&path.into_inner()).await {
+        Ok(Some(document)) => HttpResponse::Ok().json(document),
+        Ok(None) => HttpResponse::NotFound().json(json!({ "error": "Document not found" })),
+        Err(e) => {
+            error!("{:?}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Internal server error",
+                "details": e.to_string()
+            }))
+        }
+    }
+}
+
+/// GET /api/documents/{id}/content
+pub async fn download_document_endpoint(
+    document_service: web::Data<DocumentService>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    match document_service
+        .get_document_content(&path.into_inner())
+        .await
+    {
+        Ok(Some((document, bytes))) => HttpResponse::Ok()
+            .content_type(document.content_type)
+            .insert_header(header::ContentDisposition::attachment(document.file_name))
+            .body(bytes),
+        Ok(None) => HttpResponse::NotFound().json(json!({ "error": "Document not found" })),
+        Err(e) if is_not_found(&e) => HttpResponse::NotFound().json(json!({
+            "error": "File not found",
+            "details": e.to_string()
+        })),
+        Err(e) => {
+            error!("{:?}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Internal server error",
+                "details": e.to_string()
+            }))
+        }
+    }
+}
+
+/// DELETE /api/documents/{id}
+pub async fn delete_document_endpoint(
+    document_service: web::Data<DocumentService>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    match document_service.delete_document(&path.into_inner()).await {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => HttpResponse::NotFound().json(json!({ "error": "Document not found" })),
+        Err(e) => {
+            error!("{:?}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Internal server error",
+                "details": e.to_string()
+            }))
+        }
+    }
+}
+
+/// POST /api/documents/{id}/links
+/// Crea un enlace público firmado; el body (`expires_in_seconds`) es opcional.
+pub async fn create_document_link_endpoint(
+    link_service: web::Data<DownloadLinkService>,
+    path: web::Path<String>,
+    body: Option<web::Json<CreateDocumentLinkRequest>>,
+) -> HttpResponse {
+    let body = body.map(|b| b.into_inner()).unwrap_or_default();
+    if let Err(errors) = body.validate() {
+        return validation_error(errors);
+    }
+    match link_service
+        .create_link(&path.into_inner(), body.expires_in_seconds)
+        .await
+    {
+        Ok(Some(link)) => HttpResponse::Created().json(link),
+        Ok(None) => HttpResponse::NotFound().json(json!({ "error": "Document not found" })),
+        Err(e) => HttpResponse::BadRequest().json(json!({
+            "error": "No se pudo crear el enlace",
+            "details": e.to_string()
+        })),
+    }
+}
+
+/// GET /api/documents/{id}/links
+pub async fn list_document_links_endpoint(
+    link_service: web::Data<DownloadLinkService>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    match link_service.list_links(&path.into_inner()).await {
+        Ok(links) => HttpResponse::Ok().json(links),
+        Err(e) => {
+            error!("{:?}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Internal server error",
+                "details": e.to_string()
+            }))
+        }
+    }
+}
+
+/// DELETE /api/documents/{id}/links/{link_id}
+/// Revoca el enlace (queda en el listado con `revoked_at`).
+pub async fn revoke_document_link_endpoint(
+    link_service: web::Data<DownloadLinkService>,
+    path: web::Path<(String, String)>,
+) -> HttpResponse {
+    let (document_id, link_id) = path.into_inner();
+    match link_service.revoke_link(&document_id, &link_id).await {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => HttpResponse::NotFound().json(json!({ "error": "Link not found" })),
+        Err(e) => {
+            error!("{:?}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Internal server error",
+                "details": e.to_string()
+            }))
+        }
+    }
+}
+
+/// GET /api/public/links/{link_id}?expires=...&signature=...
+/// Descarga pública (sin API key) a través de un enlace firmado.
+pub async fn public_link_download_endpoint(
+    link_service: web::Data<DownloadLinkService>,
+    path: web::Path<String>,
+    query: web::Query<DocumentLinkQuery>,
+) -> HttpResponse {
+    match link_service
+        .resolve(&path.into_inner(), query.expires, &query.signature)
+        .await
+    {
+        Ok(LinkResolution::Valid { document, bytes }) => HttpResponse::Ok()
+            .content_type(document.content_type)
+            .insert_header(header::ContentDisposition {
+                disposition: header::DispositionType::Inline,
+                parameters: vec![header::DispositionParam::Filename(document.file_name)],
+            })
+            .insert_header(header::CacheControl(vec![header::CacheDirective::Private]))
+            .body(bytes),
+        Ok(LinkResolution::Invalid) => {
+            HttpResponse::Forbidden().json(json!({ "error": "Enlace inválido" }))
+        }
+        Ok(LinkResolution::Gone) => HttpResponse::Gone().json(json!({
+            "error": "El enlace expiró o fue revocado"
+        })),
+        Err(e) if is_not_found(&e) => HttpResponse::Gone().json(json!({
+            "error": "El enlace expiró o fue revocado"
+        })),
+        Err(e) => {
+            error!("{:?}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Internal server error",
+                "details": e.to_string()
+            }))
+        }
+    }
+}
@@ -1,16 +1,20 @@
 //! handlers/email_handler.rs
 
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
 use serde_json::json;
 
 use crate::{
+    handlers::{tenant_id, validation_error},
     models::{
         email_model::{EmailAttachment, SendUniversalEmailRequest},
         operation_model::CreateOperationRequest,
-        pdf_model::PdfRequest,
     },
     services::{
-        email_service::EmailService, operation_service::OperationService, pdf_service::PdfService,
+        email_service::EmailService,
+        error::is_not_found,
+        operation_service::OperationService,
+        pdf_service::PdfService,
+        template_service::{render_metadata, TemplateService},
     },
 };
 
@@ -28,7 +32,7 @@ pub async fn email_status_endpoint(
             "status": status
         })),
         Err(e) => {
-            let status_code = if e.to_string().contains("not found") {
+            let status_code = if is_not_found(&e) {
                 actix_web::http::StatusCode::NOT_FOUND
             } else {
                 actix_web::http::StatusCode::INTERNAL_SERVER_ERROR
@@ -53,14 +57,52 @@ pub async fn email_status_endpoint(
 
 /// POST /api/email/send-unified
 pub async fn send_universal_email_endpoint(
+    http_req: HttpRequest,
     email_service: web::Data<EmailService>,
     pdf_service: web::Data<PdfService>,
+    template_service: web::Data<TemplateService>,
     _op_service: web::Data<OperationService>,
     body: web::Json<SendUniversalEmailRequest>,
 ) -> HttpResponse {
-    let req_body = body.into_inner(); // Convertimos el JSON en struct
+    let mut req_body = body.into_inner(); // Convertimos el JSON en struct
+    req_body.tenant = tenant_id(&http_req);
     let op_service_cloned = _op_service.clone();
 
+    // Preflight de los campos `pdf_*` antes de crear la operación
+    if let Some(pdf_request) = req_body.planned_pdf_request() {
+        if let Err(errors) = pdf_service.validate_request(&pdf_request) {
+            return validation_error(errors.for_pdf_fields());
+        }
+    }
+
+    // Fijar la versión de la plantilla antes de crear la operación
+    let pdf_template = match &req_body.pdf_template_id {
+        Some(template_id) => {
+            match template_service
+                .pin_version(template_id, req_body.pdf_template_version)
+                .await
+            {
+                Ok(pinned) => {
+                    req_body.pdf_template_id = Some(pinned.template_id.clone());
+                    req_body.pdf_template_version = Some(pinned.version);
+                    Some(pinned)
+                }
+                Err(e) => {
+                    let mut response = if is_not_found(&e) {
+                        HttpResponse::NotFound()
+                    } else {
+                        HttpResponse::InternalServerError()
+                    };
+                    return response.json(json!({
+                        "success": false,
+                        "error": format!("Template Error: {}", e)
+                    }));
+                }
+            }
+        }
+        None => None,
+    };
+
     //log html for pdf
     if let Some(html) = &req_body.pdf_html {
         log::info!("HTML for PDF: {}", html);
@@ -74,7 +116,16 @@ pub async fn send_universal_email_endpoint(
             json!({
                 "recipients": req_body.recipients,
                 "subject": req_body.subject,
-                "pdf_planned": req_body.pdf_html.is_some(),
+                "pdf_planned": req_body.pdf_html.is_some() || req_body.pdf_template_id.is_some(),
+                "pdf_template": pdf_template.map(|t| {
+                    let mut metadata = render_metadata(
+                        &t.template_id,
+                        t.version,
+                        req_body.pdf_data.as_ref(),
+                    );
+                    metadata["name"] = json!(t.template_name);
+                    metadata
+                }),
                 "other_attachments": req_body.other_attachments.as_ref().map(|a| a.len()).unwrap_or(0)
             })
             .to_string(),
@@ -106,6 +157,7 @@ pub async fn send_universal_email_endpoint(
                 req_body_clone,
                 email_service_cloned,
                 pdf_service_cloned,
+                template_service.clone(),
             )
             .await
             {
@@ -131,6 +183,7 @@ pub async fn send_universal_email_endpoint(
             req_body,
             email_service.clone(),
             pdf_service.clone(),
+            template_service.clone(),
         )
         .await
         {
@@ -162,25 +215,22 @@ async fn do_full_email_work(
     mut req_body: SendUniversalEmailRequest,
     email_service: web::Data<EmailService>,
     pdf_service: web::Data<PdfService>,
+    template_service: web::Data<TemplateService>,
 ) -> Result<(), anyhow::Error> {
     // 1. Lista de adjuntos final
     let mut final_attachments = vec![];
 
-    // 2. ¿Generar PDF?
-    if let Some(html) = req_body.pdf_html.take() {
-        let pdf_request = PdfRequest {
-            file_name: req_body
-                .pdf_attachment_name
-                .clone()
-                .unwrap_or_else(|| "document.pdf".to_string()),
-            html,
-            orientation: req_body.pdf_orientation.clone(),
-            page_size_preset: req_body.pdf_page_size_preset.clone(),
-            custom_page_size: req_body.pdf_custom_page_size.clone(),
-            margins: req_body.pdf_margins.clone(),
-            scale: req_body.pdf_scale,
-            store_local_pdf: Some(false),
-        };
+    // 2. ¿Generar PDF? (HTML inline o plantilla del servidor)
+    let pdf_html = template_service
+        .resolve_html(
+            req_body.pdf_template_id.as_deref(),
+            req_body.pdf_template_version,
+            req_body.pdf_data.as_ref(),
+            req_body.pdf_html.take(),
+        )
+        .await?;
+    if let Some(html) = pdf_html {
+        let pdf_request = req_body.pdf_request(html);
 
         let pdf_bytes = pdf_service
             .generate_pdf(pdf_request)
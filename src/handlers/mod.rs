@@ -2,7 +2,39 @@
 //! Módulo que agrupa los distintos handlers (PDF, notificaciones, email, etc.).
 // pub mod email_handler;
 //! handlers/mod.rs
+pub mod admin_handler;
+pub mod batch_handler;
+pub mod document_handler;
 pub mod email_handler;
 pub mod notification_handler;
 pub mod operation_handler;
 pub mod pdf_handler;
+pub mod signing_handler;
+pub mod template_handler;
+
+use actix_web::{HttpRequest, HttpResponse};
+use serde_json::json;
+
+use crate::models::validation::ValidationErrors;
+
+/// Header con el tenant cuyos valores por defecto de PDF se aplican
+pub const TENANT_HEADER: &str = "X-Tenant-Id";
+
+/// Tenant del request (header `X-Tenant-Id`), si viene
+pub fn tenant_id(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(TENANT_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+/// 422 con los errores por campo del preflight
+pub fn validation_error(errors: ValidationErrors) -> HttpResponse {
+    HttpResponse::UnprocessableEntity().json(json!({
+        "success": false,
+        "error": errors.to_string(),
+        "errors": errors.errors
+    }))
+}
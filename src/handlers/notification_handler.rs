@@ -1,23 +1,75 @@
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
+use log::error;
 use serde_json::json;
 
 use crate::{
+    handlers::{tenant_id, validation_error},
     models::{
         notification_model::{NotificationRequest, NotificationResponse},
         operation_model::CreateOperationRequest,
+        validation::ValidationErrors,
+    },
+    services::{
+        error::is_not_found,
+        notification_service::NotificationService,
+        operation_service::OperationService,
+        pdf_service::PdfService,
+        template_service::{render_metadata, TemplateService},
     },
-    services::{notification_service::NotificationService, operation_service::OperationService},
 };
 
 /// POST /api/notifications/send
 pub async fn send_unified_notification_endpoint(
+    http_req: HttpRequest,
     body: web::Json<NotificationRequest>,
     notification_service: web::Data<NotificationService>,
     operation_service: web::Data<OperationService>,
+    template_service: web::Data<TemplateService>,
+    pdf_service: web::Data<PdfService>,
 ) -> HttpResponse {
-    let req_body = body.into_inner();
+    let mut req_body = body.into_inner();
+    req_body.tenant = tenant_id(&http_req);
     let op_service_cloned = operation_service.clone();
 
+    // Preflight de los campos `pdf_*` antes de crear la operación
+    let mut errors = req_body
+        .planned_pdf_request()
+        .and_then(|pdf_request| pdf_service.validate_request(&pdf_request).err())
+        .map(ValidationErrors::for_pdf_fields)
+        .unwrap_or_default();
+    req_body.check_link(&mut errors);
+    if let Err(errors) = errors.into_result() {
+        return validation_error(errors);
+    }
+
+    // Fijar la versión de la plantilla antes de crear la operación
+    let pdf_template = match &req_body.pdf_template_id {
+        Some(template_id) => {
+            match template_service
+                .pin_version(template_id, req_body.pdf_template_version)
+                .await
+            {
+                Ok(pinned) => {
+                    req_body.pdf_template_id = Some(pinned.template_id.clone());
+                    req_body.pdf_template_version = Some(pinned.version);
+                    Some(pinned)
+                }
+                Err(e) => {
+                    let mut response = if is_not_found(&e) {
+                        HttpResponse::NotFound()
+                    } else {
+                        HttpResponse::InternalServerError()
+                    };
+                    return response.json(json!({
+                        "success": false,
+                        "error": format!("Template Error: {}", e)
+                    }));
+                }
+            }
+        }
+        None => None,
+    };
+
     // Crear la operación
     let create_op_req = CreateOperationRequest {
         operation_type: "send_notification".to_string(),
@@ -25,7 +77,16 @@ pub async fn send_unified_notification_endpoint(
         metadata: Some(
             serde_json::json!({
                 "channels": req_body.channels,
-                "pdf_planned": req_body.pdf_html.is_some(),
+                "pdf_planned": req_body.pdf_html.is_some() || req_body.pdf_template_id.is_some(),
+                "pdf_template": pdf_template.map(|t| {
+                    let mut metadata = render_metadata(
+                        &t.template_id,
+                        t.version,
+                        req_body.pdf_data.as_ref(),
+                    );
+                    metadata["name"] = json!(t.template_name);
+                    metadata
+                }),
                 "has_attachments": req_body.other_attachments.as_ref().map(|v| v.len()).unwrap_or(0)
             })
             .to_string(),
@@ -75,13 +136,14 @@ pub async fn send_unified_notification_endpoint(
                 message: "Notification processed successfully".to_string(),
             }),
             Err(e) => {
+                error!("{:?}", e);
                 let _ = operation_service
                     .mark_operation_failed(&op_id, format!("Send failed: {}", e))
                     .await;
                 HttpResponse::InternalServerError().json(json!({
                     "success": false,
                     "operation_id": op_id,
-                    "error": format!("{:?}", e)
+                    "error": e.to_string()
                 }))
             }
         }
@@ -1,8 +1,10 @@
 //! handlers/operation_handler.rs
 use actix_web::{web, HttpResponse};
+use log::error;
 use serde::Deserialize;
 
 use crate::models::operation_model::CreateOperationRequest;
+use crate::services::error::is_not_found;
 use crate::services::operation_service::OperationService;
 
 #[derive(Deserialize)]
@@ -18,10 +20,13 @@ pub async fn create_operation_endpoint(
 ) -> HttpResponse {
     match op_service.create_operation(body.into_inner()).await {
         Ok(resp) => HttpResponse::Ok().json(resp),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
-            "error": "Internal server error",
-            "details": format!("{:?}", e)
-        })),
+        Err(e) => {
+            error!("{:?}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Internal server error",
+                "details": e.to_string()
+            }))
+        }
     }
 }
 
@@ -35,10 +40,13 @@ pub async fn list_operations_endpoint(
 
     match op_service.list_operations(page, page_size).await {
         Ok(list) => HttpResponse::Ok().json(list),
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
-            "error": "Internal server error",
-            "details": format!("{:?}", e)
-        })),
+        Err(e) => {
+            error!("{:?}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Internal server error",
+                "details": e.to_string()
+            }))
+        }
     }
 }
 
@@ -51,9 +59,16 @@ pub async fn get_operation_endpoint(
 
     match op_service.get_operation(&op_id).await {
         Ok(op_record) => HttpResponse::Ok().json(op_record),
-        Err(e) => HttpResponse::NotFound().json(serde_json::json!({
+        Err(e) if is_not_found(&e) => HttpResponse::NotFound().json(serde_json::json!({
             "error": "Operation not found",
-            "details": format!("{:?}", e)
+            "details": e.to_string()
         })),
+        Err(e) => {
+            error!("{:?}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Internal server error",
+                "details": e.to_string()
+            }))
+        }
     }
 }
@@ -1,63 +1,813 @@
 //! handlers/pdf_handler.rs
-//! Endpoint para generar PDFs.
+//! Endpoints para generar PDFs y para unirlos/dividirlos.
 
-use std::path::PathBuf;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
 
-use actix_files::NamedFile;
-use actix_web::{web, HttpResponse};
+use actix_files::file_extension_to_mime;
+use actix_multipart::Multipart;
+use actix_web::{web, HttpRequest, HttpResponse, HttpResponseBuilder};
+use futures_util::StreamExt;
 use log::error;
+use serde_json::json;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
 
-use crate::models::pdf_model::{PdfRequest, PdfResponse};
+use crate::handlers::{tenant_id, validation_error};
+use crate::models::document_model::DocumentRecord;
+use crate::models::pdf_model::{
+    MergePdfRequest, PdfAssetBundle, PdfInputDocument, PdfRenderEnvelope, PdfRenderReport,
+    PdfRequest, PdfResponse, PdfResponseFormat, PdfThumbnailRequest, SplitPdfRequest,
+};
+use crate::services::document_service::DocumentService;
+use crate::services::error::{is_invalid_input, is_not_found};
+use crate::services::pdf_job_service::{self, PdfJobResult, PdfJobService};
 use crate::services::pdf_service::PdfService;
+use crate::services::pdf_tools::{
+    self,
+    merge::{self, MergePart},
+    page_ranges, watermark,
+};
+use crate::services::template_service::TemplateService;
+
+/// Como mucho se envían estos avisos como headers `X-Render-Warning`
+const MAX_WARNING_HEADERS: usize = 20;
+/// Largo máximo de cada header `X-Render-Warning`
+const MAX_WARNING_HEADER_LEN: usize = 300;
+/// Límites de `/api/pdf/bundle`, que se aplican mientras llegan los bytes:
+/// el ZIP, el JSON de `options`, cualquier otro campo y el total
+const MAX_BUNDLE_FIELD_BYTES: usize = 100 * 1024 * 1024;
+const MAX_OPTIONS_FIELD_BYTES: usize = 10 * 1024 * 1024;
+const MAX_SMALL_FIELD_BYTES: usize = 4 * 1024;
+const MAX_MULTIPART_BYTES: usize = MAX_BUNDLE_FIELD_BYTES + MAX_OPTIONS_FIELD_BYTES;
 
 /// Recibe una petición POST con un JSON de tipo PdfRequest
 /// y retorna un PDF binario en caso de éxito.
 pub async fn generate_pdf_endpoint(
+    http_req: HttpRequest,
     pdf_service: web::Data<PdfService>,
+    template_service: web::Data<TemplateService>,
+    document_service: web::Data<DocumentService>,
+    job_service: web::Data<PdfJobService>,
     req_body: web::Json<PdfRequest>,
 ) -> HttpResponse {
     log::info!("Entrando a generate_pdf_endpoint");
     // Convertir web::Json<PdfRequest> a la estructura interna
-    let file_name = req_body.file_name.clone();
-    let req_data = req_body.into_inner();
-    //log complete json
+    let mut req_data = req_body.into_inner();
+    req_data.tenant = tenant_id(&http_req);
+
+    render_pdf_response(
+        &pdf_service,
+        &template_service,
+        &document_service,
+        &job_service,
+        req_data,
+    )
+    .await
+}
+
+/// GET /api/pdf/defaults
+/// Orientación, página, márgenes, escala y motor que recibe un request que no los
+/// define; con el header `X-Tenant-Id`, los de ese tenant.
+pub async fn pdf_defaults_endpoint(
+    http_req: HttpRequest,
+    pdf_service: web::Data<PdfService>,
+) -> HttpResponse {
+    match pdf_service.defaults(tenant_id(&http_req).as_deref()) {
+        Ok(defaults) => HttpResponse::Ok().json(json!({
+            "success": true,
+            "defaults": defaults
+        })),
+        Err(e) => HttpResponse::NotFound().json(json!({
+            "success": false,
+            "error": e.to_string()
+        })),
+    }
+}
+
+/// POST /api/pdf/bundle (multipart/form-data)
+/// Campos:
+///  - `bundle`: archivo ZIP con el HTML de entrada y sus recursos (obligatorio, hasta 100 MB)
+///  - `options`: JSON con las opciones de PdfRequest (opcional, hasta 10 MB)
+///  - `entry_point`: HTML de entrada dentro del ZIP (opcional, default `index.html`)
+///
+/// Un campo o un total por encima del límite corta la lectura con 413.
+pub async fn generate_pdf_from_bundle_endpoint(
+    http_req: HttpRequest,
+    pdf_service: web::Data<PdfService>,
+    template_service: web::Data<TemplateService>,
+    document_service: web::Data<DocumentService>,
+    job_service: web::Data<PdfJobService>,
+    mut payload: Multipart,
+) -> HttpResponse {
+    log::info!("Entrando a generate_pdf_from_bundle_endpoint");
+
+    let mut zip = None;
+    let mut options = None;
+    let mut entry_point = None;
+    let mut total_bytes = 0;
+
+    while let Some(field) = payload.next().await {
+        let mut field = match field {
+            Ok(field) => field,
+            Err(e) => return bad_request(format!("Multipart inválido: {}", e)),
+        };
+        let name = field.name().unwrap_or_default().to_string();
+        let field_limit = match name.as_str() {
+            "bundle" => MAX_BUNDLE_FIELD_BYTES,
+            "options" => MAX_OPTIONS_FIELD_BYTES,
+            _ => MAX_SMALL_FIELD_BYTES,
+        };
+
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) => return bad_request(format!("Error leyendo el campo {}: {}", name, e)),
+            };
+            total_bytes += chunk.len();
+            if bytes.len() + chunk.len() > field_limit {
+                return payload_too_large(format!(
+                    "El campo `{}` supera {} bytes",
+                    name, field_limit
+                ));
+            }
+            if total_bytes > MAX_MULTIPART_BYTES {
+                return payload_too_large(format!(
+                    "El formulario supera {} bytes",
+                    MAX_MULTIPART_BYTES
+                ));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+
+        match name.as_str() {
+            "bundle" => zip = Some(bytes),
+            "options" => match serde_json::from_slice::<PdfRequest>(&bytes) {
+                Ok(req) => options = Some(req),
+                Err(e) => return bad_request(format!("`options` inválido: {}", e)),
+            },
+            "entry_point" => entry_point = Some(String::from_utf8_lossy(&bytes).into_owned()),
+            other => log::warn!("Campo multipart ignorado: {}", other),
+        }
+    }
+
+    let Some(zip) = zip else {
+        return bad_request("Falta el campo `bundle` con el ZIP".to_string());
+    };
+
+    let mut req_data = options.unwrap_or_default();
+    let entry_point = entry_point.or_else(|| {
+        req_data
+            .assets_bundle
+            .take()
+            .and_then(|bundle| bundle.entry_point)
+    });
+    req_data.assets_bundle = Some(PdfAssetBundle { zip, entry_point });
+    req_data.tenant = tenant_id(&http_req);
+
+    render_pdf_response(
+        &pdf_service,
+        &template_service,
+        &document_service,
+        &job_service,
+        req_data,
+    )
+    .await
+}
+
+/// Renderiza la plantilla (si la hay), genera el PDF y arma la respuesta binaria.
+/// Con `store_local_pdf` el resultado además se guarda como documento; con `async`
+/// se responde 202 y el render sigue en background.
+async fn render_pdf_response(
+    pdf_service: &PdfService,
+    template_service: &TemplateService,
+    document_service: &DocumentService,
+    job_service: &PdfJobService,
+    mut req_data: PdfRequest,
+) -> HttpResponse {
+    // Preflight: nada llega al motor si el request tiene errores
+    if let Err(errors) = pdf_service.validate_request(&req_data) {
+        return validation_error(errors);
+    }
+
+    let run_async = req_data.run_async.unwrap_or(false);
+    let file_name = req_data.file_name.clone();
+    let response_format = req_data.response_format.unwrap_or_default();
+    let store = req_data
+        .store_local_pdf
+        .unwrap_or(false)
+        .then_some(req_data.store_ttl_seconds);
+
+    // Si viene una plantilla, su render reemplaza al HTML
+    let template_headers = match template_service.apply_to_request(&mut req_data).await {
+        Ok(headers) => headers,
+        Err(e) => {
+            let status_code = if is_not_found(&e) {
+                actix_web::http::StatusCode::NOT_FOUND
+            } else {
+                actix_web::http::StatusCode::BAD_REQUEST
+            };
+            return HttpResponse::build(status_code).json(PdfResponse::error(
+                template_error_code(status_code),
+                e.to_string(),
+            ));
+        }
+    };
+
+    // Render en background: el resultado queda como documento de la operación
+    if run_async {
+        return match job_service.start_job(req_data).await {
+            Ok(op_id) => HttpResponse::Accepted().json(json!({
+                "success": true,
+                "operation_id": op_id,
+                "status_url": format!("/api/operations/{}", op_id),
+                "result_url": pdf_job_service::result_url(&op_id),
+                "message": "PDF queued for async processing"
+            })),
+            Err(e) => HttpResponse::InternalServerError().json(PdfResponse::error(
+                "job_creation_failed",
+                format!("Operation creation failed: {}", e),
+            )),
+        };
+    }
+
+    // Imagen de la página en vez del PDF
+    if let Some(options) = req_data.image.clone() {
+        let file_name = PathBuf::from(&file_name)
+            .with_extension(options.format.extension())
+            .to_string_lossy()
+            .into_owned();
+        return match pdf_service.render_image(&req_data, &options).await {
+            Ok(rendered) => {
+                let mut response = HttpResponse::Ok();
+                if let Some((template_id, version)) = template_headers {
+                    response
+                        .append_header(("X-Template-Id", template_id))
+                        .append_header(("X-Template-Version", version.to_string()));
+                }
+                let content_type = options.format.content_type();
+                let document = match store {
+                    Some(ttl) => match store_document(
+                        document_service,
+                        &mut response,
+                        &file_name,
+                        content_type,
+                        &rendered.image,
+                        ttl,
+                    )
+                    .await
+                    {
+                        Ok(document) => Some(document),
+                        Err(e) => return e,
+                    },
+                    None => None,
+                };
+                rendered_response(
+                    response,
+                    response_format,
+                    content_type,
+                    &file_name,
+                    rendered.image,
+                    rendered.report,
+                    document,
+                )
+            }
+            Err(e) if is_invalid_input(&e) => bad_request(e.to_string()),
+            Err(e) => {
+                error!("Error generando imagen: {:?}", e);
+                HttpResponse::InternalServerError().json(PdfResponse::error(
+                    "image_failed",
+                    format!("Failed to generate image: {}", e),
+                ))
+            }
+        };
+    }
 
     // Llamar a la lógica de generación
-    match pdf_service.generate_pdf(req_data).await {
-        Ok(pdf_bytes) => {
+    match pdf_service.render_pdf(req_data).await {
+        Ok(rendered) => {
+            let pdf_bytes = rendered.pdf;
             // Podríamos retornar un HttpResponse::Ok()
             // con header Content-Type: application/pdf
-            HttpResponse::Ok()
-                .append_header(("Content-Type", "application/pdf"))
-                .append_header((
-                    "Content-Disposition",
-                    format!("inline; filename=\"{}\"", file_name),
-                ))
-                .append_header(("Cache-Control", "public, must-revalidate, max-age=0"))
-                .append_header(("Pragma", "public"))
-                .append_header(("Content-Length", pdf_bytes.len().to_string()))
-                .body(pdf_bytes)
+            let mut response = HttpResponse::Ok();
+            if let Some(status) = rendered.cache {
+                response.append_header(("X-Cache", status.as_str()));
+            }
+            if let Some((template_id, version)) = template_headers {
+                response
+                    .append_header(("X-Template-Id", template_id))
+                    .append_header(("X-Template-Version", version.to_string()));
+            }
+            let document = match store {
+                Some(ttl) => match store_document(
+                    document_service,
+                    &mut response,
+                    &file_name,
+                    "application/pdf",
+                    &pdf_bytes,
+                    ttl,
+                )
+                .await
+                {
+                    Ok(document) => Some(document),
+                    Err(e) => return e,
+                },
+                None => None,
+            };
+            rendered_response(
+                response,
+                response_format,
+                "application/pdf",
+                &file_name,
+                pdf_bytes,
+                rendered.report,
+                document,
+            )
+        }
+        // Perfil de firma inexistente
+        Err(e) if is_not_found(&e) => {
+            HttpResponse::NotFound().json(PdfResponse::error("not_found", e.to_string()))
         }
+        // `source_url` hacia la red interna
+        Err(e) if is_invalid_input(&e) => bad_request(e.to_string()),
         Err(e) => {
             error!("Error generando PDF: {:?}", e);
-            HttpResponse::InternalServerError().json(PdfResponse {
-                success: false,
-                message: format!("Failed to generate PDF: {:?}", e),
-            })
+            HttpResponse::InternalServerError().json(PdfResponse::error(
+                "render_failed",
+                format!("Failed to generate PDF: {}", e),
+            ))
+        }
+    }
+}
+
+/// GET /api/pdf/jobs/{op_id}/result
+/// Descarga el resultado de un render asíncrono; `202` mientras sigue en proceso.
+pub async fn download_pdf_job_result_endpoint(
+    job_service: web::Data<PdfJobService>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let op_id = path.into_inner();
+    match job_service.get_result(&op_id).await {
+        Ok(PdfJobResult::Ready { document, bytes }) => binary_response(
+            HttpResponse::Ok(),
+            &document.content_type,
+            &document.file_name,
+            bytes,
+        ),
+        Ok(PdfJobResult::Pending(status)) => HttpResponse::Accepted().json(json!({
+            "operation_id": op_id,
+            "status": status,
+            "message": "PDF not ready yet"
+        })),
+        Ok(PdfJobResult::Failed(error_message)) => HttpResponse::Conflict().json(json!({
+            "error": "PDF generation failed",
+            "details": error_message
+        })),
+        Ok(PdfJobResult::Expired) => HttpResponse::Gone().json(json!({
+            "error": "PDF expired or deleted"
+        })),
+        Err(e) if is_not_found(&e) => HttpResponse::NotFound().json(json!({
+            "error": "PDF job not found",
+            "details": e.to_string()
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+            "error": "Internal server error",
+            "details": e.to_string()
+        })),
+    }
+}
+
+/// Guarda el resultado como documento y agrega `X-Document-Id` y `X-Operation-Id`
+/// a la respuesta. El error ya viene como respuesta HTTP.
+async fn store_document(
+    document_service: &DocumentService,
+    response: &mut HttpResponseBuilder,
+    file_name: &str,
+    content_type: &str,
+    bytes: &[u8],
+    ttl_seconds: Option<u64>,
+) -> Result<DocumentRecord, HttpResponse> {
+    match document_service
+        .store_generated(file_name, content_type, bytes, ttl_seconds)
+        .await
+    {
+        Ok(document) => {
+            response
+                .append_header(("X-Document-Id", document.id.clone()))
+                .append_header(("X-Operation-Id", document.operation_id.clone()));
+            Ok(document)
+        }
+        Err(e) => {
+            error!("Error guardando documento: {:?}", e);
+            Err(HttpResponse::InternalServerError().json(PdfResponse::error(
+                "store_failed",
+                format!("Failed to store document: {}", e),
+            )))
+        }
+    }
+}
+
+/// POST /api/pdf/merge
+/// Concatena PDFs renderizados (`render`) y subidos (`pdf_base64`) en un solo archivo.
+/// Con `watermark`, la marca se aplica al resultado (sirve también con un solo PDF subido).
+pub async fn merge_pdf_endpoint(
+    http_req: HttpRequest,
+    pdf_service: web::Data<PdfService>,
+    template_service: web::Data<TemplateService>,
+    body: web::Json<MergePdfRequest>,
+) -> HttpResponse {
+    let body = body.into_inner();
+    let tenant = tenant_id(&http_req);
+    if body.documents.is_empty() {
+        return bad_request("Se requiere al menos un documento en `documents`".to_string());
+    }
+
+    let mut inputs = Vec::with_capacity(body.documents.len());
+    for (index, input) in body.documents.into_iter().enumerate() {
+        let field = format!("documents[{}]", index);
+        match load_input_document(
+            &pdf_service,
+            &template_service,
+            tenant.as_deref(),
+            &field,
+            input,
+        )
+        .await
+        {
+            Ok(loaded) => inputs.push(loaded),
+            Err(response) => {
+                log::error!("merge: falló el documento {}", index + 1);
+                return response;
+            }
+        }
+    }
+
+    let stamp = body.watermark;
+    let result = web::block(move || {
+        let mut parts = Vec::with_capacity(inputs.len());
+        for (bytes, pages) in inputs {
+            let document = pdf_tools::load_pdf(&bytes)?;
+            let pages = match pages {
+                Some(spec) => Some(page_ranges::parse_page_ranges(
+                    &spec,
+                    document.get_pages().len() as u32,
+                )?),
+                None => None,
+            };
+            parts.push(MergePart { document, pages });
         }
+        let mut merged = merge::merge_documents(parts)?;
+        if let Some(stamp) = &stamp {
+            watermark::apply_watermark(&mut merged, stamp)?;
+        }
+        pdf_tools::save_pdf(&mut merged)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(pdf_bytes)) => binary_response(
+            HttpResponse::Ok(),
+            "application/pdf",
+            &body.file_name,
+            pdf_bytes,
+        ),
+        Ok(Err(e)) => bad_request(e.to_string()),
+        Err(e) => HttpResponse::InternalServerError().json(PdfResponse::error(
+            "merge_failed",
+            format!("Failed to merge PDF: {}", e),
+        )),
+    }
+}
+
+/// POST /api/pdf/split
+/// Extrae rangos de páginas. Un solo rango responde un PDF; varios (o ninguno,
+/// que equivale a una página por archivo) responden un ZIP.
+pub async fn split_pdf_endpoint(
+    http_req: HttpRequest,
+    pdf_service: web::Data<PdfService>,
+    template_service: web::Data<TemplateService>,
+    body: web::Json<SplitPdfRequest>,
+) -> HttpResponse {
+    let body = body.into_inner();
+    let tenant = tenant_id(&http_req);
+    let (bytes, _) = match load_input_document(
+        &pdf_service,
+        &template_service,
+        tenant.as_deref(),
+        "document",
+        body.document,
+    )
+    .await
+    {
+        Ok(loaded) => loaded,
+        Err(response) => return response,
+    };
+
+    let stem = body
+        .file_name
+        .trim_end_matches(".pdf")
+        .trim_end_matches(".PDF")
+        .to_string();
+    let ranges = body.ranges;
+
+    let part_stem = stem.clone();
+    let result = web::block(move || {
+        // Se parsea una sola vez; cada rango trabaja sobre una copia en memoria
+        let source = pdf_tools::load_pdf(&bytes)?;
+        let page_count = source.get_pages().len() as u32;
+        let ranges = ranges.unwrap_or_else(|| (1..=page_count).map(|p| p.to_string()).collect());
+
+        let mut outputs = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            let pages = page_ranges::parse_page_ranges(&range, page_count)?;
+            let part = MergePart {
+                document: source.clone(),
+                pages: Some(pages),
+            };
+            let mut extracted = merge::merge_documents(vec![part])?;
+            let name = format!("{}_{}.pdf", part_stem, range.replace([',', ' '], "_"));
+            outputs.push((name, pdf_tools::save_pdf(&mut extracted)?));
+        }
+        Ok::<_, anyhow::Error>(outputs)
+    })
+    .await;
+
+    let mut outputs = match result {
+        Ok(Ok(outputs)) => outputs,
+        Ok(Err(e)) => return bad_request(e.to_string()),
+        Err(e) => {
+            return HttpResponse::InternalServerError().json(PdfResponse::error(
+                "split_failed",
+                format!("Failed to split PDF: {}", e),
+            ))
+        }
+    };
+
+    if outputs.len() == 1 {
+        let (_, pdf_bytes) = outputs.remove(0);
+        return binary_response(
+            HttpResponse::Ok(),
+            "application/pdf",
+            &body.file_name,
+            pdf_bytes,
+        );
+    }
+
+    match zip_files(outputs) {
+        Ok(zip_bytes) => binary_response(
+            HttpResponse::Ok(),
+            "application/zip",
+            &format!("{}.zip", stem),
+            zip_bytes,
+        ),
+        Err(e) => HttpResponse::InternalServerError().json(PdfResponse::error(
+            "split_failed",
+            format!("Failed to split PDF: {}", e),
+        )),
+    }
+}
+
+/// POST /api/pdf/thumbnail
+/// Miniatura PNG/JPEG de una página de un PDF subido (`pdf_base64`) o renderizado.
+pub async fn thumbnail_pdf_endpoint(
+    http_req: HttpRequest,
+    pdf_service: web::Data<PdfService>,
+    template_service: web::Data<TemplateService>,
+    body: web::Json<PdfThumbnailRequest>,
+) -> HttpResponse {
+    let body = body.into_inner();
+    let tenant = tenant_id(&http_req);
+    if let Err(errors) = body.validate() {
+        return validation_error(errors);
+    }
+    let (bytes, _) = match load_input_document(
+        &pdf_service,
+        &template_service,
+        tenant.as_deref(),
+        "document",
+        body.document,
+    )
+    .await
+    {
+        Ok(loaded) => loaded,
+        Err(response) => return response,
+    };
+
+    let quality = body.quality.unwrap_or(90);
+    match pdf_service
+        .thumbnail(
+            &bytes,
+            body.page.unwrap_or(1),
+            body.width.unwrap_or(300),
+            body.format,
+            quality,
+        )
+        .await
+    {
+        Ok(image) => {
+            let file_name = PathBuf::from(&body.file_name)
+                .with_extension(body.format.extension())
+                .to_string_lossy()
+                .into_owned();
+            binary_response(
+                HttpResponse::Ok(),
+                body.format.content_type(),
+                &file_name,
+                image,
+            )
+        }
+        Err(e) if is_invalid_input(&e) => bad_request(e.to_string()),
+        Err(e) => {
+            error!("Error generando miniatura: {:?}", e);
+            HttpResponse::InternalServerError().json(PdfResponse::error(
+                "thumbnail_failed",
+                format!("Failed to generate thumbnail: {}", e),
+            ))
+        }
+    }
+}
+
+/// Obtiene los bytes de un documento de entrada (renderizándolo si hace falta)
+/// y su rango de páginas. El `render` hereda el tenant del header. El error ya
+/// viene como respuesta HTTP; los errores de validación del `render` llevan la
+/// ruta `field` (`documents[2]`, `document`).
+async fn load_input_document(
+    pdf_service: &PdfService,
+    template_service: &TemplateService,
+    tenant: Option<&str>,
+    field: &str,
+    input: PdfInputDocument,
+) -> Result<(Vec<u8>, Option<String>), HttpResponse> {
+    let bytes = match (input.render, input.pdf) {
+        (Some(mut req), None) => {
+            req.tenant = tenant.map(str::to_string);
+            // Mismo preflight que /api/pdf
+            if let Err(errors) = pdf_service.validate_request(&req) {
+                return Err(validation_error(
+                    errors.with_prefix(&format!("{}.render", field)),
+                ));
+            }
+            if let Err(e) = template_service.apply_to_request(&mut req).await {
+                let status_code = if is_not_found(&e) {
+                    actix_web::http::StatusCode::NOT_FOUND
+                } else {
+                    actix_web::http::StatusCode::BAD_REQUEST
+                };
+                return Err(HttpResponse::build(status_code).json(PdfResponse::error(
+                    template_error_code(status_code),
+                    e.to_string(),
+                )));
+            }
+            pdf_service.generate_pdf(req).await.map_err(|e| {
+                if is_invalid_input(&e) {
+                    return bad_request(e.to_string());
+                }
+                error!("Error generando PDF: {:?}", e);
+                HttpResponse::InternalServerError().json(PdfResponse::error(
+                    "render_failed",
+                    format!("Failed to generate PDF: {}", e),
+                ))
+            })?
+        }
+        (None, Some(pdf)) => pdf,
+        _ => {
+            return Err(bad_request(
+                "Cada documento necesita `render` o `pdf_base64` (solo uno)".to_string(),
+            ))
+        }
+    };
+    Ok((bytes, input.pages))
+}
+
+/// ZIP en memoria (sin comprimir: los PDFs ya lo están).
+fn zip_files(files: Vec<(String, Vec<u8>)>) -> anyhow::Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    for (name, bytes) in files {
+        zip.start_file(name, options)?;
+        zip.write_all(&bytes)?;
     }
+    Ok(zip.finish()?.into_inner())
+}
+
+/// Respuesta binaria con los mismos headers que `/api/pdf`.
+fn binary_response(
+    mut response: HttpResponseBuilder,
+    content_type: &str,
+    file_name: &str,
+    bytes: Vec<u8>,
+) -> HttpResponse {
+    response
+        .append_header(("Content-Type", content_type.to_string()))
+        .append_header((
+            "Content-Disposition",
+            format!("inline; filename=\"{}\"", file_name),
+        ))
+        .append_header(("Cache-Control", "public, must-revalidate, max-age=0"))
+        .append_header(("Pragma", "public"))
+        .append_header(("Content-Length", bytes.len().to_string()))
+        .body(bytes)
+}
+
+/// Respuesta de un render exitoso: el archivo con el informe del render en headers
+/// (`X-Pdf-*`, `X-Render-*`) o, con `response_format: json`, un sobre con el
+/// archivo en base64 y el informe completo.
+fn rendered_response(
+    mut response: HttpResponseBuilder,
+    format: PdfResponseFormat,
+    content_type: &str,
+    file_name: &str,
+    bytes: Vec<u8>,
+    report: PdfRenderReport,
+    document: Option<DocumentRecord>,
+) -> HttpResponse {
+    if let Some(page_count) = report.page_count {
+        response.append_header(("X-Pdf-Page-Count", page_count.to_string()));
+    }
+    response
+        .append_header(("X-Pdf-Size-Bytes", report.size_bytes.to_string()))
+        .append_header(("X-Render-Time-Ms", report.render_time_ms.to_string()))
+        .append_header(("X-Render-Backend", report.backend.as_str()))
+        .append_header(("X-Render-Warnings", report.warnings.len().to_string()));
+
+    match format {
+        PdfResponseFormat::Binary => {
+            for warning in report.warnings.iter().take(MAX_WARNING_HEADERS) {
+                response.append_header((
+                    "X-Render-Warning",
+                    warning_header(warning.kind.as_str(), &warning.message),
+                ));
+            }
+            binary_response(response, content_type, file_name, bytes)
+        }
+        PdfResponseFormat::Json => response.json(PdfRenderEnvelope {
+            success: true,
+            file_name: file_name.to_string(),
+            content_type: content_type.to_string(),
+            data_base64: base64::encode(&bytes),
+            document_id: document.as_ref().map(|document| document.id.clone()),
+            operation_id: document.map(|document| document.operation_id),
+            report,
+        }),
+    }
+}
+
+/// `kind: mensaje` en ASCII imprimible (lo único válido en un header) y truncado
+fn warning_header(kind: &str, message: &str) -> String {
+    format!("{}: {}", kind, message)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_graphic() || c == ' ' {
+                c
+            } else {
+                '?'
+            }
+        })
+        .take(MAX_WARNING_HEADER_LEN)
+        .collect()
+}
+
+/// Código de error de una plantilla que no se pudo aplicar
+fn template_error_code(status_code: actix_web::http::StatusCode) -> &'static str {
+    if status_code == actix_web::http::StatusCode::NOT_FOUND {
+        "template_not_found"
+    } else {
+        "invalid_template"
+    }
+}
+
+fn bad_request(message: String) -> HttpResponse {
+    HttpResponse::BadRequest().json(PdfResponse::error("invalid_request", message))
+}
+
+fn payload_too_large(message: String) -> HttpResponse {
+    HttpResponse::PayloadTooLarge().json(PdfResponse::error("payload_too_large", message))
 }
 
 /// GET /api/pdf/local/{filename}
-/// Sirve un archivo PDF que haya sido guardado en disco.
+/// Sirve un archivo guardado por su nombre en el almacenamiento (`<id>_<file_name>`).
+/// Es la ruta directa al archivo; lo normal es `GET /api/documents/{id}/content`.
 ///
 /// Ejemplo de URL: http://localhost:5022/api/pdf/local/XXXXX_document.pdf
-pub async fn serve_local_pdf(path: web::Path<String>) -> Result<NamedFile, std::io::Error> {
+pub async fn serve_local_pdf(
+    document_service: web::Data<DocumentService>,
+    path: web::Path<String>,
+) -> HttpResponse {
     let filename = path.into_inner();
-    // Carpeta donde guardamos los PDFs:
-    let pdf_path = format!("./files/pdfs/{}", filename);
-
-    // Actix Files gestiona los headers de Content-Type apropiados.
-    // Retorna 404 si no existe.
-    Ok(NamedFile::open(PathBuf::from(pdf_path))?)
+    match document_service.get_stored_file(&filename).await {
+        Ok(Some(bytes)) => {
+            // Content-Type según la extensión, como lo haría actix-files
+            let extension = Path::new(&filename)
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or_default();
+            let content_type = file_extension_to_mime(extension).to_string();
+            binary_response(HttpResponse::Ok(), &content_type, &filename, bytes)
+        }
+        Ok(None) => HttpResponse::NotFound().json(PdfResponse::error(
+            "file_not_found",
+            format!("File not found: {}", filename),
+        )),
+        Err(e) => bad_request(e.to_string()),
+    }
 }
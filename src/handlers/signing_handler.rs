@@ -0,0 +1,91 @@
+//! handlers/signing_handler.rs
+//! Perfiles de firma digital y verificación de firmas de PDFs.
+
+use actix_web::{web, HttpResponse};
+use log::error;
+use serde_json::json;
+
+use crate::models::signing_model::{CreateSigningProfileRequest, VerifyPdfRequest};
+use crate::services::signing_service::SigningService;
+
+/// POST /api/signing/profiles
+pub async fn create_signing_profile_endpoint(
+    signing_service: web::Data<SigningService>,
+    body: web::Json<CreateSigningProfileRequest>,
+) -> HttpResponse {
+    match signing_service.create_profile(body.into_inner()).await {
+        Ok(profile) => HttpResponse::Created().json(profile),
+        Err(e) => HttpResponse::BadRequest().json(json!({
+            "error": "No se pudo crear el perfil de firma",
+            "details": e.to_string()
+        })),
+    }
+}
+
+/// GET /api/signing/profiles
+pub async fn list_signing_profiles_endpoint(
+    signing_service: web::Data<SigningService>,
+) -> HttpResponse {
+    match signing_service.list_profiles().await {
+        Ok(list) => HttpResponse::Ok().json(list),
+        Err(e) => {
+            error!("{:?}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Internal server error",
+                "details": e.to_string()
+            }))
+        }
+    }
+}
+
+/// GET /api/signing/profiles/{id}
+/// Acepta el id o el nombre del perfil.
+pub async fn get_signing_profile_endpoint(
+    signing_service: web::Data<SigningService>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    match signing_service.get_profile(&path.into_inner()).await {
+        Ok(Some(profile)) => HttpResponse::Ok().json(profile),
+        Ok(None) => HttpResponse::NotFound().json(json!({ "error": "Signing profile not found" })),
+        Err(e) => {
+            error!("{:?}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Internal server error",
+                "details": e.to_string()
+            }))
+        }
+    }
+}
+
+/// DELETE /api/signing/profiles/{id}
+pub async fn delete_signing_profile_endpoint(
+    signing_service: web::Data<SigningService>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    match signing_service.delete_profile(&path.into_inner()).await {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => HttpResponse::NotFound().json(json!({ "error": "Signing profile not found" })),
+        Err(e) => {
+            error!("{:?}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Internal server error",
+                "details": e.to_string()
+            }))
+        }
+    }
+}
+
+/// POST /api/pdf/verify
+/// Verifica las firmas de un PDF subido en base64.
+pub async fn verify_pdf_endpoint(
+    signing_service: web::Data<SigningService>,
+    body: web::Json<VerifyPdfRequest>,
+) -> HttpResponse {
+    match signing_service.verify_pdf(body.into_inner().pdf).await {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(e) => HttpResponse::BadRequest().json(json!({
+            "error": "No se pudo verificar el PDF",
+            "details": e.to_string()
+        })),
+    }
+}
@@ -0,0 +1,264 @@
+//! handlers/template_handler.rs
+//! CRUD de plantillas HTML (Handlebars), versiones y previsualización.
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use log::error;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::handlers::{tenant_id, validation_error};
+use crate::models::template_model::{
+    CreateTemplateRequest, PreviewFormat, RollbackTemplateRequest, TemplatePreviewRequest,
+    UpdateTemplateRequest,
+};
+use crate::services::error::is_not_found;
+use crate::services::pdf_service::PdfService;
+use crate::services::template_service::TemplateService;
+
+#[derive(Deserialize)]
+pub struct PaginationQuery {
+    page: Option<u64>,
+    page_size: Option<u64>,
+}
+
+/// POST /api/templates
+pub async fn create_template_endpoint(
+    template_service: web::Data<TemplateService>,
+    body: web::Json<CreateTemplateRequest>,
+) -> HttpResponse {
+    match template_service.create_template(body.into_inner()).await {
+        Ok(template) => HttpResponse::Created().json(template),
+        Err(e) => HttpResponse::BadRequest().json(json!({
+            "error": "No se pudo crear la plantilla",
+            "details": e.to_string()
+        })),
+    }
+}
+
+/// GET /api/templates
+pub async fn list_templates_endpoint(
+    template_service: web::Data<TemplateService>,
+    query: web::Query<PaginationQuery>,
+) -> HttpResponse {
+    let page = query.page.unwrap_or(1);
+    let page_size = query.page_size.unwrap_or(10);
+
+    match template_service.list_templates(page, page_size).await {
+        Ok(list) => HttpResponse::Ok().json(list),
+        Err(e) => {
+            error!("{:?}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Internal server error",
+                "details": e.to_string()
+            }))
+        }
+    }
+}
+
+/// GET /api/templates/{id}
+/// Acepta el id o el nombre de la plantilla.
+pub async fn get_template_endpoint(
+    template_service: web::Data<TemplateService>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    match template_service.get_template(&path.into_inner()).await {
+        Ok(Some(template)) => HttpResponse::Ok().json(template),
+        Ok(None) => HttpResponse::NotFound().json(json!({ "error": "Template not found" })),
+        Err(e) => {
+            error!("{:?}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Internal server error",
+                "details": e.to_string()
+            }))
+        }
+    }
+}
+
+/// PUT /api/templates/{id}
+pub async fn update_template_endpoint(
+    template_service: web::Data<TemplateService>,
+    path: web::Path<String>,
+    body: web::Json<UpdateTemplateRequest>,
+) -> HttpResponse {
+    match template_service
+        .update_template(&path.into_inner(), body.into_inner())
+        .await
+    {
+        Ok(Some(template)) => HttpResponse::Ok().json(template),
+        Ok(None) => HttpResponse::NotFound().json(json!({ "error": "Template not found" })),
+        Err(e) => HttpResponse::BadRequest().json(json!({
+            "error": "No se pudo actualizar la plantilla",
+            "details": e.to_string()
+        })),
+    }
+}
+
+/// DELETE /api/templates/{id}
+pub async fn delete_template_endpoint(
+    template_service: web::Data<TemplateService>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    match template_service.delete_template(&path.into_inner()).await {
+        Ok(true) => HttpResponse::NoContent().finish(),
+        Ok(false) => HttpResponse::NotFound().json(json!({ "error": "Template not found" })),
+        Err(e) => {
+            error!("{:?}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Internal server error",
+                "details": e.to_string()
+            }))
+        }
+    }
+}
+
+/// GET /api/templates/{id}/versions
+pub async fn list_template_versions_endpoint(
+    template_service: web::Data<TemplateService>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    match template_service.list_versions(&path.into_inner()).await {
+        Ok(Some(versions)) => HttpResponse::Ok().json(versions),
+        Ok(None) => HttpResponse::NotFound().json(json!({ "error": "Template not found" })),
+        Err(e) => {
+            error!("{:?}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Internal server error",
+                "details": e.to_string()
+            }))
+        }
+    }
+}
+
+/// GET /api/templates/{id}/versions/{version}
+pub async fn get_template_version_endpoint(
+    template_service: web::Data<TemplateService>,
+    path: web::Path<(String, i64)>,
+) -> HttpResponse {
+    let (id, version) = path.into_inner();
+    match template_service.get_version(&id, Some(version)).await {
+        Ok(Some(version)) => HttpResponse::Ok().json(version),
+        Ok(None) => HttpResponse::NotFound().json(json!({ "error": "Template version not found" })),
+        Err(e) => {
+            error!("{:?}", e);
+            HttpResponse::InternalServerError().json(json!({
+                "error": "Internal server error",
+                "details": e.to_string()
+            }))
+        }
+    }
+}
+
+/// POST /api/templates/{id}/rollback
+/// Crea una versión nueva con el contenido de la versión indicada.
+pub async fn rollback_template_endpoint(
+    template_service: web::Data<TemplateService>,
+    path: web::Path<String>,
+    body: web::Json<RollbackTemplateRequest>,
+) -> HttpResponse {
+    match template_service
+        .rollback(&path.into_inner(), body.version)
+        .await
+    {
+        Ok(Some(template)) => HttpResponse::Ok().json(template),
+        Ok(None) => HttpResponse::NotFound().json(json!({ "error": "Template not found" })),
+        Err(e) if is_not_found(&e) => HttpResponse::NotFound().json(json!({
+            "error": "Template version not found",
+            "details": e.to_string()
+        })),
+        Err(e) => HttpResponse::BadRequest().json(json!({
+            "error": "No se pudo restaurar la plantilla",
+            "details": e.to_string()
+        })),
+    }
+}
+
+/// POST /api/templates/{id}/preview
+/// Renderiza una versión (por defecto la vigente) como HTML o PDF sin crear
+/// una operación. Si no se envían `data` se usan los `sample_data` guardados.
+pub async fn preview_template_endpoint(
+    http_req: HttpRequest,
+    template_service: web::Data<TemplateService>,
+    pdf_service: web::Data<PdfService>,
+    path: web::Path<String>,
+    body: web::Json<TemplatePreviewRequest>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    let body = body.into_inner();
+
+    let data = match body.data {
+        Some(data) => data,
+        None => match template_service.get_template(&id).await {
+            Ok(Some(template)) => template
+                .sample_data
+                .unwrap_or_else(|| serde_json::Value::Object(Default::default())),
+            Ok(None) => {
+                return HttpResponse::NotFound().json(json!({ "error": "Template not found" }))
+            }
+            Err(e) => {
+                error!("{:?}", e);
+                return HttpResponse::InternalServerError().json(json!({
+                    "error": "Internal server error",
+                    "details": e.to_string()
+                }));
+            }
+        },
+    };
+
+    let rendered = match template_service
+        .render_template(&id, body.version, &data)
+        .await
+    {
+        Ok(rendered) => rendered,
+        Err(e) => {
+            let mut response = if is_not_found(&e) {
+                HttpResponse::NotFound()
+            } else {
+                HttpResponse::BadRequest()
+            };
+            return response.json(json!({
+                "error": "No se pudo renderizar la plantilla",
+                "details": e.to_string()
+            }));
+        }
+    };
+
+    let version = rendered.version.to_string();
+    match body.format {
+        PreviewFormat::Html => HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .append_header(("X-Template-Id", rendered.template_id))
+            .append_header(("X-Template-Version", version))
+            .body(rendered.html),
+        PreviewFormat::Pdf => {
+            let mut pdf_req = body.pdf.unwrap_or_default();
+            pdf_req.html = rendered.html;
+            pdf_req.source_url = None;
+            pdf_req.template_id = None;
+            pdf_req.store_local_pdf = Some(false);
+            pdf_req.tenant = tenant_id(&http_req);
+            if let Err(errors) = pdf_service.validate_request(&pdf_req) {
+                return validation_error(errors);
+            }
+            let file_name = pdf_req.file_name.clone();
+
+            match pdf_service.generate_pdf(pdf_req).await {
+                Ok(pdf_bytes) => HttpResponse::Ok()
+                    .append_header(("Content-Type", "application/pdf"))
+                    .append_header((
+                        "Content-Disposition",
+                        format!("inline; filename=\"{}\"", file_name),
+                    ))
+                    .append_header(("X-Template-Id", rendered.template_id))
+                    .append_header(("X-Template-Version", version))
+                    .body(pdf_bytes),
+                Err(e) => {
+                    error!("Error generando preview PDF: {:?}", e);
+                    HttpResponse::InternalServerError().json(json!({
+                        "error": "Failed to generate PDF",
+                        "details": e.to_string()
+                    }))
+                }
+            }
+        }
+    }
+}
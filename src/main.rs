@@ -8,10 +8,17 @@ use services::notification_channel_service::NotificationChannelService;
 use services::notification_service::NotificationService;
 use sqlx::{Pool, Sqlite};
 
+use crate::config::pdf_config::PdfGlobalConfig;
 use crate::logger::init_logger;
+use crate::services::batch_service::BatchService;
+use crate::services::document_service::DocumentService;
+use crate::services::download_link_service::DownloadLinkService;
 use crate::services::email_service::EmailService;
 use crate::services::operation_service::OperationService;
+use crate::services::pdf_job_service::PdfJobService;
 use crate::services::pdf_service::PdfService;
+use crate::services::signing_service::SigningService;
+use crate::services::template_service::TemplateService;
 
 mod app;
 mod config;
@@ -20,6 +27,9 @@ mod logger;
 mod models;
 mod services;
 
+/// Rutas que no piden API key: se autentican por su cuenta (ej. enlaces firmados)
+const PUBLIC_PATH_PREFIX: &str = "/api/public/";
+
 pub struct ApiKeyMiddleware;
 
 impl<S> Transform<S, ServiceRequest> for ApiKeyMiddleware
@@ -59,6 +69,10 @@ where
     }
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
+        if req.path().starts_with(PUBLIC_PATH_PREFIX) {
+            return Box::pin(self.service.call(req));
+        }
+
         let api_key = std::env::var("API_KEY").unwrap_or_default();
 
         if api_key.is_empty() {
@@ -115,10 +129,6 @@ async fn main() -> std::io::Result<()> {
     dotenv().ok(); // Cargar .env al inicio
     init_logger();
 
-    let pdf_service = PdfService::new()
-        .await
-        .expect("No se pudo inicializar PdfService");
-
     // Conectarnos a la DB
     let db_pool = setup_database().await;
 
@@ -126,18 +136,88 @@ async fn main() -> std::io::Result<()> {
     let conn = db_pool.acquire().await.expect("Falló la conexión");
     drop(conn);
 
+    // Perfiles de firma digital (los usa PdfService al firmar)
+    let signing_service =
+        SigningService::new(db_pool.clone()).expect("No se pudo inicializar SigningService");
+
+    // Valores por defecto de los PDFs (PDF_CONFIG_FILE y PDF_DEFAULT_*)
+    let pdf_config =
+        PdfGlobalConfig::from_env().expect("No se pudo cargar la configuración de PDF");
+
+    let pdf_service = PdfService::new(signing_service.clone(), pdf_config)
+        .await
+        .expect("No se pudo inicializar PdfService");
+
     // OperationService
     let operation_service = OperationService::new(db_pool.clone());
     if let Err(e) = operation_service.run_migrations().await {
         panic!("Fallo en migraciones de 'operations': {:?}", e);
     }
 
+    // Contraseñas de perfiles de firma guardadas antes de SIGNING_PROFILE_KEY
+    signing_service
+        .encrypt_stored_passwords()
+        .await
+        .expect("No se pudieron cifrar las contraseñas de los perfiles de firma");
+
     // EmailService
     let email_service = EmailService::new(db_pool.clone(), operation_service.clone());
     if let Err(e) = email_service.run_migrations().await {
         panic!("Fallo en migraciones de 'emails': {:?}", e);
     }
 
+    // Dónde quedan los archivos generados (disco o S3)
+    let storage = services::storage::from_env().expect("Configuración de almacenamiento inválida");
+
+    // Documentos guardados, con limpieza periódica de los expirados
+    let document_service =
+        DocumentService::new(db_pool.clone(), operation_service.clone(), storage.clone());
+    document_service.spawn_expiry_task();
+
+    // Enlaces públicos de descarga de documentos
+    let link_service = DownloadLinkService::new(db_pool.clone(), document_service.clone())
+        .expect("No se pudo inicializar DownloadLinkService");
+
+    // Renders asíncronos de /api/pdf
+    let job_service = PdfJobService::new(
+        pdf_service.clone(),
+        operation_service.clone(),
+        document_service.clone(),
+    );
+
+    // Plantillas HTML
+    let template_service = TemplateService::new(db_pool.clone());
+
+    // PDFs en lote
+    let batch_service = BatchService::new(
+        db_pool.clone(),
+        pdf_service.clone(),
+        template_service.clone(),
+        operation_service.clone(),
+        storage.clone(),
+        document_service.default_ttl_seconds(),
+    );
+    batch_service.spawn_expiry_task();
+    match job_service.fail_interrupted_jobs().await {
+        Ok(0) => {}
+        Ok(n) => log::warn!(
+            "{} PDFs asíncronos interrumpidos por el reinicio quedaron como fallidos",
+            n
+        ),
+        Err(e) => panic!(
+            "No se pudieron cerrar los PDFs asíncronos interrumpidos: {:?}",
+            e
+        ),
+    }
+    match batch_service.fail_interrupted_batches().await {
+        Ok(0) => {}
+        Ok(n) => log::warn!(
+            "{} lotes interrumpidos por el reinicio quedaron como fallidos",
+            n
+        ),
+        Err(e) => panic!("No se pudieron cerrar los lotes interrumpidos: {:?}", e),
+    }
+
     // NUEVO: channel service
     let channel_service = NotificationChannelService::new(db_pool.clone());
 
@@ -148,6 +228,8 @@ async fn main() -> std::io::Result<()> {
         pdf_service.clone(),
         operation_service.clone(),
         channel_service.clone(),
+        template_service.clone(),
+        link_service.clone(),
     );
 
     // Levantar servidor
@@ -162,6 +244,12 @@ async fn main() -> std::io::Result<()> {
             .app_data(web::Data::new(email_service.clone()))
             .app_data(web::Data::new(channel_service.clone()))
             .app_data(web::Data::new(notification_service.clone()))
+            .app_data(web::Data::new(template_service.clone()))
+            .app_data(web::Data::new(batch_service.clone()))
+            .app_data(web::Data::new(signing_service.clone()))
+            .app_data(web::Data::new(document_service.clone()))
+            .app_data(web::Data::new(link_service.clone()))
+            .app_data(web::Data::new(job_service.clone()))
             .configure(app::init_app)
     })
     .workers(1)
@@ -0,0 +1,78 @@
+//! models/batch_model.rs
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::operation_model::OperationRecord;
+use crate::models::pdf_model::PdfRequest;
+
+/// Cómo se entrega el resultado de un lote
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchOutput {
+    /// Un ZIP con todos los PDFs (y `manifest.json`) en la misma respuesta
+    #[default]
+    Zip,
+    /// Operación asíncrona con un estado por documento
+    Operation,
+}
+
+/// Una fila de datos para renderizar la plantilla del lote
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatchTemplateRow {
+    pub file_name: Option<String>,
+    #[serde(default)]
+    pub data: serde_json::Value,
+}
+
+/// POST /api/pdf/batch
+/// Lleva `items` (PdfRequest completos) o `template_id` + `rows`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatchPdfRequest {
+    pub items: Option<Vec<PdfRequest>>,
+
+    pub template_id: Option<String>,
+    pub template_version: Option<i64>,
+    /// Opciones de página comunes a todas las filas de la plantilla
+    pub options: Option<PdfRequest>,
+    pub rows: Option<Vec<BatchTemplateRow>>,
+
+    #[serde(default)]
+    pub output: BatchOutput,
+    /// Nombre del ZIP (solo `output = "zip"`)
+    pub zip_name: Option<String>,
+}
+
+/// Estado de un documento dentro de un lote
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchItemRecord {
+    pub id: String,
+    pub item_index: i64,
+    pub file_name: String,
+    pub status: String, // "pending", "running", "done", "failed", "expired"
+    pub error_message: Option<String>,
+    pub size_bytes: Option<i64>,
+    /// Ruta para descargar el PDF cuando `status = "done"`
+    pub download_url: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// GET /api/pdf/batch/{op_id}
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchStatusResponse {
+    pub operation: OperationRecord,
+    pub total: usize,
+    pub done: usize,
+    pub failed: usize,
+    pub items: Vec<BatchItemRecord>,
+}
+
+/// Entrada de `manifest.json` dentro del ZIP
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchManifestEntry {
+    pub item_index: usize,
+    pub file_name: String,
+    pub status: String,
+    pub error_message: Option<String>,
+}
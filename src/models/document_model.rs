@@ -0,0 +1,82 @@
+//! models/document_model.rs
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::validation::ValidationErrors;
+
+/// Vida máxima que se acepta para un documento o un enlace (10 años)
+pub const MAX_TTL_SECONDS: u64 = 10 * 365 * 24 * 60 * 60;
+
+/// Documento guardado (tabla `documents`). El archivo se descarga en `download_url`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DocumentRecord {
+    pub id: String,
+    pub operation_id: String,
+    pub file_name: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    /// SHA-256 del contenido, en hex
+    pub sha256: String,
+    pub download_url: String,
+    pub created_at: DateTime<Utc>,
+    /// `None` si no expira
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// GET /api/documents
+#[derive(Debug, Clone, Deserialize)]
+pub struct ListDocumentsQuery {
+    pub page: Option<u64>,
+    pub page_size: Option<u64>,
+    /// Solo los documentos de esta operación
+    pub operation_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ListDocumentsResponse {
+    pub total: u64,
+    pub page: u64,
+    pub page_size: u64,
+    pub items: Vec<DocumentRecord>,
+}
+
+/// Enlace público de descarga de un documento (tabla `document_links`)
+#[derive(Debug, Clone, Serialize)]
+pub struct DocumentLinkRecord {
+    pub id: String,
+    pub document_id: String,
+    /// URL firmada; absoluta si está definida `PUBLIC_BASE_URL`
+    pub url: String,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// POST /api/documents/{id}/links
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CreateDocumentLinkRequest {
+    /// Vida del enlace (default `DOWNLOAD_LINK_TTL_SECONDS`); nunca pasa la del documento
+    pub expires_in_seconds: Option<u64>,
+}
+
+impl CreateDocumentLinkRequest {
+    pub fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.check_range(
+            "expires_in_seconds",
+            self.expires_in_seconds,
+            1,
+            MAX_TTL_SECONDS,
+        );
+        errors.into_result()
+    }
+}
+
+/// Query de GET /api/public/links/{link_id}
+#[derive(Debug, Clone, Deserialize)]
+pub struct DocumentLinkQuery {
+    /// Expiración en segundos Unix
+    pub expires: i64,
+    pub signature: String,
+}
@@ -3,7 +3,11 @@
 use base64;
 use serde::{Deserialize, Serialize};
 
-use crate::models::pdf_model::{PaperSize, PdfMargins, PdfOrientation, PdfPagePreset};
+use crate::models::invoice_model::PdfInvoice;
+use crate::models::pdf_model::{
+    PaperSize, PdfBackend, PdfConformance, PdfEncryption, PdfMargins, PdfOrientation,
+    PdfPagePreset, PdfRequest, PdfSignatureOptions, PdfTextHeaderFooter, PdfWatermark,
+};
 
 /// Representa un adjunto cualquiera (PDF, imagen, TXT, etc.) en base64.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,14 +22,14 @@ pub struct EmailAttachment {
     pub data: Vec<u8>,
 }
 
-fn serialize_base64<S>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+pub(crate) fn serialize_base64<S>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error>
 where
     S: serde::Serializer,
 {
     serializer.serialize_str(&base64::encode(data))
 }
 
-fn deserialize_base64<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+pub(crate) fn deserialize_base64<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
@@ -33,6 +37,20 @@ where
     base64::decode(&s).map_err(serde::de::Error::custom)
 }
 
+pub(crate) fn deserialize_optional_base64<'de, D>(
+    deserializer: D,
+) -> Result<Option<Vec<u8>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(s) => base64::decode(&s)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+        None => Ok(None),
+    }
+}
+
 /// Request unificado para enviar correos con o sin PDF + adjuntos.
 #[derive(Debug, Clone, Deserialize)]
 pub struct SendUniversalEmailRequest {
@@ -58,6 +76,15 @@ pub struct SendUniversalEmailRequest {
     /// Si está presente, indica que queremos generar un PDF a partir de este HTML
     pub pdf_html: Option<String>,
 
+    /// Plantilla del servidor (id o nombre) para el PDF; reemplaza a `pdf_html`.
+    pub pdf_template_id: Option<String>,
+
+    /// Versión de la plantilla; si es None, la vigente (queda fijada en la operación).
+    pub pdf_template_version: Option<i64>,
+
+    /// Datos JSON para renderizar `pdf_template_id`.
+    pub pdf_data: Option<serde_json::Value>,
+
     /// Orientación del PDF (portrait o landscape).
     pub pdf_orientation: Option<PdfOrientation>,
 
@@ -70,9 +97,38 @@ pub struct SendUniversalEmailRequest {
     /// Márgenes para el PDF (en mm).
     pub pdf_margins: Option<PdfMargins>,
 
+    /// Página única con el alto del contenido (tickets); solo Chromium.
+    pub pdf_auto_height: Option<bool>,
+
     /// Factor de escala (zoom); si es None, se asume 1.0
     pub pdf_scale: Option<f64>,
 
+    /// Motor de renderizado (wkhtmltopdf o chromium); si es None, el global.
+    pub pdf_backend: Option<PdfBackend>,
+
+    /// Encabezado/pie HTML con placeholders `[page]`, `[total_pages]`, `[date]`, `[title]`.
+    pub pdf_header_html: Option<String>,
+    pub pdf_footer_html: Option<String>,
+
+    /// Encabezado/pie de texto simple (izquierda, centro, derecha).
+    pub pdf_header_text: Option<PdfTextHeaderFooter>,
+    pub pdf_footer_text: Option<PdfTextHeaderFooter>,
+
+    /// Marca de agua del PDF adjunto ("BORRADOR", "PAGADO", ...).
+    pub pdf_watermark: Option<PdfWatermark>,
+
+    /// Adjuntar el PDF como PDF/A (`pdfa_2b` o `pdfa_3b`).
+    pub pdf_conformance: Option<PdfConformance>,
+
+    /// Factura electrónica Factur-X/ZUGFeRD incrustada en el PDF adjunto.
+    pub pdf_invoice: Option<PdfInvoice>,
+
+    /// Contraseñas y permisos del PDF adjunto (AES-256 por defecto).
+    pub pdf_encryption: Option<PdfEncryption>,
+
+    /// Firma digital del PDF adjunto con un perfil de firma del servidor.
+    pub pdf_signature: Option<PdfSignatureOptions>,
+
     /// Nombre con el que se adjuntará el PDF (por defecto: "document.pdf")
     pub pdf_attachment_name: Option<String>,
 
@@ -80,6 +136,69 @@ pub struct SendUniversalEmailRequest {
     // OTROS ADJUNTOS
     // ----------------------------
     pub other_attachments: Option<Vec<EmailAttachment>>,
+
+    /// Tenant (header `X-Tenant-Id`) cuyos valores por defecto completan el PDF
+    #[serde(skip)]
+    pub tenant: Option<String>,
+}
+
+impl SendUniversalEmailRequest {
+    /// PdfRequest del PDF adjunto, con el HTML ya resuelto (inline o de la plantilla)
+    pub fn pdf_request(&self, html: String) -> PdfRequest {
+        PdfRequest {
+            file_name: self
+                .pdf_attachment_name
+                .clone()
+                .unwrap_or_else(|| "document.pdf".to_string()),
+            html,
+            source_url: None,
+            source_options: None,
+            assets_bundle: None,
+            template_id: None,
+            template_version: None,
+            data: None,
+            orientation: self.pdf_orientation.clone(),
+            page_size_preset: self.pdf_page_size_preset.clone(),
+            custom_page_size: self.pdf_custom_page_size.clone(),
+            margins: self.pdf_margins.clone(),
+            auto_height: self.pdf_auto_height,
+            scale: self.pdf_scale,
+            backend: self.pdf_backend,
+            header_html: self.pdf_header_html.clone(),
+            footer_html: self.pdf_footer_html.clone(),
+            header_text: self.pdf_header_text.clone(),
+            footer_text: self.pdf_footer_text.clone(),
+            metadata: None,
+            outline: None,
+            toc: None,
+            watermark: self.pdf_watermark.clone(),
+            conformance: self.pdf_conformance,
+            invoice: self.pdf_invoice.clone(),
+            encryption: self.pdf_encryption.clone(),
+            signature: self.pdf_signature.clone(),
+            render_options: None,
+            image: None,
+            store_local_pdf: Some(false),
+            store_ttl_seconds: None,
+            cache: None,
+            run_async: None,
+            callback_url: None,
+            response_format: None,
+            tenant: self.tenant.clone(),
+        }
+    }
+
+    /// PdfRequest que se va a generar, para validar los campos `pdf_*` antes de
+    /// crear la operación (la plantilla todavía sin renderizar). None si no se pide PDF.
+    pub fn planned_pdf_request(&self) -> Option<PdfRequest> {
+        if self.pdf_html.is_none() && self.pdf_template_id.is_none() {
+            return None;
+        }
+        let mut req = self.pdf_request(self.pdf_html.clone().unwrap_or_default());
+        req.template_id = self.pdf_template_id.clone();
+        req.template_version = self.pdf_template_version;
+        Some(req)
+    }
 }
 
 /// Respuesta al consultar estado de un email/operación
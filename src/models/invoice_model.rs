@@ -0,0 +1,166 @@
+//! models/invoice_model.rs
+//! Factura electrónica Factur-X / ZUGFeRD: el PDF visual lleva incrustado el XML CII.
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+/// Perfil Factur-X (equivalente en ZUGFeRD 2.x).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FacturXProfile {
+    Minimum,
+    #[serde(rename = "basic_wl")]
+    BasicWl,
+    Basic,
+    En16931,
+    Extended,
+}
+
+impl FacturXProfile {
+    pub const ALL: [FacturXProfile; 5] = [
+        FacturXProfile::Minimum,
+        FacturXProfile::BasicWl,
+        FacturXProfile::Basic,
+        FacturXProfile::En16931,
+        FacturXProfile::Extended,
+    ];
+
+    /// `GuidelineSpecifiedDocumentContextParameter` del XML
+    pub fn guideline_id(&self) -> &'static str {
+        match self {
+            FacturXProfile::Minimum => "urn:factur-x.eu:1p0:minimum",
+            FacturXProfile::BasicWl => "urn:factur-x.eu:1p0:basicwl",
+            FacturXProfile::Basic => "urn:cen.eu:en16931:2017#compliant#urn:factur-x.eu:1p0:basic",
+            FacturXProfile::En16931 => "urn:cen.eu:en16931:2017",
+            FacturXProfile::Extended => {
+                "urn:cen.eu:en16931:2017#conformant#urn:factur-x.eu:1p0:extended"
+            }
+        }
+    }
+
+    /// `fx:ConformanceLevel` del XMP
+    pub fn conformance_level(&self) -> &'static str {
+        match self {
+            FacturXProfile::Minimum => "MINIMUM",
+            FacturXProfile::BasicWl => "BASIC WL",
+            FacturXProfile::Basic => "BASIC",
+            FacturXProfile::En16931 => "EN 16931",
+            FacturXProfile::Extended => "EXTENDED",
+        }
+    }
+
+    /// MINIMUM y BASIC WL no son facturas completas: el XML va como `Data`;
+    /// el resto como `Alternative` (representación equivalente al PDF)
+    pub fn af_relationship(&self) -> &'static str {
+        match self {
+            FacturXProfile::Minimum | FacturXProfile::BasicWl => "Data",
+            _ => "Alternative",
+        }
+    }
+
+    /// Si el perfil incluye las líneas de detalle
+    pub fn has_lines(&self) -> bool {
+        !matches!(self, FacturXProfile::Minimum | FacturXProfile::BasicWl)
+    }
+}
+
+/// Factura que se incrusta en el PDF. Lleva `xml` (CII ya armado) o `data`
+/// (datos estructurados a partir de los cuales se genera el XML).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PdfInvoice {
+    /// Default: EN 16931 para `data`; para `xml`, el que declare el propio XML
+    pub profile: Option<FacturXProfile>,
+    /// XML CII (`rsm:CrossIndustryInvoice`) en texto plano
+    pub xml: Option<String>,
+    pub data: Option<InvoiceData>,
+}
+
+/// Datos de la factura (importes en la moneda de la factura, sin descuentos globales).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceData {
+    pub number: String,
+    /// UNTDID 1001: 380 factura (default), 381 nota de crédito, 384 factura rectificativa
+    #[serde(default = "default_type_code")]
+    pub type_code: String,
+    pub issue_date: NaiveDate,
+    pub due_date: Option<NaiveDate>,
+    /// ISO 4217, ej: "EUR"
+    pub currency: String,
+    /// Referencia del comprador (Leitweg-ID en XRechnung)
+    pub buyer_reference: Option<String>,
+    pub order_reference: Option<String>,
+    #[serde(default)]
+    pub notes: Vec<String>,
+    pub seller: InvoiceParty,
+    pub buyer: InvoiceParty,
+    #[serde(default)]
+    pub lines: Vec<InvoiceLine>,
+    pub payment: Option<InvoicePayment>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceParty {
+    pub name: String,
+    /// NIF-IVA, ej: "FR32123456789"
+    pub vat_id: Option<String>,
+    /// Identificador fiscal local (sin prefijo de país)
+    pub tax_id: Option<String>,
+    /// Registro mercantil (SIREN, HRB, ...)
+    pub legal_id: Option<String>,
+    pub email: Option<String>,
+    pub address: InvoiceAddress,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceAddress {
+    pub line1: Option<String>,
+    pub line2: Option<String>,
+    pub postcode: Option<String>,
+    pub city: Option<String>,
+    /// ISO 3166-1 alfa-2, ej: "FR"
+    pub country_code: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoiceLine {
+    pub description: String,
+    pub quantity: f64,
+    /// UN/ECE Rec 20: C62 unidad (default), HUR hora, KGM kilo, ...
+    #[serde(default = "default_unit_code")]
+    pub unit_code: String,
+    /// Precio unitario neto
+    pub unit_price: f64,
+    /// Tipo de IVA en porcentaje, ej: 20
+    pub vat_rate: f64,
+    /// UNCL 5305: S estándar (default), Z tipo cero, E exento, AE inversión del sujeto pasivo
+    #[serde(default = "default_vat_category")]
+    pub vat_category: String,
+    /// Motivo de exención (obligatorio con E y AE)
+    pub vat_exemption_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InvoicePayment {
+    /// UNCL 4461: 58 transferencia SEPA (default), 30 transferencia, 49 domiciliación
+    #[serde(default = "default_payment_means")]
+    pub means_code: String,
+    pub iban: Option<String>,
+    pub bic: Option<String>,
+    pub terms: Option<String>,
+}
+
+fn default_type_code() -> String {
+    "380".to_string()
+}
+
+fn default_unit_code() -> String {
+    "C62".to_string()
+}
+
+fn default_vat_category() -> String {
+    "S".to_string()
+}
+
+fn default_payment_means() -> String {
+    "58".to_string()
+}
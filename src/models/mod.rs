@@ -1,8 +1,15 @@
 //! models/mod.rs
 //! Módulo raíz para modelos/estructuras compartidas.
 
+pub mod batch_model;
+pub mod document_model;
 pub mod email_model;
+pub mod invoice_model;
 pub mod notification_model;
 pub mod operation_channel_model;
 pub mod operation_model;
 pub mod pdf_model;
+pub mod signing_model;
+pub mod template_model;
+pub mod units;
+pub mod validation;
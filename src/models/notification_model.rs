@@ -1,6 +1,12 @@
 use crate::models::{
+    document_model::MAX_TTL_SECONDS,
     email_model::EmailAttachment,
-    pdf_model::{PaperSize, PdfMargins, PdfOrientation, PdfPagePreset},
+    invoice_model::PdfInvoice,
+    pdf_model::{
+        PaperSize, PdfBackend, PdfConformance, PdfEncryption, PdfMargins, PdfOrientation,
+        PdfPagePreset, PdfRequest, PdfSignatureOptions, PdfTextHeaderFooter, PdfWatermark,
+    },
+    validation::ValidationErrors,
 };
 use serde::{Deserialize, Serialize};
 
@@ -24,15 +30,107 @@ pub struct NotificationRequest {
 
     // PDF
     pub pdf_html: Option<String>,
+    pub pdf_template_id: Option<String>,
+    pub pdf_template_version: Option<i64>,
+    pub pdf_data: Option<serde_json::Value>,
     pub pdf_orientation: Option<PdfOrientation>,
     pub pdf_page_size_preset: Option<PdfPagePreset>,
     pub pdf_custom_page_size: Option<PaperSize>,
     pub pdf_margins: Option<PdfMargins>,
+    pub pdf_auto_height: Option<bool>,
     pub pdf_scale: Option<f64>,
+    pub pdf_backend: Option<PdfBackend>,
+    pub pdf_header_html: Option<String>,
+    pub pdf_footer_html: Option<String>,
+    pub pdf_header_text: Option<PdfTextHeaderFooter>,
+    pub pdf_footer_text: Option<PdfTextHeaderFooter>,
+    pub pdf_watermark: Option<PdfWatermark>,
+    pub pdf_conformance: Option<PdfConformance>,
+    pub pdf_invoice: Option<PdfInvoice>,
+    pub pdf_encryption: Option<PdfEncryption>,
+    pub pdf_signature: Option<PdfSignatureOptions>,
     pub pdf_attachment_name: Option<String>,
+    /// Si el PDF pesa más que esto (bytes), se guarda y se envía un enlace de
+    /// descarga en el mensaje en vez del adjunto. Requiere `PUBLIC_BASE_URL`.
+    pub pdf_link_threshold_bytes: Option<u64>,
+    /// Vida del enlace y del documento guardado para él (default `DOWNLOAD_LINK_TTL_SECONDS`)
+    pub pdf_link_expires_in_seconds: Option<u64>,
 
     // Adjuntos
     pub other_attachments: Option<Vec<EmailAttachment>>,
+
+    /// Tenant (header `X-Tenant-Id`) cuyos valores por defecto completan el PDF
+    #[serde(skip)]
+    pub tenant: Option<String>,
+}
+
+impl NotificationRequest {
+    /// Campos del envío como enlace (el resto de los `pdf_*` los valida `PdfService`)
+    pub fn check_link(&self, errors: &mut ValidationErrors) {
+        errors.check_range(
+            "pdf_link_expires_in_seconds",
+            self.pdf_link_expires_in_seconds,
+            1,
+            MAX_TTL_SECONDS,
+        );
+    }
+
+    /// PdfRequest del PDF adjunto, con el HTML ya resuelto (inline o de la plantilla)
+    pub fn pdf_request(&self, html: String) -> PdfRequest {
+        PdfRequest {
+            file_name: self
+                .pdf_attachment_name
+                .clone()
+                .unwrap_or_else(|| "document.pdf".to_string()),
+            html,
+            source_url: None,
+            source_options: None,
+            assets_bundle: None,
+            template_id: None,
+            template_version: None,
+            data: None,
+            orientation: self.pdf_orientation.clone(),
+            page_size_preset: self.pdf_page_size_preset.clone(),
+            custom_page_size: self.pdf_custom_page_size.clone(),
+            margins: self.pdf_margins.clone(),
+            auto_height: self.pdf_auto_height,
+            scale: self.pdf_scale,
+            backend: self.pdf_backend,
+            header_html: self.pdf_header_html.clone(),
+            footer_html: self.pdf_footer_html.clone(),
+            header_text: self.pdf_header_text.clone(),
+            footer_text: self.pdf_footer_text.clone(),
+            metadata: None,
+            outline: None,
+            toc: None,
+            watermark: self.pdf_watermark.clone(),
+            conformance: self.pdf_conformance,
+            invoice: self.pdf_invoice.clone(),
+            encryption: self.pdf_encryption.clone(),
+            signature: self.pdf_signature.clone(),
+            render_options: None,
+            image: None,
+            store_local_pdf: Some(false),
+            store_ttl_seconds: None,
+            cache: None,
+            run_async: None,
+            callback_url: None,
+            response_format: None,
+            tenant: self.tenant.clone(),
+        }
+    }
+
+    /// PdfRequest que se va a generar, para validar los campos `pdf_*` antes de
+    /// crear la operación (la plantilla todavía sin renderizar). None si no se pide PDF.
+    pub fn planned_pdf_request(&self) -> Option<PdfRequest> {
+        if self.pdf_html.is_none() && self.pdf_template_id.is_none() {
+            return None;
+        }
+        let mut req = self.pdf_request(self.pdf_html.clone().unwrap_or_default());
+        req.template_id = self.pdf_template_id.clone();
+        req.template_version = self.pdf_template_version;
+        Some(req)
+    }
 }
 
 /// Config de email
@@ -1,51 +1,660 @@
 //! models/pdf_model.rs
 
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
-/// Márgenes en milímetros.
-#[derive(Debug, Clone, Deserialize, Default)]
+use crate::config::pdf_config::BUILTIN_PAGE_SIZE;
+use crate::models::document_model::MAX_TTL_SECONDS;
+use crate::models::invoice_model::PdfInvoice;
+use crate::models::validation::{FieldErrorCode, ValidationErrors};
+
+/// Lado máximo de una página personalizada (mm)
+pub const MAX_PAGE_SIDE_MM: f64 = 5000.0;
+
+/// Márgenes en milímetros (en el JSON también `"0.5in"`, `"36pt"`, ... ver `units`).
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct PdfMargins {
+    #[serde(deserialize_with = "crate::models::units::deserialize_length")]
     pub top: f64,
+    #[serde(deserialize_with = "crate::models::units::deserialize_length")]
     pub bottom: f64,
+    #[serde(deserialize_with = "crate::models::units::deserialize_length")]
     pub left: f64,
+    #[serde(deserialize_with = "crate::models::units::deserialize_length")]
     pub right: f64,
 }
 
-/// Representa un tamaño personalizado en milímetros (ancho x alto).
-#[derive(Debug, Clone, Deserialize)]
+/// Representa un tamaño personalizado en milímetros (ancho x alto);
+/// en el JSON admite unidades: `{"width": "8.5in", "height": "11in"}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PaperSize {
+    #[serde(deserialize_with = "crate::models::units::deserialize_length")]
     pub width: f64,
+    #[serde(deserialize_with = "crate::models::units::deserialize_length")]
     pub height: f64,
 }
 
 /// Indica la orientación del PDF
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum PdfOrientation {
     Portrait,
     Landscape,
 }
 
+/// Alto de página (mm) de los rollos térmicos cuando no se usa `auto_height`
+const THERMAL_ROLL_HEIGHT_MM: f64 = 297.0;
+
 /// Indica un tamaño predefinido (A4, Letter, etc.),
 /// o "Custom" si el usuario prefiere anchura/altura en `custom_page_size`.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum PdfPagePreset {
+    A3,
     A4,
+    A5,
+    A6,
+    B4,
+    B5,
     Letter,
     Legal,
-    A3,
-    Tabloid, // Agrega más si lo requieres (A3, Tabloid, etc.)
+    Tabloid,
+    Executive,
+    /// Sobre DL (110 x 220 mm)
+    DL,
+    /// Sobre C5 (162 x 229 mm)
+    C5,
+    /// Sobre comercial #10 (4 1/8 x 9 1/2 in)
+    #[serde(rename = "#10", alias = "ENVELOPE_10")]
+    Envelope10,
+    /// Rollo térmico de 58 mm (tickets); con `auto_height` el alto sigue al contenido
+    #[serde(rename = "THERMAL_58")]
+    Thermal58,
+    /// Rollo térmico de 80 mm
+    #[serde(rename = "THERMAL_80")]
+    Thermal80,
+}
+
+impl PdfPagePreset {
+    /// Nombre que entiende wkhtmltopdf en `--page-size`; None si no tiene uno
+    /// (se pasa el tamaño con `--page-width`/`--page-height`).
+    pub fn wkhtmltopdf_name(&self) -> Option<&'static str> {
+        match self {
+            PdfPagePreset::A3 => Some("A3"),
+            PdfPagePreset::A4 => Some("A4"),
+            PdfPagePreset::A5 => Some("A5"),
+            PdfPagePreset::A6 => Some("A6"),
+            PdfPagePreset::B4 => Some("B4"),
+            PdfPagePreset::B5 => Some("B5"),
+            PdfPagePreset::Letter => Some("Letter"),
+            PdfPagePreset::Legal => Some("Legal"),
+            PdfPagePreset::Tabloid => Some("Tabloid"),
+            PdfPagePreset::Executive => Some("Executive"),
+            PdfPagePreset::DL => Some("DLE"),
+            PdfPagePreset::C5 => Some("C5E"),
+            PdfPagePreset::Envelope10 => Some("Comm10E"),
+            PdfPagePreset::Thermal58 | PdfPagePreset::Thermal80 => None,
+        }
+    }
+
+    /// Dimensiones (ancho, alto) en milímetros, en orientación vertical.
+    pub fn dimensions_mm(&self) -> (f64, f64) {
+        match self {
+            PdfPagePreset::A3 => (297.0, 420.0),
+            PdfPagePreset::A4 => (210.0, 297.0),
+            PdfPagePreset::A5 => (148.0, 210.0),
+            PdfPagePreset::A6 => (105.0, 148.0),
+            PdfPagePreset::B4 => (250.0, 353.0),
+            PdfPagePreset::B5 => (176.0, 250.0),
+            PdfPagePreset::Letter => (215.9, 279.4),
+            PdfPagePreset::Legal => (215.9, 355.6),
+            PdfPagePreset::Tabloid => (279.4, 431.8),
+            PdfPagePreset::Executive => (184.15, 266.7),
+            PdfPagePreset::DL => (110.0, 220.0),
+            PdfPagePreset::C5 => (162.0, 229.0),
+            PdfPagePreset::Envelope10 => (104.775, 241.3),
+            PdfPagePreset::Thermal58 => (58.0, THERMAL_ROLL_HEIGHT_MM),
+            PdfPagePreset::Thermal80 => (80.0, THERMAL_ROLL_HEIGHT_MM),
+        }
+    }
+}
+
+/// Encabezado o pie de página de texto simple, alineado a izquierda/centro/derecha.
+/// Admite los placeholders `[page]`, `[total_pages]`, `[date]` y `[title]`.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct PdfTextHeaderFooter {
+    pub left: Option<String>,
+    pub center: Option<String>,
+    pub right: Option<String>,
+}
+
+/// Cookie enviada al cargar `source_url`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PdfCookie {
+    pub name: String,
+    pub value: String,
+}
+
+/// Credenciales HTTP Basic para `source_url`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PdfBasicAuth {
+    pub username: String,
+    pub password: String,
+}
+
+/// Opciones para cargar una página remota (`source_url`).
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct PdfSourceOptions {
+    /// Cabeceras HTTP adicionales para la petición de la página (ordenadas, así
+    /// el mismo request da siempre la misma clave de caché).
+    pub headers: Option<BTreeMap<String, String>>,
+    pub cookies: Option<Vec<PdfCookie>>,
+    pub basic_auth: Option<PdfBasicAuth>,
+    /// Espera (ms) después de cargar la página, para que termine el JavaScript.
+    pub javascript_delay_ms: Option<u64>,
+    /// Espera hasta que `window.status` tenga este valor antes de imprimir.
+    pub window_status: Option<String>,
+}
+
+/// Bundle ZIP (HTML + CSS + imágenes + fuentes) a renderizar.
+/// Se extrae en el directorio temporal del request, así las rutas relativas funcionan.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PdfAssetBundle {
+    /// ZIP codificado en base64
+    #[serde(
+        rename = "zip_base64",
+        deserialize_with = "crate::models::email_model::deserialize_base64"
+    )]
+    pub zip: Vec<u8>,
+
+    /// HTML de entrada dentro del ZIP (default: `index.html`)
+    pub entry_point: Option<String>,
+}
+
+/// Metadatos del documento (diccionario `Info` del PDF).
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct PdfMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<Vec<String>>,
+    /// Aplicación que creó el contenido original
+    pub creator: Option<String>,
+}
+
+/// Índice (tabla de contenidos) generado a partir de los encabezados `h1`–`h6`,
+/// insertado al inicio del documento.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct PdfTocOptions {
+    /// Título de la página del índice (default: "Índice")
+    pub title: Option<String>,
+    /// Nivel máximo de encabezado incluido, de 1 a 6 (default: 3)
+    pub max_depth: Option<u8>,
+}
+
+impl PdfTocOptions {
+    pub fn title(&self) -> &str {
+        self.title.as_deref().unwrap_or("Índice")
+    }
+
+    pub fn max_depth(&self) -> u8 {
+        self.max_depth.unwrap_or(3).clamp(1, 6)
+    }
+}
+
+/// Algoritmo de cifrado del PDF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PdfEncryptionAlgorithm {
+    /// AES-128 (PDF 1.6, compatible con lectores antiguos)
+    Aes128,
+    /// AES-256 (PDF 2.0)
+    #[default]
+    Aes256,
+}
+
+/// Permisos del documento para quien lo abre con la contraseña de usuario.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PdfPermissions {
+    #[serde(default = "default_true")]
+    pub print: bool,
+    /// Copiar texto e imágenes
+    #[serde(default = "default_true")]
+    pub copy: bool,
+    /// Modificar, anotar, rellenar formularios y reordenar páginas
+    #[serde(default = "default_true")]
+    pub modify: bool,
+}
+
+impl Default for PdfPermissions {
+    fn default() -> Self {
+        Self {
+            print: true,
+            copy: true,
+            modify: true,
+        }
+    }
+}
+
+/// Protección con contraseña del PDF generado.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PdfEncryption {
+    /// Contraseña para abrir el documento. Sin ella se abre libremente,
+    /// pero con los permisos restringidos.
+    pub user_password: Option<String>,
+    /// Contraseña con todos los permisos; si es None se genera una aleatoria.
+    pub owner_password: Option<String>,
+    #[serde(default)]
+    pub algorithm: PdfEncryptionAlgorithm,
+    #[serde(default)]
+    pub permissions: PdfPermissions,
+}
+
+/// Firma visible: rectángulo en milímetros medido desde la esquina superior izquierda.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PdfVisibleSignature {
+    /// Página (1-based); si es None, la última
+    pub page: Option<u32>,
+    #[serde(deserialize_with = "crate::models::units::deserialize_length")]
+    pub x: f64,
+    #[serde(deserialize_with = "crate::models::units::deserialize_length")]
+    pub y: f64,
+    #[serde(deserialize_with = "crate::models::units::deserialize_length")]
+    pub width: f64,
+    #[serde(deserialize_with = "crate::models::units::deserialize_length")]
+    pub height: f64,
+    /// Texto del sello; por defecto, firmante, fecha, motivo y lugar
+    pub text: Option<String>,
+}
+
+/// Firma digital PAdES con un perfil de firma del servidor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PdfSignatureOptions {
+    /// Perfil de firma (id o nombre), ver `/api/signing/profiles`
+    pub profile_id: String,
+    pub reason: Option<String>,
+    pub location: Option<String>,
+    pub contact_info: Option<String>,
+    /// Sello visible en una página; si es None, la firma es invisible
+    pub visible: Option<PdfVisibleSignature>,
+    /// Agrega un sello de tiempo RFC 3161 de la TSA configurada (`TSA_URL`)
+    #[serde(default)]
+    pub timestamp: bool,
+}
+
+/// Nivel de conformidad PDF/A (ISO 19005) del archivo generado.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PdfConformance {
+    /// PDF/A-2b: conformidad visual, sin archivos adjuntos
+    #[serde(rename = "pdfa_2b")]
+    PdfA2b,
+    /// PDF/A-3b: como PDF/A-2b, pero admite adjuntos de cualquier tipo
+    #[serde(rename = "pdfa_3b")]
+    PdfA3b,
+}
+
+impl PdfConformance {
+    /// Parte de la norma (`pdfaid:part`)
+    pub fn part(&self) -> u8 {
+        match self {
+            PdfConformance::PdfA2b => 2,
+            PdfConformance::PdfA3b => 3,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PdfConformance::PdfA2b => "PDF/A-2b",
+            PdfConformance::PdfA3b => "PDF/A-3b",
+        }
+    }
+}
+
+/// Posición de la marca de agua en la página.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PdfWatermarkPosition {
+    #[default]
+    Center,
+    TopLeft,
+    TopCenter,
+    TopRight,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+}
+
+/// Marca de agua o sello ("BORRADOR", "COPIA", "PAGADO", ...) superpuesto a las
+/// páginas después de renderizar. Lleva `text` o `image_base64`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PdfWatermark {
+    pub text: Option<String>,
+    /// Imagen PNG o JPEG codificada en base64
+    #[serde(
+        default,
+        rename = "image_base64",
+        deserialize_with = "crate::models::email_model::deserialize_optional_base64"
+    )]
+    pub image: Option<Vec<u8>>,
+    /// Ancho de la imagen en mm (default: su tamaño a 96 dpi, sin salirse de la página)
+    #[serde(
+        default,
+        deserialize_with = "crate::models::units::deserialize_optional_length"
+    )]
+    pub width: Option<f64>,
+    /// Tamaño de letra del texto en puntos (default: 60)
+    pub font_size: Option<f64>,
+    /// Color del texto, `#RRGGBB` (default: `#808080`)
+    pub color: Option<String>,
+    /// De 0 (invisible) a 1 (opaca); default 0.3
+    pub opacity: Option<f64>,
+    /// Grados en sentido antihorario (default: 45 para texto, 0 para imagen)
+    pub rotation: Option<f64>,
+    #[serde(default)]
+    pub position: PdfWatermarkPosition,
+    /// Páginas afectadas, ej: "1-3,5,8-" (default: todas)
+    pub pages: Option<String>,
+}
+
+impl PdfWatermark {
+    fn validate(&self, errors: &mut ValidationErrors) {
+        match (&self.text, &self.image) {
+            (None, None) => errors.add(
+                "watermark",
+                FieldErrorCode::Required,
+                "lleva `text` o `image_base64`",
+            ),
+            (Some(text), None) if text.trim().is_empty() => errors.add(
+                "watermark.text",
+                FieldErrorCode::Required,
+                "no puede estar vacío",
+            ),
+            _ => {}
+        }
+        errors.check_range("watermark.opacity", self.opacity, 0.0, 1.0);
+        errors.check_range("watermark.font_size", self.font_size, 1.0, 500.0);
+        errors.check_range("watermark.width", self.width, 1.0, MAX_PAGE_SIDE_MM);
+        errors.check_range("watermark.rotation", self.rotation, -360.0, 360.0);
+        if let Some(color) = &self.color {
+            let hex = color.trim().trim_start_matches('#');
+            if !matches!(hex.len(), 3 | 6) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                errors.add(
+                    "watermark.color",
+                    FieldErrorCode::Invalid,
+                    "se espera un color #RRGGBB",
+                );
+            }
+        }
+    }
+
+    pub fn font_size(&self) -> f64 {
+        self.font_size.unwrap_or(60.0)
+    }
+
+    pub fn opacity(&self) -> f64 {
+        self.opacity.unwrap_or(0.3).clamp(0.0, 1.0)
+    }
+
+    pub fn rotation(&self) -> f64 {
+        let default = if self.image.is_some() { 0.0 } else { 45.0 };
+        self.rotation.unwrap_or(default)
+    }
+}
+
+/// Formato de imagen de salida.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    #[default]
+    Png,
+    #[serde(alias = "jpg")]
+    Jpeg,
+    Webp,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Webp => "webp",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Webp => "image/webp",
+        }
+    }
+}
+
+/// Recorte en píxeles CSS, medido desde la esquina superior izquierda de la página.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageCrop {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Captura de la página como imagen en vez de PDF.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PdfImageOptions {
+    #[serde(default)]
+    pub format: ImageFormat,
+    /// Ancho de la ventana en píxeles CSS (default: 1024)
+    pub width: Option<u32>,
+    /// Alto de la ventana; si es None se captura la página completa
+    pub height: Option<u32>,
+    /// Resolución: 96 (default) es un píxel por píxel CSS, 192 el doble
+    pub dpi: Option<u32>,
+    /// Calidad de JPEG y WebP, de 1 a 100 (default: 90)
+    pub quality: Option<u8>,
+    pub crop: Option<ImageCrop>,
+}
+
+impl PdfImageOptions {
+    fn validate(&self, errors: &mut ValidationErrors) {
+        errors.check_range("image.width", self.width, 1, 10_000);
+        errors.check_range("image.height", self.height, 1, 100_000);
+        errors.check_range("image.dpi", self.dpi, 24, 960);
+        errors.check_range("image.quality", self.quality, 1, 100);
+        if let Some(crop) = &self.crop {
+            errors.check_range("image.crop.width", Some(crop.width), 1, 10_000);
+            errors.check_range("image.crop.height", Some(crop.height), 1, 100_000);
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width.unwrap_or(1024)
+    }
+
+    /// Píxeles de la imagen por píxel CSS
+    pub fn scale(&self) -> f64 {
+        f64::from(self.dpi.unwrap_or(96)) / 96.0
+    }
+
+    pub fn quality(&self) -> u8 {
+        self.quality.unwrap_or(90).clamp(1, 100)
+    }
+}
+
+/// Tipo de medio CSS con el que se renderiza la página.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PdfMediaType {
+    /// Reglas `@media print` (default)
+    #[default]
+    Print,
+    /// Como se ve en pantalla
+    Screen,
+}
+
+/// Opciones finas del motor de renderizado. Si es None, cada una conserva el
+/// comportamiento por defecto; las que el motor elegido no soporta se rechazan.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct PdfRenderOptions {
+    /// Imprime colores e imágenes de fondo (default: true)
+    pub print_background: Option<bool>,
+    /// `print` (default) o `screen`
+    pub media_type: Option<PdfMediaType>,
+    /// Solo wkhtmltopdf: PDF en escala de grises
+    pub grayscale: Option<bool>,
+    /// Solo wkhtmltopdf: resolución, de 72 a 1200 (default: 96)
+    pub dpi: Option<u32>,
+    /// Solo wkhtmltopdf: calidad JPEG de las imágenes, de 1 a 100 (default: 94)
+    pub image_quality: Option<u8>,
+    /// Ejecuta el JavaScript de la página (default: true)
+    pub enable_javascript: Option<bool>,
+    /// Espera (ms) después de cargar la página, hasta 60000. Reemplaza a
+    /// `source_options.javascript_delay_ms`; no se pueden usar los dos.
+    pub javascript_delay_ms: Option<u64>,
+    /// Solo wkhtmltopdf: ajuste del ancho del contenido a la página (default: true)
+    pub smart_shrinking: Option<bool>,
+    /// Solo wkhtmltopdf: tamaño mínimo de letra en puntos, de 1 a 72
+    pub minimum_font_size: Option<u32>,
+    /// Solo wkhtmltopdf: los enlaces a otros sitios quedan como texto plano
+    pub disable_external_links: Option<bool>,
+    /// Solo wkhtmltopdf: `false` impide que el HTML inline lea incluso su
+    /// directorio de trabajo (fuera de él nunca puede leer)
+    pub local_file_access: Option<bool>,
+}
+
+impl PdfRenderOptions {
+    /// Rangos y soporte del motor. Con `image`, solo aplican las opciones de JavaScript.
+    pub fn validate(&self, backend: PdfBackend, image: bool, errors: &mut ValidationErrors) {
+        errors.check_range("render_options.dpi", self.dpi, 72, 1200);
+        errors.check_range("render_options.image_quality", self.image_quality, 1, 100);
+        errors.check_range(
+            "render_options.javascript_delay_ms",
+            self.javascript_delay_ms,
+            0,
+            60_000,
+        );
+        errors.check_range(
+            "render_options.minimum_font_size",
+            self.minimum_font_size,
+            1,
+            72,
+        );
+
+        let pdf_only = [
+            ("print_background", self.print_background.is_some()),
+            ("media_type", self.media_type.is_some()),
+            ("grayscale", self.grayscale.is_some()),
+            ("dpi", self.dpi.is_some()),
+            ("image_quality", self.image_quality.is_some()),
+            ("smart_shrinking", self.smart_shrinking.is_some()),
+            ("minimum_font_size", self.minimum_font_size.is_some()),
+            (
+                "disable_external_links",
+                self.disable_external_links.is_some(),
+            ),
+            ("local_file_access", self.local_file_access.is_some()),
+        ];
+        if image {
+            for name in set_names(&pdf_only) {
+                errors.add(
+                    &format!("render_options.{}", name),
+                    FieldErrorCode::Unsupported,
+                    "no aplica a imágenes",
+                );
+            }
+            return;
+        }
+
+        let wkhtmltopdf_only = [
+            ("grayscale", self.grayscale.is_some()),
+            ("dpi", self.dpi.is_some()),
+            ("image_quality", self.image_quality.is_some()),
+            ("smart_shrinking", self.smart_shrinking.is_some()),
+            ("minimum_font_size", self.minimum_font_size.is_some()),
+            (
+                "disable_external_links",
+                self.disable_external_links.is_some(),
+            ),
+            ("local_file_access", self.local_file_access.is_some()),
+        ];
+        if backend == PdfBackend::Chromium {
+            for name in set_names(&wkhtmltopdf_only) {
+                errors.add(
+                    &format!("render_options.{}", name),
+                    FieldErrorCode::Unsupported,
+                    "no soportada por chromium (usar el backend wkhtmltopdf)",
+                );
+            }
+        }
+    }
+}
+
+fn set_names(options: &[(&'static str, bool)]) -> Vec<&'static str> {
+    options
+        .iter()
+        .filter(|(_, set)| *set)
+        .map(|(name, _)| *name)
+        .collect()
+}
+
+/// Motor de renderizado HTML -> PDF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PdfBackend {
+    Wkhtmltopdf,
+    Chromium,
+}
+
+impl PdfBackend {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PdfBackend::Wkhtmltopdf => "wkhtmltopdf",
+            PdfBackend::Chromium => "chromium",
+        }
+    }
+}
+
+impl std::str::FromStr for PdfBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "wkhtmltopdf" => Ok(PdfBackend::Wkhtmltopdf),
+            "chromium" | "chrome" => Ok(PdfBackend::Chromium),
+            other => Err(anyhow::anyhow!("Backend de PDF desconocido: {}", other)),
+        }
+    }
 }
 
 /// Request para generar PDF usando wkhtmltopdf
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PdfRequest {
     /// Nombre final (no necesariamente se usa en la salida, pero sí para logs)
+    #[serde(default = "default_file_name")]
     pub file_name: String,
-    /// Contenido HTML a convertir
+    /// Contenido HTML a convertir. Puede ir vacío si se usa `source_url`.
+    #[serde(default)]
     pub html: String,
 
+    /// URL (http/https) a renderizar en lugar de `html`.
+    pub source_url: Option<String>,
+
+    /// Cabeceras, cookies, auth y esperas para `source_url`.
+    pub source_options: Option<PdfSourceOptions>,
+
+    /// ZIP con el HTML de entrada y sus recursos relativos (CSS, imágenes, fuentes).
+    pub assets_bundle: Option<PdfAssetBundle>,
+
+    /// Plantilla del servidor (id o nombre) a usar en lugar de `html`.
+    pub template_id: Option<String>,
+
+    /// Versión de la plantilla; si es None, la vigente.
+    pub template_version: Option<i64>,
+
+    /// Datos JSON con los que se renderiza `template_id`.
+    pub data: Option<serde_json::Value>,
+
     /// Orientación (portrait o landscape). Si es `None`, se asume portrait
     pub orientation: Option<PdfOrientation>,
 
@@ -59,13 +668,177 @@ pub struct PdfRequest {
     /// Márgenes (mm). Si es `None`, usar un default.
     pub margins: Option<PdfMargins>,
 
+    /// Una sola página continua con el alto del contenido (tickets en rollo térmico);
+    /// el ancho sale del tamaño de página. Solo Chromium.
+    pub auto_height: Option<bool>,
+
     /// Factor de escala (zoom). Ej: 1.0 (100%), 0.8 (80%), etc.
     /// Si es None, se asume 1.0
     pub scale: Option<f64>,
 
-    /// NUEVO: si es true, además de generar el PDF en memoria,
-    /// lo guardaremos en disco en ./files/pdfs.
+    /// Motor de renderizado. Si es `None`, se usa el backend global
+    /// del servicio (variable de entorno `PDF_BACKEND`).
+    pub backend: Option<PdfBackend>,
+
+    /// Encabezado HTML repetido en cada página (fragmento HTML).
+    /// Admite los placeholders `[page]`, `[total_pages]`, `[date]` y `[title]`.
+    pub header_html: Option<String>,
+
+    /// Pie de página HTML repetido en cada página (mismos placeholders).
+    pub footer_html: Option<String>,
+
+    /// Encabezado de texto simple; se ignora si hay `header_html`.
+    pub header_text: Option<PdfTextHeaderFooter>,
+
+    /// Pie de página de texto simple; se ignora si hay `footer_html`.
+    pub footer_text: Option<PdfTextHeaderFooter>,
+
+    /// Título, autor, asunto, palabras clave y creador del PDF.
+    pub metadata: Option<PdfMetadata>,
+
+    /// Marcadores (outline) generados desde los encabezados `h1`–`h6`.
+    /// Si es `None`, se respeta el comportamiento por defecto del backend.
+    pub outline: Option<bool>,
+
+    /// Página de índice generada al inicio del documento.
+    pub toc: Option<PdfTocOptions>,
+
+    /// Marca de agua superpuesta a las páginas ya renderizadas.
+    pub watermark: Option<PdfWatermark>,
+
+    /// Archivo PDF/A: XMP, perfil ICC de salida y validación del resultado.
+    /// No se puede combinar con `encryption`.
+    pub conformance: Option<PdfConformance>,
+
+    /// Factura electrónica Factur-X/ZUGFeRD: incrusta el XML CII y genera PDF/A-3b.
+    pub invoice: Option<PdfInvoice>,
+
+    /// Contraseñas y permisos; se aplica como último paso, sobre el PDF final.
+    pub encryption: Option<PdfEncryption>,
+
+    /// Firma digital; se aplica sobre el PDF final (después del cifrado).
+    pub signature: Option<PdfSignatureOptions>,
+
+    /// Fondos, media CSS, escala de grises, DPI, JavaScript, etc. (ver `PdfRenderOptions`).
+    pub render_options: Option<PdfRenderOptions>,
+
+    /// Responde una imagen de la página (PNG, JPEG o WebP) en vez del PDF.
+    /// Las opciones que modifican el PDF (marca de agua, cifrado, ...) no aplican.
+    pub image: Option<PdfImageOptions>,
+
+    /// Si es true, además de responder el PDF se guarda como documento
+    /// (./files/pdfs + tabla `documents`); su id va en `X-Document-Id`.
     pub store_local_pdf: Option<bool>,
+
+    /// Segundos que se conserva el documento guardado (default `DOCUMENT_TTL_SECONDS`;
+    /// 0 = no expira).
+    pub store_ttl_seconds: Option<u64>,
+
+    /// Con la caché de renders activa (`PDF_CACHE_MAX_BYTES`), `false` fuerza un render nuevo.
+    pub cache: Option<bool>,
+
+    /// Si es true se responde 202 con una operación `generate_pdf` y el render sigue en
+    /// background; el resultado se descarga en `/api/pdf/jobs/{operation_id}/result`.
+    #[serde(rename = "async")]
+    pub run_async: Option<bool>,
+
+    /// URL (http/https) que recibe un POST con `PdfJobCallback` al terminar un render asíncrono.
+    pub callback_url: Option<String>,
+
+    /// `binary` (default): el archivo con el informe del render en headers `X-Render-*`;
+    /// `json`: un sobre con el archivo en base64 y el informe completo.
+    pub response_format: Option<PdfResponseFormat>,
+
+    /// Tenant (header `X-Tenant-Id`) cuyos valores por defecto completan el request.
+    #[serde(skip)]
+    pub tenant: Option<String>,
+}
+
+/// Forma de la respuesta de `/api/pdf`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PdfResponseFormat {
+    #[default]
+    Binary,
+    Json,
+}
+
+/// Categoría de un aviso del motor de renderizado
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PdfRenderWarningKind {
+    /// Un recurso (imagen, CSS, fuente, script) no se pudo cargar
+    MissingResource,
+    /// El motor bloqueó el acceso a un recurso (p. ej. archivos locales)
+    BlockedRequest,
+    Font,
+    /// Errores de red o de TLS
+    Network,
+    /// Excepción de JavaScript no capturada
+    Javascript,
+    Other,
+}
+
+impl PdfRenderWarningKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PdfRenderWarningKind::MissingResource => "missing_resource",
+            PdfRenderWarningKind::BlockedRequest => "blocked_request",
+            PdfRenderWarningKind::Font => "font",
+            PdfRenderWarningKind::Network => "network",
+            PdfRenderWarningKind::Javascript => "javascript",
+            PdfRenderWarningKind::Other => "other",
+        }
+    }
+}
+
+/// Aviso del motor durante un render que no impidió generar el documento
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PdfRenderWarning {
+    pub kind: PdfRenderWarningKind,
+    pub message: String,
+    /// Recurso afectado, si el motor lo informa
+    pub url: Option<String>,
+}
+
+/// Datos de un render: motor, páginas, tamaño, duración y avisos
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PdfRenderReport {
+    pub backend: PdfBackend,
+    /// None para imágenes o si el PDF no se pudo leer (p. ej. cifrado)
+    pub page_count: Option<u32>,
+    pub size_bytes: u64,
+    pub render_time_ms: u64,
+    pub warnings: Vec<PdfRenderWarning>,
+}
+
+/// Aviso enviado a `callback_url` cuando termina (o falla) un render asíncrono
+#[derive(Debug, Clone, Serialize)]
+pub struct PdfJobCallback {
+    pub operation_id: String,
+    /// "done" o "failed"
+    pub status: String,
+    pub error: Option<String>,
+    pub document_id: Option<String>,
+    /// Ruta de descarga del resultado (relativa al servicio)
+    pub result_url: Option<String>,
+    /// Informe del render; None si falló
+    pub report: Option<PdfRenderReport>,
+}
+
+/// Respuesta de `/api/pdf` con `response_format: json`
+#[derive(Debug, Clone, Serialize)]
+pub struct PdfRenderEnvelope {
+    pub success: bool,
+    pub file_name: String,
+    pub content_type: String,
+    /// Archivo generado (PDF o imagen) en base64
+    pub data_base64: String,
+    /// Con `store_local_pdf`: documento guardado y su operación
+    pub document_id: Option<String>,
+    pub operation_id: Option<String>,
+    #[serde(flatten)]
+    pub report: PdfRenderReport,
 }
 
 /// Respuesta genérica
@@ -73,24 +846,611 @@ pub struct PdfRequest {
 pub struct PdfResponse {
     pub success: bool,
     pub message: String,
+    /// Código estable del error (`render_failed`, `invalid_request`, ...): el
+    /// mensaje puede cambiar, el código no
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<&'static str>,
+}
+
+impl PdfResponse {
+    pub fn error(code: &'static str, message: impl Into<String>) -> Self {
+        PdfResponse {
+            success: false,
+            message: message.into(),
+            code: Some(code),
+        }
+    }
+}
+
+/// URL absoluta con esquema http o https
+fn is_http_url(url: &str) -> bool {
+    reqwest::Url::parse(url).is_ok_and(|parsed| matches!(parsed.scheme(), "http" | "https"))
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_file_name() -> String {
+    "output.pdf".to_string()
+}
+
+impl PdfRequest {
+    /// Valida `render_options` (y `auto_height`) para el motor que va a renderizar
+    /// (`image`: si el resultado es una imagen en vez de un PDF).
+    pub fn validate_render_options(
+        &self,
+        backend: PdfBackend,
+        image: bool,
+    ) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        self.check_render_options(backend, image, &mut errors);
+        errors.into_result()
+    }
+
+    pub fn check_render_options(
+        &self,
+        backend: PdfBackend,
+        image: bool,
+        errors: &mut ValidationErrors,
+    ) {
+        if self.auto_height == Some(true) && backend != PdfBackend::Chromium {
+            errors.add(
+                "auto_height",
+                FieldErrorCode::Unsupported,
+                format!("no está soportado por el backend {}", backend.as_str()),
+            );
+        }
+        let Some(options) = &self.render_options else {
+            return;
+        };
+        let source_delay = self
+            .source_options
+            .as_ref()
+            .is_some_and(|source| source.javascript_delay_ms.is_some());
+        if options.javascript_delay_ms.is_some() && source_delay {
+            errors.add(
+                "render_options.javascript_delay_ms",
+                FieldErrorCode::Conflict,
+                "no se puede combinar con `source_options.javascript_delay_ms`",
+            );
+        }
+        if options.local_file_access == Some(true)
+            && (self.source_url.is_some() || self.assets_bundle.is_some())
+        {
+            errors.add(
+                "render_options.local_file_access",
+                FieldErrorCode::Conflict,
+                "no se puede activar con `source_url` ni `assets_bundle`",
+            );
+        }
+        options.validate(backend, image, errors);
+    }
+
+    /// Chequeos del request que no dependen del motor: contenido, página, márgenes,
+    /// escala y combinaciones de opciones que se ignorarían o fallarían al renderizar.
+    pub fn preflight(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        self.check_source(&mut errors);
+        self.check_page(&mut errors);
+        self.check_options(&mut errors);
+        errors.into_result()
+    }
+
+    fn check_source(&self, errors: &mut ValidationErrors) {
+        if self.html.trim().is_empty()
+            && self.template_id.is_none()
+            && self.source_url.is_none()
+            && self.assets_bundle.is_none()
+        {
+            errors.add(
+                "html",
+                FieldErrorCode::Required,
+                "no hay contenido que renderizar (HTML, plantilla, URL o bundle)",
+            );
+        }
+        if let Some(url) = &self.source_url {
+            if !is_http_url(url) {
+                errors.add(
+                    "source_url",
+                    FieldErrorCode::Invalid,
+                    "debe ser una URL http o https",
+                );
+            }
+            if self.assets_bundle.is_some() {
+                errors.add(
+                    "assets_bundle",
+                    FieldErrorCode::Conflict,
+                    "no se puede combinar con `source_url`",
+                );
+            }
+            if self.template_id.is_some() {
+                errors.add(
+                    "template_id",
+                    FieldErrorCode::Conflict,
+                    "no se puede combinar con `source_url`",
+                );
+            }
+        }
+        if self.template_version.is_some() && self.template_id.is_none() {
+            errors.add(
+                "template_id",
+                FieldErrorCode::Required,
+                "`template_version` requiere `template_id`",
+            );
+        }
+        let file_name = self.file_name.trim();
+        if file_name.is_empty()
+            || file_name
+                .chars()
+                .any(|c| c.is_control() || matches!(c, '/' | '\\' | '"'))
+        {
+            errors.add(
+                "file_name",
+                FieldErrorCode::Invalid,
+                "debe ser un nombre de archivo sin rutas, comillas ni caracteres de control",
+            );
+        }
+    }
+
+    fn check_page(&self, errors: &mut ValidationErrors) {
+        if self.page_size_preset.is_some() && self.custom_page_size.is_some() {
+            errors.add(
+                "custom_page_size",
+                FieldErrorCode::Conflict,
+                "no se puede combinar con `page_size_preset`",
+            );
+        }
+        if let Some(size) = &self.custom_page_size {
+            errors.check_range(
+                "custom_page_size.width",
+                Some(size.width),
+                1.0,
+                MAX_PAGE_SIDE_MM,
+            );
+            errors.check_range(
+                "custom_page_size.height",
+                Some(size.height),
+                1.0,
+                MAX_PAGE_SIDE_MM,
+            );
+        }
+        errors.check_range("scale", self.scale, 0.1, 2.0);
+
+        let auto_height = self.auto_height == Some(true);
+        if auto_height && matches!(self.orientation, Some(PdfOrientation::Landscape)) {
+            errors.add(
+                "auto_height",
+                FieldErrorCode::Conflict,
+                "no se puede combinar con `orientation: landscape`",
+            );
+        }
+        if auto_height && self.image.is_some() {
+            errors.add(
+                "auto_height",
+                FieldErrorCode::Conflict,
+                "no se puede combinar con `image`",
+            );
+        }
+
+        let Some(margins) = &self.margins else {
+            return;
+        };
+        let (mut width, mut height) = match (&self.page_size_preset, &self.custom_page_size) {
+            (Some(preset), _) => preset.dimensions_mm(),
+            (None, Some(size)) => (size.width, size.height),
+            (None, None) => BUILTIN_PAGE_SIZE.dimensions_mm(),
+        };
+        if matches!(self.orientation, Some(PdfOrientation::Landscape)) {
+            std::mem::swap(&mut width, &mut height);
+        }
+        for (side, value, max) in [
+            ("top", margins.top, height),
+            ("bottom", margins.bottom, height),
+            ("left", margins.left, width),
+            ("right", margins.right, width),
+        ] {
+            errors.check_range(&format!("margins.{}", side), Some(value), 0.0, max);
+        }
+        if margins.left + margins.right >= width {
+            errors.add(
+                "margins",
+                FieldErrorCode::OutOfRange,
+                format!(
+                    "`left` + `right` ({} mm) no dejan espacio en una página de {} mm de ancho",
+                    margins.left + margins.right,
+                    width
+                ),
+            );
+        }
+        // Con `auto_height` el alto final se calcula sumando los márgenes
+        if !auto_height && margins.top + margins.bottom >= height {
+            errors.add(
+                "margins",
+                FieldErrorCode::OutOfRange,
+                format!(
+                    "`top` + `bottom` ({} mm) no dejan espacio en una página de {} mm de alto",
+                    margins.top + margins.bottom,
+                    height
+                ),
+            );
+        }
+    }
+
+    fn check_options(&self, errors: &mut ValidationErrors) {
+        if let Some(toc) = &self.toc {
+            errors.check_range("toc.max_depth", toc.max_depth, 1, 6);
+        }
+        if let Some(watermark) = &self.watermark {
+            watermark.validate(errors);
+        }
+        if let Some(visible) = self.signature.as_ref().and_then(|s| s.visible.as_ref()) {
+            errors.check_range("signature.visible.page", visible.page, 1, u32::MAX);
+            errors.check_range(
+                "signature.visible.x",
+                Some(visible.x),
+                0.0,
+                MAX_PAGE_SIDE_MM,
+            );
+            errors.check_range(
+                "signature.visible.y",
+                Some(visible.y),
+                0.0,
+                MAX_PAGE_SIDE_MM,
+            );
+            errors.check_range(
+                "signature.visible.width",
+                Some(visible.width),
+                1.0,
+                MAX_PAGE_SIDE_MM,
+            );
+            errors.check_range(
+                "signature.visible.height",
+                Some(visible.height),
+                1.0,
+                MAX_PAGE_SIDE_MM,
+            );
+        }
+        // Una factura también es PDF/A aunque no traiga `conformance` (el error
+        // de `effective_conformance` es una factura con pdfa_2b, se reporta abajo)
+        let pdfa = !matches!(self.effective_conformance(), Ok(None));
+        if pdfa && self.encryption.is_some() {
+            errors.add(
+                "encryption",
+                FieldErrorCode::Conflict,
+                "no se puede combinar con PDF/A (`conformance` o `invoice`), que prohíbe el cifrado",
+            );
+        }
+        // El texto de las marcas de agua y de la firma visible usa Helvetica sin
+        // incrustar, y PDF/A exige fuentes incrustadas
+        if pdfa {
+            if self.watermark.as_ref().is_some_and(|w| w.text.is_some()) {
+                errors.add(
+                    "watermark.text",
+                    FieldErrorCode::Conflict,
+                    "no se puede combinar con PDF/A (usa una marca de agua con `image`)",
+                );
+            }
+            if self.signature.as_ref().is_some_and(|s| s.visible.is_some()) {
+                errors.add(
+                    "signature.visible",
+                    FieldErrorCode::Conflict,
+                    "no se puede combinar con PDF/A (la firma invisible sí)",
+                );
+            }
+        }
+        if self.conformance == Some(PdfConformance::PdfA2b) && self.invoice.is_some() {
+            errors.add(
+                "conformance",
+                FieldErrorCode::Conflict,
+                "una factura Factur-X requiere pdfa_3b (PDF/A-2b no admite adjuntos)",
+            );
+        }
+
+        if let Some(image) = &self.image {
+            image.validate(errors);
+            // Las opciones que modifican el PDF no tienen efecto sobre una imagen
+            let pdf_only = [
+                ("metadata", self.metadata.is_some()),
+                ("outline", self.outline.is_some()),
+                ("toc", self.toc.is_some()),
+                ("watermark", self.watermark.is_some()),
+                ("conformance", self.conformance.is_some()),
+                ("invoice", self.invoice.is_some()),
+                ("encryption", self.encryption.is_some()),
+                ("signature", self.signature.is_some()),
+            ];
+            for name in set_names(&pdf_only) {
+                errors.add(
+                    name,
+                    FieldErrorCode::Conflict,
+                    "no se puede combinar con `image`",
+                );
+            }
+        }
+
+        errors.check_range(
+            "store_ttl_seconds",
+            self.store_ttl_seconds,
+            0,
+            MAX_TTL_SECONDS,
+        );
+
+        let run_async = self.run_async.unwrap_or(false);
+        if let Some(url) = &self.callback_url {
+            if !run_async {
+                errors.add(
+                    "callback_url",
+                    FieldErrorCode::Conflict,
+                    "requiere `async: true`",
+                );
+            }
+            if !is_http_url(url) {
+                errors.add(
+                    "callback_url",
+                    FieldErrorCode::Invalid,
+                    "debe ser una URL http o https",
+                );
+            }
+        }
+        if run_async && self.response_format == Some(PdfResponseFormat::Json) {
+            errors.add(
+                "response_format",
+                FieldErrorCode::Conflict,
+                "no aplica con `async: true` (el resultado se descarga aparte)",
+            );
+        }
+    }
+
+    /// Nivel PDF/A del resultado: el pedido o, con una factura Factur-X, PDF/A-3b
+    /// (la única parte que admite el XML incrustado).
+    pub fn effective_conformance(&self) -> anyhow::Result<Option<PdfConformance>> {
+        match (self.conformance, &self.invoice) {
+            (Some(PdfConformance::PdfA2b), Some(_)) => Err(anyhow::anyhow!(
+                "Una factura Factur-X requiere `conformance` pdfa_3b (PDF/A-2b no admite adjuntos)"
+            )),
+            (_, Some(_)) => Ok(Some(PdfConformance::PdfA3b)),
+            (conformance, None) => Ok(conformance),
+        }
+    }
 }
 
 impl Default for PdfRequest {
     fn default() -> Self {
         Self {
-            file_name: "output.pdf".to_string(),
+            file_name: default_file_name(),
             html: "".to_string(),
-            orientation: Some(PdfOrientation::Portrait),
-            page_size_preset: Some(PdfPagePreset::A4),
+            source_url: None,
+            source_options: None,
+            assets_bundle: None,
+            template_id: None,
+            template_version: None,
+            data: None,
+            // Página, márgenes y escala salen de `PdfGlobalConfig` al renderizar
+            orientation: None,
+            page_size_preset: None,
             custom_page_size: None,
-            margins: Some(PdfMargins {
-                top: 10.0,
-                bottom: 10.0,
-                left: 10.0,
-                right: 10.0,
-            }),
-            scale: Some(1.0),
+            margins: None,
+            auto_height: None,
+            scale: None,
+            backend: None,
+            header_html: None,
+            footer_html: None,
+            header_text: None,
+            footer_text: None,
+            metadata: None,
+            outline: None,
+            toc: None,
+            watermark: None,
+            conformance: None,
+            invoice: None,
+            encryption: None,
+            signature: None,
+            render_options: None,
+            image: None,
             store_local_pdf: Some(false),
+            store_ttl_seconds: None,
+            cache: None,
+            run_async: None,
+            callback_url: None,
+            response_format: None,
+            tenant: None,
         }
     }
 }
+
+/// Un PDF de entrada para unir o dividir: se renderiza (`render`)
+/// o se sube ya generado (`pdf_base64`).
+#[derive(Debug, Clone, Deserialize)]
+pub struct PdfInputDocument {
+    pub render: Option<PdfRequest>,
+
+    #[serde(
+        default,
+        rename = "pdf_base64",
+        deserialize_with = "crate::models::email_model::deserialize_optional_base64"
+    )]
+    pub pdf: Option<Vec<u8>>,
+
+    /// Páginas a tomar, ej: "1-3,5,8-" (default: todas)
+    pub pages: Option<String>,
+}
+
+/// POST /api/pdf/merge
+#[derive(Debug, Clone, Deserialize)]
+pub struct MergePdfRequest {
+    #[serde(default = "default_file_name")]
+    pub file_name: String,
+    /// Documentos en el orden en que se concatenan
+    pub documents: Vec<PdfInputDocument>,
+    /// Marca de agua aplicada al documento unido
+    pub watermark: Option<PdfWatermark>,
+}
+
+/// POST /api/pdf/split
+#[derive(Debug, Clone, Deserialize)]
+pub struct SplitPdfRequest {
+    #[serde(default = "default_file_name")]
+    pub file_name: String,
+    pub document: PdfInputDocument,
+    /// Un PDF por rango, ej: ["1-2", "3-"]. Sin rangos, un PDF por página.
+    pub ranges: Option<Vec<String>>,
+}
+
+/// POST /api/pdf/thumbnail
+/// Miniatura de una página de un PDF ya generado (o renderizado en el momento).
+#[derive(Debug, Clone, Deserialize)]
+pub struct PdfThumbnailRequest {
+    #[serde(default = "default_thumbnail_name")]
+    pub file_name: String,
+    pub document: PdfInputDocument,
+    /// Página (1-based, default: 1)
+    pub page: Option<u32>,
+    /// Ancho de la miniatura en píxeles (default: 300); el alto es proporcional
+    pub width: Option<u32>,
+    /// PNG (default) o JPEG
+    #[serde(default)]
+    pub format: ImageFormat,
+    /// Calidad JPEG, de 1 a 100 (default: 90)
+    pub quality: Option<u8>,
+}
+
+impl PdfThumbnailRequest {
+    pub fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        errors.check_range("page", self.page, 1, u32::MAX);
+        errors.check_range("width", self.width, 1, 10_000);
+        errors.check_range("quality", self.quality, 1, 100);
+        if self.format == ImageFormat::Webp {
+            errors.add(
+                "format",
+                FieldErrorCode::Unsupported,
+                "las miniaturas solo se generan en PNG o JPEG",
+            );
+        }
+        errors.into_result()
+    }
+}
+
+fn default_thumbnail_name() -> String {
+    "thumbnail".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn request(value: serde_json::Value) -> PdfRequest {
+        serde_json::from_value(value).unwrap()
+    }
+
+    /// (campo, código) de cada error del preflight
+    fn preflight_errors(value: serde_json::Value) -> Vec<(String, FieldErrorCode)> {
+        match request(value).preflight() {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors
+                .errors
+                .into_iter()
+                .map(|e| (e.field, e.code))
+                .collect(),
+        }
+    }
+
+    fn error(field: &str, code: FieldErrorCode) -> (String, FieldErrorCode) {
+        (field.to_string(), code)
+    }
+
+    #[test]
+    fn preflight_accepts_a_plain_request() {
+        assert!(preflight_errors(json!({ "html": "<p>Hola</p>", "margins": {
+            "top": "1cm", "bottom": 10, "left": "0.5in", "right": 10
+        }}))
+        .is_empty());
+    }
+
+    #[test]
+    fn preflight_requires_content_and_a_safe_file_name() {
+        assert_eq!(
+            preflight_errors(json!({ "html": " ", "file_name": "../x.pdf" })),
+            [
+                error("html", FieldErrorCode::Required),
+                error("file_name", FieldErrorCode::Invalid)
+            ]
+        );
+        assert_eq!(
+            preflight_errors(json!({ "source_url": "file:///etc/passwd" })),
+            [error("source_url", FieldErrorCode::Invalid)]
+        );
+    }
+
+    #[test]
+    fn preflight_checks_page_and_margins() {
+        assert_eq!(
+            preflight_errors(json!({
+                "html": "x",
+                "page_size_preset": "A4",
+                "custom_page_size": { "width": 100, "height": 100 },
+                "scale": 3
+            })),
+            [
+                error("custom_page_size", FieldErrorCode::Conflict),
+                error("scale", FieldErrorCode::OutOfRange)
+            ]
+        );
+        // A5 apaisado: 210 x 148 mm
+        assert_eq!(
+            preflight_errors(json!({
+                "html": "x",
+                "page_size_preset": "A5",
+                "orientation": "landscape",
+                "margins": { "top": 80, "bottom": 70, "left": 10, "right": -1 }
+            })),
+            [
+                error("margins.right", FieldErrorCode::OutOfRange),
+                error("margins", FieldErrorCode::OutOfRange)
+            ]
+        );
+        assert_eq!(
+            preflight_errors(json!({
+                "html": "x",
+                "auto_height": true,
+                "orientation": "landscape"
+            })),
+            [error("auto_height", FieldErrorCode::Conflict)]
+        );
+    }
+
+    #[test]
+    fn preflight_rejects_pdfa_with_encryption_or_helvetica_text() {
+        assert_eq!(
+            preflight_errors(json!({
+                "html": "x",
+                "conformance": "pdfa_2b",
+                "encryption": { "user_password": "secreto" },
+                "watermark": { "text": "BORRADOR" }
+            })),
+            [
+                error("encryption", FieldErrorCode::Conflict),
+                error("watermark.text", FieldErrorCode::Conflict)
+            ]
+        );
+    }
+    #[test]
+    fn nested_render_errors_carry_the_document_path() {
+        let mut errors = request(json!({ "html": "x", "margins": {
+            "top": -1, "bottom": 10, "left": 10, "right": 10
+        }}))
+        .preflight()
+        .unwrap_err();
+        errors.add("tenant", FieldErrorCode::Invalid, "Tenant desconocido");
+        let fields: Vec<String> = errors
+            .with_prefix("documents[2].render")
+            .errors
+            .into_iter()
+            .map(|e| e.field)
+            .collect();
+        assert_eq!(fields, ["documents[2].render.margins.top", "tenant"]);
+    }
+}
@@ -0,0 +1,91 @@
+//! models/signing_model.rs
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Perfil de firma: un certificado PKCS#12 guardado en el servidor.
+/// Nunca incluye la clave privada ni la contraseña.
+#[derive(Debug, Clone, Serialize)]
+pub struct SigningProfileRecord {
+    pub id: String,
+    pub name: String,
+    pub subject: String,
+    pub issuer: String,
+    pub serial_number: String,
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// POST /api/signing/profiles
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateSigningProfileRequest {
+    pub name: String,
+    /// Archivo .p12/.pfx codificado en base64
+    #[serde(
+        rename = "pkcs12_base64",
+        deserialize_with = "crate::models::email_model::deserialize_base64"
+    )]
+    pub pkcs12: Vec<u8>,
+    pub password: String,
+}
+
+/// POST /api/pdf/verify
+#[derive(Debug, Clone, Deserialize)]
+pub struct VerifyPdfRequest {
+    #[serde(
+        rename = "pdf_base64",
+        deserialize_with = "crate::models::email_model::deserialize_base64"
+    )]
+    pub pdf: Vec<u8>,
+}
+
+/// Sello de tiempo (RFC 3161) de una firma.
+#[derive(Debug, Clone, Serialize)]
+pub struct TimestampInfo {
+    /// Hora certificada por la TSA (`genTime`)
+    pub time: Option<String>,
+    /// La TSA firmó un hash que coincide con el de la firma
+    pub valid: bool,
+}
+
+/// Resultado de verificar una firma del documento.
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct SignatureVerification {
+    /// Nombre del campo de firma (`/T`), si se encontró
+    pub field_name: Option<String>,
+    pub sub_filter: Option<String>,
+    pub signer: Option<String>,
+    pub issuer: Option<String>,
+    /// Número de serie (hex) del certificado del firmante
+    pub serial_number: Option<String>,
+    /// Perfil de firma del servidor con ese mismo certificado. Solo informa
+    /// quién firmó: no hace que el certificado sea de confianza.
+    pub server_profile: Option<String>,
+    /// Fecha declarada en el diccionario de firma (`/M`)
+    pub signing_time: Option<String>,
+    pub reason: Option<String>,
+    pub location: Option<String>,
+    /// El contenido firmado no cambió y la firma criptográfica es correcta
+    pub integrity_valid: bool,
+    /// El certificado encadena con una CA del sistema o de `SIGNING_TRUSTED_CERTS`
+    pub certificate_trusted: bool,
+    /// La firma cubre el archivo completo (no hubo cambios posteriores)
+    pub covers_whole_document: bool,
+    pub timestamp: Option<TimestampInfo>,
+    /// Motivo por el que la firma no es válida
+    pub error: Option<String>,
+}
+
+/// Respuesta de POST /api/pdf/verify
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyPdfResponse {
+    pub signed: bool,
+    /// true si hay firmas, todas son íntegras y todos los certificados son de confianza
+    pub valid: bool,
+    /// true si hay firmas y ninguna cambió desde que se firmó
+    pub integrity_valid: bool,
+    /// true si hay firmas y todos los certificados encadenan con una CA de confianza
+    pub trusted: bool,
+    pub signatures: Vec<SignatureVerification>,
+}
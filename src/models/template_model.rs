@@ -0,0 +1,97 @@
+//! models/template_model.rs
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::pdf_model::PdfRequest;
+
+/// Plantilla HTML (Handlebars) almacenada en el servidor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateRecord {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    /// Contenido de la versión vigente
+    pub content: String,
+    pub current_version: i64,
+    /// Datos de ejemplo que usa `/preview` si no se envían otros
+    pub sample_data: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Versión inmutable del contenido de una plantilla.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateVersionRecord {
+    pub template_id: String,
+    pub template_name: String,
+    pub version: i64,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Request para crear una plantilla
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateTemplateRequest {
+    pub name: String,
+    pub description: Option<String>,
+    /// HTML con sintaxis Handlebars, ej: `<h1>Factura {{number}}</h1>`
+    pub content: String,
+    pub sample_data: Option<serde_json::Value>,
+}
+
+/// Request para actualizar una plantilla (solo los campos presentes).
+/// Un `content` distinto al vigente crea una nueva versión.
+#[derive(Debug, Clone, Deserialize)]
+pub struct UpdateTemplateRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub content: Option<String>,
+    pub sample_data: Option<serde_json::Value>,
+}
+
+/// POST /api/templates/{id}/rollback
+#[derive(Debug, Clone, Deserialize)]
+pub struct RollbackTemplateRequest {
+    /// Versión cuyo contenido pasa a ser el vigente (como versión nueva).
+    pub version: i64,
+}
+
+/// Formato de salida de `/preview`
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PreviewFormat {
+    #[default]
+    Html,
+    Pdf,
+}
+
+/// POST /api/templates/{id}/preview
+#[derive(Debug, Clone, Deserialize)]
+pub struct TemplatePreviewRequest {
+    /// Versión a renderizar; si es None, la vigente.
+    pub version: Option<i64>,
+    /// Datos a usar; si es None, el `sample_data` de la plantilla.
+    pub data: Option<serde_json::Value>,
+    #[serde(default)]
+    pub format: PreviewFormat,
+    /// Opciones de página para `format = "pdf"` (se ignoran html/plantilla).
+    pub pdf: Option<PdfRequest>,
+}
+
+/// Resultado de renderizar una versión concreta de una plantilla.
+#[derive(Debug, Clone)]
+pub struct RenderedTemplate {
+    pub html: String,
+    pub template_id: String,
+    pub version: i64,
+}
+
+/// Para listar plantillas con paginación
+#[derive(Debug, Clone, Serialize)]
+pub struct ListTemplatesResponse {
+    pub total: u64,
+    pub page: u64,
+    pub page_size: u64,
+    pub items: Vec<TemplateRecord>,
+}
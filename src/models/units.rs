@@ -0,0 +1,145 @@
+//! models/units.rs
+//! Longitudes con unidad. Las dimensiones del PDF (página, márgenes, marca de agua,
+//! firma visible) aceptan un número, en milímetros, o un string con su unidad:
+//! `"210mm"`, `"21cm"`, `"8.5in"`, `"612pt"`, `"794px"`.
+
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use serde::{Deserialize, Deserializer, Serialize};
+
+pub const MM_PER_INCH: f64 = 25.4;
+/// Píxeles CSS por pulgada
+pub const CSS_PX_PER_INCH: f64 = 96.0;
+/// Puntos tipográficos por pulgada
+pub const PT_PER_INCH: f64 = 72.0;
+
+/// Unidad de longitud
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PdfUnit {
+    #[default]
+    Mm,
+    Cm,
+    In,
+    Pt,
+    /// Píxel CSS (1/96 de pulgada)
+    Px,
+}
+
+impl PdfUnit {
+    /// Milímetros que mide una unidad
+    pub fn mm(self) -> f64 {
+        match self {
+            PdfUnit::Mm => 1.0,
+            PdfUnit::Cm => 10.0,
+            PdfUnit::In => MM_PER_INCH,
+            PdfUnit::Pt => MM_PER_INCH / PT_PER_INCH,
+            PdfUnit::Px => MM_PER_INCH / CSS_PX_PER_INCH,
+        }
+    }
+
+    pub fn to_mm(self, value: f64) -> f64 {
+        value * self.mm()
+    }
+}
+
+impl FromStr for PdfUnit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "mm" => Ok(PdfUnit::Mm),
+            "cm" => Ok(PdfUnit::Cm),
+            "in" => Ok(PdfUnit::In),
+            "pt" => Ok(PdfUnit::Pt),
+            "px" => Ok(PdfUnit::Px),
+            other => Err(anyhow!(
+                "Unidad desconocida '{}' (se espera mm, cm, in, pt o px)",
+                other
+            )),
+        }
+    }
+}
+
+/// `"12.5mm"`, `"1 in"`, `"72pt"` a milímetros; sin unidad se asumen milímetros
+pub fn parse_length_mm(value: &str) -> anyhow::Result<f64> {
+    let value = value.trim();
+    let number = value.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let unit = &value[number.len()..];
+    let number: f64 = number
+        .trim()
+        .parse()
+        .map_err(|_| anyhow!("Longitud inválida '{}' (ej: \"10mm\", \"0.5in\")", value))?;
+    let unit = if unit.is_empty() {
+        PdfUnit::Mm
+    } else {
+        unit.parse()?
+    };
+    Ok(unit.to_mm(number))
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawLength {
+    Number(f64),
+    Text(String),
+}
+
+impl RawLength {
+    fn into_mm(self) -> anyhow::Result<f64> {
+        match self {
+            RawLength::Number(mm) => Ok(mm),
+            RawLength::Text(text) => parse_length_mm(&text),
+        }
+    }
+}
+
+/// Para `#[serde(deserialize_with)]`: número (mm) o string con unidad
+pub(crate) fn deserialize_length<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    RawLength::deserialize(deserializer)?
+        .into_mm()
+        .map_err(serde::de::Error::custom)
+}
+
+/// Como `deserialize_length`, para campos opcionales (con `#[serde(default)]`)
+pub(crate) fn deserialize_optional_length<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<RawLength>::deserialize(deserializer)?
+        .map(RawLength::into_mm)
+        .transpose()
+        .map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_mm(value: &str, expected: f64) {
+        let mm = parse_length_mm(value).unwrap();
+        assert!((mm - expected).abs() < 1e-9, "{:?} -> {}", value, mm);
+    }
+
+    #[test]
+    fn parses_lengths_with_and_without_unit() {
+        assert_mm("12.5mm", 12.5);
+        assert_mm("10", 10.0);
+        assert_mm(" 2 cm ", 20.0);
+        assert_mm("1in", 25.4);
+        assert_mm("72pt", 25.4);
+        assert_mm("96px", 25.4);
+        assert_mm("1IN", 25.4);
+    }
+
+    #[test]
+    fn rejects_unknown_units_and_numbers() {
+        for value in ["", "mm", "abc", "10km", "1,5mm"] {
+            assert!(parse_length_mm(value).is_err(), "{:?}", value);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! models/validation.rs
+//! Validación previa (preflight) de los requests de PDF: errores por campo con un
+//! código estable, para responder 422 antes de lanzar el motor de renderizado.
+
+use std::fmt;
+
+use serde::Serialize;
+
+/// Qué le pasa al campo
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldErrorCode {
+    /// Falta un campo obligatorio
+    Required,
+    /// Número fuera del rango admitido
+    OutOfRange,
+    /// Formato inválido (URL, color, nombre de archivo, ...)
+    Invalid,
+    /// No se puede combinar con otro campo del request
+    Conflict,
+    /// El motor elegido (o el servidor) no lo soporta
+    Unsupported,
+}
+
+/// Error de un campo del request
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    /// Ruta del campo en el JSON (`margins.top`, `pdf_scale`, ...)
+    pub field: String,
+    pub code: FieldErrorCode,
+    pub message: String,
+}
+
+/// Errores de un request; se responden juntos con 422
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ValidationErrors {
+    pub errors: Vec<FieldError>,
+}
+
+impl ValidationErrors {
+    pub fn add(&mut self, field: &str, code: FieldErrorCode, message: impl Into<String>) {
+        self.errors.push(FieldError {
+            field: field.to_string(),
+            code,
+            message: message.into(),
+        });
+    }
+
+    /// Rango cerrado `[min, max]`; un NaN también queda fuera
+    pub fn check_range<T>(&mut self, field: &str, value: Option<T>, min: T, max: T)
+    where
+        T: PartialOrd + fmt::Display,
+    {
+        if let Some(value) = value {
+            if !(value >= min && value <= max) {
+                self.add(
+                    field,
+                    FieldErrorCode::OutOfRange,
+                    format!("debe estar entre {} y {} (recibido {})", min, max, value),
+                );
+            }
+        }
+    }
+
+    /// Campos del `PdfRequest` armado con los `pdf_*` de email y notificaciones:
+    /// `margins.top` pasa a `pdf_margins.top` y `file_name` a `pdf_attachment_name`.
+    pub fn for_pdf_fields(mut self) -> Self {
+        // `tenant` viene del header, no de un campo del JSON
+        for error in self.errors.iter_mut().filter(|e| e.field != "tenant") {
+            let field = match error.field.strip_prefix("file_name") {
+                Some(rest) => format!("attachment_name{}", rest),
+                None => error.field.clone(),
+            };
+            error.field = format!("pdf_{}", field);
+        }
+        self
+    }
+
+    /// Campos de un `PdfRequest` anidado: con `documents[2].render`, `margins.top`
+    /// pasa a `documents[2].render.margins.top`.
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        // `tenant` viene del header, no de un campo del JSON
+        for error in self.errors.iter_mut().filter(|e| e.field != "tenant") {
+            error.field = format!("{}.{}", prefix, error.field);
+        }
+        self
+    }
+
+    pub fn into_result(self) -> Result<(), ValidationErrors> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let messages: Vec<String> = self
+            .errors
+            .iter()
+            .map(|error| format!("{}: {}", error.field, error.message))
+            .collect();
+        write!(f, "Request inválido: {}", messages.join("; "))
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
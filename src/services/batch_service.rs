@@ -0,0 +1,655 @@
+//! services/batch_service.rs
+//! Generación de PDFs en lote: como un ZIP en la misma respuesta o como una
+//! operación asíncrona con un estado por documento (tabla `batch_items`).
+
+use std::{
+    collections::HashSet,
+    fs,
+    io::{Seek, SeekFrom, Write},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use futures::{stream, StreamExt};
+use serde_json::json;
+use sqlx::{Pool, Sqlite};
+use uuid::Uuid;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::models::batch_model::{
+    BatchItemRecord, BatchManifestEntry, BatchPdfRequest, BatchStatusResponse,
+};
+use crate::models::operation_model::CreateOperationRequest;
+use crate::models::pdf_model::PdfRequest;
+use crate::services::{
+    document_service::{expires_after, timestamp, CLEANUP_INTERVAL},
+    error::{is_not_found, ServiceError},
+    operation_service::OperationService,
+    pdf_service::PdfService,
+    storage::Storage,
+    template_service::TemplateService,
+};
+
+/// Máximo de documentos por lote
+const MAX_BATCH_ITEMS: usize = 1_000;
+
+/// Documentos de un mismo lote que se renderizan a la vez. Menor que
+/// MAX_CONCURRENT_PROCESSES de PdfService para no acaparar todos los permisos.
+const BATCH_CONCURRENCY: usize = 4;
+
+/// Prefijo de los PDFs de los lotes asíncronos en el Storage (con disco: ./files/batches)
+const BATCH_PREFIX: &str = "batches";
+
+#[derive(Clone)]
+pub struct BatchService {
+    db_pool: Pool<Sqlite>,
+    pdf_service: PdfService,
+    template_service: TemplateService,
+    operation_service: OperationService,
+    storage: Arc<dyn Storage>,
+    /// Segundos de vida de los PDFs de lotes asíncronos (los mismos que los
+    /// documentos, `DOCUMENT_TTL_SECONDS`); 0 = no expiran
+    ttl_seconds: u64,
+}
+
+impl BatchService {
+    pub fn new(
+        db_pool: Pool<Sqlite>,
+        pdf_service: PdfService,
+        template_service: TemplateService,
+        operation_service: OperationService,
+        storage: Arc<dyn Storage>,
+        ttl_seconds: u64,
+    ) -> Self {
+        BatchService {
+            db_pool,
+            // Los documentos esperan su turno en vez de fallar por timeout
+            pdf_service: pdf_service.queued(),
+            template_service,
+            operation_service,
+            storage,
+            ttl_seconds,
+        }
+    }
+
+    /// Marca como fallidos los lotes (y sus documentos) que quedaron a medias
+    /// por un reinicio: el render corría en una tarea del proceso anterior.
+    pub async fn fail_interrupted_batches(&self) -> Result<u64> {
+        let now = Utc::now().to_rfc3339();
+        sqlx::query!(
+            r#"
+            UPDATE batch_items
+            SET status = 'failed',
+                error_message = 'Interrumpido por un reinicio del servidor',
+                updated_at = ?1
+            WHERE status IN ('pending', 'running')
+            "#,
+            now
+        )
+        .execute(&self.db_pool)
+        .await
+        .context("Error actualizando batch_items interrumpidos")?;
+        self.operation_service
+            .fail_interrupted_operations("batch_pdf")
+            .await
+    }
+
+    /// Convierte el request en la lista de PdfRequest a renderizar.
+    /// En modo plantilla la versión se fija una sola vez para todo el lote.
+    pub async fn expand_items(&self, req: BatchPdfRequest) -> Result<Vec<PdfRequest>> {
+        let items = match (req.items, req.template_id) {
+            (Some(_), Some(_)) => {
+                return Err(anyhow!("Usa `items` o `template_id` + `rows`, no ambos"));
+            }
+            (Some(items), None) => items,
+            (None, Some(template_id)) => {
+                let pinned = self
+                    .template_service
+                    .pin_version(&template_id, req.template_version)
+                    .await?;
+                let base = req.options.unwrap_or_default();
+                req.rows
+                    .unwrap_or_default()
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, row)| {
+                        let mut item = base.clone();
+                        item.template_id = Some(pinned.template_id.clone());
+                        item.template_version = Some(pinned.version);
+                        item.data = Some(row.data);
+                        item.file_name = row
+                            .file_name
+                            .unwrap_or_else(|| format!("document_{}.pdf", i + 1));
+                        item
+                    })
+                    .collect()
+            }
+            (None, None) => {
+                return Err(anyhow!("Se requiere `items` o `template_id` + `rows`"));
+            }
+        };
+
+        if items.is_empty() {
+            return Err(anyhow!("El lote no tiene documentos"));
+        }
+        if items.len() > MAX_BATCH_ITEMS {
+            return Err(anyhow!(
+                "El lote supera el máximo de {} documentos",
+                MAX_BATCH_ITEMS
+            ));
+        }
+        Ok(items)
+    }
+
+    /// Renderiza todos los documentos y los escribe en un ZIP temporal en disco
+    /// (junto a `manifest.json` con el resultado de cada uno). Los que fallan
+    /// quedan en el manifest sin afectar al resto.
+    pub async fn render_zip(&self, items: Vec<PdfRequest>) -> Result<fs::File> {
+        let mut zip =
+            ZipWriter::new(tempfile::tempfile().context("No se pudo crear ZIP temporal")?);
+        // Los PDFs ya vienen comprimidos
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+        let mut used_names = HashSet::new();
+        let mut manifest = Vec::new();
+
+        let mut results = stream::iter(items.into_iter().enumerate())
+            .map(|(index, item)| async move {
+                let file_name = item.file_name.clone();
+                (index, file_name, self.render_item(item).await)
+            })
+            .buffered(BATCH_CONCURRENCY);
+
+        while let Some((index, file_name, result)) = results.next().await {
+            let entry_name = unique_entry_name(index, &file_name, &mut used_names);
+            match result {
+                Ok(pdf_bytes) => {
+                    zip.start_file(entry_name.as_str(), options)?;
+                    zip.write_all(&pdf_bytes)?;
+                    manifest.push(BatchManifestEntry {
+                        item_index: index,
+                        file_name: entry_name,
+                        status: "done".to_string(),
+                        error_message: None,
+                    });
+                }
+                Err(e) => {
+                    log::error!(
+                        "Lote: falló el documento {} ({}): {:?}",
+                        index,
+                        file_name,
+                        e
+                    );
+                    manifest.push(BatchManifestEntry {
+                        item_index: index,
+                        file_name: entry_name,
+                        status: "failed".to_string(),
+                        error_message: Some(e.to_string()),
+                    });
+                }
+            }
+        }
+
+        zip.start_file("manifest.json", SimpleFileOptions::default())?;
+        zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+
+        let mut file = zip.finish().context("Error cerrando ZIP del lote")?;
+        file.seek(SeekFrom::Start(0))?;
+        Ok(file)
+    }
+
+    /// Crea la operación `batch_pdf` con un item `pending` por documento y
+    /// lanza el render en background. Retorna el id de la operación.
+    pub async fn start_batch_operation(&self, items: Vec<PdfRequest>) -> Result<String> {
+        let op = self
+            .operation_service
+            .create_operation(CreateOperationRequest {
+                operation_type: "batch_pdf".to_string(),
+                is_async: true,
+                metadata: Some(json!({ "total_items": items.len() }).to_string()),
+            })
+            .await?;
+        let op_id = op.id;
+
+        let mut item_ids = Vec::with_capacity(items.len());
+        for (index, item) in items.iter().enumerate() {
+            item_ids.push(self.create_item(&op_id, index, &item.file_name).await?);
+        }
+
+        let service = self.clone();
+        let op_id_clone = op_id.clone();
+        tokio::spawn(async move {
+            if let Err(e) = service.process_batch(&op_id_clone, item_ids, items).await {
+                let _ = service
+                    .operation_service
+                    .mark_operation_failed(&op_id_clone, format!("Batch error: {:?}", e))
+                    .await;
+            }
+        });
+
+        Ok(op_id)
+    }
+
+    async fn process_batch(
+        &self,
+        op_id: &str,
+        item_ids: Vec<String>,
+        items: Vec<PdfRequest>,
+    ) -> Result<()> {
+        self.operation_service
+            .update_operation_status(op_id, "running", None)
+            .await?;
+
+        let total = items.len();
+        let failed = stream::iter(item_ids.into_iter().zip(items).enumerate())
+            .map(|(index, (item_id, item))| async move {
+                self.process_item(op_id, &item_id, index, item).await
+            })
+            .buffer_unordered(BATCH_CONCURRENCY)
+            .filter(|ok| futures::future::ready(!ok))
+            .count()
+            .await;
+
+        match failed {
+            0 => {
+                self.operation_service
+                    .update_operation_status(op_id, "done", None)
+                    .await
+            }
+            n if n == total => {
+                self.operation_service
+                    .update_operation_status(op_id, "failed", Some("Fallaron todos los documentos"))
+                    .await
+            }
+            n => {
+                let message = format!("{} de {} documentos fallaron", n, total);
+                self.operation_service
+                    .update_operation_status(op_id, "done", Some(&message))
+                    .await
+            }
+        }
+    }
+
+    /// Renderiza y guarda un documento del lote. Retorna `false` si falló
+    /// (el error queda registrado en su item).
+    async fn process_item(
+        &self,
+        op_id: &str,
+        item_id: &str,
+        index: usize,
+        item: PdfRequest,
+    ) -> bool {
+        let _ = self
+            .update_item(item_id, "running", None, None, None, None)
+            .await;
+
+        let key = format!(
+            "{}/{}/{}_{}",
+            BATCH_PREFIX,
+            op_id,
+            index,
+            sanitize_file_name(&item.file_name)
+        );
+        let result = async {
+            let pdf_bytes = self.render_item(item).await?;
+            self.storage
+                .put(&key, &pdf_bytes, "application/pdf")
+                .await
+                .context("No se pudo guardar el PDF del lote")?;
+            let expires_at = match self.ttl_seconds {
+                0 => None,
+                ttl => Some(timestamp(expires_after(Utc::now(), ttl)?)),
+            };
+            Ok::<_, anyhow::Error>((pdf_bytes.len(), expires_at))
+        }
+        .await;
+
+        match result {
+            Ok((size, expires_at)) => {
+                let _ = self
+                    .update_item(
+                        item_id,
+                        "done",
+                        None,
+                        Some(&key),
+                        Some(size as i64),
+                        expires_at.as_deref(),
+                    )
+                    .await;
+                true
+            }
+            Err(e) => {
+                log::error!("Lote: falló el documento {}: {:?}", item_id, e);
+                let _ = self
+                    .update_item(item_id, "failed", Some(&e.to_string()), None, None, None)
+                    .await;
+                false
+            }
+        }
+    }
+
+    /// Render de un solo documento (plantilla incluida) a través de PdfService,
+    /// que es quien limita la concurrencia global.
+    async fn render_item(&self, mut item: PdfRequest) -> Result<Vec<u8>> {
+        self.template_service.apply_to_request(&mut item).await?;
+        self.pdf_service.generate_pdf(item).await
+    }
+
+    /// Estado de un lote asíncrono con sus documentos
+    pub async fn get_batch(&self, op_id: &str) -> Result<BatchStatusResponse> {
+        let operation = match self.operation_service.get_operation(op_id).await {
+            Ok(operation) if operation.operation_type == "batch_pdf" => operation,
+            Err(e) if !is_not_found(&e) => return Err(e),
+            _ => return Err(ServiceError::not_found("Batch", op_id)),
+        };
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, item_index, file_name, status, error_message, size_bytes,
+                   created_at, updated_at
+            FROM batch_items
+            WHERE operation_id = ?1
+            ORDER BY item_index ASC
+            "#,
+            op_id
+        )
+        .fetch_all(&self.db_pool)
+        .await
+        .context("Error consultando batch_items")?;
+
+        let mut items = Vec::with_capacity(rows.len());
+        for r in rows {
+            let download_url = (r.status == "done")
+                .then(|| format!("/api/pdf/batch/{}/items/{}", op_id, r.item_index));
+            items.push(BatchItemRecord {
+                id: r.id.unwrap_or_default(),
+                item_index: r.item_index,
+                file_name: r.file_name,
+                status: r.status,
+                error_message: r.error_message,
+                size_bytes: r.size_bytes,
+                download_url,
+                created_at: r.created_at.parse()?,
+                updated_at: r.updated_at.parse()?,
+            });
+        }
+
+        Ok(BatchStatusResponse {
+            operation,
+            total: items.len(),
+            done: items.iter().filter(|i| i.status == "done").count(),
+            failed: items.iter().filter(|i| i.status == "failed").count(),
+            items,
+        })
+    }
+
+    /// Nombre y contenido del PDF de un documento ya generado. `Ok(None)` si no
+    /// existe o todavía no terminó.
+    pub async fn get_item_file(
+        &self,
+        op_id: &str,
+        item_index: i64,
+    ) -> Result<Option<(String, Vec<u8>)>> {
+        let row = sqlx::query!(
+            r#"
+            SELECT file_name, file_path
+            FROM batch_items
+            WHERE operation_id = ?1 AND item_index = ?2 AND status = 'done'
+            "#,
+            op_id,
+            item_index
+        )
+        .fetch_optional(&self.db_pool)
+        .await
+        .context("Error consultando batch_item")?;
+
+        let Some((file_name, Some(key))) = row.map(|r| (r.file_name, r.file_path)) else {
+            return Ok(None);
+        };
+        let bytes = self
+            .storage
+            .get(&key)
+            .await?
+            .ok_or_else(|| ServiceError::not_found("Stored file", &key))?;
+        Ok(Some((file_name, bytes)))
+    }
+
+    /// Borra los PDFs de lotes expirados; sus items quedan como `expired`.
+    /// Retorna cuántos borró.
+    pub async fn purge_expired(&self) -> Result<usize> {
+        let now = timestamp(Utc::now());
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, file_path
+            FROM batch_items
+            WHERE status = 'done' AND expires_at IS NOT NULL AND expires_at <= ?1
+            "#,
+            now
+        )
+        .fetch_all(&self.db_pool)
+        .await
+        .context("Error consultando batch_items expirados")?;
+
+        for r in &rows {
+            let id = r.id.clone().unwrap_or_default();
+            self.update_item(&id, "expired", None, None, None, None)
+                .await?;
+            // La fila ya no apunta al archivo: si falla el borrado solo queda en el log
+            if let Some(key) = &r.file_path {
+                if let Err(e) = self.storage.delete(key).await {
+                    log::warn!("No se pudo borrar {}: {:?}", key, e);
+                }
+            }
+        }
+        Ok(rows.len())
+    }
+
+    /// Lanza la limpieza periódica de PDFs de lotes expirados.
+    pub fn spawn_expiry_task(&self) {
+        let service = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+            loop {
+                interval.tick().await;
+                match service.purge_expired().await {
+                    Ok(0) => {}
+                    Ok(n) => log::info!("PDFs de lotes expirados borrados: {}", n),
+                    Err(e) => log::error!("Error limpiando PDFs de lotes expirados: {:?}", e),
+                }
+            }
+        });
+    }
+
+    async fn create_item(&self, op_id: &str, index: usize, file_name: &str) -> Result<String> {
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now().to_rfc3339();
+        let index = index as i64;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO batch_items (
+                id, operation_id, item_index, file_name, status,
+                error_message, file_path, size_bytes, created_at, updated_at
+            )
+            VALUES (?1, ?2, ?3, ?4, 'pending', NULL, NULL, NULL, ?5, ?5)
+            "#,
+            id,
+            op_id,
+            index,
+            file_name,
+            now
+        )
+        .execute(&self.db_pool)
+        .await
+        .context("Error creando batch_item")?;
+
+        Ok(id)
+    }
+
+    async fn update_item(
+        &self,
+        item_id: &str,
+        status: &str,
+        error_message: Option<&str>,
+        file_path: Option<&str>,
+        size_bytes: Option<i64>,
+        expires_at: Option<&str>,
+    ) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        sqlx::query!(
+            r#"
+            UPDATE batch_items
+            SET status = ?1, error_message = ?2, file_path = ?3, size_bytes = ?4,
+                expires_at = ?5, updated_at = ?6
+            WHERE id = ?7
+            "#,
+            status,
+            error_message,
+            file_path,
+            size_bytes,
+            expires_at,
+            now,
+            item_id
+        )
+        .execute(&self.db_pool)
+        .await
+        .context("Error actualizando batch_item")?;
+        Ok(())
+    }
+}
+
+/// Deja solo el nombre de archivo (sin carpetas) y con extensión `.pdf`.
+fn sanitize_file_name(file_name: &str) -> String {
+    let base = file_name
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .trim();
+    let base = if base.is_empty() || base == "." || base == ".." {
+        "document"
+    } else {
+        base
+    };
+    if base.to_lowercase().ends_with(".pdf") {
+        base.to_string()
+    } else {
+        format!("{}.pdf", base)
+    }
+}
+
+/// Nombre dentro del ZIP; si se repite se le antepone el índice.
+fn unique_entry_name(index: usize, file_name: &str, used: &mut HashSet<String>) -> String {
+    let name = sanitize_file_name(file_name);
+    let name = if used.contains(&name) {
+        format!("{}_{}", index, name)
+    } else {
+        name
+    };
+    used.insert(name.clone());
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+    use crate::models::pdf_model::{PdfBackend, PdfImageOptions};
+    use crate::services::renderer::{PdfRenderer, RenderFiles, RenderOutput};
+    use crate::services::signing_service::SigningService;
+    use crate::services::storage::filesystem::FilesystemStorage;
+
+    /// Este test no renderiza
+    struct NoRenderer;
+
+    #[async_trait]
+    impl PdfRenderer for NoRenderer {
+        fn backend(&self) -> PdfBackend {
+            PdfBackend::Wkhtmltopdf
+        }
+
+        async fn render(&self, _req: &PdfRequest, _files: &RenderFiles) -> Result<RenderOutput> {
+            Err(anyhow!("sin motor"))
+        }
+
+        async fn render_image(
+            &self,
+            _req: &PdfRequest,
+            _files: &RenderFiles,
+            _options: &PdfImageOptions,
+        ) -> Result<RenderOutput> {
+            Err(anyhow!("sin motor"))
+        }
+    }
+
+    #[tokio::test]
+    async fn expired_batch_pdfs_are_deleted() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let operation_service = OperationService::new(pool.clone());
+        operation_service.run_migrations().await.unwrap();
+        let storage_dir = tempfile::tempdir().unwrap();
+        let storage: Arc<dyn Storage> =
+            Arc::new(FilesystemStorage::new(storage_dir.path().to_path_buf()));
+        let pdf_service = PdfService::for_tests(
+            Arc::new(NoRenderer),
+            SigningService::new(pool.clone()).unwrap(),
+            1,
+            std::time::Duration::from_secs(1),
+        );
+        let service = BatchService::new(
+            pool.clone(),
+            pdf_service,
+            TemplateService::new(pool.clone()),
+            operation_service.clone(),
+            storage.clone(),
+            60,
+        );
+
+        let op_id = operation_service
+            .create_operation(CreateOperationRequest {
+                operation_type: "batch_pdf".to_string(),
+                is_async: true,
+                metadata: None,
+            })
+            .await
+            .unwrap()
+            .id;
+        let past = timestamp(Utc::now() - chrono::Duration::hours(1));
+        let future = timestamp(expires_after(Utc::now(), 60).unwrap());
+        for (index, expires_at) in [past, future].iter().enumerate() {
+            let item_id = service.create_item(&op_id, index, "a.pdf").await.unwrap();
+            let key = format!("{}/{}/{}_a.pdf", BATCH_PREFIX, op_id, index);
+            storage.put(&key, b"%PDF", "application/pdf").await.unwrap();
+            service
+                .update_item(
+                    &item_id,
+                    "done",
+                    None,
+                    Some(&key),
+                    Some(4),
+                    Some(expires_at),
+                )
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(service.purge_expired().await.unwrap(), 1);
+        let batch = service.get_batch(&op_id).await.unwrap();
+        let statuses: Vec<&str> = batch.items.iter().map(|i| i.status.as_str()).collect();
+        assert_eq!(statuses, ["expired", "done"]);
+        assert!(service.get_item_file(&op_id, 0).await.unwrap().is_none());
+        assert!(storage
+            .get(&format!("{}/{}/0_a.pdf", BATCH_PREFIX, op_id))
+            .await
+            .unwrap()
+            .is_none());
+        assert!(service.get_item_file(&op_id, 1).await.unwrap().is_some());
+        assert_eq!(service.purge_expired().await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,402 @@
+//! services/document_service.rs
+//! Almacén de documentos generados: archivo en el Storage configurado (bajo `pdfs/`),
+//! fila en la tabla `documents` (ligada a su operación) y expiración con limpieza
+//! en background.
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::json;
+use sqlx::{Pool, Sqlite};
+use uuid::Uuid;
+
+use crate::models::document_model::{DocumentRecord, ListDocumentsResponse};
+use crate::models::operation_model::CreateOperationRequest;
+use crate::services::{
+    error::ServiceError, operation_service::OperationService, storage::Storage, util::hex,
+};
+
+/// Prefijo de los documentos dentro del Storage (con disco: ./files/pdfs)
+const DOCUMENTS_PREFIX: &str = "pdfs";
+
+/// Sin `DOCUMENT_TTL_SECONDS` los documentos no expiran, como antes de que
+/// existiera la expiración: solo esa variable o `store_ttl_seconds` los borran
+const DEFAULT_TTL_SECONDS: u64 = 0;
+
+/// Cada cuánto se borran los documentos (y PDFs de lotes) expirados
+pub const CLEANUP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Clone)]
+pub struct DocumentService {
+    db_pool: Pool<Sqlite>,
+    operation_service: OperationService,
+    storage: Arc<dyn Storage>,
+    /// Segundos de vida de un documento sin `store_ttl_seconds`; 0 = no expira
+    default_ttl_seconds: u64,
+}
+
+impl DocumentService {
+    pub fn new(
+        db_pool: Pool<Sqlite>,
+        operation_service: OperationService,
+        storage: Arc<dyn Storage>,
+    ) -> Self {
+        let default_ttl_seconds = std::env::var("DOCUMENT_TTL_SECONDS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_TTL_SECONDS);
+        DocumentService {
+            db_pool,
+            operation_service,
+            storage,
+            default_ttl_seconds,
+        }
+    }
+
+    /// Segundos de vida de un documento sin `store_ttl_seconds`; 0 = no expira
+    pub fn default_ttl_seconds(&self) -> u64 {
+        self.default_ttl_seconds
+    }
+
+    /// Guarda el resultado de un render síncrono: crea su operación
+    /// `generate_pdf` (ya terminada) y el documento ligado a ella.
+    pub async fn store_generated(
+        &self,
+        file_name: &str,
+        content_type: &str,
+        bytes: &[u8],
+        ttl_seconds: Option<u64>,
+    ) -> Result<DocumentRecord> {
+        let op = self
+            .operation_service
+            .create_operation(CreateOperationRequest {
+                operation_type: "generate_pdf".to_string(),
+                is_async: false,
+                metadata: Some(
+                    json!({ "file_name": file_name, "content_type": content_type }).to_string(),
+                ),
+            })
+            .await?;
+        let document = self
+            .store(&op.id, file_name, content_type, bytes, ttl_seconds)
+            .await;
+        match &document {
+            Ok(_) => {
+                self.operation_service
+                    .update_operation_status(&op.id, "done", None)
+                    .await?
+            }
+            Err(e) => {
+                self.operation_service
+                    .mark_operation_failed(&op.id, e.to_string())
+                    .await?
+            }
+        }
+        document
+    }
+
+    /// Escribe el archivo y registra el documento en la operación `operation_id`.
+    pub async fn store(
+        &self,
+        operation_id: &str,
+        file_name: &str,
+        content_type: &str,
+        bytes: &[u8],
+        ttl_seconds: Option<u64>,
+    ) -> Result<DocumentRecord> {
+        let id = Uuid::new_v4().to_string();
+        let storage_key = format!("{}_{}", id, base_file_name(file_name));
+
+        let object_key = object_key(&storage_key);
+        self.storage
+            .put(&object_key, bytes, content_type)
+            .await
+            .context("No se pudo guardar el documento")?;
+
+        let now = Utc::now();
+        let created_at = now.to_rfc3339();
+        let expires_at = match ttl_seconds.unwrap_or(self.default_ttl_seconds) {
+            0 => None,
+            ttl => Some(timestamp(expires_after(now, ttl)?)),
+        };
+        let size_bytes = bytes.len() as i64;
+        let sha256 = hex(&openssl::sha::sha256(bytes));
+
+        let inserted = sqlx::query!(
+            r#"
+            INSERT INTO documents
+                (id, operation_id, file_name, storage_key, content_type, size_bytes, sha256, created_at, expires_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+            "#,
+            id,
+            operation_id,
+            file_name,
+            storage_key,
+            content_type,
+            size_bytes,
+            sha256,
+            created_at,
+            expires_at
+        )
+        .execute(&self.db_pool)
+        .await
+        .context("Fallo al insertar documento");
+        if let Err(e) = inserted {
+            self.remove_object(&storage_key).await;
+            return Err(e);
+        }
+
+        log::info!(
+            "Documento {} guardado en {} ({}, {} bytes)",
+            id,
+            object_key,
+            self.storage.kind(),
+            size_bytes
+        );
+
+        Ok(DocumentRecord {
+            download_url: download_url(&id),
+            id,
+            operation_id: operation_id.to_string(),
+            file_name: file_name.to_string(),
+            content_type: content_type.to_string(),
+            size_bytes,
+            sha256,
+            created_at: created_at.parse()?,
+            expires_at: expires_at.map(|e| e.parse()).transpose()?,
+        })
+    }
+
+    /// Documento vigente (no expirado) por id
+    pub async fn get_document(&self, id: &str) -> Result<Option<DocumentRecord>> {
+        Ok(self.find_document(id).await?.map(|(record, _)| record))
+    }
+
+    /// Documento vigente con el contenido de su archivo
+    pub async fn get_document_content(
+        &self,
+        id: &str,
+    ) -> Result<Option<(DocumentRecord, Vec<u8>)>> {
+        let Some((record, storage_key)) = self.find_document(id).await? else {
+            return Ok(None);
+        };
+        let bytes = self
+            .storage
+            .get(&object_key(&storage_key))
+            .await?
+            .ok_or_else(|| ServiceError::not_found("Stored file", &storage_key))?;
+        Ok(Some((record, bytes)))
+    }
+
+    /// Archivo guardado, por su nombre en el Storage (`<id>_<file_name>`).
+    pub async fn get_stored_file(&self, storage_key: &str) -> Result<Option<Vec<u8>>> {
+        if storage_key.contains('/') {
+            return Ok(None);
+        }
+        self.storage.get(&object_key(storage_key)).await
+    }
+
+    /// Fila vigente y su clave en el Storage
+    async fn find_document(&self, id: &str) -> Result<Option<(DocumentRecord, String)>> {
+        let now = timestamp(Utc::now());
+        let row = sqlx::query!(
+            r#"
+            SELECT id, operation_id, file_name, storage_key, content_type, size_bytes, sha256,
+                   created_at, expires_at
+            FROM documents
+            WHERE id = ?1 AND (expires_at IS NULL OR expires_at > ?2)
+            "#,
+            id,
+            now
+        )
+        .fetch_optional(&self.db_pool)
+        .await
+        .context("Error consultando documento")?;
+
+        match row {
+            Some(r) => {
+                let id = r.id.unwrap_or_default();
+                let record = DocumentRecord {
+                    download_url: download_url(&id),
+                    id,
+                    operation_id: r.operation_id,
+                    file_name: r.file_name,
+                    content_type: r.content_type,
+                    size_bytes: r.size_bytes,
+                    sha256: r.sha256,
+                    created_at: r.created_at.parse()?,
+                    expires_at: r.expires_at.map(|e| e.parse()).transpose()?,
+                };
+                Ok(Some((record, r.storage_key)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Lista los documentos vigentes, más nuevos primero, opcionalmente de una operación
+    pub async fn list_documents(
+        &self,
+        page: u64,
+        page_size: u64,
+        operation_id: Option<&str>,
+    ) -> Result<ListDocumentsResponse> {
+        let now = timestamp(Utc::now());
+        let offset = (page.max(1) - 1) * page_size;
+        let page_size_i64 = page_size as i64;
+        let offset_i64 = offset as i64;
+
+        let total_row = sqlx::query!(
+            r#"
+            SELECT COUNT(*) as cnt FROM documents
+            WHERE (?1 IS NULL OR operation_id = ?1)
+              AND (expires_at IS NULL OR expires_at > ?2)
+            "#,
+            operation_id,
+            now
+        )
+        .fetch_one(&self.db_pool)
+        .await?;
+        let total = total_row.cnt as u64;
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, operation_id, file_name, content_type, size_bytes, sha256,
+                   created_at, expires_at
+            FROM documents
+            WHERE (?1 IS NULL OR operation_id = ?1)
+              AND (expires_at IS NULL OR expires_at > ?2)
+            ORDER BY created_at DESC
+            LIMIT ?3 OFFSET ?4
+            "#,
+            operation_id,
+            now,
+            page_size_i64,
+            offset_i64
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        let mut items = Vec::with_capacity(rows.len());
+        for r in rows {
+            let id = r.id.unwrap_or_default();
+            items.push(DocumentRecord {
+                download_url: download_url(&id),
+                id,
+                operation_id: r.operation_id,
+                file_name: r.file_name,
+                content_type: r.content_type,
+                size_bytes: r.size_bytes,
+                sha256: r.sha256,
+                created_at: r.created_at.parse()?,
+                expires_at: r.expires_at.map(|e| e.parse()).transpose()?,
+            });
+        }
+
+        Ok(ListDocumentsResponse {
+            total,
+            page,
+            page_size,
+            items,
+        })
+    }
+
+    /// Borra el documento y su archivo. Retorna `false` si no existía.
+    pub async fn delete_document(&self, id: &str) -> Result<bool> {
+        let row = sqlx::query!(
+            r#"DELETE FROM documents WHERE id = ?1 RETURNING storage_key"#,
+            id
+        )
+        .fetch_optional(&self.db_pool)
+        .await
+        .context("Error borrando documento")?;
+
+        match row {
+            Some(r) => {
+                self.remove_object(&r.storage_key).await;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Borra los documentos expirados (filas y archivos). Retorna cuántos borró.
+    pub async fn purge_expired(&self) -> Result<usize> {
+        let now = timestamp(Utc::now());
+        let rows = sqlx::query!(
+            r#"
+            DELETE FROM documents
+            WHERE expires_at IS NOT NULL AND expires_at <= ?1
+            RETURNING storage_key
+            "#,
+            now
+        )
+        .fetch_all(&self.db_pool)
+        .await
+        .context("Error borrando documentos expirados")?;
+
+        for r in &rows {
+            self.remove_object(&r.storage_key).await;
+        }
+        Ok(rows.len())
+    }
+
+    /// Borra el archivo; si falla solo queda en el log (la fila ya no existe).
+    async fn remove_object(&self, storage_key: &str) {
+        let key = object_key(storage_key);
+        if let Err(e) = self.storage.delete(&key).await {
+            log::warn!("No se pudo borrar {}: {:?}", key, e);
+        }
+    }
+
+    /// Lanza la limpieza periódica de documentos expirados.
+    pub fn spawn_expiry_task(&self) {
+        let service = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+            loop {
+                interval.tick().await;
+                match service.purge_expired().await {
+                    Ok(0) => {}
+                    Ok(n) => log::info!("Documentos expirados borrados: {}", n),
+                    Err(e) => log::error!("Error limpiando documentos expirados: {:?}", e),
+                }
+            }
+        });
+    }
+}
+
+/// `now` + `seconds`; error (y no pánico) si la fecha se sale del rango de chrono
+pub fn expires_after(now: DateTime<Utc>, seconds: u64) -> Result<DateTime<Utc>> {
+    i64::try_from(seconds)
+        .ok()
+        .and_then(chrono::Duration::try_seconds)
+        .and_then(|ttl| now.checked_add_signed(ttl))
+        .ok_or_else(|| anyhow!("Vida de {} segundos fuera de rango", seconds))
+}
+
+fn object_key(storage_key: &str) -> String {
+    format!("{}/{}", DOCUMENTS_PREFIX, storage_key)
+}
+
+fn download_url(id: &str) -> String {
+    format!("/api/documents/{}/content", id)
+}
+
+/// Formato fijo (segundos, "Z") para que las fechas se comparen como texto en SQLite.
+pub(crate) fn timestamp(date: DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Solo el nombre de archivo, sin carpetas.
+fn base_file_name(file_name: &str) -> &str {
+    match file_name
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .trim()
+    {
+        "" | "." | ".." => "document",
+        base => base,
+    }
+}
@@ -0,0 +1,248 @@
+//! services/download_link_service.rs
+//! Enlaces públicos de descarga de documentos guardados: la URL lleva la
+//! expiración y una firma HMAC-SHA256, y la tabla `document_links` permite revocarlos.
+//! Se sirven en `/api/public/...`, fuera de `ApiKeyMiddleware`.
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use openssl::{hash::MessageDigest, memcmp, pkey::PKey, rand::rand_bytes, sign::Signer};
+use sqlx::{Pool, Sqlite};
+use uuid::Uuid;
+
+use crate::models::document_model::{DocumentLinkRecord, DocumentRecord};
+use crate::services::document_service::{expires_after, timestamp, DocumentService};
+use crate::services::util::hex;
+
+/// Vida por defecto de un enlace si no se define `DOWNLOAD_LINK_TTL_SECONDS` (24 horas)
+const DEFAULT_LINK_TTL_SECONDS: u64 = 24 * 60 * 60;
+
+/// Resultado de abrir un enlace público
+pub enum LinkResolution {
+    Valid {
+        document: DocumentRecord,
+        bytes: Vec<u8>,
+    },
+    /// Firma incorrecta o enlace inexistente
+    Invalid,
+    /// Expirado, revocado, o el documento ya no existe
+    Gone,
+}
+
+#[derive(Clone)]
+pub struct DownloadLinkService {
+    db_pool: Pool<Sqlite>,
+    document_service: DocumentService,
+    /// Clave HMAC (`DOWNLOAD_LINK_SECRET`)
+    secret: Vec<u8>,
+    default_ttl_seconds: u64,
+    /// Base de las URLs absolutas (`PUBLIC_BASE_URL`), sin `/` final
+    public_base_url: Option<String>,
+}
+
+impl DownloadLinkService {
+    pub fn new(db_pool: Pool<Sqlite>, document_service: DocumentService) -> Result<Self> {
+        let secret = match std::env::var("DOWNLOAD_LINK_SECRET") {
+            Ok(secret) if !secret.is_empty() => secret.into_bytes(),
+            _ => {
+                log::warn!(
+                    "Sin DOWNLOAD_LINK_SECRET: los enlaces de descarga dejan de valer al reiniciar \
+                     y no sirven entre réplicas"
+                );
+                let mut secret = vec![0u8; 32];
+                rand_bytes(&mut secret)?;
+                secret
+            }
+        };
+        let default_ttl_seconds = std::env::var("DOWNLOAD_LINK_TTL_SECONDS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_LINK_TTL_SECONDS);
+        let public_base_url = std::env::var("PUBLIC_BASE_URL")
+            .ok()
+            .map(|url| url.trim_end_matches('/').to_string())
+            .filter(|url| !url.is_empty());
+
+        Ok(DownloadLinkService {
+            db_pool,
+            document_service,
+            secret,
+            default_ttl_seconds,
+            public_base_url,
+        })
+    }
+
+    /// Servicio de documentos sobre el que se crean los enlaces
+    pub fn document_service(&self) -> &DocumentService {
+        &self.document_service
+    }
+
+    /// Vida de un enlace sin `expires_in_seconds`
+    pub fn default_ttl_seconds(&self) -> u64 {
+        self.default_ttl_seconds
+    }
+
+    /// `true` si las URLs generadas son absolutas (hace falta para enviarlas por email/WhatsApp)
+    pub fn has_public_base_url(&self) -> bool {
+        self.public_base_url.is_some()
+    }
+
+    /// Crea un enlace para el documento. `Ok(None)` si el documento no existe o expiró.
+    /// El enlace nunca dura más que el documento.
+    pub async fn create_link(
+        &self,
+        document_id: &str,
+        expires_in_seconds: Option<u64>,
+    ) -> Result<Option<DocumentLinkRecord>> {
+        let Some(document) = self.document_service.get_document(document_id).await? else {
+            return Ok(None);
+        };
+        let ttl = expires_in_seconds.unwrap_or(self.default_ttl_seconds);
+        if ttl == 0 {
+            return Err(anyhow!("`expires_in_seconds` debe ser mayor a 0"));
+        }
+
+        let now = Utc::now();
+        let mut expires_at = expires_after(now, ttl)?;
+        if let Some(document_expires_at) = document.expires_at {
+            expires_at = expires_at.min(document_expires_at);
+        }
+        // La firma usa segundos Unix: la fila guarda la misma precisión
+        let expires_at = DateTime::from_timestamp(expires_at.timestamp(), 0)
+            .ok_or_else(|| anyhow!("Expiración fuera de rango"))?;
+
+        let id = Uuid::new_v4().to_string();
+        let expires_at_str = timestamp(expires_at);
+        let created_at = now.to_rfc3339();
+        sqlx::query!(
+            r#"
+            INSERT INTO document_links (id, document_id, expires_at, revoked_at, created_at)
+            VALUES (?1, ?2, ?3, NULL, ?4)
+            "#,
+            id,
+            document_id,
+            expires_at_str,
+            created_at
+        )
+        .execute(&self.db_pool)
+        .await
+        .context("Fallo al insertar enlace de descarga")?;
+
+        Ok(Some(DocumentLinkRecord {
+            url: self.signed_url(&id, expires_at.timestamp())?,
+            id,
+            document_id: document_id.to_string(),
+            expires_at,
+            revoked_at: None,
+            created_at: created_at.parse()?,
+        }))
+    }
+
+    /// Enlaces de un documento (vigentes, expirados y revocados), más nuevos primero
+    pub async fn list_links(&self, document_id: &str) -> Result<Vec<DocumentLinkRecord>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, document_id, expires_at, revoked_at, created_at
+            FROM document_links
+            WHERE document_id = ?1
+            ORDER BY created_at DESC
+            "#,
+            document_id
+        )
+        .fetch_all(&self.db_pool)
+        .await
+        .context("Error consultando enlaces de descarga")?;
+
+        let mut items = Vec::with_capacity(rows.len());
+        for r in rows {
+            let id = r.id.unwrap_or_default();
+            let expires_at: DateTime<Utc> = r.expires_at.parse()?;
+            items.push(DocumentLinkRecord {
+                url: self.signed_url(&id, expires_at.timestamp())?,
+                id,
+                document_id: r.document_id,
+                expires_at,
+                revoked_at: r.revoked_at.map(|e| e.parse()).transpose()?,
+                created_at: r.created_at.parse()?,
+            });
+        }
+        Ok(items)
+    }
+
+    /// Revoca el enlace. Retorna `false` si no existe para ese documento.
+    pub async fn revoke_link(&self, document_id: &str, link_id: &str) -> Result<bool> {
+        let now = Utc::now().to_rfc3339();
+        let result = sqlx::query!(
+            r#"
+            UPDATE document_links
+            SET revoked_at = COALESCE(revoked_at, ?1)
+            WHERE id = ?2 AND document_id = ?3
+            "#,
+            now,
+            link_id,
+            document_id
+        )
+        .execute(&self.db_pool)
+        .await
+        .context("Error revocando enlace de descarga")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Valida firma, expiración y revocación, y retorna el documento con su contenido.
+    pub async fn resolve(
+        &self,
+        link_id: &str,
+        expires: i64,
+        signature: &str,
+    ) -> Result<LinkResolution> {
+        let expected = self.sign(link_id, expires)?;
+        if expected.len() != signature.len()
+            || !memcmp::eq(expected.as_bytes(), signature.as_bytes())
+        {
+            return Ok(LinkResolution::Invalid);
+        }
+        if expires <= Utc::now().timestamp() {
+            return Ok(LinkResolution::Gone);
+        }
+
+        let row = sqlx::query!(
+            r#"SELECT document_id, revoked_at FROM document_links WHERE id = ?1"#,
+            link_id
+        )
+        .fetch_optional(&self.db_pool)
+        .await
+        .context("Error consultando enlace de descarga")?;
+        let Some(row) = row else {
+            return Ok(LinkResolution::Invalid);
+        };
+        if row.revoked_at.is_some() {
+            return Ok(LinkResolution::Gone);
+        }
+
+        match self
+            .document_service
+            .get_document_content(&row.document_id)
+            .await?
+        {
+            Some((document, bytes)) => Ok(LinkResolution::Valid { document, bytes }),
+            None => Ok(LinkResolution::Gone),
+        }
+    }
+
+    fn signed_url(&self, link_id: &str, expires: i64) -> Result<String> {
+        Ok(format!(
+            "{}/api/public/links/{}?expires={}&signature={}",
+            self.public_base_url.as_deref().unwrap_or_default(),
+            link_id,
+            expires,
+            self.sign(link_id, expires)?
+        ))
+    }
+
+    /// HMAC-SHA256 de `<link_id>:<expires>` en hex
+    fn sign(&self, link_id: &str, expires: i64) -> Result<String> {
+        let key = PKey::hmac(&self.secret)?;
+        let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+        signer.update(format!("{}:{}", link_id, expires).as_bytes())?;
+        Ok(hex(&signer.sign_to_vec()?))
+    }
+}
@@ -18,7 +18,7 @@ use sqlx::{Pool, Sqlite};
 
 use crate::{
     models::email_model::{EmailAttachment, EmailStatusResponse, SendUniversalEmailRequest},
-    services::operation_service::OperationService,
+    services::{error::ServiceError, operation_service::OperationService},
 };
 
 #[derive(Debug, Clone)]
@@ -75,9 +75,10 @@ impl EmailService {
             r#"SELECT status, error_message FROM emails WHERE operation_id = ?1"#,
             operation_id
         )
-        .fetch_one(&self.db_pool)
+        .fetch_optional(&self.db_pool)
         .await
-        .context("Email operation not found")?;
+        .context("Error consultando el email")?
+        .ok_or_else(|| ServiceError::not_found("Email operation", operation_id))?;
 
         Ok(EmailStatusResponse {
             id: operation_id.to_string(),
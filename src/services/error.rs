@@ -0,0 +1,82 @@
+//! services/error.rs
+//! Errores de los servicios que los handlers necesitan distinguir. Viajan dentro
+//! de `anyhow::Error` y se reconocen por su tipo, no por el texto del mensaje.
+
+use std::fmt;
+
+#[derive(Debug)]
+pub enum ServiceError {
+    /// No existe el recurso `resource` ("Template", "Signing profile", ...) con ese id
+    NotFound { resource: &'static str, id: String },
+    /// La entrada del cliente no sirve (un PDF que no se puede leer, una página
+    /// que no existe, ...): es un 4xx, no una falla del servidor
+    InvalidInput(String),
+}
+
+impl ServiceError {
+    pub fn not_found(resource: &'static str, id: impl fmt::Display) -> anyhow::Error {
+        ServiceError::NotFound {
+            resource,
+            id: id.to_string(),
+        }
+        .into()
+    }
+
+    pub fn invalid_input(message: impl Into<String>) -> anyhow::Error {
+        ServiceError::InvalidInput(message.into()).into()
+    }
+}
+
+impl fmt::Display for ServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServiceError::NotFound { resource, id } => write!(f, "{} not found: {}", resource, id),
+            ServiceError::InvalidInput(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for ServiceError {}
+
+/// Si el error (o alguna de sus causas, aunque tenga `context` encima) es un
+/// `ServiceError::NotFound`
+pub fn is_not_found(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<ServiceError>(),
+            Some(ServiceError::NotFound { .. })
+        )
+    })
+}
+
+/// Igual que `is_not_found`, para `ServiceError::InvalidInput`
+pub fn is_invalid_input(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<ServiceError>(),
+            Some(ServiceError::InvalidInput(_))
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{anyhow, Context};
+
+    use super::*;
+
+    #[test]
+    fn not_found_is_detected_by_type_and_through_context() {
+        let error = ServiceError::not_found("Template", "factura");
+        assert_eq!(error.to_string(), "Template not found: factura");
+        assert!(is_not_found(&error));
+
+        let wrapped = Err::<(), _>(error)
+            .context("No se pudo aplicar la plantilla")
+            .unwrap_err();
+        assert!(is_not_found(&wrapped));
+
+        // Un mensaje parecido no alcanza
+        assert!(!is_not_found(&anyhow!("Template not found: factura")));
+    }
+}
@@ -1,8 +1,20 @@
 //! services/mod.rs
 //! Módulo que agrupa distintos "servicios" o "capas de negocio" de la app.
 
+pub mod batch_service;
+pub mod document_service;
+pub mod download_link_service;
 pub mod email_service;
+pub mod error;
 pub mod notification_channel_service;
 pub mod notification_service;
 pub mod operation_service;
+pub mod pdf_job_service;
 pub mod pdf_service;
+pub mod pdf_tools;
+pub mod render_cache;
+pub mod renderer;
+pub mod signing_service;
+pub mod storage;
+pub mod template_service;
+pub mod util;
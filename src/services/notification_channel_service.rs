@@ -87,6 +87,7 @@ impl NotificationChannelService {
         Ok(())
     }
 
+    #[allow(dead_code)]
     pub async fn get_channel(&self, channel_id: &str) -> Result<OperationChannelRecord> {
         let row = sqlx::query!(
             r#"
@@ -4,23 +4,25 @@ use sqlx::{Pool, Sqlite};
 use std::env;
 
 use crate::{
-    models::{
-        email_model::EmailAttachment, notification_model::NotificationRequest,
-        pdf_model::PdfRequest,
-    },
+    models::{email_model::EmailAttachment, notification_model::NotificationRequest},
     services::{
-        email_service::EmailService, notification_channel_service::NotificationChannelService,
+        download_link_service::DownloadLinkService, email_service::EmailService,
+        notification_channel_service::NotificationChannelService,
         operation_service::OperationService, pdf_service::PdfService,
+        renderer::header_footer::escape_html, template_service::TemplateService,
     },
 };
 
 #[derive(Clone)]
 pub struct NotificationService {
+    #[allow(dead_code)]
     db_pool: Pool<Sqlite>,
     email_service: EmailService,
     pdf_service: PdfService,
     operation_service: OperationService,
     channel_service: NotificationChannelService,
+    template_service: TemplateService,
+    link_service: DownloadLinkService,
     http_client: Client,
 }
 
@@ -31,6 +33,8 @@ impl NotificationService {
         pdf_service: PdfService,
         operation_service: OperationService,
         channel_service: NotificationChannelService,
+        template_service: TemplateService,
+        link_service: DownloadLinkService,
     ) -> Self {
         Self {
             db_pool,
@@ -38,6 +42,8 @@ impl NotificationService {
             pdf_service,
             operation_service,
             channel_service,
+            template_service,
+            link_service,
             http_client: Client::new(),
         }
     }
@@ -58,9 +64,18 @@ impl NotificationService {
             .update_operation_status(&op_id, "running", None)
             .await?;
 
-        // 2) Generar PDF (si pdf_html existe)
+        // 2) Generar PDF (si hay pdf_html o pdf_template_id)
         let mut final_attachments = vec![];
-        if let Some(html) = req.pdf_html.take() {
+        let pdf_html = self
+            .template_service
+            .resolve_html(
+                req.pdf_template_id.as_deref(),
+                req.pdf_template_version,
+                req.pdf_data.as_ref(),
+                req.pdf_html.take(),
+            )
+            .await?;
+        if let Some(html) = pdf_html {
             log::info!(
                 "(process_notification) Se recibió pdf_html con longitud={} chars, generando PDF...",
                 html.len()
@@ -74,15 +89,30 @@ impl NotificationService {
                 .pdf_attachment_name
                 .clone()
                 .unwrap_or_else(|| "document.pdf".to_string());
-            final_attachments.push(EmailAttachment {
-                filename: pdf_filename.clone(),
-                content_type: "application/pdf".to_string(),
-                data: pdf_bytes,
-            });
-            log::info!(
-                "(process_notification) Se agregó un adjunto PDF: {}",
-                pdf_filename
-            );
+            let over_threshold = req
+                .pdf_link_threshold_bytes
+                .is_some_and(|limit| pdf_bytes.len() as u64 > limit);
+            if over_threshold {
+                let url = self
+                    .share_pdf_link(&op_id, &req, &pdf_filename, &pdf_bytes)
+                    .await?;
+                add_link_to_messages(&mut req, &pdf_filename, &url);
+                log::info!(
+                    "(process_notification) PDF de {} bytes enviado como enlace: {}",
+                    pdf_bytes.len(),
+                    pdf_filename
+                );
+            } else {
+                final_attachments.push(EmailAttachment {
+                    filename: pdf_filename.clone(),
+                    content_type: "application/pdf".to_string(),
+                    data: pdf_bytes,
+                });
+                log::info!(
+                    "(process_notification) Se agregó un adjunto PDF: {}",
+                    pdf_filename
+                );
+            }
         } else {
             log::info!("(process_notification) No se recibió pdf_html, no se generará PDF.");
         }
@@ -217,19 +247,7 @@ impl NotificationService {
             req.pdf_orientation,
             req.pdf_page_size_preset
         );
-        let pdf_req = PdfRequest {
-            file_name: req
-                .pdf_attachment_name
-                .clone()
-                .unwrap_or_else(|| "document.pdf".to_string()),
-            html,
-            orientation: req.pdf_orientation.clone(),
-            page_size_preset: req.pdf_page_size_preset.clone(),
-            custom_page_size: req.pdf_custom_page_size.clone(),
-            margins: req.pdf_margins.clone(),
-            scale: req.pdf_scale,
-            store_local_pdf: Some(false),
-        };
+        let pdf_req = req.pdf_request(html);
 
         let pdf_bytes = self
             .pdf_service
@@ -244,6 +262,36 @@ impl NotificationService {
         Ok(pdf_bytes)
     }
 
+    /// Guarda el PDF como documento de la operación y crea su enlace público.
+    async fn share_pdf_link(
+        &self,
+        op_id: &str,
+        req: &NotificationRequest,
+        file_name: &str,
+        pdf_bytes: &[u8],
+    ) -> Result<String> {
+        if !self.link_service.has_public_base_url() {
+            return Err(anyhow!(
+                "PUBLIC_BASE_URL no está configurada: no se puede enviar el PDF como enlace"
+            ));
+        }
+        // El documento existe solo para el enlace: dura lo mismo
+        let ttl = req
+            .pdf_link_expires_in_seconds
+            .unwrap_or(self.link_service.default_ttl_seconds());
+        let document = self
+            .link_service
+            .document_service()
+            .store(op_id, file_name, "application/pdf", pdf_bytes, Some(ttl))
+            .await?;
+        let link = self
+            .link_service
+            .create_link(&document.id, Some(ttl))
+            .await?
+            .ok_or_else(|| anyhow!("Documento recién guardado no encontrado"))?;
+        Ok(link.url)
+    }
+
     async fn send_via_email(
         &self,
         op_id: &str,
@@ -279,14 +327,29 @@ impl NotificationService {
             async_send: false,
             // No generaremos PDF acá, pues ya lo hicimos arriba en `final_attachments`.
             pdf_html: None,
+            pdf_template_id: None,
+            pdf_template_version: None,
+            pdf_data: None,
             pdf_orientation: None,
             pdf_page_size_preset: None,
             pdf_custom_page_size: None,
             pdf_margins: None,
+            pdf_auto_height: None,
             pdf_scale: None,
+            pdf_backend: None,
+            pdf_header_html: None,
+            pdf_footer_html: None,
+            pdf_header_text: None,
+            pdf_footer_text: None,
+            pdf_watermark: None,
+            pdf_conformance: None,
+            pdf_invoice: None,
+            pdf_encryption: None,
+            pdf_signature: None,
             pdf_attachment_name: None,
             // Los adjuntos van tanto aquí (para referencia) como en el tercer param
             other_attachments: Some(attachments.to_vec()),
+            tenant: req.tenant.clone(),
         };
 
         log::info!("(send_via_email) Llamando a email_service.send_unified...");
@@ -375,7 +438,7 @@ impl NotificationService {
         }
 
         // 2) Enviar mensaje de texto
-        if message.len() > 0 {
+        if !message.is_empty() {
             log::info!(
                 "(send_via_whatsapp) Enviando texto a {} destinatarios...",
                 recipients.len()
@@ -611,3 +674,25 @@ impl NotificationService {
     //     Ok(())
     // }
 }
+
+/// Agrega el enlace de descarga al cuerpo del email (HTML) y al mensaje de WhatsApp.
+fn add_link_to_messages(req: &mut NotificationRequest, file_name: &str, url: &str) {
+    let link_html = format!(
+        "<p><a href=\"{}\">{}</a></p>",
+        escape_html(url),
+        escape_html(file_name)
+    );
+    req.body = Some(format!(
+        "{}{}",
+        req.body.take().unwrap_or_default(),
+        link_html
+    ));
+
+    if let Some(wa_config) = req.whatsapp_config.as_mut() {
+        let line = format!("{}: {}", file_name, url);
+        wa_config.message = Some(match wa_config.message.take() {
+            Some(message) if !message.is_empty() => format!("{}\n{}", message, line),
+            _ => line,
+        });
+    }
+}
@@ -7,6 +7,7 @@ use crate::models::operation_model::{
     CreateOperationRequest, CreateOperationResponse, ListOperationsResponse, OperationRecord,
     OperationStatusResponse,
 };
+use crate::services::error::ServiceError;
 
 #[derive(Clone, Debug)]
 pub struct OperationService {
@@ -70,9 +71,10 @@ impl OperationService {
             "#,
             op_id
         )
-        .fetch_one(&self.db_pool)
+        .fetch_optional(&self.db_pool)
         .await
-        .context("No se encontró operación con ese id")?;
+        .context("Error consultando la operación")?
+        .ok_or_else(|| ServiceError::not_found("Operation", op_id))?;
 
         // parsea strings a boolean e ISO8601
         Ok(OperationRecord {
@@ -146,13 +148,16 @@ impl OperationService {
         op_id: &str,
         error: String,
     ) -> Result<(), anyhow::Error> {
+        // Mismo formato que `created_at`: get_operation lo parsea como RFC 3339
+        let now = Utc::now().to_rfc3339();
         sqlx::query!(
             r#"UPDATE operations 
             SET status = 'failed', 
                 error_message = ?, 
-                updated_at = CURRENT_TIMESTAMP 
+                updated_at = ? 
             WHERE id = ?"#,
             error,
+            now,
             op_id
         )
         .execute(&self.db_pool)
@@ -161,6 +166,43 @@ impl OperationService {
         Ok(())
     }
 
+    /// Marca como fallidas las operaciones de `operation_type` que quedaron
+    /// `pending` o `running`: al arrancar el servidor ya nadie las va a terminar.
+    /// Retorna cuántas se marcaron.
+    pub async fn fail_interrupted_operations(&self, operation_type: &str) -> Result<u64> {
+        let now = Utc::now().to_rfc3339();
+        let result = sqlx::query!(
+            r#"UPDATE operations
+            SET status = 'failed',
+                error_message = 'Interrumpida por un reinicio del servidor',
+                updated_at = ?1
+            WHERE operation_type = ?2 AND status IN ('pending', 'running')"#,
+            now,
+            operation_type
+        )
+        .execute(&self.db_pool)
+        .await
+        .context("Failed to fail interrupted operations")?;
+        Ok(result.rows_affected())
+    }
+
+    /// Reemplaza el JSON de metadata de la operación
+    pub async fn update_operation_metadata(
+        &self,
+        operation_id: &str,
+        metadata: &str,
+    ) -> Result<()> {
+        sqlx::query!(
+            r#"UPDATE operations SET metadata = ?1 WHERE id = ?2"#,
+            metadata,
+            operation_id
+        )
+        .execute(&self.db_pool)
+        .await
+        .context("Failed to update operation metadata")?;
+        Ok(())
+    }
+
     pub async fn update_operation_status(
         &self,
         operation_id: &str,
@@ -0,0 +1,509 @@
+//! services/pdf_job_service.rs
+//! Renders asíncronos (`"async": true` en `/api/pdf`): operación `generate_pdf`,
+//! render en background, resultado guardado como documento de la operación y,
+//! opcionalmente, aviso a `callback_url` al terminar.
+
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use openssl::{hash::MessageDigest, pkey::PKey, sign::Signer};
+use reqwest::{header::CONTENT_TYPE, redirect, Client, Url};
+use serde_json::json;
+
+use crate::models::document_model::DocumentRecord;
+use crate::models::operation_model::CreateOperationRequest;
+use crate::models::pdf_model::{PdfJobCallback, PdfRenderReport, PdfRequest};
+use crate::services::{
+    document_service::DocumentService,
+    error::{is_not_found, ServiceError},
+    operation_service::OperationService,
+    pdf_service::PdfService,
+    template_service,
+    util::{hex, resolve_public_host},
+};
+
+/// Intentos de entrega del callback (con espera creciente entre uno y otro)
+const CALLBACK_ATTEMPTS: u32 = 3;
+const CALLBACK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Estado del resultado de un render asíncrono
+pub enum PdfJobResult {
+    /// Todavía `pending` o `running`
+    Pending(String),
+    Failed(Option<String>),
+    Ready {
+        document: DocumentRecord,
+        bytes: Vec<u8>,
+    },
+    /// Terminó, pero el documento expiró o fue borrado
+    Expired,
+}
+
+#[derive(Clone)]
+pub struct PdfJobService {
+    pdf_service: PdfService,
+    operation_service: OperationService,
+    document_service: DocumentService,
+    /// Clave HMAC de los callbacks (`CALLBACK_SECRET`); sin ella van sin firmar
+    callback_secret: Option<Vec<u8>>,
+    /// `CALLBACK_ALLOW_PRIVATE_HOSTS=true`: admite callbacks a la red interna
+    allow_private_callbacks: bool,
+}
+
+impl PdfJobService {
+    pub fn new(
+        pdf_service: PdfService,
+        operation_service: OperationService,
+        document_service: DocumentService,
+    ) -> Self {
+        let callback_secret = std::env::var("CALLBACK_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty())
+            .map(String::into_bytes);
+        if callback_secret.is_none() {
+            log::warn!("Sin CALLBACK_SECRET: los callbacks de los PDFs asíncronos van sin firmar");
+        }
+        let allow_private_callbacks = std::env::var("CALLBACK_ALLOW_PRIVATE_HOSTS")
+            .is_ok_and(|value| value.eq_ignore_ascii_case("true") || value == "1");
+
+        PdfJobService {
+            // Sin cliente esperando: el render espera su turno en vez de fallar por timeout
+            pdf_service: pdf_service.queued(),
+            operation_service,
+            document_service,
+            callback_secret,
+            allow_private_callbacks,
+        }
+    }
+
+    /// Marca como fallidos los renders que quedaron `pending` o `running` por un
+    /// reinicio: la tarea que los procesaba ya no existe.
+    pub async fn fail_interrupted_jobs(&self) -> Result<u64> {
+        self.operation_service
+            .fail_interrupted_operations("generate_pdf")
+            .await
+    }
+
+    /// Crea la operación `generate_pdf` y lanza el render en background.
+    /// La plantilla ya debe estar aplicada. Retorna el id de la operación.
+    pub async fn start_job(&self, req: PdfRequest) -> Result<String> {
+        let op = self
+            .operation_service
+            .create_operation(CreateOperationRequest {
+                operation_type: "generate_pdf".to_string(),
+                is_async: true,
+                metadata: Some(
+                    json!({
+                        "file_name": req.file_name,
+                        "callback_url": req.callback_url,
+                        "template": template_metadata(&req),
+                    })
+                    .to_string(),
+                ),
+            })
+            .await?;
+        let op_id = op.id;
+
+        let service = self.clone();
+        let op_id_clone = op_id.clone();
+        tokio::spawn(async move {
+            service.run_job(&op_id_clone, req).await;
+        });
+
+        Ok(op_id)
+    }
+
+    async fn run_job(&self, op_id: &str, req: PdfRequest) {
+        let callback_url = req.callback_url.clone();
+        let file_name = req.file_name.clone();
+        let template = template_metadata(&req);
+
+        let callback = match self.process_job(op_id, req).await {
+            Ok((document, report)) => {
+                let result_url = result_url(op_id);
+                let metadata = json!({
+                    "file_name": file_name,
+                    "callback_url": callback_url,
+                    "template": template,
+                    "document_id": document.id,
+                    "result_url": result_url,
+                    "report": report,
+                });
+                let _ = self
+                    .operation_service
+                    .update_operation_metadata(op_id, &metadata.to_string())
+                    .await;
+                let _ = self
+                    .operation_service
+                    .update_operation_status(op_id, "done", None)
+                    .await;
+                PdfJobCallback {
+                    operation_id: op_id.to_string(),
+                    status: "done".to_string(),
+                    error: None,
+                    document_id: Some(document.id),
+                    result_url: Some(result_url),
+                    report: Some(report),
+                }
+            }
+            Err(e) => {
+                log::error!("Render asíncrono {} falló: {:?}", op_id, e);
+                let _ = self
+                    .operation_service
+                    .mark_operation_failed(op_id, e.to_string())
+                    .await;
+                PdfJobCallback {
+                    operation_id: op_id.to_string(),
+                    status: "failed".to_string(),
+                    error: Some(e.to_string()),
+                    document_id: None,
+                    result_url: None,
+                    report: None,
+                }
+            }
+        };
+
+        if let Some(url) = callback_url {
+            self.send_callback(&url, &callback).await;
+        }
+    }
+
+    /// Renderiza (PDF o imagen) y guarda el resultado como documento de la operación
+    async fn process_job(
+        &self,
+        op_id: &str,
+        req: PdfRequest,
+    ) -> Result<(DocumentRecord, PdfRenderReport)> {
+        self.operation_service
+            .update_operation_status(op_id, "running", None)
+            .await?;
+
+        let ttl_seconds = req.store_ttl_seconds;
+        let (file_name, content_type, bytes, report) = match req.image.clone() {
+            Some(options) => {
+                let file_name = PathBuf::from(&req.file_name)
+                    .with_extension(options.format.extension())
+                    .to_string_lossy()
+                    .into_owned();
+                let rendered = self.pdf_service.render_image(&req, &options).await?;
+                (
+                    file_name,
+                    options.format.content_type(),
+                    rendered.image,
+                    rendered.report,
+                )
+            }
+            None => {
+                let file_name = req.file_name.clone();
+                let rendered = self.pdf_service.render_pdf(req).await?;
+                (file_name, "application/pdf", rendered.pdf, rendered.report)
+            }
+        };
+
+        let document = self
+            .document_service
+            .store(op_id, &file_name, content_type, &bytes, ttl_seconds)
+            .await?;
+        Ok((document, report))
+    }
+
+    /// POST del aviso a `callback_url`, firmado con `CALLBACK_SECRET`; los fallos
+    /// solo quedan en el log
+    async fn send_callback(&self, url: &str, callback: &PdfJobCallback) {
+        let prepared = async {
+            let client = self.callback_client(url).await?;
+            let body = serde_json::to_vec(callback)?;
+            Ok::<_, anyhow::Error>((client, body))
+        }
+        .await;
+        let (client, body) = match prepared {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                log::warn!(
+                    "Callback de {} a {} descartado: {}",
+                    callback.operation_id,
+                    url,
+                    e
+                );
+                return;
+            }
+        };
+
+        for attempt in 1..=CALLBACK_ATTEMPTS {
+            let mut request = client
+                .post(url)
+                .timeout(CALLBACK_TIMEOUT)
+                .header(CONTENT_TYPE, "application/json");
+            if let Some(secret) = &self.callback_secret {
+                // La marca de tiempo va firmada: el receptor puede rechazar reenvíos viejos
+                let timestamp = Utc::now().timestamp();
+                match sign_callback(secret, timestamp, &body) {
+                    Ok(signature) => {
+                        request = request
+                            .header("X-Signature-Timestamp", timestamp)
+                            .header("X-Signature", format!("sha256={}", signature));
+                    }
+                    Err(e) => {
+                        log::error!(
+                            "No se pudo firmar el callback de {}: {}",
+                            callback.operation_id,
+                            e
+                        );
+                        return;
+                    }
+                }
+            }
+            let result = request
+                .body(body.clone())
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            match result {
+                Ok(_) => {
+                    log::info!("Callback de {} entregado a {}", callback.operation_id, url);
+                    return;
+                }
+                Err(e) => log::warn!(
+                    "Callback de {} a {} falló (intento {}/{}): {}",
+                    callback.operation_id,
+                    url,
+                    attempt,
+                    CALLBACK_ATTEMPTS,
+                    e
+                ),
+            }
+            if attempt < CALLBACK_ATTEMPTS {
+                tokio::time::sleep(Duration::from_secs(2u64.pow(attempt))).await;
+            }
+        }
+    }
+
+    /// Cliente para un callback: solo http(s), sin redirecciones y, salvo con
+    /// `CALLBACK_ALLOW_PRIVATE_HOSTS`, solo a direcciones públicas. El host se
+    /// resuelve una vez y el cliente queda fijado a esas IPs, así un DNS que cambia
+    /// entre la validación y el envío no lleva el POST a la red interna.
+    async fn callback_client(&self, url: &str) -> Result<Client> {
+        let parsed = Url::parse(url).with_context(|| format!("URL inválida: {}", url))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err(anyhow!("Esquema no permitido: {}", parsed.scheme()));
+        }
+        let builder = Client::builder().redirect(redirect::Policy::none());
+        if self.allow_private_callbacks {
+            return Ok(builder.build()?);
+        }
+
+        let addrs = resolve_public_host(&parsed).await?;
+        let builder = match parsed.domain() {
+            Some(domain) => builder.resolve_to_addrs(domain, &addrs),
+            None => builder,
+        };
+        Ok(builder.build()?)
+    }
+
+    /// Estado y, si terminó, contenido del resultado de la operación `generate_pdf`
+    pub async fn get_result(&self, op_id: &str) -> Result<PdfJobResult> {
+        let operation = match self.operation_service.get_operation(op_id).await {
+            Ok(operation) if operation.operation_type == "generate_pdf" => operation,
+            Err(e) if !is_not_found(&e) => return Err(e),
+            _ => return Err(ServiceError::not_found("PDF job", op_id)),
+        };
+
+        match operation.status.as_str() {
+            "done" => {}
+            "failed" => return Ok(PdfJobResult::Failed(operation.error_message)),
+            status => return Ok(PdfJobResult::Pending(status.to_string())),
+        }
+
+        let documents = self
+            .document_service
+            .list_documents(1, 1, Some(op_id))
+            .await?;
+        let Some(document) = documents.items.into_iter().next() else {
+            return Ok(PdfJobResult::Expired);
+        };
+        match self
+            .document_service
+            .get_document_content(&document.id)
+            .await
+        {
+            Ok(Some((document, bytes))) => Ok(PdfJobResult::Ready { document, bytes }),
+            Ok(None) => Ok(PdfJobResult::Expired),
+            Err(e) if is_not_found(&e) => Ok(PdfJobResult::Expired),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Plantilla y datos del render, si el request usó una (ya fijada por
+/// `TemplateService::apply_to_request`)
+fn template_metadata(req: &PdfRequest) -> Option<serde_json::Value> {
+    let (template_id, version) = req.template_id.as_deref().zip(req.template_version)?;
+    Some(template_service::render_metadata(
+        template_id,
+        version,
+        req.data.as_ref(),
+    ))
+}
+
+/// Ruta de descarga del resultado
+pub fn result_url(op_id: &str) -> String {
+    format!("/api/pdf/jobs/{}/result", op_id)
+}
+
+/// HMAC-SHA256 de `<timestamp>.<body>` en hex
+fn sign_callback(secret: &[u8], timestamp: i64, body: &[u8]) -> Result<String> {
+    let key = PKey::hmac(secret)?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(format!("{}.", timestamp).as_bytes())?;
+    signer.update(body)?;
+    Ok(hex(&signer.sign_to_vec()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use lopdf::{dictionary, Document, Object};
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    use super::*;
+    use crate::models::pdf_model::{PdfBackend, PdfImageOptions};
+    use crate::services::renderer::{PdfRenderer, RenderFiles, RenderOutput};
+    use crate::services::signing_service::SigningService;
+    use crate::services::storage::filesystem::FilesystemStorage;
+
+    /// Motor que tarda `delay` en devolver un PDF de una página
+    struct SlowRenderer {
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl PdfRenderer for SlowRenderer {
+        fn backend(&self) -> PdfBackend {
+            PdfBackend::Wkhtmltopdf
+        }
+
+        async fn render(&self, _req: &PdfRequest, _files: &RenderFiles) -> Result<RenderOutput> {
+            tokio::time::sleep(self.delay).await;
+            let mut doc = Document::with_version("1.7");
+            let pages_id = doc.new_object_id();
+            let page_id = doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+            });
+            doc.objects.insert(
+                pages_id,
+                Object::Dictionary(dictionary! {
+                    "Type" => "Pages",
+                    "Kids" => vec![page_id.into()],
+                    "Count" => 1,
+                }),
+            );
+            let catalog_id =
+                doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+            doc.trailer.set("Root", catalog_id);
+            let mut bytes = Vec::new();
+            doc.save_to(&mut bytes)?;
+            Ok(RenderOutput {
+                bytes,
+                warnings: Vec::new(),
+            })
+        }
+
+        async fn render_image(
+            &self,
+            _req: &PdfRequest,
+            _files: &RenderFiles,
+            _options: &PdfImageOptions,
+        ) -> Result<RenderOutput> {
+            Err(anyhow::anyhow!("sin imágenes"))
+        }
+    }
+
+    fn html_request() -> PdfRequest {
+        PdfRequest {
+            html: "<p>Hola</p>".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn job_waits_for_a_render_slot_instead_of_failing() {
+        // Una sola conexión: cada conexión a `:memory:` es una base distinta
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let operation_service = OperationService::new(pool.clone());
+        operation_service.run_migrations().await.unwrap();
+        let storage_dir = tempfile::tempdir().unwrap();
+        let document_service = DocumentService::new(
+            pool.clone(),
+            operation_service.clone(),
+            Arc::new(FilesystemStorage::new(storage_dir.path().to_path_buf())),
+        );
+        // Un solo permiso, y quien tiene un cliente esperando aguanta 50 ms
+        let pdf_service = PdfService::for_tests(
+            Arc::new(SlowRenderer {
+                delay: Duration::from_millis(500),
+            }),
+            SigningService::new(pool.clone()).unwrap(),
+            1,
+            Duration::from_millis(50),
+        );
+        let job_service = PdfJobService::new(
+            pdf_service.clone(),
+            operation_service.clone(),
+            document_service,
+        );
+
+        // Ocupa el único permiso
+        let busy = {
+            let pdf_service = pdf_service.clone();
+            tokio::spawn(async move { pdf_service.render_pdf(html_request()).await })
+        };
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // Un render síncrono se rinde, el trabajo asíncrono espera su turno
+        let error = pdf_service.render_pdf(html_request()).await.err().unwrap();
+        assert!(error.to_string().contains("Timeout esperando permiso"));
+        let op_id = job_service.start_job(html_request()).await.unwrap();
+
+        assert!(busy.await.unwrap().is_ok());
+        let mut status = String::new();
+        for _ in 0..100 {
+            status = operation_service
+                .get_operation(&op_id)
+                .await
+                .unwrap()
+                .status;
+            if status == "done" || status == "failed" {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(status, "done");
+    }
+
+    #[test]
+    fn callback_signature_covers_timestamp_and_body() {
+        let signature = sign_callback(b"secreto", 1_700_000_000, br#"{"status":"done"}"#).unwrap();
+        // HMAC-SHA256 de `1700000000.{"status":"done"}`
+        assert_eq!(
+            signature,
+            "51e9bf50c618b8530f0624d7f546308a4cd7e6b161238609c6ef204d4668fd2b"
+        );
+        assert_ne!(
+            signature,
+            sign_callback(b"secreto", 1_700_000_001, br#"{"status":"done"}"#).unwrap()
+        );
+        assert_ne!(
+            signature,
+            sign_callback(b"secreto", 1_700_000_000, br#"{"status":"failed"}"#).unwrap()
+        );
+    }
+}
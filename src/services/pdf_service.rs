@@ -1,22 +1,43 @@
-use crate::models::pdf_model::{PdfMargins, PdfOrientation, PdfPagePreset, PdfRequest};
+use crate::{
+    config::pdf_config::{PdfDefaults, PdfGlobalConfig},
+    models::{
+        pdf_model::{ImageFormat, PdfBackend, PdfImageOptions, PdfRenderReport, PdfRequest},
+        validation::{FieldErrorCode, ValidationErrors},
+    },
+    services::{
+        error::ServiceError,
+        pdf_tools::{
+            self, encryption::encrypt_document, facturx, metadata::apply_metadata, pdfa, signature,
+            watermark::apply_watermark,
+        },
+        render_cache::{CachePurge, CacheStatus, RenderCache},
+        renderer::{
+            asset_bundle, chromium_renderer::ChromiumRenderer, pdf_rasterizer::PdfRasterizer,
+            wkhtmltopdf_renderer::WkhtmltopdfRenderer, PdfRenderer, RenderFiles, RenderOutput,
+        },
+        signing_service::SigningService,
+        util::resolve_public_host,
+    },
+};
 use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
 use std::{
+    collections::HashMap,
     fs,
-    path::{Path, PathBuf},
+    path::PathBuf,
     sync::Arc,
     time::{Duration, Instant},
 };
 use tokio::{
-    process::Command,
     sync::{Semaphore, SemaphorePermit},
     time::timeout,
 };
 use uuid::Uuid;
 
-/// Cantidad máxima de wkhtmltopdf simultáneos
+/// Cantidad máxima de renders simultáneos
 const MAX_CONCURRENT_PROCESSES: usize = 8;
-/// Tiempo máximo para generar un PDF
-const PDF_GENERATION_TIMEOUT: Duration = Duration::from_secs(300);
+/// Espera máxima por un permiso en los renders con un cliente esperando
+const PERMIT_TIMEOUT: Duration = Duration::from_secs(5);
 /// Prefijo de carpeta temporal
 const TEMP_DIR_PREFIX: &str = "pdf_service_";
 
@@ -24,196 +45,631 @@ const TEMP_DIR_PREFIX: &str = "pdf_service_";
 pub struct PdfService {
     semaphore: Arc<Semaphore>,
     temp_dir: Arc<PathBuf>,
-    wkhtmltopdf_path: Arc<PathBuf>,
+    renderers: Arc<HashMap<PdfBackend, Arc<dyn PdfRenderer>>>,
+    default_backend: PdfBackend,
+    /// Miniaturas de PDFs; None si `pdftoppm` no está instalado
+    rasterizer: Option<Arc<PdfRasterizer>>,
+    signing_service: SigningService,
+    /// Caché de renders; None si `PDF_CACHE_MAX_BYTES` no está definida
+    cache: Option<Arc<RenderCache>>,
+    /// Valores por defecto de página, márgenes, escala y motor (servicio y tenants)
+    pdf_config: Arc<PdfGlobalConfig>,
+    /// Cuánto se espera un permiso del semáforo; None espera sin límite
+    permit_timeout: Option<Duration>,
+    /// `SOURCE_URL_ALLOW_PRIVATE_HOSTS=true`: admite `source_url` de la red interna
+    allow_private_sources: bool,
+}
+
+/// PDF generado por `render_pdf`
+pub struct RenderedPdf {
+    pub pdf: Vec<u8>,
+    /// Resultado de la caché; None si está desactivada
+    pub cache: Option<CacheStatus>,
+    pub report: PdfRenderReport,
+}
+
+/// Imagen generada por `render_image`
+pub struct RenderedImage {
+    pub image: Vec<u8>,
+    pub report: PdfRenderReport,
 }
 
 impl PdfService {
-    pub async fn new() -> Result<Self> {
+    pub async fn new(
+        signing_service: SigningService,
+        mut pdf_config: PdfGlobalConfig,
+    ) -> Result<Self> {
         // Crea un subdirectorio temporal (para HTML/PDF provisionales).
         let temp_dir = std::env::temp_dir().join(format!("{}_{}", TEMP_DIR_PREFIX, Uuid::new_v4()));
         fs::create_dir_all(&temp_dir)?;
 
-        // Verifica que wkhtmltopdf esté en PATH
-        let wkhtmltopdf_path =
-            which::which("wkhtmltopdf").context("No se encontró wkhtmltopdf en el sistema")?;
+        // Registra los motores disponibles en el sistema
+        let mut renderers: HashMap<PdfBackend, Arc<dyn PdfRenderer>> = HashMap::new();
+        match which::which("wkhtmltopdf") {
+            Ok(path) => {
+                log::info!("wkhtmltopdf encontrado en {:?}", path);
+                let wkhtmltoimage = which::which("wkhtmltoimage").ok();
+                if wkhtmltoimage.is_none() {
+                    log::warn!("No se encontró wkhtmltoimage; sin imágenes con wkhtmltopdf");
+                }
+                renderers.insert(
+                    PdfBackend::Wkhtmltopdf,
+                    Arc::new(WkhtmltopdfRenderer::new(path, wkhtmltoimage)),
+                );
+            }
+            Err(_) => log::warn!("No se encontró wkhtmltopdf; backend deshabilitado"),
+        }
+        match ChromiumRenderer::find_executable() {
+            Some(path) => {
+                log::info!("Chromium encontrado en {:?}", path);
+                renderers.insert(PdfBackend::Chromium, Arc::new(ChromiumRenderer::new(path)));
+            }
+            None => log::warn!("No se encontró Chromium; backend deshabilitado"),
+        }
+
+        // Backend global: PDF_BACKEND (o `backend` en PDF_CONFIG_FILE), o el primero
+        // disponible (wkhtmltopdf primero)
+        let default_backend = match pdf_config.defaults.backend {
+            Some(backend) => backend,
+            None => [PdfBackend::Wkhtmltopdf, PdfBackend::Chromium]
+                .into_iter()
+                .find(|b| renderers.contains_key(b))
+                .ok_or_else(|| {
+                    anyhow!("No se encontró ningún motor de PDF (wkhtmltopdf o Chromium)")
+                })?,
+        };
+        if let Some(missing) = pdf_config
+            .backends()
+            .find(|backend| !renderers.contains_key(backend))
+        {
+            return Err(anyhow!(
+                "El backend configurado ({}) no está instalado",
+                missing.as_str()
+            ));
+        }
+        pdf_config.defaults.backend = Some(default_backend);
+        log::info!("Backend de PDF por defecto: {}", default_backend.as_str());
+
+        let rasterizer = PdfRasterizer::find().map(Arc::new);
+        if rasterizer.is_none() {
+            log::warn!("No se encontró pdftoppm; miniaturas de PDF deshabilitadas");
+        }
+        let allow_private_sources = std::env::var("SOURCE_URL_ALLOW_PRIVATE_HOSTS")
+            .is_ok_and(|value| value.eq_ignore_ascii_case("true") || value == "1");
 
         Ok(Self {
             semaphore: Arc::new(Semaphore::new(MAX_CONCURRENT_PROCESSES)),
             temp_dir: Arc::new(temp_dir),
-            wkhtmltopdf_path: Arc::new(wkhtmltopdf_path),
+            renderers: Arc::new(renderers),
+            default_backend,
+            rasterizer,
+            signing_service,
+            cache: RenderCache::from_env().map(Arc::new),
+            pdf_config: Arc::new(pdf_config),
+            permit_timeout: Some(PERMIT_TIMEOUT),
+            allow_private_sources,
         })
     }
 
+    /// Copia que comparte semáforo, caché y motores pero espera su turno sin
+    /// límite de tiempo: para trabajos en background (lotes), donde no hay un
+    /// cliente esperando y fallar por timeout solo pierde el documento.
+    pub fn queued(&self) -> Self {
+        Self {
+            permit_timeout: None,
+            ..self.clone()
+        }
+    }
+
+    /// Servicio con un solo motor, `permits` renders simultáneos y espera
+    /// `permit_timeout`, sin caché ni miniaturas.
+    #[cfg(test)]
+    pub(crate) fn for_tests(
+        renderer: Arc<dyn PdfRenderer>,
+        signing_service: SigningService,
+        permits: usize,
+        permit_timeout: Duration,
+    ) -> Self {
+        let temp_dir = std::env::temp_dir().join(format!("{}_{}", TEMP_DIR_PREFIX, Uuid::new_v4()));
+        let default_backend = renderer.backend();
+        Self {
+            semaphore: Arc::new(Semaphore::new(permits)),
+            temp_dir: Arc::new(temp_dir),
+            renderers: Arc::new(HashMap::from([(default_backend, renderer)])),
+            default_backend,
+            rasterizer: None,
+            signing_service,
+            cache: None,
+            pdf_config: Arc::new(PdfGlobalConfig::default()),
+            permit_timeout: Some(permit_timeout),
+            allow_private_sources: false,
+        }
+    }
+
+    /// Valores por defecto que recibe un request de `tenant` (o del servicio, sin tenant).
+    pub fn defaults(&self, tenant: Option<&str>) -> Result<PdfDefaults> {
+        self.pdf_config.resolve(tenant)
+    }
+
+    /// Completa página, márgenes, escala y motor con los valores por defecto del
+    /// tenant del request: el único lugar donde se aplican.
+    fn with_defaults(&self, req: &mut PdfRequest) -> Result<()> {
+        self.defaults(req.tenant.as_deref())?.apply(req);
+        Ok(())
+    }
+
     /// Genera un PDF en memoria (Vec<u8>).
-    /// Si `req.store_local_pdf == Some(true)`, además se guarda localmente en ./files/pdfs/
+    /// Guardarlo (`store_local_pdf`) le corresponde a DocumentService.
     pub async fn generate_pdf(&self, req: PdfRequest) -> Result<Vec<u8>> {
+        Ok(self.render_pdf(req).await?.pdf)
+    }
+
+    /// Como `generate_pdf`, pero con el informe del render (páginas, tiempo,
+    /// avisos del motor) y si el PDF salió de la caché de renders.
+    pub async fn render_pdf(&self, mut req: PdfRequest) -> Result<RenderedPdf> {
         let start = Instant::now();
+        self.with_defaults(&mut req)?;
+        // Motor de renderizado: el del request o el global
+        let renderer = self.renderer_for(&req)?;
+
+        let Some(cache) = &self.cache else {
+            let output = self.generate_uncached(&req, renderer).await?;
+            return Ok(rendered_pdf(renderer.backend(), output, start, None));
+        };
+        let Some(key) = RenderCache::key(&req, renderer.backend())? else {
+            let output = self.generate_uncached(&req, renderer).await?;
+            return Ok(rendered_pdf(
+                renderer.backend(),
+                output,
+                start,
+                Some(CacheStatus::Bypass),
+            ));
+        };
+        // El informe guardado conserva páginas y avisos; el tiempo es el de esta respuesta
+        if let Some((pdf, mut report)) = cache.get(&key) {
+            log::info!("PDF servido desde la caché de renders");
+            report.render_time_ms = start.elapsed().as_millis() as u64;
+            return Ok(RenderedPdf {
+                pdf,
+                cache: Some(CacheStatus::Hit),
+                report,
+            });
+        }
+        let output = self.generate_uncached(&req, renderer).await?;
+        let rendered = rendered_pdf(renderer.backend(), output, start, Some(CacheStatus::Miss));
+        cache.insert(key, &rendered.pdf, &rendered.report);
+        Ok(rendered)
+    }
+
+    /// Chequeos del request que no necesitan renderizar (preflight): campos del
+    /// request, motor disponible y `render_options` válidas para él.
+    pub fn validate_request(&self, req: &PdfRequest) -> Result<(), ValidationErrors> {
+        let mut req = req.clone();
+        if let Err(e) = self.with_defaults(&mut req) {
+            let mut errors = ValidationErrors::default();
+            errors.add("tenant", FieldErrorCode::Invalid, e.to_string());
+            return Err(errors);
+        }
+        let req = &req;
+        let mut errors = req.preflight().err().unwrap_or_default();
+        match self.renderer_for(req) {
+            Ok(renderer) => {
+                req.check_render_options(renderer.backend(), req.image.is_some(), &mut errors)
+            }
+            Err(e) => errors.add("backend", FieldErrorCode::Unsupported, e.to_string()),
+        }
+        errors.into_result()
+    }
+
+    /// Vacía la caché de renders; None si está desactivada
+    pub fn purge_cache(&self) -> Option<CachePurge> {
+        self.cache.as_ref().map(|cache| cache.purge())
+    }
+
+    async fn generate_uncached(
+        &self,
+        req: &PdfRequest,
+        renderer: &Arc<dyn PdfRenderer>,
+    ) -> Result<RenderOutput> {
+        let start = Instant::now();
+        req.preflight()?;
+        req.validate_render_options(renderer.backend(), false)?;
+        self.check_source_url(req).await?;
 
         // Control de concurrencia
         let _guard = self.acquire_permit().await?;
 
-        // Crea archivos temporales (HTML y PDF)
-        let temp_files = self.create_temp_files(&req.file_name)?;
-        let _cleanup = TempCleanup::new(temp_files.clone()); // al final se borran
+        // PDF/A prohíbe el cifrado: se rechaza antes de renderizar
+        if let (Some(level), Some(_)) = (req.effective_conformance()?, &req.encryption) {
+            return Err(anyhow!(
+                "`conformance` ({}) no se puede combinar con `encryption`",
+                level.label()
+            ));
+        }
 
-        // Escribir HTML a disco
-        fs::write(&temp_files.html_path, &req.html).with_context(|| {
-            format!(
-                "Error escribiendo HTML temporal en {:?}",
-                temp_files.html_path
-            )
-        })?;
+        // Factura electrónica: el XML se valida (o se genera) antes de renderizar
+        let invoice = req.invoice.as_ref().map(facturx::prepare).transpose()?;
+
+        // Crea archivos temporales (HTML y PDF)
+        let mut temp_files = self.create_temp_files()?;
+        let _cleanup = TempCleanup::new(temp_files.work_dir.clone()); // al final se borran
+        prepare_input(req, &mut temp_files)?;
 
-        // Llamar a wkhtmltopdf
-        let pdf_data = self.run_wkhtmltopdf(&req, &temp_files).await?;
+        // Renderizar con el motor elegido
+        let output = renderer.render(req, &temp_files).await?;
 
-        // Si el usuario quiere guardarlo localmente, lo hacemos ahora
-        if req.store_local_pdf.unwrap_or(false) {
-            // Creamos la carpeta si no existe
-            let _ = fs::create_dir_all("./files/pdfs");
+        // Ajustes comunes a todos los motores sobre el PDF ya generado
+        let pdf_data = self.post_process(req, output.bytes, invoice).await?;
 
-            // Generamos un nombre único: "<uuid>_<nombreOriginal>.pdf"
-            let unique_name = format!("{}_{}", req.file_name, Uuid::new_v4());
+        let elapsed = start.elapsed().as_secs_f32();
+        log::info!(
+            "PDF generado con {} en {:.2}s ({} avisos)",
+            renderer.backend().as_str(),
+            elapsed,
+            output.warnings.len()
+        );
 
-            let local_path = Path::new("./files/pdfs").join(&unique_name);
-            fs::write(&local_path, &pdf_data)
-                .with_context(|| format!("No se pudo guardar PDF en {:?}", local_path))?;
+        // Retornamos los bytes en memoria (útil si vas a adjuntarlos por email, etc.)
+        Ok(RenderOutput {
+            bytes: pdf_data,
+            warnings: output.warnings,
+        })
+    }
 
-            log::info!(
-                "PDF guardado localmente en {:?} ({} bytes)",
-                local_path,
-                pdf_data.len()
-            );
+    /// Post-proceso con lopdf, en este orden: metadatos del diccionario `Info`,
+    /// marca de agua, campo de firma, factura Factur-X, conversión y validación
+    /// PDF/A, cifrado y, sobre el archivo ya serializado, la firma.
+    async fn post_process(
+        &self,
+        req: &PdfRequest,
+        pdf: Vec<u8>,
+        invoice: Option<facturx::InvoiceXml>,
+    ) -> Result<Vec<u8>> {
+        let conformance = req.effective_conformance()?;
+        if req.metadata.is_none()
+            && req.watermark.is_none()
+            && req.encryption.is_none()
+            && req.signature.is_none()
+            && conformance.is_none()
+        {
+            return Ok(pdf);
         }
+        let signer = match &req.signature {
+            Some(options) => Some(
+                self.signing_service
+                    .load_signer(&options.profile_id)
+                    .await?,
+            ),
+            None => None,
+        };
+        let signer_name = signer
+            .as_ref()
+            .map(|s| signature::certificate_name(&s.cert));
+
+        let metadata = req.metadata.clone();
+        let watermark = req.watermark.clone();
+        let encryption = req.encryption.clone();
+        let signature_options = req.signature.clone();
+        let (pdf, byte_range) = tokio::task::spawn_blocking(move || {
+            let mut doc = pdf_tools::load_pdf(&pdf)?;
+            if let Some(metadata) = &metadata {
+                apply_metadata(&mut doc, metadata)?;
+            }
+            if let Some(watermark) = &watermark {
+                apply_watermark(&mut doc, watermark)?;
+            }
+            if let (Some(options), Some(name)) = (&signature_options, &signer_name) {
+                signature::add_signature_field(&mut doc, options, name, Utc::now())?;
+            }
+            if let Some(invoice) = &invoice {
+                facturx::embed(&mut doc, invoice, Utc::now())?;
+            }
+            if let Some(level) = conformance {
+                let extension = invoice
+                    .as_ref()
+                    .map(|invoice| facturx::xmp_extension(invoice.profile));
+                pdfa::convert(&mut doc, level, Utc::now(), extension.as_deref())?;
+                let issues = pdfa::validate(&doc, level);
+                if !issues.is_empty() {
+                    return Err(anyhow!(
+                        "El PDF no cumple {}: {}",
+                        level.label(),
+                        issues.join("; ")
+                    ));
+                }
+            }
+            if let Some(encryption) = &encryption {
+                encrypt_document(&mut doc, encryption)?;
+            }
+            let mut pdf = pdf_tools::save_pdf(&mut doc)?;
+            if conformance.is_some() {
+                pdf = pdfa::write_header(&pdf)?;
+            }
+            let byte_range = match signature_options {
+                Some(_) => Some(signature::finalize_byte_range(&mut pdf)?),
+                None => None,
+            };
+            Ok::<_, anyhow::Error>((pdf, byte_range))
+        })
+        .await
+        .context("Falló el post-proceso del PDF")??;
+
+        // La firma va al final: después de ella no se puede tocar ningún byte
+        match (signer, byte_range, &req.signature) {
+            (Some(signer), Some(range), Some(options)) => {
+                self.signing_service
+                    .sign_prepared(pdf, range, &signer, options.timestamp)
+                    .await
+            }
+            _ => Ok(pdf),
+        }
+    }
 
-        let elapsed = start.elapsed().as_secs_f32();
-        log::info!("PDF generado en {:.2}s", elapsed);
+    /// Renderiza el HTML (o la URL) como imagen en vez de PDF, con el mismo
+    /// control de concurrencia y archivos temporales que `generate_pdf`.
+    pub async fn render_image(
+        &self,
+        req: &PdfRequest,
+        options: &PdfImageOptions,
+    ) -> Result<RenderedImage> {
+        let start = Instant::now();
+        let mut req = req.clone();
+        self.with_defaults(&mut req)?;
+        let req = &req;
+        let renderer = self.renderer_for(req)?;
+        req.preflight()?;
+        req.validate_render_options(renderer.backend(), true)?;
+        self.check_source_url(req).await?;
+        let _guard = self.acquire_permit().await?;
 
-        // Retornamos los bytes en memoria (útil si vas a adjuntarlos por email, etc.)
-        Ok(pdf_data)
+        let mut temp_files = self.create_temp_files()?;
+        let _cleanup = TempCleanup::new(temp_files.work_dir.clone());
+        prepare_input(req, &mut temp_files)?;
+
+        let output = renderer.render_image(req, &temp_files, options).await?;
+        log::info!(
+            "Imagen {} generada con {} en {:.2}s",
+            options.format.extension(),
+            renderer.backend().as_str(),
+            start.elapsed().as_secs_f32()
+        );
+        Ok(RenderedImage {
+            report: PdfRenderReport {
+                backend: renderer.backend(),
+                page_count: None,
+                size_bytes: output.bytes.len() as u64,
+                render_time_ms: start.elapsed().as_millis() as u64,
+                warnings: output.warnings,
+            },
+            image: output.bytes,
+        })
     }
 
-    async fn acquire_permit(&self) -> Result<SemaphorePermit> {
-        timeout(Duration::from_secs(5), self.semaphore.acquire())
+    /// Miniatura de la página `page` (1-based) de un PDF ya generado.
+    pub async fn thumbnail(
+        &self,
+        pdf: &[u8],
+        page: u32,
+        width: u32,
+        format: ImageFormat,
+        quality: u8,
+    ) -> Result<Vec<u8>> {
+        let rasterizer = self.rasterizer.as_ref().ok_or_else(|| {
+            anyhow!("Miniaturas no disponibles: pdftoppm (poppler-utils) no está instalado")
+        })?;
+        let page_count = pdf_tools::load_pdf(pdf)
+            .map_err(|e| ServiceError::invalid_input(e.to_string()))?
+            .get_pages()
+            .len() as u32;
+        if page == 0 || page > page_count {
+            return Err(ServiceError::invalid_input(format!(
+                "La página {} no existe (el PDF tiene {})",
+                page, page_count
+            )));
+        }
+
+        let _guard = self.acquire_permit().await?;
+        let temp_files = self.create_temp_files()?;
+        let _cleanup = TempCleanup::new(temp_files.work_dir.clone());
+        fs::write(&temp_files.pdf_path, pdf).with_context(|| {
+            format!(
+                "Error escribiendo PDF temporal en {:?}",
+                temp_files.pdf_path
+            )
+        })?;
+
+        rasterizer
+            .thumbnail(&temp_files.pdf_path, page, width, format, quality)
             .await
-            .context("Timeout esperando permiso en PdfService")?
-            .map_err(|_| anyhow!("No se pudo adquirir el semaphore"))
     }
 
-    fn create_temp_files(&self, base_name: &str) -> Result<TempFiles> {
-        let id = Uuid::new_v4().to_string();
-        let html_path = self.temp_dir.join(format!("{}_{}.html", base_name, id));
-        let pdf_path = self.temp_dir.join(format!("{}_{}.pdf", base_name, id));
-        Ok(TempFiles {
-            html_path,
-            pdf_path,
+    /// Motor del request o, si no pide ninguno, el global.
+    fn renderer_for(&self, req: &PdfRequest) -> Result<&Arc<dyn PdfRenderer>> {
+        let backend = req.backend.unwrap_or(self.default_backend);
+        self.renderers.get(&backend).ok_or_else(|| {
+            anyhow!(
+                "El backend {} no está disponible en este servidor",
+                backend.as_str()
+            )
         })
     }
 
-    async fn run_wkhtmltopdf(&self, req: &PdfRequest, paths: &TempFiles) -> Result<Vec<u8>> {
-        let mut cmd = Command::new(&*self.wkhtmltopdf_path);
+    /// El motor no debe poder llegar a la red interna (metadatos de la nube,
+    /// servicios sin autenticación, ...) a través de `source_url`.
+    async fn check_source_url(&self, req: &PdfRequest) -> Result<()> {
+        match &req.source_url {
+            Some(url) => validate_source_url(url, self.allow_private_sources).await,
+            None => Ok(()),
+        }
+    }
 
-        // ===== ORIENTACIÓN =====
-        let orientation = req
-            .orientation
-            .as_ref()
-            .unwrap_or(&PdfOrientation::Portrait);
-        let orientation_str = match orientation {
-            PdfOrientation::Landscape => "Landscape",
-            PdfOrientation::Portrait => "Portrait",
+    async fn acquire_permit(&self) -> Result<SemaphorePermit<'_>> {
+        let permit = match self.permit_timeout {
+            Some(limit) => timeout(limit, self.semaphore.acquire())
+                .await
+                .context("Timeout esperando permiso en PdfService")?,
+            None => self.semaphore.acquire().await,
         };
-        cmd.arg("--orientation").arg(orientation_str);
-
-        // ===== TAMAÑO DE PÁGINA =====
-        if let Some(preset) = &req.page_size_preset {
-            let preset_str = match preset {
-                PdfPagePreset::A4 => "A4",
-                PdfPagePreset::Letter => "Letter",
-                PdfPagePreset::Legal => "Legal",
-                PdfPagePreset::A3 => "A3",
-                PdfPagePreset::Tabloid => "Tabloid",
-            };
-            cmd.arg("--page-size").arg(preset_str);
-        } else if let Some(custom) = &req.custom_page_size {
-            cmd.arg("--page-width").arg(format!("{}mm", custom.width));
-            cmd.arg("--page-height").arg(format!("{}mm", custom.height));
-        } else {
-            // Default a A4
-            cmd.arg("--page-size").arg("A4");
-        }
-
-        // ===== MÁRGENES =====
-        let margins = req.margins.as_ref().unwrap_or(&PdfMargins {
-            top: 10.0,
-            bottom: 10.0,
-            left: 10.0,
-            right: 10.0,
-        });
-        cmd.arg("--margin-top").arg(format!("{}mm", margins.top));
-        cmd.arg("--margin-bottom")
-            .arg(format!("{}mm", margins.bottom));
-        cmd.arg("--margin-left").arg(format!("{}mm", margins.left));
-        cmd.arg("--margin-right")
-            .arg(format!("{}mm", margins.right));
-
-        // ===== ESCALA (zoom) =====
-        let scale = req.scale.unwrap_or(1.0);
-        if (scale - 1.0).abs() > f64::EPSILON {
-            cmd.arg("--zoom").arg(format!("{}", scale));
-        }
-
-        // ===== OTRAS OPCIONES =====
-        cmd.arg("--enable-local-file-access");
-        cmd.arg("--print-media-type");
-
-        // Entradas/salidas
-        cmd.arg(&paths.html_path);
-        cmd.arg(&paths.pdf_path);
-
-        cmd.stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped());
-
-        let output = timeout(PDF_GENERATION_TIMEOUT, cmd.output())
-            .await
-            .context("Timeout ejecutando wkhtmltopdf")?
-            .context("No se pudo lanzar wkhtmltopdf")?;
+        permit.map_err(|_| anyhow!("No se pudo adquirir el semaphore"))
+    }
 
-        if !output.status.success() {
-            let stderr_msg = String::from_utf8_lossy(&output.stderr);
-            return Err(anyhow!("wkhtmltopdf falló: {}", stderr_msg));
-        }
+    /// Crea un directorio exclusivo para este render dentro de `temp_dir`.
+    fn create_temp_files(&self) -> Result<RenderFiles> {
+        let work_dir = self.temp_dir.join(Uuid::new_v4().to_string());
+        fs::create_dir_all(&work_dir)
+            .with_context(|| format!("No se pudo crear directorio temporal {:?}", work_dir))?;
+        Ok(RenderFiles {
+            html_path: work_dir.join("input.html"),
+            pdf_path: work_dir.join("output.pdf"),
+            bundle_dir: None,
+            work_dir,
+        })
+    }
+}
 
-        // Leer el PDF final
-        let pdf_bytes = fs::read(&paths.pdf_path)
-            .with_context(|| format!("Error leyendo PDF final en {:?}", paths.pdf_path))?;
+/// Arma el `RenderedPdf` con el informe del render. Si el PDF no se puede leer
+/// (p. ej. cifrado con contraseña de apertura) las páginas quedan en None.
+fn rendered_pdf(
+    backend: PdfBackend,
+    output: RenderOutput,
+    start: Instant,
+    cache: Option<CacheStatus>,
+) -> RenderedPdf {
+    let render_time_ms = start.elapsed().as_millis() as u64;
+    let page_count = pdf_tools::load_pdf(&output.bytes)
+        .ok()
+        .map(|doc| doc.get_pages().len() as u32);
+    RenderedPdf {
+        report: PdfRenderReport {
+            backend,
+            page_count,
+            size_bytes: output.bytes.len() as u64,
+            render_time_ms,
+            warnings: output.warnings,
+        },
+        pdf: output.bytes,
+        cache,
+    }
+}
 
-        Ok(pdf_bytes)
+/// Deja la entrada lista para el motor: extrae el bundle ZIP o escribe el HTML
+/// inline a disco (la URL remota ya pasó por `check_source_url`).
+fn prepare_input(req: &PdfRequest, temp_files: &mut RenderFiles) -> Result<()> {
+    match (&req.source_url, &req.assets_bundle) {
+        (Some(_), Some(_)) => {
+            return Err(anyhow!(
+                "`assets_bundle` no se puede combinar con `source_url`"
+            ));
+        }
+        (Some(_), None) => {}
+        (None, Some(bundle)) => {
+            let bundle_dir = temp_files.work_dir.join("bundle");
+            asset_bundle::extract_bundle(&bundle.zip, &bundle_dir)?;
+
+            let entry_point = bundle
+                .entry_point
+                .as_deref()
+                .unwrap_or(asset_bundle::DEFAULT_ENTRY_POINT);
+            let entry_path = bundle_dir.join(asset_bundle::safe_relative_path(entry_point)?);
+            // Un `html` (o plantilla) en el request reemplaza al HTML de entrada del ZIP
+            if !req.html.trim().is_empty() {
+                fs::write(&entry_path, &req.html).with_context(|| {
+                    format!("Error escribiendo HTML temporal en {:?}", entry_path)
+                })?;
+            } else if !entry_path.is_file() {
+                return Err(anyhow!(
+                    "El bundle no contiene el HTML de entrada '{}'",
+                    entry_point
+                ));
+            }
+            temp_files.html_path = entry_path;
+            temp_files.bundle_dir = Some(bundle_dir);
+        }
+        (None, None) => {
+            if req.html.trim().is_empty() {
+                return Err(anyhow!("Se requiere `html` o `source_url`"));
+            }
+            fs::write(&temp_files.html_path, &req.html).with_context(|| {
+                format!(
+                    "Error escribiendo HTML temporal en {:?}",
+                    temp_files.html_path
+                )
+            })?;
+        }
     }
+    Ok(())
+}
+
+/// Solo se aceptan URLs http/https (nada de file://, data:, etc.) y, salvo con
+/// `allow_private`, cuyo host resuelva a direcciones públicas. Es un error del
+/// cliente (400), no del render.
+async fn validate_source_url(url: &str, allow_private: bool) -> Result<()> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| {
+        ServiceError::invalid_input(format!("source_url inválida ({}): {}", e, url))
+    })?;
+    match parsed.scheme() {
+        "http" | "https" => {}
+        other => {
+            return Err(ServiceError::invalid_input(format!(
+                "Esquema no permitido en source_url: {}",
+                other
+            )))
+        }
+    }
+    if !allow_private {
+        resolve_public_host(&parsed)
+            .await
+            .map_err(|e| ServiceError::invalid_input(format!("source_url no permitida: {}", e)))?;
+    }
+    Ok(())
 }
 
 // --------------------------------------------------------------------------------
 // Estructuras auxiliares
 // --------------------------------------------------------------------------------
-#[derive(Clone)]
-struct TempFiles {
-    html_path: PathBuf,
-    pdf_path: PathBuf,
-}
-
 struct TempCleanup {
-    files: TempFiles,
+    work_dir: PathBuf,
 }
 
 impl TempCleanup {
-    fn new(files: TempFiles) -> Self {
-        Self { files }
+    fn new(work_dir: PathBuf) -> Self {
+        Self { work_dir }
     }
 }
 
-/// Borra los archivos temporales al salir de scope
+/// Borra el directorio temporal del render al salir de scope
 impl Drop for TempCleanup {
     fn drop(&mut self) {
-        let _ = fs::remove_file(&self.files.html_path);
-        let _ = fs::remove_file(&self.files.pdf_path);
+        let _ = fs::remove_dir_all(&self.work_dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::error::is_invalid_input;
+
+    #[tokio::test]
+    async fn source_url_must_be_public_http() {
+        for url in [
+            "file:///etc/passwd",
+            "http://127.0.0.1:5022/api/documents",
+            "http://localhost/",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://[::ffff:10.0.0.1]/",
+        ] {
+            let e = validate_source_url(url, false).await.unwrap_err();
+            assert!(is_invalid_input(&e), "{}: {}", url, e);
+        }
+        validate_source_url("https://8.8.8.8/informe", false)
+            .await
+            .unwrap();
+        // Con SOURCE_URL_ALLOW_PRIVATE_HOSTS la intranet vale, el esquema no
+        validate_source_url("http://127.0.0.1:8080/estado", true)
+            .await
+            .unwrap();
+        assert!(validate_source_url("file:///etc/passwd", true)
+            .await
+            .is_err());
     }
 }
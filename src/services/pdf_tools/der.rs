@@ -0,0 +1,141 @@
+//! services/pdf_tools/der.rs
+//! Lectura y escritura mínima de DER (ASN.1) para manipular estructuras CMS
+//! que openssl no expone: atributos no firmados, sellos de tiempo, etc.
+//! Solo soporta etiquetas de un byte y longitudes definidas (lo que produce DER).
+
+use anyhow::{anyhow, Result};
+
+pub const TAG_BOOLEAN: u8 = 0x01;
+pub const TAG_INTEGER: u8 = 0x02;
+pub const TAG_OCTET_STRING: u8 = 0x04;
+pub const TAG_NULL: u8 = 0x05;
+pub const TAG_OID: u8 = 0x06;
+pub const TAG_UTC_TIME: u8 = 0x17;
+pub const TAG_GENERALIZED_TIME: u8 = 0x18;
+pub const TAG_SEQUENCE: u8 = 0x30;
+pub const TAG_SET: u8 = 0x31;
+/// `[0]` constructed (contexto 0)
+pub const TAG_CONTEXT_0: u8 = 0xA0;
+/// `[1]` constructed (contexto 1)
+pub const TAG_CONTEXT_1: u8 = 0xA1;
+
+/// Un elemento TLV. `raw` incluye etiqueta y longitud; `content`, solo el valor.
+#[derive(Debug, Clone, Copy)]
+pub struct Tlv<'a> {
+    pub tag: u8,
+    pub content: &'a [u8],
+    pub raw: &'a [u8],
+}
+
+impl<'a> Tlv<'a> {
+    /// Elementos contenidos en una estructura (SEQUENCE, SET, [n]).
+    pub fn children(&self) -> Result<Vec<Tlv<'a>>> {
+        parse_all(self.content)
+    }
+
+    /// Hijo número `index`, con la etiqueta esperada.
+    pub fn child(&self, index: usize, tag: u8) -> Result<Tlv<'a>> {
+        let children = self.children()?;
+        let child = children
+            .get(index)
+            .ok_or_else(|| anyhow!("Estructura ASN.1 incompleta"))?;
+        if child.tag != tag {
+            return Err(anyhow!(
+                "Etiqueta ASN.1 inesperada: {:#04x} (se esperaba {:#04x})",
+                child.tag,
+                tag
+            ));
+        }
+        Ok(*child)
+    }
+}
+
+/// Lee el primer elemento de `input` y retorna el resto.
+pub fn parse(input: &[u8]) -> Result<(Tlv<'_>, &[u8])> {
+    let truncated = || anyhow!("Estructura ASN.1 truncada");
+    let tag = *input.first().ok_or_else(truncated)?;
+    let first = *input.get(1).ok_or_else(truncated)?;
+    let (length, header) = if first < 0x80 {
+        (first as usize, 2)
+    } else {
+        let count = (first & 0x7F) as usize;
+        if count == 0 || count > 4 {
+            return Err(anyhow!("Longitud ASN.1 no soportada"));
+        }
+        let bytes = input.get(2..2 + count).ok_or_else(truncated)?;
+        let length = bytes.iter().fold(0usize, |acc, b| (acc << 8) | *b as usize);
+        (length, 2 + count)
+    };
+    let end = header
+        .checked_add(length)
+        .filter(|end| *end <= input.len())
+        .ok_or_else(truncated)?;
+    Ok((
+        Tlv {
+            tag,
+            content: &input[header..end],
+            raw: &input[..end],
+        },
+        &input[end..],
+    ))
+}
+
+/// Lee todos los elementos consecutivos de `input`.
+pub fn parse_all(mut input: &[u8]) -> Result<Vec<Tlv<'_>>> {
+    let mut items = Vec::new();
+    while !input.is_empty() {
+        let (item, rest) = parse(input)?;
+        items.push(item);
+        input = rest;
+    }
+    Ok(items)
+}
+
+/// Codifica un elemento con su longitud DER.
+pub fn encode(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|b| *b == 0)
+            .collect();
+        out.push(0x80 | bytes.len() as u8);
+        out.extend(bytes);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+/// Codifica una estructura a partir de sus elementos ya codificados.
+pub fn encode_constructed(tag: u8, items: &[&[u8]]) -> Vec<u8> {
+    encode(tag, &items.concat())
+}
+
+/// INTEGER positivo a partir de bytes big-endian sin signo.
+pub fn encode_unsigned_integer(bytes: &[u8]) -> Vec<u8> {
+    let trimmed: Vec<u8> = bytes.iter().copied().skip_while(|b| *b == 0).collect();
+    let mut content = if trimmed.is_empty() { vec![0] } else { trimmed };
+    if content[0] & 0x80 != 0 {
+        content.insert(0, 0);
+    }
+    encode(TAG_INTEGER, &content)
+}
+
+/// Convierte `UTCTime` o `GeneralizedTime` a RFC 3339 (UTC).
+pub fn time_to_rfc3339(tlv: &Tlv) -> Option<String> {
+    let text = std::str::from_utf8(tlv.content).ok()?;
+    let text = text.trim_end_matches('Z');
+    // Las fracciones de segundo no interesan aquí
+    let text = text.split('.').next()?;
+    let format = match tlv.tag {
+        TAG_UTC_TIME => "%y%m%d%H%M%S",
+        TAG_GENERALIZED_TIME => "%Y%m%d%H%M%S",
+        _ => return None,
+    };
+    let time = chrono::NaiveDateTime::parse_from_str(text, format).ok()?;
+    Some(time.and_utc().to_rfc3339())
+}
@@ -0,0 +1,650 @@
+//! services/pdf_tools/encryption.rs
+//! Cifrado con el "Standard Security Handler" del PDF (contraseñas y permisos).
+//!
+//! - AES-256: revisión 6 (PDF 2.0, ISO 32000-2), filtro `AESV3`.
+//! - AES-128: revisión 4 (PDF 1.6), filtro `AESV2`.
+//!
+//! lopdf solo sabe descifrar, así que el cifrado se hace aquí con openssl.
+
+use anyhow::{anyhow, Context, Result};
+use lopdf::{dictionary, Document, Object, ObjectId, StringFormat};
+use openssl::{
+    hash::{hash, MessageDigest},
+    rand::rand_bytes,
+    sha::{sha256, sha384, sha512},
+    symm::{encrypt, Cipher, Crypter, Mode},
+};
+
+use crate::models::pdf_model::{PdfEncryption, PdfEncryptionAlgorithm};
+use crate::services::pdf_tools::ensure_file_id;
+use crate::services::util::hex;
+
+/// Relleno estándar de contraseñas (revisiones 2 a 4).
+const PASSWORD_PADDING: [u8; 32] = [
+    0x28, 0xBF, 0x4E, 0x5E, 0x4E, 0x75, 0x8A, 0x41, 0x64, 0x00, 0x4E, 0x56, 0xFF, 0xFA, 0x01, 0x08,
+    0x2E, 0x2E, 0x00, 0xB6, 0xD0, 0x68, 0x3E, 0x80, 0x2F, 0x0C, 0xA9, 0xFE, 0x64, 0x53, 0x69, 0x7A,
+];
+
+/// Longitud máxima de contraseña en la revisión 6 (bytes UTF-8).
+const MAX_PASSWORD_BYTES: usize = 127;
+
+// Bits de permisos (tabla 22 de ISO 32000): todos los reservados en 1
+const PERMISSIONS_BASE: u32 = 0xFFFF_F0C0;
+const PERMISSION_PRINT: u32 = 1 << 2;
+const PERMISSION_MODIFY: u32 = 1 << 3;
+const PERMISSION_COPY: u32 = 1 << 4;
+const PERMISSION_ANNOTATE: u32 = 1 << 5;
+const PERMISSION_FILL_FORMS: u32 = 1 << 8;
+const PERMISSION_ACCESSIBILITY: u32 = 1 << 9;
+const PERMISSION_ASSEMBLE: u32 = 1 << 10;
+const PERMISSION_PRINT_HIGH_QUALITY: u32 = 1 << 11;
+
+/// Cifra todas las cadenas y streams del documento y agrega el diccionario
+/// `/Encrypt`. Debe ser el último paso antes de serializar.
+pub fn encrypt_document(doc: &mut Document, options: &PdfEncryption) -> Result<()> {
+    if doc.is_encrypted() {
+        return Err(anyhow!("El documento ya está cifrado"));
+    }
+
+    let user_password = options.user_password.clone().unwrap_or_default();
+    // Sin contraseña de propietario, una aleatoria: los permisos se siguen aplicando
+    let owner_password = match &options.owner_password {
+        Some(password) if !password.is_empty() => password.clone(),
+        _ => hex(&random_bytes(16)?),
+    };
+    for password in [&user_password, &owner_password] {
+        if password.len() > MAX_PASSWORD_BYTES {
+            return Err(anyhow!(
+                "Las contraseñas del PDF admiten como máximo {} bytes",
+                MAX_PASSWORD_BYTES
+            ));
+        }
+    }
+    let permissions = permissions(options) as i32;
+
+    let (encrypt_dict, key) = match options.algorithm {
+        PdfEncryptionAlgorithm::Aes256 => {
+            aes256_handler(&user_password, &owner_password, permissions)?
+        }
+        PdfEncryptionAlgorithm::Aes128 => {
+            let file_id = ensure_file_id(doc)?;
+            aes128_handler(&user_password, &owner_password, permissions, &file_id)?
+        }
+    };
+
+    for (&id, object) in doc.objects.iter_mut() {
+        // Los streams de objetos y de xref no se escriben tal cual (lopdf los regenera)
+        let skip = object
+            .type_name()
+            .map(|name| ["ObjStm", "XRef"].contains(&name))
+            .unwrap_or(false);
+        if !skip {
+            // El /Contents de una firma nunca se cifra (ISO 32000-1, 7.6.1)
+            let signature_contents = match object {
+                Object::Dictionary(dict) if dict.type_is(b"Sig") => dict.remove(b"Contents"),
+                _ => None,
+            };
+            let object_key = key.object_key(id)?;
+            encrypt_object(object, &object_key)?;
+            if let (Some(contents), Object::Dictionary(dict)) = (signature_contents, object) {
+                dict.set("Contents", contents);
+            }
+        }
+    }
+
+    // El diccionario de cifrado se agrega después: sus cadenas van en claro
+    let encrypt_id = doc.add_object(encrypt_dict);
+    doc.trailer.set("Encrypt", encrypt_id);
+    if doc.trailer.get(b"ID").is_err() {
+        ensure_file_id(doc)?;
+    }
+    if options.algorithm == PdfEncryptionAlgorithm::Aes256 && doc.version.as_str() < "1.7" {
+        doc.version = "1.7".to_string();
+    }
+    Ok(())
+}
+
+/// Valor `/P` a partir de los permisos pedidos. La extracción para
+/// accesibilidad (lectores de pantalla) siempre se permite.
+fn permissions(options: &PdfEncryption) -> u32 {
+    let allowed = &options.permissions;
+    let mut p = PERMISSIONS_BASE | PERMISSION_ACCESSIBILITY;
+    if allowed.print {
+        p |= PERMISSION_PRINT | PERMISSION_PRINT_HIGH_QUALITY;
+    }
+    if allowed.copy {
+        p |= PERMISSION_COPY;
+    }
+    if allowed.modify {
+        p |= PERMISSION_MODIFY | PERMISSION_ANNOTATE | PERMISSION_FILL_FORMS | PERMISSION_ASSEMBLE;
+    }
+    p
+}
+
+/// Clave de cifrado del documento y cómo se deriva la de cada objeto.
+enum FileKey {
+    /// AESV3: la misma clave para todos los objetos
+    Aes256(Vec<u8>),
+    /// AESV2: MD5(clave + número + generación + "sAlT")
+    Aes128(Vec<u8>),
+}
+
+impl FileKey {
+    fn object_key(&self, (number, generation): ObjectId) -> Result<ObjectKey> {
+        match self {
+            FileKey::Aes256(key) => Ok(ObjectKey {
+                cipher: Cipher::aes_256_cbc(),
+                key: key.clone(),
+            }),
+            FileKey::Aes128(key) => {
+                let mut input = key.clone();
+                input.extend_from_slice(&number.to_le_bytes()[..3]);
+                input.extend_from_slice(&generation.to_le_bytes());
+                input.extend_from_slice(b"sAlT");
+                Ok(ObjectKey {
+                    cipher: Cipher::aes_128_cbc(),
+                    key: md5(&input)?,
+                })
+            }
+        }
+    }
+}
+
+struct ObjectKey {
+    cipher: Cipher,
+    key: Vec<u8>,
+}
+
+impl ObjectKey {
+    /// AES-CBC con relleno PKCS#5 y el IV aleatorio al inicio.
+    fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let iv = random_bytes(16)?;
+        let mut out = iv.clone();
+        out.extend(encrypt(self.cipher, &self.key, Some(&iv), data)?);
+        Ok(out)
+    }
+}
+
+fn encrypt_object(object: &mut Object, key: &ObjectKey) -> Result<()> {
+    match object {
+        Object::String(bytes, format) => {
+            *bytes = key.encrypt(bytes)?;
+            *format = StringFormat::Hexadecimal;
+        }
+        Object::Array(items) => {
+            for item in items {
+                encrypt_object(item, key)?;
+            }
+        }
+        Object::Dictionary(dict) => {
+            for (_, value) in dict.iter_mut() {
+                encrypt_object(value, key)?;
+            }
+        }
+        Object::Stream(stream) => {
+            for (_, value) in stream.dict.iter_mut() {
+                encrypt_object(value, key)?;
+            }
+            let content = key.encrypt(&stream.content)?;
+            stream.set_content(content);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// --------------------------------------------------------------------------------
+// Revisión 6 (AES-256)
+// --------------------------------------------------------------------------------
+
+fn aes256_handler(
+    user: &str,
+    owner: &str,
+    permissions: i32,
+) -> Result<(lopdf::Dictionary, FileKey)> {
+    let file_key = random_bytes(32)?;
+    let (user, owner) = (user.as_bytes(), owner.as_bytes());
+
+    // /U y /UE: hash de validación + sal de validación + sal de clave
+    let user_salts = random_bytes(16)?;
+    let mut u = hash_r6(user, &user_salts[..8], &[])?;
+    u.extend_from_slice(&user_salts);
+    let ue = aes256_no_padding(
+        Cipher::aes_256_cbc(),
+        &hash_r6(user, &user_salts[8..], &[])?,
+        &file_key,
+    )?;
+
+    // /O y /OE: igual, pero el hash incluye /U
+    let owner_salts = random_bytes(16)?;
+    let mut o = hash_r6(owner, &owner_salts[..8], &u)?;
+    o.extend_from_slice(&owner_salts);
+    let oe = aes256_no_padding(
+        Cipher::aes_256_cbc(),
+        &hash_r6(owner, &owner_salts[8..], &u)?,
+        &file_key,
+    )?;
+
+    // /Perms: permisos cifrados para detectar manipulación de /P
+    let mut perms = Vec::with_capacity(16);
+    perms.extend_from_slice(&(permissions as u32).to_le_bytes());
+    perms.extend_from_slice(&[0xFF; 4]);
+    perms.extend_from_slice(b"Tadb");
+    perms.extend(random_bytes(4)?);
+    let perms = aes256_no_padding(Cipher::aes_256_ecb(), &file_key, &perms)?;
+
+    let dict = dictionary! {
+        "Filter" => "Standard",
+        "V" => 5,
+        "R" => 6,
+        "Length" => 256,
+        "CF" => dictionary! {
+            "StdCF" => dictionary! {
+                "CFM" => "AESV3",
+                "AuthEvent" => "DocOpen",
+                "Length" => 32,
+            },
+        },
+        "StmF" => "StdCF",
+        "StrF" => "StdCF",
+        "O" => hex_string(o),
+        "U" => hex_string(u),
+        "OE" => hex_string(oe),
+        "UE" => hex_string(ue),
+        "P" => permissions,
+        "Perms" => hex_string(perms),
+        "EncryptMetadata" => true,
+    };
+    Ok((dict, FileKey::Aes256(file_key)))
+}
+
+/// Algoritmo 2.B de ISO 32000-2: SHA-256/384/512 iterado sobre AES-128.
+fn hash_r6(password: &[u8], salt: &[u8], user_key: &[u8]) -> Result<Vec<u8>> {
+    let mut input = password.to_vec();
+    input.extend_from_slice(salt);
+    input.extend_from_slice(user_key);
+    let mut k = sha256(&input).to_vec();
+
+    let mut round = 0u32;
+    loop {
+        let mut block = password.to_vec();
+        block.extend_from_slice(&k);
+        block.extend_from_slice(user_key);
+        let k1 = block.repeat(64);
+
+        let mut crypter = Crypter::new(
+            Cipher::aes_128_cbc(),
+            Mode::Encrypt,
+            &k[..16],
+            Some(&k[16..32]),
+        )?;
+        crypter.pad(false);
+        let mut e = vec![0u8; k1.len() + 16];
+        let mut len = crypter.update(&k1, &mut e)?;
+        len += crypter.finalize(&mut e[len..])?;
+        e.truncate(len);
+
+        let selector: u32 = e[..16].iter().map(|b| *b as u32).sum::<u32>() % 3;
+        k = match selector {
+            0 => sha256(&e).to_vec(),
+            1 => sha384(&e).to_vec(),
+            _ => sha512(&e).to_vec(),
+        };
+
+        round += 1;
+        let last = *e.last().context("Hash de contraseña vacío")? as u32;
+        if round >= 64 && last <= round - 32 {
+            break;
+        }
+    }
+    k.truncate(32);
+    Ok(k)
+}
+
+fn aes256_no_padding(cipher: Cipher, key: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    let iv = [0u8; 16];
+    let iv = cipher.iv_len().map(|len| &iv[..len]);
+    let mut crypter = Crypter::new(cipher, Mode::Encrypt, key, iv)?;
+    crypter.pad(false);
+    let mut out = vec![0u8; data.len() + cipher.block_size()];
+    let mut len = crypter.update(data, &mut out)?;
+    len += crypter.finalize(&mut out[len..])?;
+    out.truncate(len);
+    Ok(out)
+}
+
+// --------------------------------------------------------------------------------
+// Revisión 4 (AES-128)
+// --------------------------------------------------------------------------------
+
+fn aes128_handler(
+    user: &str,
+    owner: &str,
+    permissions: i32,
+    file_id: &[u8],
+) -> Result<(lopdf::Dictionary, FileKey)> {
+    let user_padded = pad_password(user);
+
+    // /O (algoritmo 3): RC4 de la contraseña de usuario con la clave del propietario
+    let mut owner_key = md5(&pad_password(owner))?;
+    for _ in 0..50 {
+        owner_key = md5(&owner_key)?;
+    }
+    let o = rc4_rounds(&owner_key, &user_padded);
+
+    // Clave del documento (algoritmo 2)
+    let mut input = user_padded.to_vec();
+    input.extend_from_slice(&o);
+    input.extend_from_slice(&(permissions as u32).to_le_bytes());
+    input.extend_from_slice(file_id);
+    let mut file_key = md5(&input)?;
+    for _ in 0..50 {
+        file_key = md5(&file_key)?;
+    }
+
+    // /U (algoritmo 5)
+    let mut input = PASSWORD_PADDING.to_vec();
+    input.extend_from_slice(file_id);
+    let mut u = rc4_rounds(&file_key, &md5(&input)?);
+    u.resize(32, 0);
+
+    let dict = dictionary! {
+        "Filter" => "Standard",
+        "V" => 4,
+        "R" => 4,
+        "Length" => 128,
+        "CF" => dictionary! {
+            "StdCF" => dictionary! {
+                "CFM" => "AESV2",
+                "AuthEvent" => "DocOpen",
+                "Length" => 16,
+            },
+        },
+        "StmF" => "StdCF",
+        "StrF" => "StdCF",
+        "O" => hex_string(o),
+        "U" => hex_string(u),
+        "P" => permissions,
+    };
+    Ok((dict, FileKey::Aes128(file_key)))
+}
+
+fn pad_password(password: &str) -> [u8; 32] {
+    let bytes = password.as_bytes();
+    let len = bytes.len().min(32);
+    let mut padded = [0u8; 32];
+    padded[..len].copy_from_slice(&bytes[..len]);
+    padded[len..].copy_from_slice(&PASSWORD_PADDING[..32 - len]);
+    padded
+}
+
+/// RC4 con `key` y luego 19 pasadas más con `key XOR i`.
+fn rc4_rounds(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut out = rc4(key, data);
+    for i in 1..=19u8 {
+        let round_key: Vec<u8> = key.iter().map(|b| b ^ i).collect();
+        out = rc4(&round_key, &out);
+    }
+    out
+}
+
+/// RC4 (solo se usa para /O y /U; OpenSSL 3 lo deshabilita por defecto).
+fn rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut s: Vec<u8> = (0..=255).collect();
+    let mut j = 0u8;
+    for i in 0..256 {
+        j = j.wrapping_add(s[i]).wrapping_add(key[i % key.len()]);
+        s.swap(i, j as usize);
+    }
+    let (mut i, mut j) = (0u8, 0u8);
+    data.iter()
+        .map(|byte| {
+            i = i.wrapping_add(1);
+            j = j.wrapping_add(s[i as usize]);
+            s.swap(i as usize, j as usize);
+            byte ^ s[s[i as usize].wrapping_add(s[j as usize]) as usize]
+        })
+        .collect()
+}
+
+// --------------------------------------------------------------------------------
+// Utilidades
+// --------------------------------------------------------------------------------
+
+fn md5(data: &[u8]) -> Result<Vec<u8>> {
+    Ok(hash(MessageDigest::md5(), data)?.to_vec())
+}
+
+fn random_bytes(len: usize) -> Result<Vec<u8>> {
+    let mut bytes = vec![0u8; len];
+    rand_bytes(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn hex_string(bytes: Vec<u8>) -> Object {
+    Object::String(bytes, StringFormat::Hexadecimal)
+}
+
+#[cfg(test)]
+mod tests {
+    //! lopdf 0.34 solo descifra RC4 (revisiones 2 y 3), así que el lado del
+    //! lector (validar la contraseña de usuario, recuperar la clave y descifrar)
+    //! se implementa aquí siguiendo ISO 32000 y se lee el PDF guardado con lopdf.
+
+    use lopdf::Stream;
+    use openssl::symm::decrypt;
+
+    use super::*;
+    use crate::models::pdf_model::PdfPermissions;
+    use crate::services::pdf_tools::save_pdf;
+
+    const CONTENT: &[u8] = b"BT /F1 12 Tf 72 720 Td (Hola) Tj ET";
+    const TITLE: &[u8] = b"Documento secreto";
+
+    fn sample_pdf() -> Document {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let content_id = doc.add_object(Stream::new(dictionary! {}, CONTENT.to_vec()));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+            "Contents" => content_id,
+        });
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        let info_id = doc.add_object(dictionary! {
+            "Title" => Object::String(TITLE.to_vec(), StringFormat::Literal),
+        });
+        doc.trailer.set("Root", catalog_id);
+        doc.trailer.set("Info", info_id);
+        doc
+    }
+
+    /// Cifra, guarda y vuelve a cargar el PDF
+    fn encrypt_and_reload(algorithm: PdfEncryptionAlgorithm) -> Document {
+        let mut doc = sample_pdf();
+        let options = PdfEncryption {
+            user_password: Some("usuario".to_string()),
+            owner_password: Some("propietario".to_string()),
+            algorithm,
+            permissions: PdfPermissions {
+                print: false,
+                copy: true,
+                modify: false,
+            },
+        };
+        encrypt_document(&mut doc, &options).unwrap();
+        let bytes = save_pdf(&mut doc).unwrap();
+        Document::load_mem(&bytes).unwrap()
+    }
+
+    fn encrypt_dict(doc: &Document) -> &lopdf::Dictionary {
+        doc.get_encrypted().unwrap()
+    }
+
+    fn bytes<'a>(dict: &'a lopdf::Dictionary, key: &[u8]) -> &'a [u8] {
+        dict.get(key).unwrap().as_str().unwrap()
+    }
+
+    fn file_id(doc: &Document) -> Vec<u8> {
+        let ids = doc.trailer.get(b"ID").unwrap().as_array().unwrap();
+        ids[0].as_str().unwrap().to_vec()
+    }
+
+    /// Algoritmo 2.A: valida la contraseña de usuario contra /U y descifra /UE
+    fn r6_file_key(dict: &lopdf::Dictionary, password: &str) -> Option<Vec<u8>> {
+        let u = bytes(dict, b"U");
+        let (hash, validation_salt, key_salt) = (&u[..32], &u[32..40], &u[40..48]);
+        if hash_r6(password.as_bytes(), validation_salt, &[]).unwrap() != hash {
+            return None;
+        }
+        let intermediate = hash_r6(password.as_bytes(), key_salt, &[]).unwrap();
+        let mut crypter = Crypter::new(
+            Cipher::aes_256_cbc(),
+            Mode::Decrypt,
+            &intermediate,
+            Some(&[0u8; 16]),
+        )
+        .unwrap();
+        crypter.pad(false);
+        let ue = bytes(dict, b"UE");
+        let mut key = vec![0u8; ue.len() + 16];
+        let len = crypter.update(ue, &mut key).unwrap();
+        key.truncate(len);
+        Some(key)
+    }
+
+    /// Algoritmos 2 y 6: deriva la clave con la contraseña de usuario y la
+    /// valida contra los primeros 16 bytes de /U
+    fn r4_file_key(doc: &Document, password: &str) -> Option<Vec<u8>> {
+        let dict = encrypt_dict(doc);
+        let id = file_id(doc);
+        let permissions = dict.get(b"P").unwrap().as_i64().unwrap() as i32;
+        let mut input = pad_password(password).to_vec();
+        input.extend_from_slice(bytes(dict, b"O"));
+        input.extend_from_slice(&permissions.to_le_bytes());
+        input.extend_from_slice(&id);
+        let mut key = md5(&input).unwrap();
+        for _ in 0..50 {
+            key = md5(&key).unwrap();
+        }
+
+        let mut input = PASSWORD_PADDING.to_vec();
+        input.extend_from_slice(&id);
+        let expected = rc4_rounds(&key, &md5(&input).unwrap());
+        (bytes(dict, b"U")[..16] == expected[..]).then_some(key)
+    }
+
+    /// Descifra una cadena o stream: IV en los primeros 16 bytes, relleno PKCS#5
+    fn decrypt_aes(cipher: Cipher, key: &[u8], data: &[u8]) -> Vec<u8> {
+        decrypt(cipher, key, Some(&data[..16]), &data[16..]).unwrap()
+    }
+
+    fn content_and_title(doc: &Document) -> ((ObjectId, Vec<u8>), (ObjectId, Vec<u8>)) {
+        let page_id = *doc.get_pages().get(&1).unwrap();
+        let content_id = doc
+            .get_dictionary(page_id)
+            .unwrap()
+            .get(b"Contents")
+            .unwrap()
+            .as_reference()
+            .unwrap();
+        let content = doc
+            .get_object(content_id)
+            .unwrap()
+            .as_stream()
+            .unwrap()
+            .content
+            .clone();
+        let info_id = doc.trailer.get(b"Info").unwrap().as_reference().unwrap();
+        let title = bytes(doc.get_dictionary(info_id).unwrap(), b"Title").to_vec();
+        ((content_id, content), (info_id, title))
+    }
+
+    #[test]
+    fn aes256_round_trip_with_user_password() {
+        let doc = encrypt_and_reload(PdfEncryptionAlgorithm::Aes256);
+        let dict = encrypt_dict(&doc);
+        assert_eq!(dict.get(b"R").unwrap().as_i64().unwrap(), 6);
+        assert!(r6_file_key(dict, "otra").is_none());
+        let key = r6_file_key(dict, "usuario").unwrap();
+
+        // AESV3 usa la misma clave para todos los objetos
+        let ((_, content), (_, title)) = content_and_title(&doc);
+        assert_ne!(content, CONTENT);
+        assert_eq!(decrypt_aes(Cipher::aes_256_cbc(), &key, &content), CONTENT);
+        assert_eq!(decrypt_aes(Cipher::aes_256_cbc(), &key, &title), TITLE);
+    }
+
+    #[test]
+    fn aes256_perms_matches_requested_permissions() {
+        let doc = encrypt_and_reload(PdfEncryptionAlgorithm::Aes256);
+        let dict = encrypt_dict(&doc);
+        let key = r6_file_key(dict, "usuario").unwrap();
+        let p = dict.get(b"P").unwrap().as_i64().unwrap() as i32 as u32;
+
+        let mut crypter = Crypter::new(Cipher::aes_256_ecb(), Mode::Decrypt, &key, None).unwrap();
+        crypter.pad(false);
+        let mut perms = vec![0u8; 32];
+        let len = crypter.update(bytes(dict, b"Perms"), &mut perms).unwrap();
+        perms.truncate(len);
+
+        assert_eq!(u32::from_le_bytes(perms[..4].try_into().unwrap()), p);
+        assert_eq!(&perms[4..8], &[0xFF; 4]);
+        // 'T': /EncryptMetadata true
+        assert_eq!(&perms[8..12], b"Tadb");
+        assert_permissions(p);
+    }
+
+    #[test]
+    fn aes128_round_trip_with_user_password() {
+        let doc = encrypt_and_reload(PdfEncryptionAlgorithm::Aes128);
+        let dict = encrypt_dict(&doc);
+        assert_eq!(dict.get(b"R").unwrap().as_i64().unwrap(), 4);
+        assert!(r4_file_key(&doc, "otra").is_none());
+        let key = r4_file_key(&doc, "usuario").unwrap();
+
+        // AESV2: clave por objeto, MD5(clave + número + generación + "sAlT")
+        let ((content_id, content), (info_id, title)) = content_and_title(&doc);
+        let object_key = |id: ObjectId| FileKey::Aes128(key.clone()).object_key(id).unwrap().key;
+        assert_ne!(content, CONTENT);
+        assert_eq!(
+            decrypt_aes(Cipher::aes_128_cbc(), &object_key(content_id), &content),
+            CONTENT
+        );
+        assert_eq!(
+            decrypt_aes(Cipher::aes_128_cbc(), &object_key(info_id), &title),
+            TITLE
+        );
+        assert_permissions(dict.get(b"P").unwrap().as_i64().unwrap() as i32 as u32);
+    }
+
+    /// print: false, copy: true, modify: false
+    fn assert_permissions(p: u32) {
+        assert_eq!(p & PERMISSIONS_BASE, PERMISSIONS_BASE);
+        assert_eq!(p & (PERMISSION_PRINT | PERMISSION_PRINT_HIGH_QUALITY), 0);
+        assert_ne!(p & PERMISSION_COPY, 0);
+        assert_ne!(p & PERMISSION_ACCESSIBILITY, 0);
+        assert_eq!(
+            p & (PERMISSION_MODIFY
+                | PERMISSION_ANNOTATE
+                | PERMISSION_FILL_FORMS
+                | PERMISSION_ASSEMBLE),
+            0
+        );
+    }
+
+    #[test]
+    fn rc4_matches_known_vector() {
+        // Vector clásico: clave "Key", texto "Plaintext"
+        assert_eq!(hex(&rc4(b"Key", b"Plaintext")), "bbf316e8d940af0ad3");
+    }
+}
@@ -0,0 +1,764 @@
+//! services/pdf_tools/facturx.rs
+//! Factura electrónica Factur-X / ZUGFeRD: genera (o revisa) el XML CII y lo
+//! incrusta como archivo asociado del PDF/A-3, con su extensión XMP.
+
+use std::fmt::Write as _;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use lopdf::{dictionary, text_string, Dictionary, Document, Object, Stream};
+use rust_decimal::{Decimal, RoundingStrategy};
+
+use crate::models::invoice_model::{
+    FacturXProfile, InvoiceData, InvoiceLine, InvoiceParty, PdfInvoice,
+};
+use crate::services::pdf_tools::pdf_date;
+use crate::services::renderer::header_footer::escape_html;
+
+/// Nombre que exige Factur-X 1.0 (y ZUGFeRD 2.1+) para el XML incrustado
+pub const XML_FILE_NAME: &str = "factur-x.xml";
+/// Espacio de nombres del esquema de extensión XMP
+const FX_NAMESPACE: &str = "urn:factur-x:pdfa:CrossIndustryDocument:invoice:1p0#";
+/// Espacios de nombres CII D16B del elemento raíz (`rsm`) y de sus agregados (`ram`)
+const RSM_NAMESPACE: &str = "urn:un:unece:uncefact:data:standard:CrossIndustryInvoice:100";
+const RAM_NAMESPACE: &str =
+    "urn:un:unece:uncefact:data:standard:ReusableAggregateBusinessInformationEntity:100";
+
+/// XML listo para incrustar, con el perfil que declara.
+#[derive(Debug, Clone)]
+pub struct InvoiceXml {
+    pub xml: Vec<u8>,
+    pub profile: FacturXProfile,
+}
+
+/// Valida la factura del request y devuelve el XML CII a incrustar: el recibido
+/// en `xml` o el generado a partir de `data`.
+pub fn prepare(invoice: &PdfInvoice) -> Result<InvoiceXml> {
+    match (&invoice.xml, &invoice.data) {
+        (Some(xml), None) => {
+            let xml = xml.trim();
+            let declared = declared_profile(xml)?;
+            let profile = match (invoice.profile, declared) {
+                (Some(requested), Some(declared)) if requested != declared => {
+                    return Err(anyhow!(
+                        "`invoice.profile` ({}) no coincide con el perfil del XML ({})",
+                        requested.conformance_level(),
+                        declared.conformance_level()
+                    ));
+                }
+                (_, Some(declared)) => declared,
+                (Some(requested), None) => requested,
+                (None, None) => {
+                    return Err(anyhow!(
+                        "No se reconoce el perfil Factur-X del XML; indicar `invoice.profile`"
+                    ));
+                }
+            };
+            Ok(InvoiceXml {
+                xml: xml.as_bytes().to_vec(),
+                profile,
+            })
+        }
+        (None, Some(data)) => {
+            let profile = invoice.profile.unwrap_or(FacturXProfile::En16931);
+            Ok(InvoiceXml {
+                xml: build_cii(data, profile)?.into_bytes(),
+                profile,
+            })
+        }
+        _ => Err(anyhow!("`invoice` lleva `xml` o `data` (uno de los dos)")),
+    }
+}
+
+/// Revisa que el XML del cliente sea una factura CII bien formada (raíz
+/// `rsm:CrossIndustryInvoice` en su espacio de nombres) y devuelve el perfil que
+/// declara en `ExchangedDocumentContext`, si es uno de Factur-X.
+fn declared_profile(xml: &str) -> Result<Option<FacturXProfile>> {
+    // roxmltree rechaza los DTD por defecto: nada de entidades externas
+    let document = roxmltree::Document::parse(xml)
+        .map_err(|e| anyhow!("`invoice.xml` no es un XML bien formado: {}", e))?;
+    let root = document.root_element();
+    if !root.has_tag_name((RSM_NAMESPACE, "CrossIndustryInvoice")) {
+        return Err(anyhow!(
+            "`invoice.xml` no es una factura CII: la raíz debe ser rsm:CrossIndustryInvoice ({})",
+            RSM_NAMESPACE
+        ));
+    }
+    let id = child(root, RSM_NAMESPACE, "ExchangedDocumentContext")
+        .and_then(|context| {
+            child(
+                context,
+                RAM_NAMESPACE,
+                "GuidelineSpecifiedDocumentContextParameter",
+            )
+        })
+        .and_then(|parameter| child(parameter, RAM_NAMESPACE, "ID"))
+        .and_then(|id| id.text())
+        .map(str::trim);
+    Ok(id.and_then(|id| {
+        FacturXProfile::ALL
+            .into_iter()
+            .find(|p| p.guideline_id() == id)
+    }))
+}
+
+fn child<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    namespace: &str,
+    name: &str,
+) -> Option<roxmltree::Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name((namespace, name)))
+}
+
+/// Incrusta el XML como archivo asociado del documento (`/EmbeddedFiles` y `/AF`).
+pub fn embed(doc: &mut Document, invoice: &InvoiceXml, now: DateTime<Utc>) -> Result<()> {
+    let file = Stream::new(
+        dictionary! {
+            "Type" => "EmbeddedFile",
+            "Subtype" => Object::Name(b"text/xml".to_vec()),
+            "Params" => dictionary! {
+                "ModDate" => Object::string_literal(pdf_date(now)),
+                "Size" => invoice.xml.len() as i64,
+            },
+        },
+        invoice.xml.clone(),
+    );
+    let file_id = doc.add_object(file);
+    let spec_id = doc.add_object(dictionary! {
+        "Type" => "Filespec",
+        "F" => Object::string_literal(XML_FILE_NAME),
+        "UF" => text_string(XML_FILE_NAME),
+        "Desc" => text_string("Factur-X/ZUGFeRD invoice"),
+        "AFRelationship" => invoice.profile.af_relationship(),
+        "EF" => dictionary! { "F" => file_id, "UF" => file_id },
+    });
+
+    let catalog = doc.catalog()?;
+    let names_id = catalog.get(b"Names").and_then(Object::as_reference).ok();
+    let names = match names_id {
+        Some(id) => doc.get_dictionary_mut(id)?,
+        None => {
+            let catalog = doc.catalog_mut()?;
+            if !catalog.has(b"Names") {
+                catalog.set("Names", Dictionary::new());
+            }
+            catalog.get_mut(b"Names").and_then(Object::as_dict_mut)?
+        }
+    };
+    add_to_name_tree(names, spec_id)?;
+
+    let catalog = doc.catalog_mut()?;
+    match catalog.get_mut(b"AF") {
+        Ok(Object::Array(files)) => files.push(spec_id.into()),
+        _ => catalog.set("AF", vec![Object::Reference(spec_id)]),
+    }
+    Ok(())
+}
+
+/// Agrega el adjunto al árbol de nombres `/EmbeddedFiles`, que debe quedar
+/// ordenado por nombre.
+fn add_to_name_tree(names: &mut Dictionary, spec_id: lopdf::ObjectId) -> Result<()> {
+    if !names.has(b"EmbeddedFiles") {
+        names.set(
+            "EmbeddedFiles",
+            dictionary! { "Names" => Vec::<Object>::new() },
+        );
+    }
+    let tree = names
+        .get_mut(b"EmbeddedFiles")
+        .and_then(Object::as_dict_mut)
+        .map_err(|_| anyhow!("El árbol /EmbeddedFiles del PDF no es editable"))?;
+    let entries = tree
+        .get_mut(b"Names")
+        .and_then(Object::as_array_mut)
+        .map_err(|_| anyhow!("El árbol /EmbeddedFiles del PDF no es editable"))?;
+
+    let key = XML_FILE_NAME.as_bytes();
+    let position = entries
+        .chunks(2)
+        .position(|pair| pair[0].as_str().is_ok_and(|name| name > key))
+        .map_or(entries.len(), |pair| pair * 2);
+    entries.splice(
+        position..position,
+        [Object::string_literal(XML_FILE_NAME), spec_id.into()],
+    );
+    Ok(())
+}
+
+/// Propiedades `fx:` y el esquema de extensión que PDF/A exige para declararlas.
+pub fn xmp_extension(profile: FacturXProfile) -> String {
+    let property = |name: &str, description: &str| {
+        format!(
+            "<rdf:li rdf:parseType=\"Resource\">\
+             <pdfaProperty:name>{}</pdfaProperty:name>\
+             <pdfaProperty:valueType>Text</pdfaProperty:valueType>\
+             <pdfaProperty:category>external</pdfaProperty:category>\
+             <pdfaProperty:description>{}</pdfaProperty:description>\
+             </rdf:li>\n",
+            name, description
+        )
+    };
+    format!(
+        "<rdf:Description rdf:about=\"\" xmlns:fx=\"{ns}\">\n\
+         <fx:DocumentType>INVOICE</fx:DocumentType>\n\
+         <fx:DocumentFileName>{file}</fx:DocumentFileName>\n\
+         <fx:Version>1.0</fx:Version>\n\
+         <fx:ConformanceLevel>{level}</fx:ConformanceLevel>\n\
+         </rdf:Description>\n\
+         <rdf:Description rdf:about=\"\" \
+         xmlns:pdfaExtension=\"http://www.aiim.org/pdfa/ns/extension/\" \
+         xmlns:pdfaSchema=\"http://www.aiim.org/pdfa/ns/schema#\" \
+         xmlns:pdfaProperty=\"http://www.aiim.org/pdfa/ns/property#\">\n\
+         <pdfaExtension:schemas><rdf:Bag><rdf:li rdf:parseType=\"Resource\">\n\
+         <pdfaSchema:schema>Factur-X PDFA Extension Schema</pdfaSchema:schema>\n\
+         <pdfaSchema:namespaceURI>{ns}</pdfaSchema:namespaceURI>\n\
+         <pdfaSchema:prefix>fx</pdfaSchema:prefix>\n\
+         <pdfaSchema:property><rdf:Seq>\n\
+         {properties}\
+         </rdf:Seq></pdfaSchema:property>\n\
+         </rdf:li></rdf:Bag></pdfaExtension:schemas>\n\
+         </rdf:Description>\n",
+        ns = FX_NAMESPACE,
+        file = XML_FILE_NAME,
+        level = profile.conformance_level(),
+        properties = [
+            property("DocumentFileName", "name of the embedded XML invoice file"),
+            property("DocumentType", "INVOICE"),
+            property("Version", "The actual version of the Factur-X XML schema"),
+            property(
+                "ConformanceLevel",
+                "The conformance level of the embedded Factur-X data"
+            ),
+        ]
+        .concat(),
+    )
+}
+
+// --------------------------------------------------------------------------------
+// XML CII (UN/CEFACT Cross Industry Invoice D16B)
+// --------------------------------------------------------------------------------
+
+/// Subtotal de IVA por categoría y tipo.
+struct TaxGroup<'a> {
+    category: &'a str,
+    rate: Decimal,
+    basis: Decimal,
+    exemption_reason: Option<&'a str>,
+}
+
+impl TaxGroup<'_> {
+    fn tax(&self) -> Result<Decimal> {
+        let tax = self
+            .basis
+            .checked_mul(self.rate)
+            .ok_or_else(|| anyhow!("Importe fuera de rango en el IVA al {}%", self.rate))?;
+        Ok(round2(tax / Decimal::ONE_HUNDRED))
+    }
+}
+
+/// Cantidad, precio y tipo de una línea, en decimal exacto.
+struct LineAmounts {
+    quantity: Decimal,
+    unit_price: Decimal,
+    vat_rate: Decimal,
+    total: Decimal,
+}
+
+impl LineAmounts {
+    /// El total de la línea se redondea a 2 decimales antes de sumar (BR-CO-10).
+    fn new(line: &InvoiceLine, number: usize) -> Result<Self> {
+        let quantity = amount(line.quantity, number)?;
+        let unit_price = amount(line.unit_price, number)?;
+        let total = quantity
+            .checked_mul(unit_price)
+            .ok_or_else(|| anyhow!("Importe fuera de rango en la línea {}", number))?;
+        Ok(LineAmounts {
+            quantity,
+            unit_price,
+            vat_rate: amount(line.vat_rate, number)?,
+            total: round2(total),
+        })
+    }
+}
+
+/// Los importes del request llegan como número JSON (f64); se toman por su
+/// representación decimal más corta, así 0.1 es 0.1 y no 0.1000000000000000055...
+fn amount(value: f64, number: usize) -> Result<Decimal> {
+    Decimal::try_from(value).map_err(|_| anyhow!("Importe fuera de rango en la línea {}", number))
+}
+
+fn sum(values: impl IntoIterator<Item = Decimal>) -> Result<Decimal> {
+    values.into_iter().try_fold(Decimal::ZERO, |acc, value| {
+        acc.checked_add(value)
+            .ok_or_else(|| anyhow!("Importe total fuera de rango"))
+    })
+}
+
+fn build_cii(data: &InvoiceData, profile: FacturXProfile) -> Result<String> {
+    validate_data(data)?;
+    let full = profile != FacturXProfile::Minimum;
+
+    let amounts = data
+        .lines
+        .iter()
+        .enumerate()
+        .map(|(index, line)| LineAmounts::new(line, index + 1))
+        .collect::<Result<Vec<_>>>()?;
+    let mut groups: Vec<TaxGroup> = Vec::new();
+    for (line, amounts) in data.lines.iter().zip(&amounts) {
+        match groups
+            .iter_mut()
+            .find(|g| g.category == line.vat_category && g.rate == amounts.vat_rate)
+        {
+            Some(group) => group.basis = sum([group.basis, amounts.total])?,
+            None => groups.push(TaxGroup {
+                category: &line.vat_category,
+                rate: amounts.vat_rate,
+                basis: amounts.total,
+                exemption_reason: line.vat_exemption_reason.as_deref(),
+            }),
+        }
+    }
+    let line_total = sum(amounts.iter().map(|a| a.total))?;
+    let group_taxes = groups
+        .iter()
+        .map(TaxGroup::tax)
+        .collect::<Result<Vec<_>>>()?;
+    let tax_total = sum(group_taxes.iter().copied())?;
+    let grand_total = sum([line_total, tax_total])?;
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <rsm:CrossIndustryInvoice \
+         xmlns:rsm=\"urn:un:unece:uncefact:data:standard:CrossIndustryInvoice:100\" \
+         xmlns:ram=\"urn:un:unece:uncefact:data:standard:ReusableAggregateBusinessInformationEntity:100\" \
+         xmlns:qdt=\"urn:un:unece:uncefact:data:standard:QualifiedDataType:100\" \
+         xmlns:udt=\"urn:un:unece:uncefact:data:standard:UnqualifiedDataType:100\">\n",
+    );
+    let _ = writeln!(
+        xml,
+        "<rsm:ExchangedDocumentContext>\
+         <ram:GuidelineSpecifiedDocumentContextParameter><ram:ID>{}</ram:ID>\
+         </ram:GuidelineSpecifiedDocumentContextParameter>\
+         </rsm:ExchangedDocumentContext>",
+        profile.guideline_id()
+    );
+
+    let _ = write!(
+        xml,
+        "<rsm:ExchangedDocument><ram:ID>{}</ram:ID><ram:TypeCode>{}</ram:TypeCode>\
+         <ram:IssueDateTime>{}</ram:IssueDateTime>",
+        text(&data.number),
+        text(&data.type_code),
+        date(data.issue_date)
+    );
+    if full {
+        for note in &data.notes {
+            let _ = write!(
+                xml,
+                "<ram:IncludedNote><ram:Content>{}</ram:Content></ram:IncludedNote>",
+                text(note)
+            );
+        }
+    }
+    xml.push_str("</rsm:ExchangedDocument>\n<rsm:SupplyChainTradeTransaction>\n");
+
+    if profile.has_lines() {
+        for (index, (line, amounts)) in data.lines.iter().zip(&amounts).enumerate() {
+            write_line(&mut xml, index + 1, line, amounts);
+        }
+    }
+
+    xml.push_str("<ram:ApplicableHeaderTradeAgreement>");
+    if let Some(reference) = &data.buyer_reference {
+        let _ = write!(
+            xml,
+            "<ram:BuyerReference>{}</ram:BuyerReference>",
+            text(reference)
+        );
+    }
+    write_party(&mut xml, "SellerTradeParty", &data.seller, full, true);
+    write_party(&mut xml, "BuyerTradeParty", &data.buyer, full, full);
+    if let Some(order) = &data.order_reference {
+        let _ = write!(
+            xml,
+            "<ram:BuyerOrderReferencedDocument><ram:IssuerAssignedID>{}</ram:IssuerAssignedID>\
+             </ram:BuyerOrderReferencedDocument>",
+            text(order)
+        );
+    }
+    xml.push_str("</ram:ApplicableHeaderTradeAgreement>\n<ram:ApplicableHeaderTradeDelivery/>\n");
+
+    let _ = write!(
+        xml,
+        "<ram:ApplicableHeaderTradeSettlement><ram:InvoiceCurrencyCode>{}</ram:InvoiceCurrencyCode>",
+        text(&data.currency)
+    );
+    if full {
+        if let Some(payment) = &data.payment {
+            let _ = write!(
+                xml,
+                "<ram:SpecifiedTradeSettlementPaymentMeans><ram:TypeCode>{}</ram:TypeCode>",
+                text(&payment.means_code)
+            );
+            if let Some(iban) = &payment.iban {
+                let _ = write!(
+                    xml,
+                    "<ram:PayeePartyCreditorFinancialAccount><ram:IBANID>{}</ram:IBANID>\
+                     </ram:PayeePartyCreditorFinancialAccount>",
+                    text(&iban.replace(' ', ""))
+                );
+            }
+            if let Some(bic) = &payment.bic {
+                let _ = write!(
+                    xml,
+                    "<ram:PayeeSpecifiedCreditorFinancialInstitution><ram:BICID>{}</ram:BICID>\
+                     </ram:PayeeSpecifiedCreditorFinancialInstitution>",
+                    text(bic)
+                );
+            }
+            xml.push_str("</ram:SpecifiedTradeSettlementPaymentMeans>");
+        }
+        for (group, tax) in groups.iter().zip(&group_taxes) {
+            let _ = write!(
+                xml,
+                "<ram:ApplicableTradeTax><ram:CalculatedAmount>{:.2}</ram:CalculatedAmount>\
+                 <ram:TypeCode>VAT</ram:TypeCode>",
+                tax
+            );
+            if let Some(reason) = group.exemption_reason {
+                let _ = write!(
+                    xml,
+                    "<ram:ExemptionReason>{}</ram:ExemptionReason>",
+                    text(reason)
+                );
+            }
+            let _ = write!(
+                xml,
+                "<ram:BasisAmount>{:.2}</ram:BasisAmount><ram:CategoryCode>{}</ram:CategoryCode>\
+                 <ram:RateApplicablePercent>{}</ram:RateApplicablePercent></ram:ApplicableTradeTax>",
+                group.basis,
+                text(group.category),
+                decimal(group.rate)
+            );
+        }
+        let terms = data.payment.as_ref().and_then(|p| p.terms.as_deref());
+        if terms.is_some() || data.due_date.is_some() {
+            xml.push_str("<ram:SpecifiedTradePaymentTerms>");
+            if let Some(terms) = terms {
+                let _ = write!(xml, "<ram:Description>{}</ram:Description>", text(terms));
+            }
+            if let Some(due) = data.due_date {
+                let _ = write!(
+                    xml,
+                    "<ram:DueDateDateTime>{}</ram:DueDateDateTime>",
+                    date(due)
+                );
+            }
+            xml.push_str("</ram:SpecifiedTradePaymentTerms>");
+        }
+    }
+
+    xml.push_str("<ram:SpecifiedTradeSettlementHeaderMonetarySummation>");
+    if full {
+        let _ = write!(
+            xml,
+            "<ram:LineTotalAmount>{:.2}</ram:LineTotalAmount>",
+            line_total
+        );
+    }
+    let _ = write!(
+        xml,
+        "<ram:TaxBasisTotalAmount>{:.2}</ram:TaxBasisTotalAmount>\
+         <ram:TaxTotalAmount currencyID=\"{}\">{:.2}</ram:TaxTotalAmount>\
+         <ram:GrandTotalAmount>{:.2}</ram:GrandTotalAmount>\
+         <ram:DuePayableAmount>{:.2}</ram:DuePayableAmount>\
+         </ram:SpecifiedTradeSettlementHeaderMonetarySummation>\
+         </ram:ApplicableHeaderTradeSettlement>\n\
+         </rsm:SupplyChainTradeTransaction>\n\
+         </rsm:CrossIndustryInvoice>\n",
+        line_total,
+        text(&data.currency),
+        tax_total,
+        grand_total,
+        grand_total
+    );
+    Ok(xml)
+}
+
+fn write_line(xml: &mut String, id: usize, line: &InvoiceLine, amounts: &LineAmounts) {
+    let _ = writeln!(
+        xml,
+        "<ram:IncludedSupplyChainTradeLineItem>\
+         <ram:AssociatedDocumentLineDocument><ram:LineID>{id}</ram:LineID></ram:AssociatedDocumentLineDocument>\
+         <ram:SpecifiedTradeProduct><ram:Name>{name}</ram:Name></ram:SpecifiedTradeProduct>\
+         <ram:SpecifiedLineTradeAgreement><ram:NetPriceProductTradePrice>\
+         <ram:ChargeAmount>{price}</ram:ChargeAmount></ram:NetPriceProductTradePrice>\
+         </ram:SpecifiedLineTradeAgreement>\
+         <ram:SpecifiedLineTradeDelivery>\
+         <ram:BilledQuantity unitCode=\"{unit}\">{quantity}</ram:BilledQuantity>\
+         </ram:SpecifiedLineTradeDelivery>\
+         <ram:SpecifiedLineTradeSettlement><ram:ApplicableTradeTax><ram:TypeCode>VAT</ram:TypeCode>\
+         <ram:CategoryCode>{category}</ram:CategoryCode>\
+         <ram:RateApplicablePercent>{rate}</ram:RateApplicablePercent></ram:ApplicableTradeTax>\
+         <ram:SpecifiedTradeSettlementLineMonetarySummation>\
+         <ram:LineTotalAmount>{total:.2}</ram:LineTotalAmount>\
+         </ram:SpecifiedTradeSettlementLineMonetarySummation>\
+         </ram:SpecifiedLineTradeSettlement></ram:IncludedSupplyChainTradeLineItem>",
+        id = id,
+        name = text(&line.description),
+        price = decimal(amounts.unit_price),
+        unit = attribute(&line.unit_code),
+        quantity = decimal(amounts.quantity),
+        category = text(&line.vat_category),
+        rate = decimal(amounts.vat_rate),
+        total = amounts.total,
+    );
+}
+
+/// Parte comercial. En MINIMUM el comprador solo lleva nombre e identificador legal
+/// (`address` en false).
+fn write_party(xml: &mut String, tag: &str, party: &InvoiceParty, full: bool, address: bool) {
+    let _ = write!(
+        xml,
+        "<ram:{}><ram:Name>{}</ram:Name>",
+        tag,
+        text(&party.name)
+    );
+    if let Some(legal_id) = &party.legal_id {
+        let _ = write!(
+            xml,
+            "<ram:SpecifiedLegalOrganization><ram:ID>{}</ram:ID></ram:SpecifiedLegalOrganization>",
+            text(legal_id)
+        );
+    }
+    if address {
+        let a = &party.address;
+        xml.push_str("<ram:PostalTradeAddress>");
+        if full {
+            let fields = [
+                ("PostcodeCode", &a.postcode),
+                ("LineOne", &a.line1),
+                ("LineTwo", &a.line2),
+                ("CityName", &a.city),
+            ];
+            for (name, value) in fields {
+                if let Some(value) = value {
+                    let _ = write!(xml, "<ram:{0}>{1}</ram:{0}>", name, text(value));
+                }
+            }
+        }
+        let _ = write!(
+            xml,
+            "<ram:CountryID>{}</ram:CountryID></ram:PostalTradeAddress>",
+            text(&a.country_code)
+        );
+    }
+    if full {
+        if let Some(email) = &party.email {
+            let _ = write!(
+                xml,
+                "<ram:URIUniversalCommunication><ram:URIID schemeID=\"EM\">{}</ram:URIID>\
+                 </ram:URIUniversalCommunication>",
+                text(email)
+            );
+        }
+    }
+    if address {
+        for (scheme, id) in [("VA", &party.vat_id), ("FC", &party.tax_id)] {
+            if let Some(id) = id {
+                let _ = write!(
+                    xml,
+                    "<ram:SpecifiedTaxRegistration><ram:ID schemeID=\"{}\">{}</ram:ID>\
+                     </ram:SpecifiedTaxRegistration>",
+                    scheme,
+                    text(id)
+                );
+            }
+        }
+    }
+    let _ = write!(xml, "</ram:{}>", tag);
+}
+
+/// Reglas mínimas para no generar un XML que el receptor va a rechazar.
+fn validate_data(data: &InvoiceData) -> Result<()> {
+    if data.number.trim().is_empty() {
+        return Err(anyhow!("`invoice.data.number` es obligatorio"));
+    }
+    if data.currency.len() != 3 || !data.currency.chars().all(|c| c.is_ascii_uppercase()) {
+        return Err(anyhow!(
+            "`invoice.data.currency` debe ser un código ISO 4217 (ej: EUR): {}",
+            data.currency
+        ));
+    }
+    for (role, party) in [("seller", &data.seller), ("buyer", &data.buyer)] {
+        let country = &party.address.country_code;
+        if country.len() != 2 || !country.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err(anyhow!(
+                "`invoice.data.{}.address.country_code` debe ser ISO 3166-1 alfa-2: {}",
+                role,
+                country
+            ));
+        }
+        if party.name.trim().is_empty() {
+            return Err(anyhow!("`invoice.data.{}.name` es obligatorio", role));
+        }
+    }
+    let seller = &data.seller;
+    if seller.vat_id.is_none() && seller.tax_id.is_none() && seller.legal_id.is_none() {
+        return Err(anyhow!(
+            "El vendedor necesita `vat_id`, `tax_id` o `legal_id`"
+        ));
+    }
+    if data.lines.is_empty() {
+        return Err(anyhow!("La factura necesita al menos una línea"));
+    }
+    for (index, line) in data.lines.iter().enumerate() {
+        if !line.quantity.is_finite() || !line.unit_price.is_finite() || !line.vat_rate.is_finite()
+        {
+            return Err(anyhow!("Importes inválidos en la línea {}", index + 1));
+        }
+        if line.unit_price < 0.0 {
+            return Err(anyhow!(
+                "El precio unitario no puede ser negativo (línea {})",
+                index + 1
+            ));
+        }
+        match line.vat_category.as_str() {
+            "S" if line.vat_rate <= 0.0 => {
+                return Err(anyhow!(
+                    "La categoría S necesita un tipo de IVA mayor que 0 (línea {})",
+                    index + 1
+                ));
+            }
+            "Z" | "E" | "AE" | "K" | "G" | "O" if line.vat_rate != 0.0 => {
+                return Err(anyhow!(
+                    "La categoría {} lleva IVA 0 (línea {})",
+                    line.vat_category,
+                    index + 1
+                ));
+            }
+            "E" | "AE" | "K" | "G" | "O" if line.vat_exemption_reason.is_none() => {
+                return Err(anyhow!(
+                    "La categoría {} necesita `vat_exemption_reason` (línea {})",
+                    line.vat_category,
+                    index + 1
+                ));
+            }
+            "S" | "Z" | "E" | "AE" | "K" | "G" | "O" | "L" | "M" => {}
+            other => {
+                return Err(anyhow!(
+                    "Categoría de IVA desconocida: {} (línea {})",
+                    other,
+                    index + 1
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Redondeo comercial a céntimos: la mitad se aleja de cero (2.675 -> 2.68)
+fn round2(value: Decimal) -> Decimal {
+    value.round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero)
+}
+
+/// Número sin ceros de más: 20 -> "20", 5.5 -> "5.5", 0.125 -> "0.125"
+fn decimal(value: Decimal) -> String {
+    value
+        .round_dp_with_strategy(4, RoundingStrategy::MidpointAwayFromZero)
+        .normalize()
+        .to_string()
+}
+
+fn date(value: NaiveDate) -> String {
+    format!(
+        "<udt:DateTimeString format=\"102\">{}</udt:DateTimeString>",
+        value.format("%Y%m%d")
+    )
+}
+
+fn text(value: &str) -> String {
+    escape_html(value)
+}
+
+fn attribute(value: &str) -> String {
+    escape_html(value).replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn cii(root: &str, guideline: &str) -> String {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+             <rsm:{root} xmlns:rsm=\"{RSM_NAMESPACE}\" xmlns:ram=\"{RAM_NAMESPACE}\">\
+             <rsm:ExchangedDocumentContext><ram:GuidelineSpecifiedDocumentContextParameter>\
+             <ram:ID>{guideline}</ram:ID></ram:GuidelineSpecifiedDocumentContextParameter>\
+             </rsm:ExchangedDocumentContext></rsm:{root}>"
+        )
+    }
+
+    fn with_xml(xml: String) -> PdfInvoice {
+        PdfInvoice {
+            profile: None,
+            xml: Some(xml),
+            data: None,
+        }
+    }
+
+    #[test]
+    fn client_xml_must_be_a_well_formed_cii() {
+        let basic = FacturXProfile::Basic.guideline_id();
+        let prepared = prepare(&with_xml(cii("CrossIndustryInvoice", basic))).unwrap();
+        assert_eq!(prepared.profile, FacturXProfile::Basic);
+
+        // Mal formado, aunque mencione CrossIndustryInvoice
+        let truncated =
+            cii("CrossIndustryInvoice", basic).replace("</rsm:CrossIndustryInvoice>", "");
+        assert!(prepare(&with_xml(truncated)).is_err());
+        // Otra raíz, o la raíz correcta en otro espacio de nombres
+        assert!(prepare(&with_xml(cii("Invoice", basic))).is_err());
+        let foreign = cii("CrossIndustryInvoice", basic).replace(RSM_NAMESPACE, "urn:otro");
+        assert!(prepare(&with_xml(foreign)).is_err());
+        // Sin DTD: nada de entidades
+        let dtd = format!(
+            "<!DOCTYPE x [<!ENTITY e SYSTEM \"file:///etc/passwd\">]>{}",
+            cii("CrossIndustryInvoice", "&e;")
+        );
+        assert!(prepare(&with_xml(dtd)).is_err());
+    }
+
+    #[test]
+    fn totals_are_rounded_in_exact_decimal() {
+        let data: InvoiceData = serde_json::from_value(json!({
+            "number": "F-1",
+            "issue_date": "2026-01-15",
+            "currency": "EUR",
+            "seller": { "name": "Vendedor", "vat_id": "FR32123456789", "address": { "country_code": "FR" } },
+            "buyer": { "name": "Comprador", "address": { "country_code": "FR" } },
+            "lines": [
+                // En f64, 1.005 es 1.00499999... y 2.675 es 2.67499999...
+                { "description": "A", "quantity": 1, "unit_price": 1.005, "vat_rate": 20 },
+                { "description": "B", "quantity": 1, "unit_price": 2.675, "vat_rate": 20 },
+                { "description": "C", "quantity": 3, "unit_price": 0.1, "vat_rate": 5.5 }
+            ]
+        }))
+        .unwrap();
+        let xml = build_cii(&data, FacturXProfile::En16931).unwrap();
+
+        assert!(xml.contains("<ram:LineTotalAmount>1.01</ram:LineTotalAmount>"));
+        assert!(xml.contains("<ram:LineTotalAmount>2.68</ram:LineTotalAmount>"));
+        assert!(xml.contains("<ram:ChargeAmount>0.1</ram:ChargeAmount>"));
+        // 3.69 al 20% = 0.738 -> 0.74; 0.30 al 5.5% = 0.0165 -> 0.02
+        assert!(xml.contains("<ram:CalculatedAmount>0.74</ram:CalculatedAmount>"));
+        assert!(xml.contains("<ram:CalculatedAmount>0.02</ram:CalculatedAmount>"));
+        assert!(xml.contains("<ram:RateApplicablePercent>5.5</ram:RateApplicablePercent>"));
+        assert!(xml.contains("<ram:LineTotalAmount>3.99</ram:LineTotalAmount>"));
+        assert!(xml.contains("<ram:GrandTotalAmount>4.75</ram:GrandTotalAmount>"));
+        assert!(roxmltree::Document::parse(&xml).is_ok());
+    }
+}
@@ -0,0 +1,359 @@
+//! services/pdf_tools/image.rs
+//! Imágenes PNG y JPEG como `XObject` de imagen. El JPEG se copia tal cual
+//! (`DCTDecode`); el PNG reutiliza sus datos comprimidos con el predictor PNG
+//! y, si tiene transparencia, la separa en una `SMask`.
+
+use std::io::{Read, Write};
+
+use anyhow::{anyhow, Context, Result};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream, StringFormat};
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Imagen agregada al documento y su tamaño en píxeles.
+pub struct EmbeddedImage {
+    pub id: ObjectId,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Agrega la imagen (PNG o JPEG, según su firma) al documento.
+pub fn add_image(doc: &mut Document, bytes: &[u8]) -> Result<EmbeddedImage> {
+    if bytes.starts_with(PNG_SIGNATURE) {
+        add_png(doc, bytes)
+    } else if bytes.starts_with(&[0xFF, 0xD8]) {
+        add_jpeg(doc, bytes)
+    } else {
+        Err(anyhow!(
+            "Formato de imagen no soportado (se acepta PNG o JPEG)"
+        ))
+    }
+}
+
+// --------------------------------------------------------------------------------
+// JPEG
+// --------------------------------------------------------------------------------
+
+fn add_jpeg(doc: &mut Document, bytes: &[u8]) -> Result<EmbeddedImage> {
+    let (width, height, components) = jpeg_info(bytes)?;
+    let mut dict = dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Image",
+        "Width" => width as i64,
+        "Height" => height as i64,
+        "BitsPerComponent" => 8,
+        "Filter" => "DCTDecode",
+    };
+    match components {
+        1 => dict.set("ColorSpace", "DeviceGray"),
+        3 => dict.set("ColorSpace", "DeviceRGB"),
+        4 => {
+            // Los JPEG CMYK de Adobe guardan los valores invertidos
+            dict.set("ColorSpace", "DeviceCMYK");
+            dict.set(
+                "Decode",
+                [1, 0, 1, 0, 1, 0, 1, 0].map(Object::Integer).to_vec(),
+            );
+        }
+        n => return Err(anyhow!("JPEG con {} componentes no soportado", n)),
+    }
+    let mut stream = Stream::new(dict, bytes.to_vec());
+    stream.allows_compression = false;
+    Ok(EmbeddedImage {
+        id: doc.add_object(stream),
+        width,
+        height,
+    })
+}
+
+/// Ancho, alto y número de componentes, del primer marcador SOF.
+fn jpeg_info(bytes: &[u8]) -> Result<(u32, u32, u8)> {
+    let invalid = || anyhow!("JPEG inválido o truncado");
+    let mut pos = 2;
+    loop {
+        while bytes.get(pos) == Some(&0xFF) && bytes.get(pos + 1) == Some(&0xFF) {
+            pos += 1;
+        }
+        if bytes.get(pos) != Some(&0xFF) {
+            return Err(invalid());
+        }
+        let marker = *bytes.get(pos + 1).ok_or_else(invalid)?;
+        let segment = bytes.get(pos + 2..pos + 4).ok_or_else(invalid)?;
+        let length = u16::from_be_bytes([segment[0], segment[1]]) as usize;
+        let is_sof = matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC);
+        if is_sof {
+            let sof = bytes.get(pos + 4..pos + 10).ok_or_else(invalid)?;
+            let height = u16::from_be_bytes([sof[1], sof[2]]) as u32;
+            let width = u16::from_be_bytes([sof[3], sof[4]]) as u32;
+            return Ok((width, height, sof[5]));
+        }
+        pos += 2 + length;
+    }
+}
+
+// --------------------------------------------------------------------------------
+// PNG
+// --------------------------------------------------------------------------------
+
+struct PngInfo {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: u8,
+    palette: Vec<u8>,
+    transparency: Option<Vec<u8>>,
+    data: Vec<u8>,
+}
+
+impl PngInfo {
+    fn channels(&self) -> usize {
+        match self.color_type {
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1,
+        }
+    }
+
+    /// Bytes por fila, sin el byte de filtro
+    fn stride(&self) -> usize {
+        (self.width as usize * self.channels() * self.bit_depth as usize).div_ceil(8)
+    }
+}
+
+fn add_png(doc: &mut Document, bytes: &[u8]) -> Result<EmbeddedImage> {
+    let png = parse_png(bytes)?;
+    let colors = match png.color_type {
+        0 | 3 | 4 => 1,
+        _ => 3,
+    };
+    let mut dict = dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Image",
+        "Width" => png.width as i64,
+        "Height" => png.height as i64,
+        "Filter" => "FlateDecode",
+    };
+    match png.color_type {
+        0 | 4 => dict.set("ColorSpace", "DeviceGray"),
+        3 => {
+            let count = png.palette.len() / 3;
+            dict.set(
+                "ColorSpace",
+                vec![
+                    Object::Name(b"Indexed".to_vec()),
+                    Object::Name(b"DeviceRGB".to_vec()),
+                    (count.max(1) as i64 - 1).into(),
+                    Object::String(png.palette.clone(), StringFormat::Hexadecimal),
+                ],
+            );
+        }
+        _ => dict.set("ColorSpace", "DeviceRGB"),
+    }
+
+    let content = if matches!(png.color_type, 4 | 6) {
+        // Con canal alfa: se separa en la imagen de color y una SMask en gris
+        if png.bit_depth != 8 {
+            return Err(anyhow!("PNG con transparencia de 16 bits no soportado"));
+        }
+        let pixels = unfilter(&png)?;
+        let channels = png.channels();
+        let mut color = Vec::with_capacity(pixels.len() / channels * (channels - 1));
+        let mut alpha = Vec::with_capacity(pixels.len() / channels);
+        for pixel in pixels.chunks_exact(channels) {
+            color.extend_from_slice(&pixel[..channels - 1]);
+            alpha.push(pixel[channels - 1]);
+        }
+        let mask_id = add_soft_mask(doc, &png, &alpha)?;
+        dict.set("SMask", mask_id);
+        dict.set("BitsPerComponent", 8);
+        deflate(&color)?
+    } else {
+        match (&png.transparency, png.color_type) {
+            (Some(alpha), 3) => {
+                let mask_id = add_soft_mask(doc, &png, &palette_alpha(&png, alpha)?)?;
+                dict.set("SMask", mask_id);
+            }
+            // Gris o RGB con un color transparente: máscara por color
+            (Some(key), _) => {
+                let mask: Vec<Object> = key
+                    .chunks_exact(2)
+                    .flat_map(|v| {
+                        let value = u16::from_be_bytes([v[0], v[1]]) as i64;
+                        [value.into(), value.into()]
+                    })
+                    .collect();
+                dict.set("Mask", mask);
+            }
+            (None, _) => {}
+        }
+        dict.set("BitsPerComponent", png.bit_depth as i64);
+        dict.set(
+            "DecodeParms",
+            dictionary! {
+                "Predictor" => 15,
+                "Colors" => colors,
+                "BitsPerComponent" => png.bit_depth as i64,
+                "Columns" => png.width as i64,
+            },
+        );
+        png.data
+    };
+
+    let mut stream = Stream::new(dict, content);
+    stream.allows_compression = false;
+    Ok(EmbeddedImage {
+        id: doc.add_object(stream),
+        width: png.width,
+        height: png.height,
+    })
+}
+
+fn parse_png(bytes: &[u8]) -> Result<PngInfo> {
+    let invalid = || anyhow!("PNG inválido o truncado");
+    let mut pos = PNG_SIGNATURE.len();
+    let mut header: Option<&[u8]> = None;
+    let mut palette = Vec::new();
+    let mut transparency = None;
+    let mut data = Vec::new();
+
+    while pos + 8 <= bytes.len() {
+        let length = u32::from_be_bytes(bytes[pos..pos + 4].try_into()?) as usize;
+        let kind = &bytes[pos + 4..pos + 8];
+        let body = bytes.get(pos + 8..pos + 8 + length).ok_or_else(invalid)?;
+        match kind {
+            b"IHDR" => header = Some(body),
+            b"PLTE" => palette = body.to_vec(),
+            b"tRNS" => transparency = Some(body.to_vec()),
+            b"IDAT" => data.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+        // longitud + tipo + datos + CRC
+        pos += 12 + length;
+    }
+
+    let header = header.filter(|h| h.len() >= 13).ok_or_else(invalid)?;
+    if header[12] != 0 {
+        return Err(anyhow!("PNG entrelazado no soportado"));
+    }
+    let png = PngInfo {
+        width: u32::from_be_bytes(header[0..4].try_into()?),
+        height: u32::from_be_bytes(header[4..8].try_into()?),
+        bit_depth: header[8],
+        color_type: header[9],
+        palette,
+        transparency,
+        data,
+    };
+    if !matches!(png.color_type, 0 | 2 | 3 | 4 | 6) || png.data.is_empty() {
+        return Err(invalid());
+    }
+    if png.color_type == 3 && png.palette.is_empty() {
+        return Err(anyhow!("PNG indexado sin paleta"));
+    }
+    Ok(png)
+}
+
+/// Descomprime los `IDAT` y deshace los filtros de fila del PNG.
+fn unfilter(png: &PngInfo) -> Result<Vec<u8>> {
+    let mut raw = Vec::new();
+    ZlibDecoder::new(png.data.as_slice())
+        .read_to_end(&mut raw)
+        .context("No se pudieron descomprimir los datos del PNG")?;
+
+    let stride = png.stride();
+    let bpp = (png.channels() * png.bit_depth as usize).div_ceil(8);
+    let rows = png.height as usize;
+    if raw.len() < rows * (stride + 1) {
+        return Err(anyhow!("PNG truncado"));
+    }
+
+    let mut out = vec![0u8; rows * stride];
+    for row in 0..rows {
+        let filter = raw[row * (stride + 1)];
+        let line = &raw[row * (stride + 1) + 1..(row + 1) * (stride + 1)];
+        let (done, current) = out.split_at_mut(row * stride);
+        let previous = if row > 0 {
+            &done[(row - 1) * stride..]
+        } else {
+            &[][..]
+        };
+        let current = &mut current[..stride];
+        for i in 0..stride {
+            let left = if i >= bpp { current[i - bpp] } else { 0 };
+            let up = previous.get(i).copied().unwrap_or(0);
+            let up_left = if i >= bpp {
+                previous.get(i - bpp).copied().unwrap_or(0)
+            } else {
+                0
+            };
+            let predicted = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                other => return Err(anyhow!("Filtro PNG desconocido: {}", other)),
+            };
+            current[i] = line[i].wrapping_add(predicted);
+        }
+    }
+    Ok(out)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Alfa por píxel de un PNG indexado, desde la tabla `tRNS` de la paleta.
+fn palette_alpha(png: &PngInfo, table: &[u8]) -> Result<Vec<u8>> {
+    let pixels = unfilter(png)?;
+    let stride = png.stride();
+    let depth = png.bit_depth as usize;
+    let per_byte = 8 / depth;
+    let mask = ((1u16 << depth) - 1) as u8;
+
+    let mut alpha = Vec::with_capacity((png.width * png.height) as usize);
+    for row in pixels.chunks_exact(stride) {
+        for x in 0..png.width as usize {
+            let byte = row[x / per_byte];
+            let shift = 8 - depth * (x % per_byte + 1);
+            let index = ((byte >> shift) & mask) as usize;
+            alpha.push(table.get(index).copied().unwrap_or(255));
+        }
+    }
+    Ok(alpha)
+}
+
+fn add_soft_mask(doc: &mut Document, png: &PngInfo, alpha: &[u8]) -> Result<ObjectId> {
+    let dict: Dictionary = dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Image",
+        "Width" => png.width as i64,
+        "Height" => png.height as i64,
+        "ColorSpace" => "DeviceGray",
+        "BitsPerComponent" => 8,
+        "Filter" => "FlateDecode",
+    };
+    let mut stream = Stream::new(dict, deflate(alpha)?);
+    stream.allows_compression = false;
+    Ok(doc.add_object(stream))
+}
+
+fn deflate(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
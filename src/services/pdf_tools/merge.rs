@@ -0,0 +1,129 @@
+//! services/pdf_tools/merge.rs
+//! Une páginas de varios PDFs en un documento nuevo. Extraer páginas de un
+//! solo PDF es el mismo caso con una sola parte.
+
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Result};
+use lopdf::{dictionary, Dictionary, Document, Object};
+
+/// Atributos que una página puede heredar de sus nodos `Pages` padres.
+/// Como el árbol de páginas se reconstruye, se copian a cada página.
+const INHERITABLE_KEYS: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// Un PDF de entrada y las páginas a tomar (1-based, en ese orden).
+/// `None` toma todas las páginas.
+pub struct MergePart {
+    pub document: Document,
+    pub pages: Option<Vec<u32>>,
+}
+
+/// Construye un documento nuevo con las páginas de cada parte, en orden.
+pub fn merge_documents(parts: Vec<MergePart>) -> Result<Document> {
+    let version = parts
+        .iter()
+        .map(|p| p.document.version.clone())
+        .max()
+        .unwrap_or_else(|| "1.4".to_string());
+    let mut out = Document::with_version(version);
+    let pages_id = out.new_object_id();
+    let mut kids = Vec::new();
+    let mut used_ids = HashSet::new();
+
+    for (part_index, part) in parts.into_iter().enumerate() {
+        let mut doc = part.document;
+        if doc.is_encrypted() {
+            return Err(anyhow!(
+                "El documento {} está cifrado y no se puede unir",
+                part_index + 1
+            ));
+        }
+
+        // Ids sin choques con lo ya copiado
+        doc.renumber_objects_with(out.max_id + 1);
+        out.max_id = doc.max_id;
+
+        let page_ids = doc.get_pages();
+        let selected = part
+            .pages
+            .unwrap_or_else(|| page_ids.keys().copied().collect());
+
+        let mut page_dicts = Vec::with_capacity(selected.len());
+        for number in selected {
+            let page_id = page_ids.get(&number).ok_or_else(|| {
+                anyhow!(
+                    "El documento {} no tiene la página {}",
+                    part_index + 1,
+                    number
+                )
+            })?;
+            let mut page = doc.get_dictionary(*page_id)?.clone();
+            inherit_attributes(&doc, &mut page);
+            page.set("Parent", pages_id);
+            page_dicts.push((*page_id, page));
+        }
+
+        // Todo menos la estructura del documento (catálogo, árbol de páginas, outline)
+        for (id, object) in doc.objects {
+            match object.type_name().unwrap_or_default() {
+                "Catalog" | "Pages" | "Page" | "Outlines" | "Outline" => {}
+                _ => {
+                    out.objects.insert(id, object);
+                }
+            }
+        }
+
+        for (page_id, page) in page_dicts {
+            // Una página repetida necesita su propio objeto
+            let id = if used_ids.insert(page_id) {
+                page_id
+            } else {
+                out.new_object_id()
+            };
+            out.objects.insert(id, Object::Dictionary(page));
+            kids.push(Object::Reference(id));
+        }
+    }
+
+    if kids.is_empty() {
+        return Err(anyhow!("El resultado no tiene páginas"));
+    }
+
+    let count = kids.len() as i64;
+    out.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => kids,
+            "Count" => count,
+        }),
+    );
+    let catalog_id = out.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    out.trailer.set("Root", catalog_id);
+
+    // Quita lo que solo usaban las páginas descartadas
+    out.prune_objects();
+    out.renumber_objects();
+    Ok(out)
+}
+
+/// Copia a la página los atributos heredables que solo están en sus padres.
+fn inherit_attributes(doc: &Document, page: &mut Dictionary) {
+    let mut parent = page.get(b"Parent").and_then(Object::as_reference).ok();
+    while let Some(parent_id) = parent {
+        let Ok(node) = doc.get_dictionary(parent_id) else {
+            break;
+        };
+        for key in INHERITABLE_KEYS {
+            if !page.has(key) {
+                if let Ok(value) = node.get(key) {
+                    page.set(key.to_vec(), value.clone());
+                }
+            }
+        }
+        parent = node.get(b"Parent").and_then(Object::as_reference).ok();
+    }
+}
@@ -0,0 +1,41 @@
+//! services/pdf_tools/metadata.rs
+//! Diccionario `Info` del PDF (título, autor, asunto, palabras clave, creador).
+
+use anyhow::Result;
+use lopdf::{text_string, Document, Object, ObjectId};
+
+use crate::models::pdf_model::PdfMetadata;
+
+/// Escribe los campos presentes de `metadata` en el diccionario `Info`,
+/// creándolo si el documento no tiene uno. Los campos ausentes se conservan.
+pub fn apply_metadata(doc: &mut Document, metadata: &PdfMetadata) -> Result<()> {
+    let info_id = info_id(doc);
+    let info = doc.get_dictionary_mut(info_id)?;
+
+    let keywords = metadata.keywords.as_ref().map(|k| k.join(", "));
+    let fields = [
+        ("Title", metadata.title.as_ref()),
+        ("Author", metadata.author.as_ref()),
+        ("Subject", metadata.subject.as_ref()),
+        ("Keywords", keywords.as_ref()),
+        ("Creator", metadata.creator.as_ref()),
+    ];
+    for (key, value) in fields {
+        if let Some(value) = value {
+            info.set(key, text_string(value));
+        }
+    }
+    Ok(())
+}
+
+/// Id del diccionario `Info`; si el documento no tiene uno, se crea vacío.
+pub fn info_id(doc: &mut Document) -> ObjectId {
+    match doc.trailer.get(b"Info").and_then(Object::as_reference) {
+        Ok(id) if doc.get_dictionary(id).is_ok() => id,
+        _ => {
+            let id = doc.add_object(lopdf::Dictionary::new());
+            doc.trailer.set("Info", id);
+            id
+        }
+    }
+}
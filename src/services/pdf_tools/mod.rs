@@ -0,0 +1,111 @@
+//! services/pdf_tools/mod.rs
+//! Operaciones sobre PDFs ya generados (unir, extraer páginas, ...), con lopdf.
+
+pub mod der;
+pub mod encryption;
+pub mod facturx;
+pub mod image;
+pub mod merge;
+pub mod metadata;
+pub mod outline;
+pub mod page_ranges;
+pub mod pdfa;
+pub mod signature;
+pub mod watermark;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use lopdf::{Document, Object, ObjectId, StringFormat};
+use openssl::rand::rand_bytes;
+
+/// Milímetros a puntos PDF
+pub const MM_TO_PT: f64 = 72.0 / 25.4;
+
+/// Carga un PDF desde memoria.
+pub fn load_pdf(bytes: &[u8]) -> Result<Document> {
+    Document::load_mem(bytes).context("El archivo no es un PDF válido")
+}
+
+/// Serializa el documento a bytes.
+pub fn save_pdf(doc: &mut Document) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    doc.save_to(&mut out).context("Error escribiendo el PDF")?;
+    Ok(out)
+}
+
+/// `MediaBox` de una página `[x0, y0, x1, y1]` en puntos, buscando también
+/// en los nodos `Pages` padres (es un atributo heredable). Por defecto, A4.
+pub fn media_box(doc: &Document, page_id: ObjectId) -> [f64; 4] {
+    let mut node = doc.get_dictionary(page_id).ok();
+    while let Some(dict) = node {
+        if let Ok(values) = dict.get(b"MediaBox").and_then(|b| match b {
+            Object::Reference(id) => doc.get_object(*id).and_then(Object::as_array),
+            other => other.as_array(),
+        }) {
+            let numbers: Vec<f64> = values
+                .iter()
+                .filter_map(|v| v.as_float().ok().map(f64::from))
+                .collect();
+            if let [x0, y0, x1, y1] = numbers[..] {
+                return [x0, y0, x1, y1];
+            }
+        }
+        node = dict
+            .get(b"Parent")
+            .and_then(Object::as_reference)
+            .and_then(|id| doc.get_dictionary(id))
+            .ok();
+    }
+    [0.0, 0.0, 595.28, 841.89]
+}
+
+/// Texto para un literal `( )` con WinAnsi: Latin-1 tal cual, el resto como `?`.
+pub fn win_ansi_literal(text: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        let byte = match c as u32 {
+            0x20..=0x7E | 0xA0..=0xFF => c as u8,
+            _ => b'?',
+        };
+        if matches!(byte, b'(' | b')' | b'\\') {
+            out.push(b'\\');
+        }
+        out.push(byte);
+    }
+    out
+}
+
+/// Fecha PDF: `D:YYYYMMDDHHmmSS+00'00'`
+pub fn pdf_date(time: DateTime<Utc>) -> String {
+    format!("D:{}+00'00'", time.format("%Y%m%d%H%M%S"))
+}
+
+/// Primer elemento de `/ID` del trailer; si no existe, se genera.
+pub fn ensure_file_id(doc: &mut Document) -> Result<Vec<u8>> {
+    if let Ok(Object::Array(ids)) = doc.trailer.get(b"ID") {
+        if let Some(Object::String(id, _)) = ids.first() {
+            return Ok(id.clone());
+        }
+    }
+    let mut id = vec![0u8; 16];
+    rand_bytes(&mut id)?;
+    let value = Object::String(id.clone(), StringFormat::Hexadecimal);
+    doc.trailer.set("ID", vec![value.clone(), value]);
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn win_ansi_literal_escapes_and_replaces() {
+        assert_eq!(
+            win_ansi_literal("Año (2026)"),
+            b"A\xf1o \\(2026\\)".to_vec()
+        );
+        assert_eq!(win_ansi_literal("a\\b"), b"a\\\\b".to_vec());
+        // Fuera de Latin-1 (y los controles) no hay glifo en WinAnsi con Helvetica
+        assert_eq!(win_ansi_literal("€ ✓\n"), b"? ??".to_vec());
+    }
+}
@@ -0,0 +1,212 @@
+//! services/pdf_tools/outline.rs
+//! Lectura y escritura del outline (marcadores) de un PDF.
+
+use anyhow::Result;
+use lopdf::{decode_text_string, dictionary, text_string, Dictionary, Document, Object, ObjectId};
+
+/// Un marcador: nivel (1 = h1), título y página (1-based).
+#[derive(Debug, Clone)]
+pub struct OutlineEntry {
+    pub level: usize,
+    pub title: String,
+    pub page: u32,
+}
+
+/// Lee el outline en orden de documento. Los marcadores cuyo destino no se
+/// puede resolver a una página se omiten.
+pub fn read_outline(doc: &Document) -> Vec<OutlineEntry> {
+    let mut entries = Vec::new();
+    let Ok(catalog) = doc.catalog() else {
+        return entries;
+    };
+    let Ok(outlines) = catalog.get(b"Outlines").and_then(|o| resolve_dict(doc, o)) else {
+        return entries;
+    };
+
+    let page_numbers: std::collections::HashMap<ObjectId, u32> =
+        doc.get_pages().into_iter().map(|(n, id)| (id, n)).collect();
+
+    let mut stack = vec![(first_child(doc, outlines), 1usize)];
+    // Recorrido en profundidad, iterativo para no depender del tamaño del outline
+    while let Some((current, level)) = stack.pop() {
+        let Some(item) = current else {
+            continue;
+        };
+        stack.push((next_sibling(doc, item), level));
+        stack.push((first_child(doc, item), level + 1));
+
+        let title = item
+            .get(b"Title")
+            .ok()
+            .and_then(|t| decode_text_string(t).ok())
+            .unwrap_or_default();
+        if let Some(page) = destination_page(doc, item).and_then(|id| page_numbers.get(&id)) {
+            entries.push(OutlineEntry {
+                level,
+                title,
+                page: *page,
+            });
+        }
+    }
+    entries
+}
+
+/// Reemplaza el outline del documento por `entries`. Un salto de nivel
+/// (h1 -> h3) cuelga del marcador anterior de menor nivel.
+pub fn write_outline(doc: &mut Document, entries: &[OutlineEntry]) -> Result<()> {
+    let pages = doc.get_pages();
+    let outlines_id = doc.new_object_id();
+
+    // (id, nivel, índice del padre)
+    let mut nodes: Vec<(ObjectId, usize, Option<usize>)> = Vec::new();
+    let mut dicts: Vec<Dictionary> = Vec::new();
+    for entry in entries {
+        let Some(page_id) = pages.get(&entry.page) else {
+            continue;
+        };
+        let parent = nodes.iter().rposition(|(_, level, _)| *level < entry.level);
+        let id = doc.new_object_id();
+        let mut item = dictionary! {
+            "Title" => text_string(&entry.title),
+            "Dest" => vec![Object::Reference(*page_id), "Fit".into()],
+        };
+        item.set("Parent", parent.map(|p| nodes[p].0).unwrap_or(outlines_id));
+        nodes.push((id, entry.level, parent));
+        dicts.push(item);
+    }
+
+    // Enlaces First/Last/Prev/Next entre hermanos
+    let mut root_children = Vec::new();
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); nodes.len()];
+    for (index, (_, _, parent)) in nodes.iter().enumerate() {
+        match parent {
+            Some(p) => children[*p].push(index),
+            None => root_children.push(index),
+        }
+    }
+    let link = |dicts: &mut Vec<Dictionary>, siblings: &[usize]| {
+        for pair in siblings.windows(2) {
+            dicts[pair[0]].set("Next", nodes[pair[1]].0);
+            dicts[pair[1]].set("Prev", nodes[pair[0]].0);
+        }
+    };
+    link(&mut dicts, &root_children);
+    for (index, kids) in children.iter().enumerate() {
+        if let (Some(first), Some(last)) = (kids.first(), kids.last()) {
+            dicts[index].set("First", nodes[*first].0);
+            dicts[index].set("Last", nodes[*last].0);
+            dicts[index].set("Count", kids.len() as i64);
+        }
+        link(&mut dicts, kids);
+    }
+
+    for ((id, _, _), item) in nodes.iter().zip(dicts) {
+        doc.objects.insert(*id, Object::Dictionary(item));
+    }
+
+    let mut outlines = dictionary! { "Type" => "Outlines" };
+    if let (Some(first), Some(last)) = (root_children.first(), root_children.last()) {
+        outlines.set("First", nodes[*first].0);
+        outlines.set("Last", nodes[*last].0);
+        outlines.set("Count", root_children.len() as i64);
+    }
+    doc.objects
+        .insert(outlines_id, Object::Dictionary(outlines));
+
+    let catalog = doc.catalog_mut()?;
+    catalog.set("Outlines", outlines_id);
+    catalog.set("PageMode", "UseOutlines");
+    Ok(())
+}
+
+fn resolve_dict<'a>(doc: &'a Document, object: &'a Object) -> lopdf::Result<&'a Dictionary> {
+    match object {
+        Object::Reference(id) => doc.get_dictionary(*id),
+        other => other.as_dict(),
+    }
+}
+
+fn first_child<'a>(doc: &'a Document, item: &'a Dictionary) -> Option<&'a Dictionary> {
+    item.get(b"First").and_then(|o| resolve_dict(doc, o)).ok()
+}
+
+fn next_sibling<'a>(doc: &'a Document, item: &'a Dictionary) -> Option<&'a Dictionary> {
+    item.get(b"Next").and_then(|o| resolve_dict(doc, o)).ok()
+}
+
+/// Página destino de un marcador: `/Dest` o una acción `/GoTo`, directa o por nombre.
+fn destination_page(doc: &Document, item: &Dictionary) -> Option<ObjectId> {
+    let dest = match item.get(b"Dest") {
+        Ok(dest) => dest.clone(),
+        Err(_) => {
+            let action = item.get(b"A").and_then(|a| resolve_dict(doc, a)).ok()?;
+            action.get(b"D").ok()?.clone()
+        }
+    };
+    resolve_destination(doc, &dest, 0)
+}
+
+fn resolve_destination(doc: &Document, dest: &Object, depth: usize) -> Option<ObjectId> {
+    if depth > 8 {
+        return None;
+    }
+    match dest {
+        Object::Array(items) => items.first()?.as_reference().ok(),
+        Object::Reference(id) => resolve_destination(doc, doc.get_object(*id).ok()?, depth + 1),
+        Object::Dictionary(dict) => resolve_destination(doc, dict.get(b"D").ok()?, depth + 1),
+        Object::String(name, _) | Object::Name(name) => {
+            let target = named_destination(doc, name)?;
+            resolve_destination(doc, &target, depth + 1)
+        }
+        _ => None,
+    }
+}
+
+/// Busca un destino con nombre en `/Dests` del catálogo o en el árbol `/Names/Dests`.
+fn named_destination(doc: &Document, name: &[u8]) -> Option<Object> {
+    let catalog = doc.catalog().ok()?;
+    if let Ok(dests) = catalog.get(b"Dests").and_then(|d| resolve_dict(doc, d)) {
+        if let Ok(dest) = dests.get(name) {
+            return Some(dest.clone());
+        }
+    }
+    let names = catalog
+        .get(b"Names")
+        .and_then(|n| resolve_dict(doc, n))
+        .ok()?;
+    let tree = names
+        .get(b"Dests")
+        .and_then(|d| resolve_dict(doc, d))
+        .ok()?;
+    search_name_tree(doc, tree, name, 0)
+}
+
+fn search_name_tree(
+    doc: &Document,
+    node: &Dictionary,
+    name: &[u8],
+    depth: usize,
+) -> Option<Object> {
+    if depth > 16 {
+        return None;
+    }
+    if let Ok(Object::Array(pairs)) = node.get(b"Names") {
+        for pair in pairs.chunks(2) {
+            if let [Object::String(key, _), value] = pair {
+                if key.as_slice() == name {
+                    return Some(value.clone());
+                }
+            }
+        }
+    }
+    if let Ok(Object::Array(kids)) = node.get(b"Kids") {
+        for kid in kids {
+            if let Ok(kid) = resolve_dict(doc, kid) {
+                if let Some(found) = search_name_tree(doc, kid, name, depth + 1) {
+                    return Some(found);
+                }
+            }
+        }
+    }
+    None
+}
@@ -0,0 +1,68 @@
+//! services/pdf_tools/page_ranges.rs
+//! Rangos de páginas tipo "1-3,5,8-" (1-based, extremos incluidos).
+
+use anyhow::{anyhow, Result};
+
+/// Expande `spec` a números de página en el orden escrito.
+/// Un extremo vacío significa el inicio o el final del documento.
+pub fn parse_page_ranges(spec: &str, page_count: u32) -> Result<Vec<u32>> {
+    let mut pages = Vec::new();
+    for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (start, end) = match part.split_once('-') {
+            Some((start, end)) => (
+                parse_bound(start, 1, part)?,
+                parse_bound(end, page_count, part)?,
+            ),
+            None => {
+                let page = parse_bound(part, 0, part)?;
+                (page, page)
+            }
+        };
+        if start == 0 || start > end || end > page_count {
+            return Err(anyhow!(
+                "Rango de páginas inválido '{}' (el documento tiene {} páginas)",
+                part,
+                page_count
+            ));
+        }
+        pages.extend(start..=end);
+    }
+
+    if pages.is_empty() {
+        return Err(anyhow!("Rango de páginas vacío: '{}'", spec));
+    }
+    Ok(pages)
+}
+
+fn parse_bound(value: &str, default: u32, part: &str) -> Result<u32> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(default);
+    }
+    value
+        .parse()
+        .map_err(|_| anyhow!("Rango de páginas inválido '{}'", part))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_ranges_in_written_order() {
+        assert_eq!(
+            parse_page_ranges("1-3, 5,8-", 10).unwrap(),
+            [1, 2, 3, 5, 8, 9, 10]
+        );
+        assert_eq!(parse_page_ranges("-2", 10).unwrap(), [1, 2]);
+        assert_eq!(parse_page_ranges("4,1", 4).unwrap(), [4, 1]);
+        assert_eq!(parse_page_ranges("-", 3).unwrap(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn rejects_invalid_ranges() {
+        for spec in ["0", "3-2", "11", "9-11", "a", "1-b", "", " , "] {
+            assert!(parse_page_ranges(spec, 10).is_err(), "{:?}", spec);
+        }
+    }
+}
@@ -0,0 +1,728 @@
+//! services/pdf_tools/pdfa.rs
+//! Salida PDF/A-2b y PDF/A-3b: convierte lo que se puede arreglar (XMP,
+//! perfil ICC de salida, cabecera, banderas de anotaciones) y valida el
+//! resto. Lo que no se puede arreglar se reporta, nunca se ignora.
+
+use std::collections::BTreeSet;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use lopdf::{
+    content::Content, decode_text_string, dictionary, text_string, xref::XrefType, Dictionary,
+    Document, Object, Stream,
+};
+
+use crate::models::pdf_model::PdfConformance;
+use crate::services::pdf_tools::{ensure_file_id, metadata::info_id, pdf_date};
+use crate::services::renderer::header_footer::escape_html;
+
+/// Comentario de la segunda línea: PDF/A exige al menos cuatro bytes > 127
+const BINARY_COMMENT: &[u8] = b"%\xE2\xE3\xCF\xD3\n";
+
+/// Identificador del perfil ICC de salida
+const OUTPUT_CONDITION: &str = "sRGB IEC61966-2.1";
+
+/// Acciones prohibidas por PDF/A-2 y PDF/A-3
+const FORBIDDEN_ACTIONS: [&str; 11] = [
+    "Launch",
+    "Sound",
+    "Movie",
+    "ResetForm",
+    "ImportData",
+    "JavaScript",
+    "Hide",
+    "SetOCGState",
+    "Rendition",
+    "Trans",
+    "GoTo3DView",
+];
+
+/// Anotaciones prohibidas
+const FORBIDDEN_ANNOTATIONS: [&str; 5] = ["Sound", "Movie", "Screen", "3D", "RichMedia"];
+
+/// Bits de `/F` en anotaciones: Invisible, Hidden, Print, NoView, ToggleNoView
+const ANNOT_INVISIBLE: i64 = 1;
+const ANNOT_HIDDEN: i64 = 2;
+const ANNOT_PRINT: i64 = 4;
+const ANNOT_NO_VIEW: i64 = 32;
+const ANNOT_TOGGLE_NO_VIEW: i64 = 256;
+
+/// Ajusta el documento a PDF/A: versión, `/ID`, fechas y XMP coherentes con
+/// `Info`, intención de salida sRGB, anotaciones imprimibles e imágenes sin
+/// interpolación; la cabecera binaria la agrega `write_header` al guardar.
+/// `xmp_extension` son bloques `rdf:Description` adicionales (ej: las
+/// propiedades Factur-X con su esquema de extensión).
+pub fn convert(
+    doc: &mut Document,
+    level: PdfConformance,
+    now: DateTime<Utc>,
+    xmp_extension: Option<&str>,
+) -> Result<()> {
+    doc.version = "1.7".to_string();
+    // `write_header` corrige los offsets de una tabla xref, no de un xref stream
+    doc.reference_table.cross_reference_type = XrefType::CrossReferenceTable;
+    ensure_file_id(doc)?;
+
+    let info_id = info_id(doc);
+    let info = doc.get_dictionary_mut(info_id)?;
+    info.set("CreationDate", Object::string_literal(pdf_date(now)));
+    info.set("ModDate", Object::string_literal(pdf_date(now)));
+    if !info.has(b"Producer") {
+        info.set("Producer", text_string("pdf_service"));
+    }
+    let xmp = xmp_packet(info, level, now, xmp_extension.unwrap_or(""));
+    let metadata_id = doc.add_object(uncompressed(
+        dictionary! { "Type" => "Metadata", "Subtype" => "XML" },
+        xmp.into_bytes(),
+    ));
+
+    let icc = srgb_icc_profile();
+    let icc_id = doc.add_object(Stream::new(dictionary! { "N" => 3 }, icc));
+    let intent = dictionary! {
+        "Type" => "OutputIntent",
+        "S" => "GTS_PDFA1",
+        "OutputConditionIdentifier" => Object::string_literal(OUTPUT_CONDITION),
+        "Info" => Object::string_literal(OUTPUT_CONDITION),
+        "DestOutputProfile" => icc_id,
+    };
+
+    let catalog = doc.catalog_mut()?;
+    catalog.set("Metadata", metadata_id);
+    catalog.set("OutputIntents", vec![Object::Dictionary(intent)]);
+
+    let acro_form_id = catalog.get(b"AcroForm").and_then(Object::as_reference).ok();
+    if let Ok(form) = catalog.get_mut(b"AcroForm").and_then(Object::as_dict_mut) {
+        form.remove(b"NeedAppearances");
+    }
+    if let Some(id) = acro_form_id {
+        if let Ok(form) = doc.get_dictionary_mut(id) {
+            form.remove(b"NeedAppearances");
+        }
+    }
+
+    for object in doc.objects.values_mut() {
+        let dict = match object {
+            Object::Dictionary(dict) => dict,
+            Object::Stream(stream) => &mut stream.dict,
+            _ => continue,
+        };
+        if dict.type_is(b"Annot") || dict.has(b"Subtype") && dict.has(b"Rect") {
+            let flags = dict.get(b"F").and_then(Object::as_i64).unwrap_or(0);
+            let hidden = ANNOT_INVISIBLE | ANNOT_HIDDEN | ANNOT_NO_VIEW | ANNOT_TOGGLE_NO_VIEW;
+            dict.set("F", (flags & !hidden) | ANNOT_PRINT);
+        }
+        if is_image(dict) && dict.has(b"Interpolate") {
+            dict.set("Interpolate", false);
+        }
+    }
+    Ok(())
+}
+
+/// Agrega el comentario binario tras `%PDF-1.7` a un PDF recién guardado por
+/// lopdf (que solo escribe la versión) y desplaza los offsets de la tabla xref
+/// y de `startxref` en consecuencia.
+pub fn write_header(pdf: &[u8]) -> Result<Vec<u8>> {
+    let invalid = || anyhow!("PDF sin la estructura esperada para la cabecera PDF/A");
+    if !pdf.starts_with(b"%PDF-") {
+        return Err(invalid());
+    }
+    let header_end = pdf.iter().position(|&b| b == b'\n').ok_or_else(invalid)? + 1;
+    let shift = BINARY_COMMENT.len();
+
+    let startxref = find_last(pdf, b"startxref").ok_or_else(invalid)?;
+    let xref_offset: usize = std::str::from_utf8(&pdf[startxref + b"startxref".len()..])
+        .ok()
+        .and_then(|tail| tail.split_whitespace().next())
+        .and_then(|number| number.parse().ok())
+        .ok_or_else(invalid)?;
+    if !pdf
+        .get(xref_offset..)
+        .is_some_and(|xref| xref.starts_with(b"xref"))
+    {
+        return Err(invalid());
+    }
+
+    let mut out = Vec::with_capacity(pdf.len() + shift + 1);
+    out.extend_from_slice(&pdf[..header_end]);
+    out.extend_from_slice(BINARY_COMMENT);
+    out.extend_from_slice(&pdf[header_end..xref_offset]);
+
+    // Secciones `inicio cantidad` seguidas de entradas de 20 bytes
+    // `oooooooooo ggggg n\r\n`; solo las `n` (en uso) apuntan a un offset
+    let mut pos = xref_offset;
+    let line = |pos: &mut usize| -> Result<&[u8]> {
+        let rest = &pdf[*pos..];
+        let len = rest.iter().position(|&b| b == b'\n').ok_or_else(invalid)? + 1;
+        *pos += len;
+        Ok(&rest[..len])
+    };
+    out.extend_from_slice(line(&mut pos)?); // "xref"
+    loop {
+        let section = line(&mut pos)?;
+        let fields: Vec<usize> = std::str::from_utf8(section)
+            .ok()
+            .map(|text| {
+                text.split_whitespace()
+                    .filter_map(|n| n.parse().ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let [_, count] = fields[..] else {
+            // Fin de la tabla ("trailer")
+            pos -= section.len();
+            break;
+        };
+        out.extend_from_slice(section);
+        for _ in 0..count {
+            let entry = pdf.get(pos..pos + 20).ok_or_else(invalid)?;
+            pos += 20;
+            if entry[17] == b'n' {
+                let offset: usize = std::str::from_utf8(&entry[..10])
+                    .ok()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or_else(invalid)?;
+                out.extend_from_slice(format!("{:010}", offset + shift).as_bytes());
+                out.extend_from_slice(&entry[10..]);
+            } else {
+                out.extend_from_slice(entry);
+            }
+        }
+    }
+    out.extend_from_slice(&pdf[pos..startxref]);
+    out.extend_from_slice(format!("startxref\n{}\n%%EOF", xref_offset + shift).as_bytes());
+    lopdf::Document::load_mem(&out).context("La cabecera PDF/A dejó un PDF ilegible")?;
+    Ok(out)
+}
+
+fn find_last(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .rposition(|window| window == needle)
+}
+
+/// Lista lo que impide que el documento sea PDF/A del nivel pedido.
+/// Una lista vacía significa que pasó todas las comprobaciones.
+pub fn validate(doc: &Document, level: PdfConformance) -> Vec<String> {
+    let mut issues = BTreeSet::new();
+
+    if doc.is_encrypted() {
+        issues.insert("El documento está cifrado".to_string());
+    }
+    match doc.catalog() {
+        Ok(catalog) => validate_catalog(doc, catalog, level, &mut issues),
+        Err(_) => {
+            issues.insert("El documento no tiene catálogo".to_string());
+        }
+    }
+
+    for object in doc.objects.values() {
+        let Some(dict) = as_dict(object) else {
+            continue;
+        };
+        if dict.type_is(b"Font") {
+            validate_font(doc, dict, &mut issues);
+        }
+        if let Ok(action) = dict.get(b"S").and_then(Object::as_name_str) {
+            if FORBIDDEN_ACTIONS.contains(&action) {
+                issues.insert(format!("Acción prohibida: {}", action));
+            }
+        }
+        if dict.has(b"AA") {
+            issues.insert("Acciones adicionales (/AA) no permitidas".to_string());
+        }
+        if dict.type_is(b"Annot") {
+            validate_annotation(dict, &mut issues);
+        }
+        if is_image(dict) && (dict.has(b"Alternates") || dict.has(b"OPI")) {
+            issues.insert("Imagen con /Alternates u /OPI".to_string());
+        }
+        if let Object::Stream(_) = object {
+            match dict.get(b"Subtype").and_then(Object::as_name_str) {
+                Ok("PS") => {
+                    issues.insert("XObject PostScript no permitido".to_string());
+                }
+                Ok("Form") if dict.has(b"PS") => {
+                    issues.insert("Form XObject con PostScript (/PS)".to_string());
+                }
+                _ => {}
+            }
+        }
+        if dict.type_is(b"ExtGState") {
+            if dict.has(b"TR") || dict.has(b"HTP") {
+                issues.insert("Estado gráfico con función de transferencia (/TR)".to_string());
+            }
+            if let Ok(tr2) = dict.get(b"TR2") {
+                if tr2.as_name_str().ok() != Some("Default") {
+                    issues.insert("Estado gráfico con /TR2 distinto de /Default".to_string());
+                }
+            }
+        }
+        // Con una intención de salida RGB no se puede usar CMYK directo
+        for key in [&b"ColorSpace"[..], b"CS"] {
+            if uses_device_cmyk(dict.get(key).ok()) {
+                issues
+                    .insert("Espacio de color DeviceCMYK sin intención de salida CMYK".to_string());
+            }
+        }
+        if let Ok(resources) = dict.get(b"ColorSpace").and_then(Object::as_dict) {
+            if resources.iter().any(|(_, cs)| uses_device_cmyk(Some(cs))) {
+                issues
+                    .insert("Espacio de color DeviceCMYK sin intención de salida CMYK".to_string());
+            }
+        }
+        if let Object::Stream(stream) = object {
+            if stream
+                .dict
+                .get(b"Subtype")
+                .and_then(Object::as_name_str)
+                .ok()
+                == Some("Form")
+            {
+                let content = if stream.dict.has(b"Filter") {
+                    stream.decompressed_content().ok()
+                } else {
+                    Some(stream.content.clone())
+                };
+                if let Some(content) = content {
+                    validate_content(&content, &mut issues);
+                }
+            }
+        }
+    }
+
+    for page_id in doc.get_pages().into_values() {
+        if let Ok(content) = doc.get_page_content(page_id) {
+            validate_content(&content, &mut issues);
+        }
+    }
+
+    issues.into_iter().collect()
+}
+
+fn validate_catalog(
+    doc: &Document,
+    catalog: &Dictionary,
+    level: PdfConformance,
+    issues: &mut BTreeSet<String>,
+) {
+    let resolve = |object: &Object| -> Option<Dictionary> {
+        match object {
+            Object::Reference(id) => doc.get_dictionary(*id).ok().cloned(),
+            Object::Dictionary(dict) => Some(dict.clone()),
+            _ => None,
+        }
+    };
+
+    match catalog.get(b"Metadata").and_then(Object::as_reference) {
+        Ok(id) => {
+            if let Ok(Object::Stream(stream)) = doc.get_object(id) {
+                if stream.dict.has(b"Filter") {
+                    issues.insert("El XMP del catálogo no puede estar comprimido".to_string());
+                }
+                let xmp = String::from_utf8_lossy(&stream.content);
+                let part = format!("<pdfaid:part>{}</pdfaid:part>", level.part());
+                if !xmp.contains(&part) {
+                    issues.insert("El XMP no declara la parte de PDF/A pedida".to_string());
+                }
+            }
+        }
+        Err(_) => {
+            issues.insert("Falta el XMP (/Metadata) del catálogo".to_string());
+        }
+    }
+    if catalog
+        .get(b"OutputIntents")
+        .and_then(Object::as_array)
+        .map_or(true, |intents| intents.is_empty())
+    {
+        issues.insert("Falta la intención de salida (/OutputIntents)".to_string());
+    }
+    if catalog.has(b"AA") {
+        issues.insert("Acciones adicionales (/AA) en el catálogo".to_string());
+    }
+    if let Some(form) = catalog.get(b"AcroForm").ok().and_then(resolve) {
+        if form.has(b"XFA") {
+            issues.insert("Formularios XFA no permitidos".to_string());
+        }
+        if form.get(b"NeedAppearances").and_then(Object::as_bool).ok() == Some(true) {
+            issues.insert("/NeedAppearances debe ser false".to_string());
+        }
+    }
+
+    let names = catalog.get(b"Names").ok().and_then(resolve);
+    if names.as_ref().is_some_and(|n| n.has(b"JavaScript")) {
+        issues.insert("JavaScript no permitido".to_string());
+    }
+    if names.as_ref().is_some_and(|n| n.has(b"EmbeddedFiles")) {
+        match level {
+            PdfConformance::PdfA2b => {
+                issues.insert("PDF/A-2b no admite adjuntos arbitrarios; usar pdfa_3b".to_string());
+            }
+            PdfConformance::PdfA3b => validate_embedded_files(doc, catalog, issues),
+        }
+    }
+}
+
+/// En PDF/A-3 cada adjunto declara su relación (`/AFRelationship`), su tipo MIME
+/// y fecha, y figura en `/AF` del catálogo.
+fn validate_embedded_files(doc: &Document, catalog: &Dictionary, issues: &mut BTreeSet<String>) {
+    let associated: Vec<Object> = match catalog.get(b"AF") {
+        Ok(Object::Array(items)) => items.clone(),
+        Ok(Object::Reference(id)) => doc
+            .get_object(*id)
+            .and_then(Object::as_array)
+            .cloned()
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    };
+
+    for (id, object) in &doc.objects {
+        let Some(spec) = as_dict(object) else {
+            continue;
+        };
+        if !spec.type_is(b"Filespec") || !spec.has(b"EF") {
+            continue;
+        }
+        let name = spec
+            .get(b"UF")
+            .or_else(|_| spec.get(b"F"))
+            .and_then(decode_text_string)
+            .unwrap_or_else(|_| "adjunto".to_string());
+        if !spec.has(b"AFRelationship") {
+            issues.insert(format!("El adjunto '{}' no tiene /AFRelationship", name));
+        }
+        if !associated.contains(&Object::Reference(*id)) {
+            issues.insert(format!(
+                "El adjunto '{}' no figura en /AF del catálogo",
+                name
+            ));
+        }
+        let file = spec
+            .get(b"EF")
+            .and_then(Object::as_dict)
+            .and_then(|ef| ef.get(b"F"))
+            .and_then(Object::as_reference)
+            .and_then(|file_id| doc.get_object(file_id));
+        if let Ok(Object::Stream(file)) = file {
+            if !file.dict.has(b"Subtype") {
+                issues.insert(format!("El adjunto '{}' no declara su tipo MIME", name));
+            }
+            let has_date = file
+                .dict
+                .get(b"Params")
+                .and_then(Object::as_dict)
+                .is_ok_and(|params| params.has(b"ModDate"));
+            if !has_date {
+                issues.insert(format!("El adjunto '{}' no tiene /Params /ModDate", name));
+            }
+        }
+    }
+}
+
+/// Toda fuente (salvo Type3) tiene que estar incrustada.
+fn validate_font(doc: &Document, font: &Dictionary, issues: &mut BTreeSet<String>) {
+    let subtype = font
+        .get(b"Subtype")
+        .and_then(Object::as_name_str)
+        .unwrap_or("");
+    if subtype == "Type3" {
+        return;
+    }
+    let descriptor_owner = if subtype == "Type0" {
+        font.get(b"DescendantFonts")
+            .and_then(|d| match d {
+                Object::Reference(id) => doc.get_object(*id),
+                other => Ok(other),
+            })
+            .and_then(Object::as_array)
+            .ok()
+            .and_then(|fonts| fonts.first())
+            .and_then(|f| match f {
+                Object::Reference(id) => doc.get_dictionary(*id).ok(),
+                Object::Dictionary(dict) => Some(dict),
+                _ => None,
+            })
+    } else {
+        Some(font)
+    };
+    let embedded = descriptor_owner
+        .and_then(|owner| owner.get(b"FontDescriptor").ok())
+        .and_then(|d| match d {
+            Object::Reference(id) => doc.get_dictionary(*id).ok(),
+            Object::Dictionary(dict) => Some(dict),
+            _ => None,
+        })
+        .is_some_and(|descriptor| {
+            descriptor.has(b"FontFile")
+                || descriptor.has(b"FontFile2")
+                || descriptor.has(b"FontFile3")
+        });
+    if !embedded {
+        let name = font
+            .get(b"BaseFont")
+            .and_then(Object::as_name_str)
+            .unwrap_or("sin nombre");
+        issues.insert(format!("Fuente no incrustada: {}", name));
+    }
+}
+
+fn validate_annotation(annot: &Dictionary, issues: &mut BTreeSet<String>) {
+    let subtype = annot
+        .get(b"Subtype")
+        .and_then(Object::as_name_str)
+        .unwrap_or("");
+    if FORBIDDEN_ANNOTATIONS.contains(&subtype) {
+        issues.insert(format!("Anotación prohibida: {}", subtype));
+        return;
+    }
+    let flags = annot.get(b"F").and_then(Object::as_i64).unwrap_or(0);
+    if flags & ANNOT_PRINT == 0 || flags & (ANNOT_INVISIBLE | ANNOT_HIDDEN | ANNOT_NO_VIEW) != 0 {
+        issues.insert(format!("Anotación {} no imprimible u oculta", subtype));
+    }
+    // Salvo Popup y Link, las anotaciones con área necesitan apariencia
+    let has_area = annot
+        .get(b"Rect")
+        .and_then(Object::as_array)
+        .map(|rect| {
+            let v: Vec<f32> = rect.iter().filter_map(|n| n.as_float().ok()).collect();
+            v.len() == 4 && (v[2] - v[0]).abs() > 0.0 && (v[3] - v[1]).abs() > 0.0
+        })
+        .unwrap_or(false);
+    if has_area && !matches!(subtype, "Popup" | "Link") && !annot.has(b"AP") {
+        issues.insert(format!("Anotación {} sin apariencia (/AP)", subtype));
+    }
+}
+
+/// Operadores de color CMYK directos en un flujo de contenido.
+fn validate_content(content: &[u8], issues: &mut BTreeSet<String>) {
+    let Ok(content) = Content::decode(content) else {
+        return;
+    };
+    for operation in content.operations {
+        let cmyk = match operation.operator.as_str() {
+            "k" | "K" => true,
+            "cs" | "CS" => uses_device_cmyk(operation.operands.first()),
+            _ => false,
+        };
+        if cmyk {
+            issues.insert("Espacio de color DeviceCMYK sin intención de salida CMYK".to_string());
+        }
+    }
+}
+
+fn uses_device_cmyk(color_space: Option<&Object>) -> bool {
+    match color_space {
+        Some(Object::Name(name)) => name == b"DeviceCMYK",
+        // [/Indexed base hival lookup]
+        Some(Object::Array(items)) => {
+            items.first().and_then(|o| o.as_name_str().ok()) == Some("Indexed")
+                && items.get(1).and_then(|o| o.as_name_str().ok()) == Some("DeviceCMYK")
+        }
+        _ => false,
+    }
+}
+
+fn is_image(dict: &Dictionary) -> bool {
+    dict.get(b"Subtype").and_then(Object::as_name_str).ok() == Some("Image")
+}
+
+fn as_dict(object: &Object) -> Option<&Dictionary> {
+    match object {
+        Object::Dictionary(dict) => Some(dict),
+        Object::Stream(stream) => Some(&stream.dict),
+        _ => None,
+    }
+}
+
+fn uncompressed(dict: Dictionary, content: Vec<u8>) -> Stream {
+    let mut stream = Stream::new(dict, content);
+    stream.allows_compression = false;
+    stream
+}
+
+// --------------------------------------------------------------------------------
+// XMP
+// --------------------------------------------------------------------------------
+
+/// Paquete XMP con la identificación PDF/A y los mismos valores que `Info`
+/// (PDF/A exige que coincidan).
+fn xmp_packet(
+    info: &Dictionary,
+    level: PdfConformance,
+    now: DateTime<Utc>,
+    extension: &str,
+) -> String {
+    let text = |key: &[u8]| {
+        info.get(key)
+            .and_then(decode_text_string)
+            .ok()
+            .map(|value| escape_html(&value))
+    };
+    let date = now.format("%Y-%m-%dT%H:%M:%S+00:00").to_string();
+
+    let mut fields = String::new();
+    if let Some(title) = text(b"Title") {
+        fields.push_str(&format!(
+            "<dc:title><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:title>\n",
+            title
+        ));
+    }
+    if let Some(author) = text(b"Author") {
+        fields.push_str(&format!(
+            "<dc:creator><rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq></dc:creator>\n",
+            author
+        ));
+    }
+    if let Some(subject) = text(b"Subject") {
+        fields.push_str(&format!(
+            "<dc:description><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:description>\n",
+            subject
+        ));
+    }
+    if let Some(keywords) = text(b"Keywords") {
+        fields.push_str(&format!("<pdf:Keywords>{}</pdf:Keywords>\n", keywords));
+    }
+    if let Some(producer) = text(b"Producer") {
+        fields.push_str(&format!("<pdf:Producer>{}</pdf:Producer>\n", producer));
+    }
+    if let Some(creator) = text(b"Creator") {
+        fields.push_str(&format!("<xmp:CreatorTool>{}</xmp:CreatorTool>\n", creator));
+    }
+
+    format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+         <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n\
+         <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n\
+         <rdf:Description rdf:about=\"\" \
+         xmlns:pdfaid=\"http://www.aiim.org/pdfa/ns/id/\" \
+         xmlns:dc=\"http://purl.org/dc/elements/1.1/\" \
+         xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\" \
+         xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\">\n\
+         <pdfaid:part>{part}</pdfaid:part>\n\
+         <pdfaid:conformance>B</pdfaid:conformance>\n\
+         <dc:format>application/pdf</dc:format>\n\
+         {fields}\
+         <xmp:CreateDate>{date}</xmp:CreateDate>\n\
+         <xmp:ModifyDate>{date}</xmp:ModifyDate>\n\
+         <xmp:MetadataDate>{date}</xmp:MetadataDate>\n\
+         </rdf:Description>\n\
+         {extension}\
+         </rdf:RDF>\n\
+         </x:xmpmeta>\n\
+         <?xpacket end=\"w\"?>",
+        part = level.part(),
+        fields = fields,
+        date = date,
+        extension = extension,
+    )
+}
+
+// --------------------------------------------------------------------------------
+// Perfil ICC sRGB (v2), generado en vez de depender de uno instalado
+// --------------------------------------------------------------------------------
+
+fn srgb_icc_profile() -> Vec<u8> {
+    // Primarios sRGB adaptados a D50 (los que usa el PCS)
+    let white = xyz_tag(0.9642, 1.0, 0.8249);
+    let red = xyz_tag(0.4361, 0.2225, 0.0139);
+    let green = xyz_tag(0.3851, 0.7169, 0.0971);
+    let blue = xyz_tag(0.1431, 0.0606, 0.7141);
+    let trc = srgb_curve_tag();
+    let description = description_tag(OUTPUT_CONDITION);
+    let copyright = text_tag("No copyright, use freely");
+
+    let tags: [(&[u8; 4], &Vec<u8>); 9] = [
+        (b"desc", &description),
+        (b"cprt", &copyright),
+        (b"wtpt", &white),
+        (b"rXYZ", &red),
+        (b"gXYZ", &green),
+        (b"bXYZ", &blue),
+        (b"rTRC", &trc),
+        (b"gTRC", &trc),
+        (b"bTRC", &trc),
+    ];
+
+    let table_len = 4 + tags.len() * 12;
+    let mut data = Vec::new();
+    let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+    for (signature, body) in tags {
+        let offset = 128 + table_len + data.len();
+        table.extend_from_slice(signature);
+        table.extend((offset as u32).to_be_bytes());
+        table.extend((body.len() as u32).to_be_bytes());
+        data.extend_from_slice(body);
+        while data.len() % 4 != 0 {
+            data.push(0);
+        }
+    }
+
+    let size = 128 + table.len() + data.len();
+    let mut header = Vec::with_capacity(128);
+    header.extend((size as u32).to_be_bytes());
+    header.extend([0u8; 4]); // CMM
+    header.extend([0x02, 0x10, 0x00, 0x00]); // versión 2.1
+    header.extend(b"mntrRGB XYZ ");
+    header.extend([0x07, 0xD0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0]); // 2000-01-01
+    header.extend(b"acsp");
+    header.extend([0u8; 24]); // plataforma, flags, fabricante, modelo, atributos
+    header.extend(0u32.to_be_bytes()); // intención perceptual
+    header.extend(s15_fixed16(0.9642));
+    header.extend(s15_fixed16(1.0));
+    header.extend(s15_fixed16(0.8249));
+    header.resize(128, 0);
+
+    [header, table, data].concat()
+}
+
+fn s15_fixed16(value: f64) -> [u8; 4] {
+    ((value * 65536.0).round() as i32).to_be_bytes()
+}
+
+fn xyz_tag(x: f64, y: f64, z: f64) -> Vec<u8> {
+    [
+        &b"XYZ "[..],
+        &[0; 4],
+        &s15_fixed16(x),
+        &s15_fixed16(y),
+        &s15_fixed16(z),
+    ]
+    .concat()
+}
+
+/// Curva de transferencia sRGB tabulada en 1024 puntos.
+fn srgb_curve_tag() -> Vec<u8> {
+    const POINTS: usize = 1024;
+    let mut tag = b"curv".to_vec();
+    tag.extend([0; 4]);
+    tag.extend((POINTS as u32).to_be_bytes());
+    for i in 0..POINTS {
+        let x = i as f64 / (POINTS - 1) as f64;
+        let y = if x <= 0.04045 {
+            x / 12.92
+        } else {
+            ((x + 0.055) / 1.055).powf(2.4)
+        };
+        tag.extend(((y * 65535.0).round() as u16).to_be_bytes());
+    }
+    tag
+}
+
+fn description_tag(text: &str) -> Vec<u8> {
+    let mut tag = b"desc".to_vec();
+    tag.extend([0; 4]);
+    tag.extend((text.len() as u32 + 1).to_be_bytes());
+    tag.extend(text.as_bytes());
+    tag.push(0);
+    // Sin descripción Unicode ni ScriptCode
+    tag.extend([0; 4 + 4 + 2 + 1 + 67]);
+    tag
+}
+
+fn text_tag(text: &str) -> Vec<u8> {
+    let mut tag = b"text".to_vec();
+    tag.extend([0; 4]);
+    tag.extend(text.as_bytes());
+    tag.push(0);
+    tag
+}
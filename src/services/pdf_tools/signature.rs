@@ -0,0 +1,1079 @@
+//! services/pdf_tools/signature.rs
+//! Firmas digitales PAdES (`ETSI.CAdES.detached`).
+//!
+//! Firmar es un proceso en dos pasos: primero se agrega al documento un campo
+//! de firma con espacio reservado (`/ByteRange` y `/Contents` de relleno) y se
+//! serializa; luego se firma el archivo completo menos ese hueco y el CMS se
+//! escribe dentro del hueco, sin mover ningún byte.
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use lopdf::{
+    decode_text_string, dictionary, text_string, Dictionary, Document, Object, ObjectId, Stream,
+    StringFormat,
+};
+use openssl::{
+    cms::{CMSOptions, CmsContentInfo},
+    hash::{hash, MessageDigest},
+    nid::Nid,
+    pkey::{PKey, Private},
+    rand::rand_bytes,
+    stack::Stack,
+    x509::{store::X509StoreRef, X509NameRef, X509StoreContext, X509},
+};
+
+use crate::{
+    models::{
+        pdf_model::{PdfSignatureOptions, PdfVisibleSignature},
+        signing_model::{SignatureVerification, TimestampInfo},
+    },
+    services::pdf_tools::{der, media_box, pdf_date, win_ansi_literal, MM_TO_PT},
+};
+
+/// Bytes reservados para el CMS (se escriben en hex, el doble en el archivo).
+/// Alcanza para la firma, la cadena de certificados y un sello de tiempo.
+const SIGNATURE_CONTENTS_BYTES: usize = 24 * 1024;
+/// Valor de relleno de `/ByteRange`; se reemplaza por los valores reales
+/// ajustando con espacios, así el archivo no cambia de tamaño.
+const BYTE_RANGE_PLACEHOLDER: i64 = 9_999_999_999;
+
+/// `CMS_CADES` de OpenSSL 3: agrega el atributo ESS `signing-certificate-v2`
+/// que exige PAdES. El crate de openssl aún no lo expone.
+const CMS_CADES: u32 = 0x10_0000;
+
+// OIDs (contenido DER, sin etiqueta ni longitud)
+const OID_SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+const OID_SHA384: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02];
+const OID_SHA512: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03];
+/// id-aa-timeStampToken (1.2.840.113549.1.9.16.2.14)
+const OID_TIMESTAMP_TOKEN: &[u8] = &[
+    0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x09, 0x10, 0x02, 0x0E,
+];
+
+/// Clave y certificados de un perfil de firma.
+pub struct Signer {
+    pub key: PKey<Private>,
+    pub cert: X509,
+    /// Certificados intermedios que se incluyen en la firma
+    pub chain: Vec<X509>,
+}
+
+/// Posición del hueco de la firma: `[0, a, b, c]` como en `/ByteRange`.
+pub type ByteRange = [usize; 4];
+
+/// Nombre legible de un certificado: su CN, o el sujeto completo.
+pub fn certificate_name(cert: &X509) -> String {
+    cert.subject_name()
+        .entries_by_nid(Nid::COMMONNAME)
+        .next()
+        .and_then(|e| e.data().as_utf8().ok())
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| format_name(cert.subject_name()))
+}
+
+/// `CN=..., O=..., C=...`
+pub fn format_name(name: &X509NameRef) -> String {
+    name.entries()
+        .map(|entry| {
+            let key = entry.object().nid().short_name().unwrap_or("?");
+            let value = entry
+                .data()
+                .as_utf8()
+                .map(|s| s.to_string())
+                .unwrap_or_default();
+            format!("{}={}", key, value)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// --------------------------------------------------------------------------------
+// Paso 1: campo de firma con espacio reservado
+// --------------------------------------------------------------------------------
+
+/// Agrega el diccionario de firma, su widget (visible o no) y el `AcroForm`.
+pub fn add_signature_field(
+    doc: &mut Document,
+    options: &PdfSignatureOptions,
+    signer_name: &str,
+    signed_at: DateTime<Utc>,
+) -> Result<()> {
+    let pages = doc.get_pages();
+    let page_number = match &options.visible {
+        Some(visible) => visible
+            .page
+            .unwrap_or_else(|| pages.keys().last().copied().unwrap_or(1)),
+        None => 1,
+    };
+    let page_id = *pages.get(&page_number).ok_or_else(|| {
+        anyhow!(
+            "La firma visible apunta a la página {}, pero el documento tiene {}",
+            page_number,
+            pages.len()
+        )
+    })?;
+
+    let mut signature = dictionary! {
+        "Type" => "Sig",
+        "Filter" => "Adobe.PPKLite",
+        "SubFilter" => "ETSI.CAdES.detached",
+        "ByteRange" => vec![
+            0.into(),
+            BYTE_RANGE_PLACEHOLDER.into(),
+            BYTE_RANGE_PLACEHOLDER.into(),
+            BYTE_RANGE_PLACEHOLDER.into(),
+        ],
+        "Contents" => Object::String(vec![0; SIGNATURE_CONTENTS_BYTES], StringFormat::Hexadecimal),
+        "M" => Object::string_literal(pdf_date(signed_at)),
+        "Name" => text_string(signer_name),
+    };
+    for (key, value) in [
+        ("Reason", &options.reason),
+        ("Location", &options.location),
+        ("ContactInfo", &options.contact_info),
+    ] {
+        if let Some(value) = value {
+            signature.set(key, text_string(value));
+        }
+    }
+    let signature_id = doc.add_object(signature);
+
+    let field_name = next_field_name(doc);
+    let mut widget = dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Widget",
+        "FT" => "Sig",
+        "T" => text_string(&field_name),
+        "V" => signature_id,
+        "P" => page_id,
+        // Imprimible y bloqueada
+        "F" => 132,
+        "Rect" => vec![0.into(), 0.into(), 0.into(), 0.into()],
+    };
+    if let Some(visible) = &options.visible {
+        let [x0, _, _, y1] = media_box(doc, page_id);
+        let width = visible.width * MM_TO_PT;
+        let height = visible.height * MM_TO_PT;
+        let left = x0 + visible.x * MM_TO_PT;
+        let top = y1 - visible.y * MM_TO_PT;
+        widget.set(
+            "Rect",
+            vec![
+                Object::Real(left as f32),
+                Object::Real((top - height) as f32),
+                Object::Real((left + width) as f32),
+                Object::Real(top as f32),
+            ],
+        );
+        let lines = appearance_lines(visible, options, signer_name, signed_at);
+        let appearance_id = doc.add_object(appearance_stream(width, height, &lines));
+        widget.set("AP", dictionary! { "N" => appearance_id });
+    }
+    let widget_id = doc.add_object(widget);
+
+    add_page_annotation(doc, page_id, widget_id)?;
+    add_form_field(doc, widget_id)
+}
+
+/// Reemplaza el relleno de `/ByteRange` por los valores reales y retorna el rango.
+/// Se llama sobre el archivo ya serializado.
+pub fn finalize_byte_range(pdf: &mut [u8]) -> Result<ByteRange> {
+    let placeholder = format!(
+        "{} {} {}",
+        BYTE_RANGE_PLACEHOLDER, BYTE_RANGE_PLACEHOLDER, BYTE_RANGE_PLACEHOLDER
+    );
+    let range_pos = find(pdf, placeholder.as_bytes(), 0)
+        .ok_or_else(|| anyhow!("No se encontró el /ByteRange de la firma"))?;
+
+    // `/Contents <000...>` se escribe después de `/ByteRange` en el mismo diccionario
+    let hex_len = SIGNATURE_CONTENTS_BYTES * 2;
+    let mut search_from = range_pos;
+    let start = loop {
+        let start = find(pdf, b"<0000", search_from)
+            .ok_or_else(|| anyhow!("No se encontró el /Contents de la firma"))?;
+        let end = start + 1 + hex_len;
+        if end < pdf.len() && pdf[end] == b'>' && pdf[start + 1..end].iter().all(|b| *b == b'0') {
+            break start;
+        }
+        search_from = start + 1;
+    };
+    let end = start + hex_len + 2;
+    let range = [0, start, end, pdf.len() - end];
+
+    let actual = format!("{} {} {}", range[1], range[2], range[3]);
+    let padded = format!("{:<width$}", actual, width = placeholder.len());
+    pdf[range_pos..range_pos + placeholder.len()].copy_from_slice(padded.as_bytes());
+    Ok(range)
+}
+
+/// Bytes cubiertos por la firma (todo el archivo menos el hueco de `/Contents`).
+pub fn signed_bytes(pdf: &[u8], range: &ByteRange) -> Vec<u8> {
+    let mut data = Vec::with_capacity(range[1] + range[3]);
+    data.extend_from_slice(&pdf[range[0]..range[0] + range[1]]);
+    data.extend_from_slice(&pdf[range[2]..range[2] + range[3]]);
+    data
+}
+
+/// Escribe el CMS (en hex) dentro del hueco reservado.
+pub fn embed_signature(pdf: &mut [u8], range: &ByteRange, cms: &[u8]) -> Result<()> {
+    let slot = &mut pdf[range[1] + 1..range[2] - 1];
+    let hex: String = cms.iter().map(|b| format!("{:02X}", b)).collect();
+    if hex.len() > slot.len() {
+        return Err(anyhow!(
+            "La firma ocupa {} bytes y el espacio reservado es de {}",
+            cms.len(),
+            SIGNATURE_CONTENTS_BYTES
+        ));
+    }
+    slot[..hex.len()].copy_from_slice(hex.as_bytes());
+    Ok(())
+}
+
+// --------------------------------------------------------------------------------
+// Paso 2: CMS y sello de tiempo
+// --------------------------------------------------------------------------------
+
+/// CMS `SignedData` desacoplado (CAdES) sobre `data`.
+pub fn sign_cms(data: &[u8], signer: &Signer) -> Result<Vec<u8>> {
+    let mut certs = Stack::new()?;
+    for cert in &signer.chain {
+        certs.push(cert.clone())?;
+    }
+    let flags = CMSOptions::DETACHED
+        | CMSOptions::BINARY
+        | CMSOptions::NOSMIMECAP
+        | CMSOptions::from_bits_retain(CMS_CADES);
+    let cms = CmsContentInfo::sign(
+        Some(&signer.cert),
+        Some(&signer.key),
+        Some(&certs),
+        Some(data),
+        flags,
+    )
+    .context("OpenSSL no pudo firmar el documento")?;
+    Ok(cms.to_der()?)
+}
+
+/// `TimeStampReq` (RFC 3161) sobre el valor de la firma, con SHA-256.
+pub fn timestamp_request(cms: &[u8]) -> Result<Vec<u8>> {
+    let signature = signature_value(cms)?;
+    let imprint = hash(MessageDigest::sha256(), &signature)?;
+    let mut nonce = [0u8; 8];
+    rand_bytes(&mut nonce)?;
+
+    let algorithm = der::encode_constructed(
+        der::TAG_SEQUENCE,
+        &[
+            &der::encode(der::TAG_OID, OID_SHA256),
+            &der::encode(der::TAG_NULL, &[]),
+        ],
+    );
+    let message_imprint = der::encode_constructed(
+        der::TAG_SEQUENCE,
+        &[&algorithm, &der::encode(der::TAG_OCTET_STRING, &imprint)],
+    );
+    Ok(der::encode_constructed(
+        der::TAG_SEQUENCE,
+        &[
+            &der::encode(der::TAG_INTEGER, &[1]),
+            &message_imprint,
+            &der::encode_unsigned_integer(&nonce),
+            // certReq: que la TSA incluya su certificado
+            &der::encode(der::TAG_BOOLEAN, &[0xFF]),
+        ],
+    ))
+}
+
+/// Extrae el token de un `TimeStampResp` y lo agrega a la firma como atributo
+/// no firmado. Falla si la TSA rechazó el pedido o selló otro hash.
+pub fn add_timestamp(cms: &[u8], response: &[u8]) -> Result<Vec<u8>> {
+    let (response, _) = der::parse(response).context("Respuesta de la TSA inválida")?;
+    let status = response
+        .child(0, der::TAG_SEQUENCE)?
+        .child(0, der::TAG_INTEGER)?;
+    // 0 = granted, 1 = grantedWithMods
+    if !matches!(status.content, [0] | [1]) {
+        return Err(anyhow!(
+            "La TSA rechazó el pedido (estado {:?})",
+            status.content
+        ));
+    }
+    let token = response.child(1, der::TAG_SEQUENCE)?;
+    let info = timestamp_info(token.raw, &signature_value(cms)?)?;
+    if !info.valid {
+        return Err(anyhow!("El sello de tiempo no corresponde a esta firma"));
+    }
+
+    let attribute = der::encode_constructed(
+        der::TAG_SEQUENCE,
+        &[
+            &der::encode(der::TAG_OID, OID_TIMESTAMP_TOKEN),
+            &der::encode(der::TAG_SET, token.raw),
+        ],
+    );
+    replace_signer_info(cms, |children| {
+        let mut items: Vec<Vec<u8>> = Vec::new();
+        let mut added = false;
+        for child in children {
+            if child.tag == der::TAG_CONTEXT_1 {
+                items.push(der::encode_constructed(
+                    der::TAG_CONTEXT_1,
+                    &[child.content, &attribute],
+                ));
+                added = true;
+            } else {
+                items.push(child.raw.to_vec());
+            }
+        }
+        if !added {
+            items.push(der::encode(der::TAG_CONTEXT_1, &attribute));
+        }
+        items.concat()
+    })
+}
+
+/// `ContentInfo` -> `SignedData`
+fn signed_data(cms: &[u8]) -> Result<der::Tlv<'_>> {
+    let (content_info, _) = der::parse(cms)?;
+    content_info
+        .child(1, der::TAG_CONTEXT_0)?
+        .child(0, der::TAG_SEQUENCE)
+}
+
+/// Primer `SignerInfo` (las firmas que generamos tienen uno solo).
+fn first_signer_info<'a>(signed_data: &der::Tlv<'a>) -> Result<der::Tlv<'a>> {
+    let signer_infos = *signed_data
+        .children()?
+        .last()
+        .filter(|t| t.tag == der::TAG_SET)
+        .ok_or_else(|| anyhow!("CMS sin signerInfos"))?;
+    signer_infos.child(0, der::TAG_SEQUENCE)
+}
+
+/// Valor de la firma (`signature OCTET STRING`) del primer `SignerInfo`.
+fn signature_value(cms: &[u8]) -> Result<Vec<u8>> {
+    let signed_data = signed_data(cms)?;
+    first_signer_info(&signed_data)?
+        .children()?
+        .into_iter()
+        .find(|t| t.tag == der::TAG_OCTET_STRING)
+        .map(|t| t.content.to_vec())
+        .ok_or_else(|| anyhow!("CMS sin valor de firma"))
+}
+
+/// Reconstruye el CMS cambiando el contenido del primer `SignerInfo`.
+fn replace_signer_info(
+    cms: &[u8],
+    rebuild: impl FnOnce(Vec<der::Tlv<'_>>) -> Vec<u8>,
+) -> Result<Vec<u8>> {
+    let (content_info, _) = der::parse(cms)?;
+    let content_type = content_info.child(0, der::TAG_OID)?;
+    let signed_data = signed_data(cms)?;
+    let mut parts = signed_data.children()?;
+    let signer_infos = parts.pop().ok_or_else(|| anyhow!("CMS sin signerInfos"))?;
+    let mut signers = signer_infos.children()?;
+    if signers.is_empty() {
+        return Err(anyhow!("CMS sin signerInfos"));
+    }
+    let first = signers.remove(0);
+
+    let new_signer = der::encode(der::TAG_SEQUENCE, &rebuild(first.children()?));
+    let mut signer_items: Vec<&[u8]> = vec![&new_signer];
+    signer_items.extend(signers.iter().map(|s| s.raw));
+    let new_signer_infos = der::encode_constructed(der::TAG_SET, &signer_items);
+
+    let mut signed_items: Vec<&[u8]> = parts.iter().map(|p| p.raw).collect();
+    signed_items.push(&new_signer_infos);
+    let new_signed_data = der::encode_constructed(der::TAG_SEQUENCE, &signed_items);
+
+    Ok(der::encode_constructed(
+        der::TAG_SEQUENCE,
+        &[
+            content_type.raw,
+            &der::encode(der::TAG_CONTEXT_0, &new_signed_data),
+        ],
+    ))
+}
+
+/// Lee el `TSTInfo` de un token y comprueba que selle `signature`.
+fn timestamp_info(token: &[u8], signature: &[u8]) -> Result<TimestampInfo> {
+    let signed_data = signed_data(token)?;
+    let encap = signed_data.child(2, der::TAG_SEQUENCE)?;
+    let octets = encap
+        .child(1, der::TAG_CONTEXT_0)?
+        .child(0, der::TAG_OCTET_STRING)?;
+    let (tst_info, _) = der::parse(octets.content)?;
+    let imprint = tst_info.child(2, der::TAG_SEQUENCE)?;
+    let algorithm = imprint
+        .child(0, der::TAG_SEQUENCE)?
+        .child(0, der::TAG_OID)?;
+    let hashed = imprint.child(1, der::TAG_OCTET_STRING)?;
+    let time = tst_info
+        .children()?
+        .iter()
+        .find(|t| t.tag == der::TAG_GENERALIZED_TIME)
+        .and_then(der::time_to_rfc3339);
+
+    let digest = match algorithm.content {
+        OID_SHA256 => MessageDigest::sha256(),
+        OID_SHA384 => MessageDigest::sha384(),
+        OID_SHA512 => MessageDigest::sha512(),
+        _ => {
+            return Err(anyhow!(
+                "Algoritmo de hash del sello de tiempo no soportado"
+            ))
+        }
+    };
+    let imprint_matches = hash(digest, signature)?.as_ref() == hashed.content;
+    let token_valid = CmsContentInfo::from_der(token)
+        .and_then(|mut cms| cms.verify(None, None, None, None, CMSOptions::NOVERIFY))
+        .is_ok();
+
+    Ok(TimestampInfo {
+        time,
+        valid: imprint_matches && token_valid,
+    })
+}
+
+// --------------------------------------------------------------------------------
+// Verificación
+// --------------------------------------------------------------------------------
+
+/// Verifica todas las firmas del documento. `trust` decide si el certificado
+/// del firmante es de confianza; la integridad se comprueba siempre.
+pub fn verify_signatures(pdf: &[u8], trust: &X509StoreRef) -> Result<Vec<SignatureVerification>> {
+    let doc = Document::load_mem(pdf).context("El archivo no es un PDF válido")?;
+    // En un PDF cifrado los textos (`/T`, `/Reason`, ...) no se pueden leer sin la
+    // contraseña; la firma sí se verifica porque `/Contents` no se cifra.
+    let encrypted = doc.is_encrypted();
+
+    // Nombre de cada campo de firma, por id del diccionario de firma
+    let mut field_names = std::collections::HashMap::new();
+    for object in doc.objects.values().filter(|_| !encrypted) {
+        if let Ok(dict) = object.as_dict() {
+            if let (Ok(value), Ok(name)) = (
+                dict.get(b"V").and_then(Object::as_reference),
+                dict.get(b"T").and_then(decode_text_string),
+            ) {
+                field_names.insert(value, name);
+            }
+        }
+    }
+
+    let mut results = Vec::new();
+    for (id, object) in &doc.objects {
+        let Ok(dict) = object.as_dict() else {
+            continue;
+        };
+        if !dict.has(b"ByteRange") || !(dict.type_is(b"Sig") || dict.has(b"SubFilter")) {
+            continue;
+        }
+        let mut result = if encrypted {
+            SignatureVerification {
+                sub_filter: sub_filter(dict),
+                ..Default::default()
+            }
+        } else {
+            describe_signature(dict, field_names.remove(id))
+        };
+        if let Err(e) = verify_signature(pdf, dict, trust, &mut result) {
+            result.error = Some(e.to_string());
+        }
+        results.push(result);
+    }
+    Ok(results)
+}
+
+fn describe_signature(dict: &Dictionary, field_name: Option<String>) -> SignatureVerification {
+    let text = |key: &[u8]| dict.get(key).and_then(decode_text_string).ok();
+    SignatureVerification {
+        field_name,
+        sub_filter: sub_filter(dict),
+        signer: text(b"Name"),
+        signing_time: text(b"M").map(|m| parse_pdf_date(&m).unwrap_or(m)),
+        reason: text(b"Reason"),
+        location: text(b"Location"),
+        ..Default::default()
+    }
+}
+
+fn sub_filter(dict: &Dictionary) -> Option<String> {
+    dict.get(b"SubFilter")
+        .and_then(Object::as_name_str)
+        .ok()
+        .map(str::to_string)
+}
+
+fn verify_signature(
+    pdf: &[u8],
+    dict: &Dictionary,
+    trust: &X509StoreRef,
+    result: &mut SignatureVerification,
+) -> Result<()> {
+    let values: Vec<i64> = dict
+        .get(b"ByteRange")
+        .and_then(Object::as_array)?
+        .iter()
+        .filter_map(|v| v.as_i64().ok())
+        .collect();
+    let range: ByteRange = match values[..] {
+        [a, b, c, d] if a >= 0 && b >= 0 && c >= 0 && d >= 0 => {
+            [a as usize, b as usize, c as usize, d as usize]
+        }
+        _ => return Err(anyhow!("/ByteRange inválido")),
+    };
+    if range[0] != 0
+        || range[1] + 1 >= range[2]
+        || range[2]
+            .checked_add(range[3])
+            .is_none_or(|end| end > pdf.len())
+    {
+        return Err(anyhow!("/ByteRange fuera del archivo"));
+    }
+    let end = range[2] + range[3];
+    result.covers_whole_document = pdf[end..].iter().all(|b| b.is_ascii_whitespace());
+
+    // El CMS se lee del archivo (no del objeto), así da igual si el PDF está cifrado
+    let hex: Vec<u8> = pdf[range[1]..range[2]]
+        .iter()
+        .copied()
+        .filter(|b| b.is_ascii_hexdigit())
+        .collect();
+    let bytes = hex_decode(&hex)?;
+    let (cms_tlv, _) = der::parse(&bytes).context("/Contents no contiene un CMS válido")?;
+    let cms = cms_tlv.raw;
+
+    let signed_data_tlv = signed_data(cms)?;
+    let certificates = cms_certificates(&signed_data_tlv);
+    let signer_info = first_signer_info(&signed_data_tlv)?;
+    let signer_cert = find_signer_certificate(&signer_info, &certificates);
+    if let Some(cert) = &signer_cert {
+        result.signer = Some(certificate_name(cert));
+        result.issuer = Some(format_name(cert.issuer_name()));
+        result.serial_number = serial_hex(cert);
+    }
+
+    let data = signed_bytes(pdf, &range);
+    let mut content = CmsContentInfo::from_der(cms)?;
+    // BINARY: sin él openssl normaliza los saltos de línea del contenido
+    let flags = CMSOptions::NOVERIFY | CMSOptions::BINARY;
+    match content.verify(None, None, Some(&data), None, flags) {
+        Ok(()) => result.integrity_valid = true,
+        Err(_) => result.error = Some("La firma no corresponde al contenido".to_string()),
+    }
+
+    if let Some(cert) = &signer_cert {
+        let mut chain = Stack::new()?;
+        for other in &certificates {
+            chain.push(other.clone())?;
+        }
+        let mut context = X509StoreContext::new()?;
+        result.certificate_trusted = context.init(trust, cert, &chain, |ctx| ctx.verify_cert())?;
+    }
+
+    result.timestamp = timestamp_attribute(&signer_info)
+        .map(|token| {
+            let signature = signature_value(cms)?;
+            timestamp_info(token, &signature)
+        })
+        .transpose()
+        .unwrap_or(Some(TimestampInfo {
+            time: None,
+            valid: false,
+        }));
+    Ok(())
+}
+
+/// Número de serie en hex, como se guarda en los perfiles de firma
+pub fn serial_hex(cert: &X509) -> Option<String> {
+    let serial = cert.serial_number().to_bn().ok()?;
+    Some(serial.to_hex_str().ok()?.to_string())
+}
+
+/// Certificados incluidos en el `SignedData` (`certificates [0]`).
+fn cms_certificates(signed_data: &der::Tlv<'_>) -> Vec<X509> {
+    signed_data
+        .children()
+        .unwrap_or_default()
+        .into_iter()
+        .find(|t| t.tag == der::TAG_CONTEXT_0)
+        .and_then(|set| set.children().ok())
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|cert| X509::from_der(cert.raw).ok())
+        .collect()
+}
+
+/// Certificado del firmante según el número de serie del `sid`; si no se
+/// puede identificar, el primero.
+fn find_signer_certificate(signer_info: &der::Tlv<'_>, certificates: &[X509]) -> Option<X509> {
+    let serial = signer_info
+        .child(1, der::TAG_SEQUENCE)
+        .and_then(|sid| sid.child(1, der::TAG_INTEGER))
+        .ok()
+        .map(|serial| {
+            serial
+                .content
+                .iter()
+                .copied()
+                .skip_while(|b| *b == 0)
+                .collect::<Vec<u8>>()
+        });
+    serial
+        .and_then(|serial| {
+            certificates.iter().find(|cert| {
+                cert.serial_number()
+                    .to_bn()
+                    .map(|bn| bn.to_vec() == serial)
+                    .unwrap_or(false)
+            })
+        })
+        .or_else(|| certificates.first())
+        .cloned()
+}
+
+/// Token del atributo no firmado `id-aa-timeStampToken`, si existe.
+fn timestamp_attribute<'a>(signer_info: &der::Tlv<'a>) -> Option<&'a [u8]> {
+    let unsigned = signer_info
+        .children()
+        .ok()?
+        .into_iter()
+        .find(|t| t.tag == der::TAG_CONTEXT_1)?;
+    unsigned.children().ok()?.into_iter().find_map(|attribute| {
+        let oid = attribute.child(0, der::TAG_OID).ok()?;
+        if oid.content != OID_TIMESTAMP_TOKEN {
+            return None;
+        }
+        let values = attribute.child(1, der::TAG_SET).ok()?;
+        Some(values.child(0, der::TAG_SEQUENCE).ok()?.raw)
+    })
+}
+
+// --------------------------------------------------------------------------------
+// Utilidades
+// --------------------------------------------------------------------------------
+
+/// Nombre libre para el campo: Signature1, Signature2, ...
+fn next_field_name(doc: &Document) -> String {
+    let existing: std::collections::HashSet<String> = doc
+        .objects
+        .values()
+        .filter_map(|o| o.as_dict().ok())
+        .filter_map(|d| d.get(b"T").and_then(decode_text_string).ok())
+        .collect();
+    (1..)
+        .map(|n| format!("Signature{}", n))
+        .find(|name| !existing.contains(name))
+        .unwrap_or_default()
+}
+
+fn add_page_annotation(doc: &mut Document, page_id: ObjectId, widget_id: ObjectId) -> Result<()> {
+    let annots = doc.get_dictionary(page_id)?.get(b"Annots").ok().cloned();
+    match annots {
+        Some(Object::Reference(id)) => {
+            doc.get_object_mut(id)?
+                .as_array_mut()?
+                .push(widget_id.into());
+        }
+        Some(Object::Array(mut items)) => {
+            items.push(widget_id.into());
+            doc.get_dictionary_mut(page_id)?.set("Annots", items);
+        }
+        _ => {
+            doc.get_dictionary_mut(page_id)?
+                .set("Annots", vec![Object::Reference(widget_id)]);
+        }
+    }
+    Ok(())
+}
+
+/// Agrega el campo al `AcroForm` del catálogo (lo crea si no existe).
+/// `SigFlags 3`: el documento tiene firmas y solo admite cambios incrementales.
+fn add_form_field(doc: &mut Document, widget_id: ObjectId) -> Result<()> {
+    let acro_form = doc.catalog()?.get(b"AcroForm").ok().cloned();
+    let form_id = match acro_form {
+        Some(Object::Reference(id)) => id,
+        Some(Object::Dictionary(dict)) => doc.add_object(dict),
+        _ => doc.add_object(dictionary! { "Fields" => Vec::<Object>::new() }),
+    };
+    doc.catalog_mut()?.set("AcroForm", form_id);
+
+    let form = doc.get_dictionary_mut(form_id)?;
+    form.set("SigFlags", 3);
+    match form.get_mut(b"Fields") {
+        Ok(Object::Array(fields)) => fields.push(widget_id.into()),
+        _ => form.set("Fields", vec![Object::Reference(widget_id)]),
+    }
+    Ok(())
+}
+
+fn appearance_lines(
+    visible: &PdfVisibleSignature,
+    options: &PdfSignatureOptions,
+    signer_name: &str,
+    signed_at: DateTime<Utc>,
+) -> Vec<String> {
+    if let Some(text) = &visible.text {
+        return text.lines().map(str::to_string).collect();
+    }
+    let mut lines = vec![
+        format!("Firmado digitalmente por {}", signer_name),
+        format!("Fecha: {}", signed_at.format("%Y-%m-%d %H:%M:%S UTC")),
+    ];
+    if let Some(reason) = &options.reason {
+        lines.push(format!("Motivo: {}", reason));
+    }
+    if let Some(location) = &options.location {
+        lines.push(format!("Lugar: {}", location));
+    }
+    lines
+}
+
+/// Form XObject del sello: recuadro y líneas de texto en Helvetica.
+fn appearance_stream(width: f64, height: f64, lines: &[String]) -> Stream {
+    let padding = 4.0;
+    let font_size =
+        ((height - 2.0 * padding) / (lines.len().max(1) as f64 * 1.25)).clamp(4.0, 10.0);
+    let leading = font_size * 1.25;
+
+    let mut content = format!(
+        "q 0 0 {w:.2} {h:.2} re W n \
+         0.96 0.97 1 rg 0 0 {w:.2} {h:.2} re f \
+         0.2 0.3 0.6 RG 1 w 0.5 0.5 {iw:.2} {ih:.2} re S \
+         BT /Helv {fs:.2} Tf 0 0 0 rg {x:.2} {y:.2} Td {lead:.2} TL\n",
+        w = width,
+        h = height,
+        iw = width - 1.0,
+        ih = height - 1.0,
+        fs = font_size,
+        x = padding,
+        y = height - padding - font_size,
+        lead = leading,
+    )
+    .into_bytes();
+    for (index, line) in lines.iter().enumerate() {
+        if index > 0 {
+            content.extend_from_slice(b"T* ");
+        }
+        content.push(b'(');
+        content.extend(win_ansi_literal(line));
+        content.extend_from_slice(b") Tj\n");
+    }
+    content.extend_from_slice(b"ET Q");
+
+    Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "BBox" => vec![0.into(), 0.into(), Object::Real(width as f32), Object::Real(height as f32)],
+            "Resources" => dictionary! {
+                "Font" => dictionary! {
+                    "Helv" => dictionary! {
+                        "Type" => "Font",
+                        "Subtype" => "Type1",
+                        "BaseFont" => "Helvetica",
+                        "Encoding" => "WinAnsiEncoding",
+                    },
+                },
+            },
+        },
+        content,
+    )
+}
+
+/// `D:YYYYMMDDHHmmSS` con zona opcional -> RFC 3339.
+fn parse_pdf_date(value: &str) -> Option<String> {
+    let value = value.strip_prefix("D:").unwrap_or(value);
+    let digits = value.get(..14)?;
+    let local = chrono::NaiveDateTime::parse_from_str(digits, "%Y%m%d%H%M%S").ok()?;
+    let zone = &value[14..];
+    let offset_seconds = match zone.chars().next() {
+        Some(sign @ ('+' | '-')) => {
+            let numbers: Vec<i32> = zone[1..]
+                .split('\'')
+                .filter(|s| !s.is_empty())
+                .filter_map(|s| s.parse().ok())
+                .collect();
+            let seconds = numbers.first().copied().unwrap_or(0) * 3600
+                + numbers.get(1).copied().unwrap_or(0) * 60;
+            if sign == '-' {
+                -seconds
+            } else {
+                seconds
+            }
+        }
+        _ => 0,
+    };
+    let offset = chrono::FixedOffset::east_opt(offset_seconds)?;
+    Some(local.and_local_timezone(offset).single()?.to_rfc3339())
+}
+
+fn hex_decode(hex: &[u8]) -> Result<Vec<u8>> {
+    let digit = |b: u8| (b as char).to_digit(16).map(|d| d as u8);
+    hex.chunks(2)
+        .map(|pair| match pair {
+            [high, low] => Some(digit(*high)? << 4 | digit(*low)?),
+            [high] => Some(digit(*high)? << 4),
+            _ => None,
+        })
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(|| anyhow!("/Contents no es hexadecimal"))
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|pos| pos + from)
+}
+
+#[cfg(test)]
+mod tests {
+    use lopdf::dictionary;
+    use openssl::{
+        asn1::Asn1Time,
+        bn::BigNum,
+        ec::{EcGroup, EcKey},
+        hash::MessageDigest,
+        x509::{extension::BasicConstraints, store::X509StoreBuilder, X509Builder, X509Name},
+    };
+
+    use super::*;
+    use crate::services::pdf_tools::save_pdf;
+
+    /// Certificado EC P-256 para `cn`, emitido por `issuer` (o autofirmado)
+    fn issue(
+        cn: &str,
+        serial: u32,
+        issuer: Option<(&X509, &PKey<Private>)>,
+    ) -> (X509, PKey<Private>) {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let mut name = X509Name::builder().unwrap();
+        name.append_entry_by_nid(Nid::COMMONNAME, cn).unwrap();
+        let name = name.build();
+
+        let mut builder = X509Builder::new().unwrap();
+        builder.set_version(2).unwrap();
+        let serial = BigNum::from_u32(serial).unwrap().to_asn1_integer().unwrap();
+        builder.set_serial_number(&serial).unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder
+            .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::days_from_now(30).unwrap())
+            .unwrap();
+        match issuer {
+            Some((issuer_cert, issuer_key)) => {
+                builder.set_issuer_name(issuer_cert.subject_name()).unwrap();
+                builder.sign(issuer_key, MessageDigest::sha256()).unwrap();
+            }
+            None => {
+                let ca = BasicConstraints::new().critical().ca().build().unwrap();
+                builder.append_extension(ca).unwrap();
+                builder.set_issuer_name(&name).unwrap();
+                builder.sign(&key, MessageDigest::sha256()).unwrap();
+            }
+        }
+        (builder.build(), key)
+    }
+
+    fn ca_and_signer() -> (X509, Signer) {
+        let (ca, ca_key) = issue("Test CA", 1, None);
+        let (cert, key) = issue("Test Signer", 2, Some((&ca, &ca_key)));
+        let signer = Signer {
+            key,
+            cert,
+            chain: Vec::new(),
+        };
+        (ca, signer)
+    }
+
+    fn one_page_pdf() -> Document {
+        let mut doc = Document::with_version("1.7");
+        let pages_id = doc.new_object_id();
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        });
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+        doc
+    }
+
+    fn options() -> PdfSignatureOptions {
+        PdfSignatureOptions {
+            profile_id: "test".to_string(),
+            reason: Some("Aprobado".to_string()),
+            location: None,
+            contact_info: None,
+            visible: None,
+            timestamp: false,
+        }
+    }
+
+    /// PDF con el hueco reservado, listo para `sign_cms`
+    fn prepared_pdf() -> (Vec<u8>, ByteRange) {
+        let mut doc = one_page_pdf();
+        add_signature_field(&mut doc, &options(), "Test Signer", Utc::now()).unwrap();
+        let mut pdf = save_pdf(&mut doc).unwrap();
+        let range = finalize_byte_range(&mut pdf).unwrap();
+        (pdf, range)
+    }
+
+    fn trust_store(certs: &[&X509]) -> openssl::x509::store::X509Store {
+        let mut store = X509StoreBuilder::new().unwrap();
+        for cert in certs {
+            store.add_cert((*cert).clone()).unwrap();
+        }
+        store.build()
+    }
+
+    /// `TimeStampResp` como el de una TSA: `TSTInfo` firmado por `tsa` sobre
+    /// `imprint` (el `MessageImprint` completo, en DER)
+    fn tsa_response(imprint: &[u8], tsa: &(X509, PKey<Private>)) -> Vec<u8> {
+        let tst_info = der::encode_constructed(
+            der::TAG_SEQUENCE,
+            &[
+                &der::encode(der::TAG_INTEGER, &[1]),
+                // Política 1.2.3.4
+                &der::encode(der::TAG_OID, &[0x2A, 0x03, 0x04]),
+                imprint,
+                &der::encode(der::TAG_INTEGER, &[7]),
+                &der::encode(der::TAG_GENERALIZED_TIME, b"20260102030405Z"),
+            ],
+        );
+        let token = CmsContentInfo::sign(
+            Some(&tsa.0),
+            Some(&tsa.1),
+            None,
+            Some(&tst_info),
+            CMSOptions::BINARY,
+        )
+        .unwrap()
+        .to_der()
+        .unwrap();
+        let status =
+            der::encode_constructed(der::TAG_SEQUENCE, &[&der::encode(der::TAG_INTEGER, &[0])]);
+        der::encode_constructed(der::TAG_SEQUENCE, &[&status, &token])
+    }
+
+    /// `MessageImprint` de un `TimeStampReq`
+    fn request_imprint(request: &[u8]) -> Vec<u8> {
+        let (request, _) = der::parse(request).unwrap();
+        request.child(1, der::TAG_SEQUENCE).unwrap().raw.to_vec()
+    }
+
+    #[test]
+    fn sign_and_verify_round_trip() {
+        let (ca, signer) = ca_and_signer();
+        let (mut pdf, range) = prepared_pdf();
+        let cms = sign_cms(&signed_bytes(&pdf, &range), &signer).unwrap();
+        embed_signature(&mut pdf, &range, &cms).unwrap();
+
+        let results = verify_signatures(&pdf, &trust_store(&[&ca])).unwrap();
+        assert_eq!(results.len(), 1);
+        let result = &results[0];
+        assert!(result.integrity_valid, "{:?}", result.error);
+        assert!(result.certificate_trusted);
+        assert!(result.covers_whole_document);
+        assert_eq!(result.signer.as_deref(), Some("Test Signer"));
+        assert_eq!(result.issuer.as_deref(), Some("CN=Test CA"));
+        assert_eq!(result.serial_number.as_deref(), Some("02"));
+        assert_eq!(result.reason.as_deref(), Some("Aprobado"));
+        assert!(result.timestamp.is_none());
+    }
+
+    #[test]
+    fn untrusted_certificate_keeps_integrity() {
+        let (_, signer) = ca_and_signer();
+        let (mut pdf, range) = prepared_pdf();
+        let cms = sign_cms(&signed_bytes(&pdf, &range), &signer).unwrap();
+        embed_signature(&mut pdf, &range, &cms).unwrap();
+
+        // La CA que emitió el certificado no está en el almacén
+        let (other_ca, _) = issue("Other CA", 3, None);
+        let result = &verify_signatures(&pdf, &trust_store(&[&other_ca])).unwrap()[0];
+        assert!(result.integrity_valid);
+        assert!(!result.certificate_trusted);
+    }
+
+    #[test]
+    fn modified_content_fails_integrity() {
+        let (ca, signer) = ca_and_signer();
+        let (mut pdf, range) = prepared_pdf();
+        let cms = sign_cms(&signed_bytes(&pdf, &range), &signer).unwrap();
+        embed_signature(&mut pdf, &range, &cms).unwrap();
+
+        // Cambia la fecha `/M` (cubierta por la firma) sin romper el PDF
+        let date = find(&pdf, b"(D:", 0).unwrap() + 3;
+        pdf[date] = if pdf[date] == b'1' { b'2' } else { b'1' };
+        let result = &verify_signatures(&pdf, &trust_store(&[&ca])).unwrap()[0];
+        assert!(!result.integrity_valid);
+        assert!(result.error.is_some());
+    }
+
+    #[test]
+    fn timestamp_from_tsa_is_embedded_and_verified() {
+        let (ca, signer) = ca_and_signer();
+        let tsa = issue("Test TSA", 4, None);
+        let (mut pdf, range) = prepared_pdf();
+        let cms = sign_cms(&signed_bytes(&pdf, &range), &signer).unwrap();
+
+        let request = timestamp_request(&cms).unwrap();
+        let response = tsa_response(&request_imprint(&request), &tsa);
+        let cms = add_timestamp(&cms, &response).unwrap();
+        embed_signature(&mut pdf, &range, &cms).unwrap();
+
+        let result = &verify_signatures(&pdf, &trust_store(&[&ca])).unwrap()[0];
+        assert!(result.integrity_valid, "{:?}", result.error);
+        let timestamp = result.timestamp.as_ref().unwrap();
+        assert!(timestamp.valid);
+        assert_eq!(timestamp.time.as_deref(), Some("2026-01-02T03:04:05+00:00"));
+    }
+
+    #[test]
+    fn timestamp_for_another_hash_is_rejected() {
+        let (_, signer) = ca_and_signer();
+        let tsa = issue("Test TSA", 4, None);
+        let cms = sign_cms(b"contenido", &signer).unwrap();
+
+        let algorithm = der::encode_constructed(
+            der::TAG_SEQUENCE,
+            &[
+                &der::encode(der::TAG_OID, OID_SHA256),
+                &der::encode(der::TAG_NULL, &[]),
+            ],
+        );
+        let imprint = der::encode_constructed(
+            der::TAG_SEQUENCE,
+            &[&algorithm, &der::encode(der::TAG_OCTET_STRING, &[0u8; 32])],
+        );
+        let response = tsa_response(&imprint, &tsa);
+        assert!(add_timestamp(&cms, &response).is_err());
+    }
+
+    #[test]
+    fn rejected_timestamp_request_is_an_error() {
+        let (_, signer) = ca_and_signer();
+        let cms = sign_cms(b"contenido", &signer).unwrap();
+        // status = rejection (2), sin token
+        let status =
+            der::encode_constructed(der::TAG_SEQUENCE, &[&der::encode(der::TAG_INTEGER, &[2])]);
+        let response = der::encode_constructed(der::TAG_SEQUENCE, &[&status]);
+        let error = add_timestamp(&cms, &response).unwrap_err();
+        assert!(error.to_string().contains("rechazó"));
+    }
+}
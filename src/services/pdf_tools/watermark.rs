@@ -0,0 +1,320 @@
+//! services/pdf_tools/watermark.rs
+//! Marca de agua superpuesta a las páginas: un Form XObject compartido
+//! (texto en Helvetica-Bold o imagen, con su opacidad) que cada página
+//! dibuja encima de su contenido, rotado y posicionado.
+
+use anyhow::{anyhow, Result};
+use lopdf::{dictionary, Dictionary, Document, Object, ObjectId, Stream};
+
+use crate::models::pdf_model::{PdfWatermark, PdfWatermarkPosition};
+use crate::services::pdf_tools::{
+    image, media_box, page_ranges::parse_page_ranges, win_ansi_literal, MM_TO_PT,
+};
+
+/// Distancia al borde de la página en las posiciones que no son `center`
+const EDGE_MARGIN: f64 = 10.0 * MM_TO_PT;
+/// Interlineado del texto, relativo al tamaño de letra
+const LINE_HEIGHT: f64 = 1.2;
+/// Descendente de Helvetica (bajo la línea base), relativo al tamaño de letra
+const DESCENT: f64 = 0.21;
+
+/// Dibuja la marca de agua en las páginas elegidas (`pages`, o todas).
+pub fn apply_watermark(doc: &mut Document, watermark: &PdfWatermark) -> Result<()> {
+    let pages = doc.get_pages();
+    let mut selected: Vec<u32> = match &watermark.pages {
+        Some(spec) => parse_page_ranges(spec, pages.len() as u32)?,
+        None => pages.keys().copied().collect(),
+    };
+    selected.sort_unstable();
+    selected.dedup();
+
+    let (form_id, width, height) = add_form(doc, watermark)?;
+    // Todo el contenido original queda entre `q` y `Q`: así un estado gráfico
+    // sin cerrar no desplaza la marca
+    let open_id = doc.add_object(Stream::new(Dictionary::new(), b"q\n".to_vec()));
+
+    for number in selected {
+        let page_id = pages[&number];
+        let name = add_page_xobject(doc, page_id, form_id)?;
+        let matrix = placement(doc, page_id, watermark, width, height);
+        let overlay = format!(
+            "\nQ q {:.4} {:.4} {:.4} {:.4} {:.2} {:.2} cm /{} Do Q\n",
+            matrix[0], matrix[1], matrix[2], matrix[3], matrix[4], matrix[5], name
+        );
+        let overlay_id = doc.add_object(Stream::new(Dictionary::new(), overlay.into_bytes()));
+        wrap_contents(doc, page_id, open_id, overlay_id)?;
+    }
+    Ok(())
+}
+
+/// Form XObject con la marca sin rotar, con origen en (0, 0). Retorna su tamaño.
+fn add_form(doc: &mut Document, watermark: &PdfWatermark) -> Result<(ObjectId, f64, f64)> {
+    let opacity = watermark.opacity();
+    let mut resources = dictionary! {
+        "ExtGState" => dictionary! {
+            "GS0" => dictionary! { "Type" => "ExtGState", "ca" => opacity, "CA" => opacity },
+        },
+    };
+
+    let (content, width, height) = match (&watermark.text, &watermark.image) {
+        (_, Some(bytes)) => {
+            let image = image::add_image(doc, bytes)?;
+            // A 96 dpi si no se indica el ancho
+            let natural = image.width as f64 * 0.75;
+            let width = watermark.width.map(|w| w * MM_TO_PT).unwrap_or(natural);
+            let height = width * image.height as f64 / image.width.max(1) as f64;
+            resources.set("XObject", dictionary! { "Im0" => image.id });
+            let content = format!("q /GS0 gs {:.2} 0 0 {:.2} 0 0 cm /Im0 Do Q", width, height);
+            (content.into_bytes(), width, height)
+        }
+        (Some(text), None) => {
+            let font_size = watermark.font_size();
+            let (r, g, b) = parse_color(watermark.color.as_deref().unwrap_or("#808080"))?;
+            resources.set(
+                "Font",
+                dictionary! {
+                    "F0" => dictionary! {
+                        "Type" => "Font",
+                        "Subtype" => "Type1",
+                        "BaseFont" => "Helvetica-Bold",
+                        "Encoding" => "WinAnsiEncoding",
+                    },
+                },
+            );
+            let lines: Vec<&str> = text.lines().collect();
+            let width = lines
+                .iter()
+                .map(|line| text_width(line, font_size))
+                .fold(0.0, f64::max);
+            let height = lines.len().max(1) as f64 * font_size * LINE_HEIGHT;
+
+            let mut content = format!(
+                "q /GS0 gs {:.3} {:.3} {:.3} rg BT /F0 {:.2} Tf\n",
+                r, g, b, font_size
+            )
+            .into_bytes();
+            for (index, line) in lines.iter().enumerate() {
+                // Cada línea centrada; la primera arriba
+                let x = (width - text_width(line, font_size)) / 2.0;
+                let y = height - (index + 1) as f64 * font_size * LINE_HEIGHT
+                    + font_size * (LINE_HEIGHT - 1.0) / 2.0
+                    + font_size * DESCENT;
+                content.extend(format!("1 0 0 1 {:.2} {:.2} Tm (", x, y).into_bytes());
+                content.extend(win_ansi_literal(line));
+                content.extend_from_slice(b") Tj\n");
+            }
+            content.extend_from_slice(b"ET Q");
+            (content, width, height)
+        }
+        (None, None) => return Err(anyhow!("La marca de agua necesita `text` o `image_base64`")),
+    };
+
+    let form = Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Form",
+            "BBox" => vec![0.into(), 0.into(), Object::Real(width as f32), Object::Real(height as f32)],
+            "Resources" => resources,
+        },
+        content,
+    );
+    Ok((doc.add_object(form), width, height))
+}
+
+/// Matriz `cm` que rota la marca alrededor de su centro y la ubica en la página.
+/// La posición y el ángulo son los que se ven, teniendo en cuenta `/Rotate`.
+fn placement(
+    doc: &Document,
+    page_id: ObjectId,
+    watermark: &PdfWatermark,
+    width: f64,
+    height: f64,
+) -> [f64; 6] {
+    let [x0, y0, x1, y1] = media_box(doc, page_id);
+    let page_rotation = page_rotation(doc, page_id);
+    let (page_width, page_height) = (x1 - x0, y1 - y0);
+    // Tamaño de la página tal como se ve
+    let (view_width, view_height) = if page_rotation % 180 == 0 {
+        (page_width, page_height)
+    } else {
+        (page_height, page_width)
+    };
+
+    let angle = (watermark.rotation() + page_rotation as f64).to_radians();
+    let (sin, cos) = angle.sin_cos();
+    // Caja que ocupa la marca ya rotada
+    let box_width = (width * cos).abs() + (height * sin).abs();
+    let box_height = (width * sin).abs() + (height * cos).abs();
+
+    use PdfWatermarkPosition::*;
+    let view_x = match watermark.position {
+        TopLeft | BottomLeft => EDGE_MARGIN + box_width / 2.0,
+        TopRight | BottomRight => view_width - EDGE_MARGIN - box_width / 2.0,
+        Center | TopCenter | BottomCenter => view_width / 2.0,
+    };
+    let view_y = match watermark.position {
+        TopLeft | TopCenter | TopRight => view_height - EDGE_MARGIN - box_height / 2.0,
+        BottomLeft | BottomCenter | BottomRight => EDGE_MARGIN + box_height / 2.0,
+        Center => view_height / 2.0,
+    };
+    // De coordenadas de la vista a coordenadas de la página (que el visor gira
+    // `/Rotate` grados en sentido horario)
+    let (x, y) = match page_rotation {
+        90 => (page_width - view_y, view_x),
+        180 => (page_width - view_x, page_height - view_y),
+        270 => (view_y, page_height - view_x),
+        _ => (view_x, view_y),
+    };
+    let center_x = x0 + x;
+    let center_y = y0 + y;
+
+    [
+        cos,
+        sin,
+        -sin,
+        cos,
+        center_x - (cos * width / 2.0 - sin * height / 2.0),
+        center_y - (sin * width / 2.0 + cos * height / 2.0),
+    ]
+}
+
+/// `/Rotate` de la página (heredable), normalizado a 0, 90, 180 o 270.
+fn page_rotation(doc: &Document, page_id: ObjectId) -> i64 {
+    let mut node = doc.get_dictionary(page_id).ok();
+    while let Some(dict) = node {
+        if let Ok(rotate) = dict.get(b"Rotate").and_then(Object::as_i64) {
+            return rotate.rem_euclid(360) / 90 * 90;
+        }
+        node = dict
+            .get(b"Parent")
+            .and_then(Object::as_reference)
+            .and_then(|id| doc.get_dictionary(id))
+            .ok();
+    }
+    0
+}
+
+/// Registra el Form XObject en los recursos de la página con un nombre libre.
+/// Los recursos efectivos (propios, referenciados o heredados) se copian a la página.
+fn add_page_xobject(doc: &mut Document, page_id: ObjectId, form_id: ObjectId) -> Result<String> {
+    let mut resources = effective_resources(doc, page_id);
+    let mut xobjects = match resources.get(b"XObject") {
+        Ok(Object::Reference(id)) => doc.get_dictionary(*id).cloned().unwrap_or_default(),
+        Ok(Object::Dictionary(dict)) => dict.clone(),
+        _ => Dictionary::new(),
+    };
+    let name = (0..)
+        .map(|n| format!("Wm{}", n))
+        .find(|name| !xobjects.has(name.as_bytes()))
+        .unwrap_or_default();
+    xobjects.set(name.clone(), form_id);
+    resources.set("XObject", xobjects);
+    doc.get_dictionary_mut(page_id)?.set("Resources", resources);
+    Ok(name)
+}
+
+fn effective_resources(doc: &Document, page_id: ObjectId) -> Dictionary {
+    let mut node = doc.get_dictionary(page_id).ok();
+    while let Some(dict) = node {
+        match dict.get(b"Resources") {
+            Ok(Object::Reference(id)) => {
+                return doc.get_dictionary(*id).cloned().unwrap_or_default()
+            }
+            Ok(Object::Dictionary(resources)) => return resources.clone(),
+            _ => {}
+        }
+        node = dict
+            .get(b"Parent")
+            .and_then(Object::as_reference)
+            .and_then(|id| doc.get_dictionary(id))
+            .ok();
+    }
+    Dictionary::new()
+}
+
+/// `/Contents` pasa a ser `[abrir, ...contenido original..., marca]`.
+fn wrap_contents(
+    doc: &mut Document,
+    page_id: ObjectId,
+    open_id: ObjectId,
+    overlay_id: ObjectId,
+) -> Result<()> {
+    let mut items = match doc.get_dictionary(page_id)?.get(b"Contents") {
+        Ok(Object::Reference(id)) => match doc.get_object(*id) {
+            Ok(Object::Array(items)) => items.clone(),
+            _ => vec![Object::Reference(*id)],
+        },
+        Ok(Object::Array(items)) => items.clone(),
+        _ => Vec::new(),
+    };
+    items.insert(0, open_id.into());
+    items.push(overlay_id.into());
+    doc.get_dictionary_mut(page_id)?.set("Contents", items);
+    Ok(())
+}
+
+/// `#RRGGBB` (o `#RGB`) a componentes entre 0 y 1.
+fn parse_color(value: &str) -> Result<(f64, f64, f64)> {
+    let hex = value.trim().trim_start_matches('#');
+    let hex = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect(),
+        6 => hex.to_string(),
+        _ => return Err(anyhow!("Color inválido '{}' (se espera #RRGGBB)", value)),
+    };
+    let channel = |i: usize| {
+        u8::from_str_radix(&hex[i..i + 2], 16)
+            .map(|v| v as f64 / 255.0)
+            .map_err(|_| anyhow!("Color inválido '{}' (se espera #RRGGBB)", value))
+    };
+    Ok((channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// Ancho del texto en Helvetica-Bold, en puntos.
+fn text_width(text: &str, font_size: f64) -> f64 {
+    let units: u32 = text.chars().map(helvetica_bold_width).sum();
+    units as f64 * font_size / 1000.0
+}
+
+/// Anchos de Helvetica-Bold (AFM estándar) en milésimas del tamaño de letra.
+/// Las letras acentuadas miden lo mismo que su letra base.
+fn helvetica_bold_width(c: char) -> u32 {
+    const ASCII: [u16; 95] = [
+        278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278,
+        278, // ' '../
+        556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584,
+        611, // 0..?
+        975, 722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833, 722,
+        778, // @..O
+        667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 333, 278, 333, 584,
+        556, // P.._
+        333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889, 611,
+        611, // `..o
+        611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584, // p..~
+    ];
+    let base = match c {
+        'À'..='Å' => 'A',
+        'Ç' => 'C',
+        'È'..='Ë' => 'E',
+        'Ì'..='Ï' => 'I',
+        'Ñ' => 'N',
+        'Ò'..='Ö' | 'Ø' => 'O',
+        'Ù'..='Ü' => 'U',
+        'Ý' => 'Y',
+        'à'..='å' => 'a',
+        'ç' => 'c',
+        'è'..='ë' => 'e',
+        'ì'..='ï' => 'i',
+        'ñ' => 'n',
+        'ò'..='ö' | 'ø' => 'o',
+        'ù'..='ü' => 'u',
+        'ý' | 'ÿ' => 'y',
+        'Æ' => return 1000,
+        'æ' => return 889,
+        'ß' | 'ð' | 'þ' => return 611,
+        other => other,
+    };
+    match base as u32 {
+        code @ 0x20..=0x7E => ASCII[(code - 0x20) as usize] as u32,
+        _ => 556,
+    }
+}
@@ -0,0 +1,275 @@
+//! services/render_cache.rs
+//! Caché en memoria de PDFs ya renderizados. La clave es el SHA-256 del `PdfRequest`
+//! (y de los archivos que trae); se activa con `PDF_CACHE_MAX_BYTES`, las entradas expiran a los
+//! `PDF_CACHE_TTL_SECONDS` y, al llenarse, sale la usada hace más tiempo.
+
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use openssl::sha::Sha256;
+
+use crate::models::pdf_model::{PdfBackend, PdfRenderReport, PdfRequest};
+use crate::services::util::hex;
+
+/// Vida de una entrada si no se define `PDF_CACHE_TTL_SECONDS` (5 minutos)
+const DEFAULT_TTL_SECONDS: u64 = 300;
+
+/// Resultado de consultar la caché; va en el header `X-Cache`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheStatus {
+    Hit,
+    Miss,
+    /// El request no se cachea (`cache: false` o firma digital)
+    Bypass,
+}
+
+impl CacheStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheStatus::Hit => "HIT",
+            CacheStatus::Miss => "MISS",
+            CacheStatus::Bypass => "BYPASS",
+        }
+    }
+}
+
+/// Entradas y bytes liberados por una purga
+#[derive(Debug, Clone, Default)]
+pub struct CachePurge {
+    pub entries: usize,
+    pub bytes: u64,
+}
+
+struct CacheEntry {
+    pdf: Vec<u8>,
+    /// Informe del render original (páginas y avisos del motor)
+    report: PdfRenderReport,
+    inserted_at: Instant,
+    last_used: Instant,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<String, CacheEntry>,
+    total_bytes: u64,
+}
+
+impl CacheState {
+    fn remove(&mut self, key: &str) -> Option<CacheEntry> {
+        let entry = self.entries.remove(key)?;
+        self.total_bytes -= entry.pdf.len() as u64;
+        Some(entry)
+    }
+}
+
+pub struct RenderCache {
+    max_bytes: u64,
+    ttl: Duration,
+    state: Mutex<CacheState>,
+}
+
+impl RenderCache {
+    /// `None` (caché desactivada) si `PDF_CACHE_MAX_BYTES` no está definida o es 0
+    pub fn from_env() -> Option<Self> {
+        let max_bytes = std::env::var("PDF_CACHE_MAX_BYTES")
+            .ok()
+            .and_then(|value| value.parse::<u64>().ok())
+            .filter(|max_bytes| *max_bytes > 0)?;
+        let ttl_seconds = std::env::var("PDF_CACHE_TTL_SECONDS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_TTL_SECONDS);
+        log::info!(
+            "Caché de renders activa: {} bytes, TTL {}s",
+            max_bytes,
+            ttl_seconds
+        );
+        Some(Self {
+            max_bytes,
+            ttl: Duration::from_secs(ttl_seconds),
+            state: Mutex::new(CacheState::default()),
+        })
+    }
+
+    /// Clave del request con el motor que lo va a renderizar: SHA-256 del JSON sin
+    /// los campos que no afectan el PDF, escrito directo en el hash, y después los
+    /// bytes del bundle y de la imagen de la marca de agua tal cual (en el JSON un
+    /// `Vec<u8>` sería un número por byte).
+    /// `None` si el resultado no se puede reutilizar: la firma lleva la hora de firmado.
+    pub fn key(req: &PdfRequest, backend: PdfBackend) -> Result<Option<String>> {
+        if req.cache == Some(false) || req.signature.is_some() {
+            return Ok(None);
+        }
+        let mut view = req.clone();
+        // No cambian el PDF resultante
+        view.file_name = String::new();
+        view.store_local_pdf = None;
+        view.store_ttl_seconds = None;
+        view.cache = None;
+        view.run_async = None;
+        view.callback_url = None;
+        view.response_format = None;
+        let files = [
+            view.assets_bundle
+                .as_mut()
+                .map(|bundle| std::mem::take(&mut bundle.zip)),
+            view.watermark
+                .as_mut()
+                .and_then(|watermark| watermark.image.take()),
+        ];
+
+        let mut hasher = Sha256::new();
+        serde_json::to_writer(HashWriter(&mut hasher), &(backend, &view))?;
+        for file in files {
+            // La longitud delante separa un archivo del siguiente
+            match file {
+                Some(bytes) => {
+                    hasher.update(&(bytes.len() as u64 + 1).to_be_bytes());
+                    hasher.update(&bytes);
+                }
+                None => hasher.update(&0u64.to_be_bytes()),
+            }
+        }
+        Ok(Some(hex(&hasher.finish())))
+    }
+
+    pub fn get(&self, key: &str) -> Option<(Vec<u8>, PdfRenderReport)> {
+        let mut state = self.lock();
+        let now = Instant::now();
+        let entry = state.entries.get_mut(key)?;
+        if now.duration_since(entry.inserted_at) >= self.ttl {
+            state.remove(key);
+            return None;
+        }
+        entry.last_used = now;
+        Some((entry.pdf.clone(), entry.report.clone()))
+    }
+
+    /// Guarda el PDF con su informe; si no entra, primero descarta los expirados y
+    /// después los menos usados. Un PDF más grande que toda la caché no se guarda.
+    pub fn insert(&self, key: String, pdf: &[u8], report: &PdfRenderReport) {
+        let size = pdf.len() as u64;
+        if size > self.max_bytes {
+            return;
+        }
+        let mut state = self.lock();
+        let now = Instant::now();
+        state.remove(&key);
+
+        if state.total_bytes + size > self.max_bytes {
+            let expired: Vec<String> = state
+                .entries
+                .iter()
+                .filter(|(_, entry)| now.duration_since(entry.inserted_at) >= self.ttl)
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in expired {
+                state.remove(&key);
+            }
+        }
+        while state.total_bytes + size > self.max_bytes {
+            let Some(oldest) = state
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            state.remove(&oldest);
+        }
+
+        state.total_bytes += size;
+        state.entries.insert(
+            key,
+            CacheEntry {
+                pdf: pdf.to_vec(),
+                report: report.clone(),
+                inserted_at: now,
+                last_used: now,
+            },
+        );
+    }
+
+    /// Vacía la caché
+    pub fn purge(&self) -> CachePurge {
+        let mut state = self.lock();
+        let purge = CachePurge {
+            entries: state.entries.len(),
+            bytes: state.total_bytes,
+        };
+        *state = CacheState::default();
+        purge
+    }
+
+    /// Un pánico con el lock tomado no deja la caché inconsistente: se sigue usando
+    fn lock(&self) -> MutexGuard<'_, CacheState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Escribe en un SHA-256 en vez de en memoria
+struct HashWriter<'a>(&'a mut Sha256);
+
+impl Write for HashWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::pdf_model::PdfAssetBundle;
+
+    fn bundle_request(zip: &[u8]) -> PdfRequest {
+        PdfRequest {
+            assets_bundle: Some(PdfAssetBundle {
+                zip: zip.to_vec(),
+                entry_point: None,
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn key(req: &PdfRequest) -> String {
+        RenderCache::key(req, PdfBackend::Wkhtmltopdf)
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn key_ignores_fields_that_do_not_change_the_pdf() {
+        let req = bundle_request(b"zip");
+        let mut renamed = req.clone();
+        renamed.file_name = "otro.pdf".to_string();
+        renamed.store_local_pdf = Some(true);
+        renamed.cache = Some(true);
+        assert_eq!(key(&req), key(&renamed));
+    }
+
+    #[test]
+    fn key_hashes_file_bytes() {
+        let big = vec![7u8; 4 * 1024 * 1024];
+        assert_eq!(key(&bundle_request(&big)), key(&bundle_request(&big)));
+        assert_ne!(key(&bundle_request(b"a")), key(&bundle_request(b"b")));
+        // Sin bundle no es lo mismo que un bundle vacío
+        assert_ne!(key(&PdfRequest::default()), key(&bundle_request(b"")));
+        assert_ne!(
+            RenderCache::key(&PdfRequest::default(), PdfBackend::Wkhtmltopdf).unwrap(),
+            RenderCache::key(&PdfRequest::default(), PdfBackend::Chromium).unwrap()
+        );
+    }
+}
@@ -0,0 +1,95 @@
+//! services/renderer/asset_bundle.rs
+//! Extrae un ZIP (HTML + CSS + imágenes + fuentes) en el directorio del render.
+
+use std::{
+    fs,
+    io::{self, Cursor, Read},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use zip::ZipArchive;
+
+/// HTML de entrada por defecto dentro del ZIP
+pub const DEFAULT_ENTRY_POINT: &str = "index.html";
+
+/// Límites para no descomprimir bombas ZIP
+const MAX_BUNDLE_ENTRIES: usize = 2_000;
+const MAX_BUNDLE_UNCOMPRESSED_BYTES: u64 = 200 * 1024 * 1024;
+
+/// Extrae `zip_bytes` dentro de `dest_dir`.
+///
+/// Cualquier entrada con ruta absoluta o con `..` que salga de `dest_dir`
+/// hace fallar toda la extracción.
+pub fn extract_bundle(zip_bytes: &[u8], dest_dir: &Path) -> Result<()> {
+    let mut archive =
+        ZipArchive::new(Cursor::new(zip_bytes)).context("El bundle no es un ZIP válido")?;
+    if archive.len() > MAX_BUNDLE_ENTRIES {
+        return Err(anyhow!(
+            "El bundle tiene demasiados archivos ({} > {})",
+            archive.len(),
+            MAX_BUNDLE_ENTRIES
+        ));
+    }
+
+    fs::create_dir_all(dest_dir)
+        .with_context(|| format!("No se pudo crear directorio {:?}", dest_dir))?;
+
+    let mut total_bytes: u64 = 0;
+    for i in 0..archive.len() {
+        let mut entry = archive
+            .by_index(i)
+            .with_context(|| format!("Error leyendo la entrada {} del bundle", i))?;
+        let relative = entry
+            .enclosed_name()
+            .ok_or_else(|| anyhow!("Ruta no permitida en el bundle: {}", entry.name()))?;
+        let out_path = dest_dir.join(relative);
+
+        if entry.is_dir() {
+            fs::create_dir_all(&out_path)
+                .with_context(|| format!("No se pudo crear directorio {:?}", out_path))?;
+            continue;
+        }
+        if entry.is_symlink() {
+            return Err(anyhow!(
+                "El bundle no puede contener enlaces simbólicos: {}",
+                entry.name()
+            ));
+        }
+
+        if let Some(parent) = out_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("No se pudo crear directorio {:?}", parent))?;
+        }
+
+        // Se cuenta lo realmente descomprimido, no lo que declara el ZIP
+        let remaining = MAX_BUNDLE_UNCOMPRESSED_BYTES - total_bytes;
+        let mut out_file = fs::File::create(&out_path)
+            .with_context(|| format!("No se pudo crear {:?}", out_path))?;
+        let written = io::copy(&mut (&mut entry).take(remaining + 1), &mut out_file)
+            .with_context(|| format!("Error extrayendo {:?}", out_path))?;
+        total_bytes += written;
+        if total_bytes > MAX_BUNDLE_UNCOMPRESSED_BYTES {
+            return Err(anyhow!(
+                "El bundle descomprimido supera {} bytes",
+                MAX_BUNDLE_UNCOMPRESSED_BYTES
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Valida una ruta relativa recibida del cliente (sin raíz ni `..`).
+pub fn safe_relative_path(path: &str) -> Result<PathBuf> {
+    let candidate = Path::new(path);
+    let is_safe = !path.is_empty()
+        && candidate
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_)));
+    if is_safe {
+        Ok(candidate.to_path_buf())
+    } else {
+        Err(anyhow!("Ruta no permitida en el bundle: {}", path))
+    }
+}
@@ -0,0 +1,961 @@
+//! services/renderer/chromium_renderer.rs
+//! Renderer basado en Chromium headless, vía DevTools protocol (`Page.printToPDF`
+//! para PDFs y `Page.captureScreenshot` para imágenes).
+
+use std::{collections::HashMap, path::PathBuf, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use chromiumoxide::{
+    cdp::browser_protocol::{
+        browser::BrowserContextId,
+        emulation::{
+            ClearDeviceMetricsOverrideParams, SetDeviceMetricsOverrideParams,
+            SetScriptExecutionDisabledParams,
+        },
+        fetch::{
+            self, ContinueRequestParams, EventRequestPaused, FailRequestParams, HeaderEntry,
+            RequestPattern,
+        },
+        network::{
+            self, CookieParam, EventLoadingFailed, EventRequestWillBeSent, EventResponseReceived,
+            Headers, SetCookiesParams,
+        },
+        page::{CaptureScreenshotFormat, PrintToPdfParams, Viewport},
+        target::{CreateBrowserContextParams, CreateTargetParams},
+    },
+    cdp::js_protocol::runtime::EventExceptionThrown,
+    listeners::EventStream,
+    page::{MediaTypeParams, ScreenshotParams},
+    Browser, BrowserConfig, Page,
+};
+use futures::{FutureExt, StreamExt};
+use tokio::{sync::Mutex, task::JoinHandle, time::timeout};
+
+use crate::{
+    models::{
+        pdf_model::{
+            ImageFormat, PdfBackend, PdfImageOptions, PdfMediaType, PdfRenderWarning,
+            PdfRenderWarningKind, PdfRequest, PdfSourceOptions, PdfTocOptions, MAX_PAGE_SIDE_MM,
+        },
+        units::{CSS_PX_PER_INCH, MM_PER_INCH},
+    },
+    services::{
+        pdf_tools::{
+            self,
+            merge::{merge_documents, MergePart},
+            outline::{read_outline, write_outline, OutlineEntry},
+        },
+        renderer::{
+            header_footer, is_landscape, margins_mm, page_size_mm, toc, PdfRenderer, RenderFiles,
+            RenderOutput,
+        },
+    },
+};
+
+/// Tiempo máximo para que Chromium genere un PDF
+const CHROMIUM_TIMEOUT: Duration = Duration::from_secs(300);
+/// Intervalo de sondeo de `window.status`
+const WINDOW_STATUS_POLL: Duration = Duration::from_millis(100);
+/// Intentos para estabilizar las páginas del índice: cada render del índice
+/// puede cambiar cuántas páginas ocupa, y con ello la numeración.
+const TOC_MAX_PASSES: usize = 3;
+/// Binarios que se buscan en PATH si no se define `CHROME_PATH`
+const CHROMIUM_BINARIES: [&str; 4] = [
+    "chromium",
+    "chromium-browser",
+    "google-chrome",
+    "google-chrome-stable",
+];
+
+/// Alto de la ventana para capturas de página completa (solo afecta a `vh`)
+const DEFAULT_VIEWPORT_HEIGHT: u32 = 768;
+/// Holgura (mm) del alto con `auto_height`, para que el redondeo no agregue
+/// una segunda página casi vacía
+const AUTO_HEIGHT_SLACK_MM: f64 = 1.0;
+
+pub struct ChromiumRenderer {
+    chromium_path: PathBuf,
+    /// Sandbox de Chromium activo, salvo `CHROMIUM_NO_SANDBOX=true`
+    sandbox: bool,
+    /// Navegador compartido; se lanza con el primer render
+    browser: Mutex<Option<Arc<SharedBrowser>>>,
+}
+
+impl ChromiumRenderer {
+    pub fn new(chromium_path: PathBuf) -> Self {
+        let no_sandbox = std::env::var("CHROMIUM_NO_SANDBOX")
+            .is_ok_and(|value| value.eq_ignore_ascii_case("true") || value == "1");
+        if no_sandbox {
+            log::warn!("CHROMIUM_NO_SANDBOX: Chromium corre sin sandbox");
+        }
+        Self {
+            chromium_path,
+            sandbox: !no_sandbox,
+            browser: Mutex::new(None),
+        }
+    }
+
+    /// Localiza el ejecutable de Chromium: primero `CHROME_PATH`, luego PATH.
+    pub fn find_executable() -> Option<PathBuf> {
+        if let Ok(path) = std::env::var("CHROME_PATH") {
+            if !path.is_empty() {
+                return Some(PathBuf::from(path));
+            }
+        }
+        CHROMIUM_BINARIES
+            .iter()
+            .find_map(|bin| which::which(bin).ok())
+    }
+
+    /// Imprime la página en un contexto propio del navegador compartido.
+    async fn run_chromium(&self, req: &PdfRequest, files: &RenderFiles) -> Result<RenderOutput> {
+        let session = self.session().await?;
+        match (self.print_to_pdf(&session, req, files).await, &req.toc) {
+            (Ok(RenderOutput { bytes, warnings }), Some(options)) => self
+                .prepend_toc(&session, req, files, options, bytes)
+                .await
+                .map(|bytes| RenderOutput { bytes, warnings }),
+            (result, _) => result,
+        }
+    }
+
+    /// Igual que `run_chromium`, pero captura la página como imagen.
+    async fn run_screenshot(
+        &self,
+        req: &PdfRequest,
+        files: &RenderFiles,
+        options: &PdfImageOptions,
+    ) -> Result<RenderOutput> {
+        let session = self.session().await?;
+        self.screenshot(&session, req, files, options).await
+    }
+
+    /// Contexto nuevo para un render. Si el navegador dejó de responder se
+    /// descarta y se lanza otro.
+    async fn session(&self) -> Result<RenderSession> {
+        let shared = self.browser().await?;
+        match RenderSession::open(shared.clone()).await {
+            Ok(session) => Ok(session),
+            Err(e) => {
+                log::warn!("Chromium no responde, se vuelve a lanzar: {:?}", e);
+                self.discard(&shared).await;
+                RenderSession::open(self.browser().await?).await
+            }
+        }
+    }
+
+    async fn browser(&self) -> Result<Arc<SharedBrowser>> {
+        let mut current = self.browser.lock().await;
+        if let Some(shared) = current.as_ref().filter(|shared| shared.is_alive()) {
+            return Ok(shared.clone());
+        }
+        let shared = Arc::new(self.launch().await?);
+        *current = Some(shared.clone());
+        Ok(shared)
+    }
+
+    async fn discard(&self, stale: &Arc<SharedBrowser>) {
+        let mut current = self.browser.lock().await;
+        if current
+            .as_ref()
+            .is_some_and(|shared| Arc::ptr_eq(shared, stale))
+        {
+            *current = None;
+        }
+    }
+
+    async fn launch(&self) -> Result<SharedBrowser> {
+        let profile = tempfile::Builder::new()
+            .prefix("pdf_service_chromium")
+            .tempdir()
+            .context("No se pudo crear el perfil de Chromium")?;
+        let mut builder = BrowserConfig::builder()
+            .chrome_executable(&self.chromium_path)
+            .user_data_dir(profile.path())
+            .new_headless_mode()
+            .arg("--disable-gpu")
+            .arg("--disable-dev-shm-usage");
+        if !self.sandbox {
+            builder = builder.no_sandbox();
+        }
+        let config = builder
+            .build()
+            .map_err(|e| anyhow!("Configuración de Chromium inválida: {}", e))?;
+
+        let (browser, mut handler) = Browser::launch(config).await.with_context(|| {
+            if self.sandbox {
+                "No se pudo lanzar Chromium (como root o en un contenedor sin user \
+                 namespaces hace falta CHROMIUM_NO_SANDBOX=true)"
+            } else {
+                "No se pudo lanzar Chromium"
+            }
+        })?;
+
+        // El handler procesa los mensajes del websocket de DevTools; si termina,
+        // el navegador se cayó
+        let handler_task = tokio::spawn(async move {
+            while let Some(event) = handler.next().await {
+                if event.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(SharedBrowser {
+            browser,
+            handler_task,
+            _profile: profile,
+        })
+    }
+
+    async fn print_to_pdf(
+        &self,
+        session: &RenderSession,
+        req: &PdfRequest,
+        files: &RenderFiles,
+    ) -> Result<RenderOutput> {
+        let page = session.blank_page().await?;
+        let mut diagnostics = PageDiagnostics::listen(&page).await?;
+        let _interceptor = load_page(&page, req, files).await?;
+
+        let render_options = req.render_options.clone().unwrap_or_default();
+        // printToPDF usa `@media print`; `screen` se emula explícitamente
+        if render_options.media_type == Some(PdfMediaType::Screen) {
+            page.emulate_media_type(MediaTypeParams::Screen)
+                .await
+                .context("No se pudo emular el medio screen en Chromium")?;
+        }
+
+        let (width, mut height) = page_size_mm(req);
+        let margins = margins_mm(req);
+        if req.auto_height == Some(true) {
+            let media = match render_options.media_type {
+                Some(PdfMediaType::Screen) => MediaTypeParams::Screen,
+                _ => MediaTypeParams::Print,
+            };
+            let content =
+                content_height_mm(&page, req, width - margins.left - margins.right, media).await?;
+            height = (content + margins.top + margins.bottom + AUTO_HEIGHT_SLACK_MM)
+                .min(MAX_PAGE_SIDE_MM);
+        }
+
+        // Chromium usa su propio encabezado (fecha/título) si solo se define uno,
+        // así que el que falte se reemplaza por una plantilla vacía.
+        let header =
+            header_footer::chromium_fragment(req.header_html.as_deref(), req.header_text.as_ref());
+        let footer =
+            header_footer::chromium_fragment(req.footer_html.as_deref(), req.footer_text.as_ref());
+        let display_header_footer = header.is_some() || footer.is_some();
+        let empty_template = || "<span></span>".to_string();
+        // El índice se arma a partir del outline, así que también lo necesita
+        let outline = req.outline == Some(true) || req.toc.is_some();
+
+        let params = PrintToPdfParams {
+            landscape: Some(is_landscape(req)),
+            print_background: Some(render_options.print_background.unwrap_or(true)),
+            scale: Some(req.scale.unwrap_or(1.0)),
+            paper_width: Some(width / MM_PER_INCH),
+            paper_height: Some(height / MM_PER_INCH),
+            margin_top: Some(margins.top / MM_PER_INCH),
+            margin_bottom: Some(margins.bottom / MM_PER_INCH),
+            margin_left: Some(margins.left / MM_PER_INCH),
+            margin_right: Some(margins.right / MM_PER_INCH),
+            display_header_footer: Some(display_header_footer),
+            header_template: display_header_footer.then(|| header.unwrap_or_else(empty_template)),
+            footer_template: display_header_footer.then(|| footer.unwrap_or_else(empty_template)),
+            generate_tagged_pdf: outline.then_some(true),
+            generate_document_outline: outline.then_some(true),
+            ..Default::default()
+        };
+
+        let pdf_bytes = page
+            .pdf(params)
+            .await
+            .context("Chromium falló al imprimir el PDF")?;
+
+        let warnings = diagnostics.collect();
+        let _ = page.close().await;
+        Ok(RenderOutput {
+            bytes: pdf_bytes,
+            warnings,
+        })
+    }
+
+    /// Fija el ancho de la ventana y la escala antes de cargar la página, y captura
+    /// la ventana, el recorte pedido o (sin `height`) la página completa.
+    async fn screenshot(
+        &self,
+        session: &RenderSession,
+        req: &PdfRequest,
+        files: &RenderFiles,
+        options: &PdfImageOptions,
+    ) -> Result<RenderOutput> {
+        let page = session.blank_page().await?;
+        let mut diagnostics = PageDiagnostics::listen(&page).await?;
+        page.execute(SetDeviceMetricsOverrideParams::new(
+            options.width(),
+            options.height.unwrap_or(DEFAULT_VIEWPORT_HEIGHT),
+            options.scale(),
+            false,
+        ))
+        .await
+        .context("No se pudo fijar el tamaño de la ventana en Chromium")?;
+        let _interceptor = load_page(&page, req, files).await?;
+
+        let format = match options.format {
+            ImageFormat::Png => CaptureScreenshotFormat::Png,
+            ImageFormat::Jpeg => CaptureScreenshotFormat::Jpeg,
+            ImageFormat::Webp => CaptureScreenshotFormat::Webp,
+        };
+        let mut params = ScreenshotParams::builder().format(format);
+        if options.format != ImageFormat::Png {
+            params = params.quality(options.quality());
+        }
+        let clip = match (&options.crop, options.height) {
+            (Some(crop), _) => Some(Viewport {
+                x: f64::from(crop.x),
+                y: f64::from(crop.y),
+                width: f64::from(crop.width),
+                height: f64::from(crop.height),
+                scale: 1.0,
+            }),
+            (None, Some(_)) => None,
+            (None, None) => {
+                let metrics = page
+                    .layout_metrics()
+                    .await
+                    .context("No se pudo medir la página en Chromium")?;
+                Some(Viewport {
+                    x: 0.0,
+                    y: 0.0,
+                    width: metrics.css_content_size.width,
+                    height: metrics.css_content_size.height,
+                    scale: 1.0,
+                })
+            }
+        };
+        if let Some(clip) = clip {
+            params = params.clip(clip).capture_beyond_viewport(true);
+        }
+
+        let image = page
+            .screenshot(params.build())
+            .await
+            .context("Chromium falló al capturar la imagen")?;
+        let warnings = diagnostics.collect();
+        let _ = page.close().await;
+        Ok(RenderOutput {
+            bytes: image,
+            warnings,
+        })
+    }
+
+    /// Renderiza el índice a partir del outline de `main_pdf` y lo antepone.
+    /// Las páginas del índice desplazan la numeración, así que se re-renderiza
+    /// hasta que la cantidad de páginas del índice no cambia.
+    async fn prepend_toc(
+        &self,
+        session: &RenderSession,
+        req: &PdfRequest,
+        files: &RenderFiles,
+        options: &PdfTocOptions,
+        main_pdf: Vec<u8>,
+    ) -> Result<Vec<u8>> {
+        let main_doc = pdf_tools::load_pdf(&main_pdf)?;
+        let entries = read_outline(&main_doc);
+
+        // Mismo formato de página y encabezados; sin fuente remota ni índice
+        let mut toc_req = req.clone();
+        toc_req.source_url = None;
+        toc_req.source_options = None;
+        toc_req.assets_bundle = None;
+        toc_req.outline = Some(false);
+        toc_req.toc = None;
+        let toc_files = RenderFiles {
+            work_dir: files.work_dir.clone(),
+            html_path: files.work_dir.join("toc.html"),
+            pdf_path: files.work_dir.join("toc.pdf"),
+            bundle_dir: None,
+        };
+
+        let mut toc_pages = 1;
+        let mut toc_doc = None;
+        for _ in 0..TOC_MAX_PASSES {
+            std::fs::write(
+                &toc_files.html_path,
+                toc::chromium_html(options, &entries, toc_pages),
+            )
+            .with_context(|| format!("Error escribiendo índice en {:?}", toc_files.html_path))?;
+            let toc_pdf = self.print_to_pdf(session, &toc_req, &toc_files).await?;
+            let doc = pdf_tools::load_pdf(&toc_pdf.bytes)?;
+            let pages = doc.get_pages().len() as u32;
+            toc_doc = Some(doc);
+            if pages == toc_pages {
+                break;
+            }
+            toc_pages = pages;
+        }
+        let toc_doc = toc_doc.ok_or_else(|| anyhow!("No se pudo generar el índice"))?;
+
+        // Al unir se pierde el outline; se reconstruye con las páginas desplazadas
+        let mut merged = merge_documents(vec![
+            MergePart {
+                document: toc_doc,
+                pages: None,
+            },
+            MergePart {
+                document: main_doc,
+                pages: None,
+            },
+        ])?;
+        if req.outline == Some(true) {
+            let shifted: Vec<OutlineEntry> = entries
+                .into_iter()
+                .map(|entry| OutlineEntry {
+                    page: entry.page + toc_pages,
+                    ..entry
+                })
+                .collect();
+            write_outline(&mut merged, &shifted)?;
+        }
+        pdf_tools::save_pdf(&mut merged)
+    }
+}
+
+/// Eventos de DevTools de los que salen los avisos del render: recursos que
+/// fallaron o respondieron con error HTTP y excepciones de JavaScript.
+struct PageDiagnostics {
+    requests: EventStream<EventRequestWillBeSent>,
+    failures: EventStream<EventLoadingFailed>,
+    responses: EventStream<EventResponseReceived>,
+    exceptions: EventStream<EventExceptionThrown>,
+}
+
+impl PageDiagnostics {
+    /// Se suscribe antes de navegar para no perder los eventos de la carga
+    async fn listen(page: &Page) -> Result<Self> {
+        page.execute(network::EnableParams::default())
+            .await
+            .context("No se pudo habilitar Network en Chromium")?;
+        page.enable_runtime()
+            .await
+            .context("No se pudo habilitar Runtime en Chromium")?;
+        let error = || "No se pudieron escuchar los eventos de Chromium";
+        Ok(Self {
+            requests: page.event_listener().await.with_context(error)?,
+            failures: page.event_listener().await.with_context(error)?,
+            responses: page.event_listener().await.with_context(error)?,
+            exceptions: page.event_listener().await.with_context(error)?,
+        })
+    }
+
+    /// Consume los eventos recibidos hasta ahora (sin esperar nuevos)
+    fn collect(&mut self) -> Vec<PdfRenderWarning> {
+        let mut urls = HashMap::new();
+        while let Some(Some(event)) = self.requests.next().now_or_never() {
+            urls.insert(event.request_id.clone(), event.request.url.clone());
+        }
+
+        let mut warnings = Vec::new();
+        while let Some(Some(event)) = self.failures.next().now_or_never() {
+            // Las cancelaciones son del propio navegador (p. ej. al cerrar la página)
+            if event.canceled == Some(true) {
+                continue;
+            }
+            let url = urls.get(&event.request_id).cloned();
+            let kind = if event.blocked_reason.is_some() {
+                PdfRenderWarningKind::BlockedRequest
+            } else if event.error_text.starts_with("net::ERR_CERT")
+                || event.error_text.contains("SSL")
+            {
+                PdfRenderWarningKind::Network
+            } else {
+                PdfRenderWarningKind::MissingResource
+            };
+            warnings.push(PdfRenderWarning {
+                kind,
+                message: format!(
+                    "Failed to load {}: {}",
+                    url.as_deref().unwrap_or("resource"),
+                    event.error_text
+                ),
+                url,
+            });
+        }
+        while let Some(Some(event)) = self.responses.next().now_or_never() {
+            let response = &event.response;
+            if response.status >= 400 {
+                warnings.push(PdfRenderWarning {
+                    kind: PdfRenderWarningKind::MissingResource,
+                    message: format!(
+                        "HTTP {} {} loading {}",
+                        response.status, response.status_text, response.url
+                    ),
+                    url: Some(response.url.clone()),
+                });
+            }
+        }
+        while let Some(Some(event)) = self.exceptions.next().now_or_never() {
+            let details = &event.exception_details;
+            let text = details
+                .exception
+                .as_ref()
+                .and_then(|exception| exception.description.clone())
+                .unwrap_or_else(|| details.text.clone());
+            warnings.push(PdfRenderWarning {
+                kind: PdfRenderWarningKind::Javascript,
+                message: format!(
+                    "{} (line {}:{})",
+                    text.lines().next().unwrap_or_default(),
+                    details.line_number + 1,
+                    details.column_number + 1
+                ),
+                url: details.url.clone(),
+            });
+        }
+        warnings
+    }
+}
+
+/// Chromium compartido por los renders, con su perfil temporal. Al soltarlo se
+/// mata el proceso (`kill_on_drop`) y se borra el perfil.
+struct SharedBrowser {
+    browser: Browser,
+    handler_task: JoinHandle<()>,
+    _profile: tempfile::TempDir,
+}
+
+impl SharedBrowser {
+    fn is_alive(&self) -> bool {
+        !self.handler_task.is_finished()
+    }
+}
+
+impl Drop for SharedBrowser {
+    fn drop(&mut self) {
+        self.handler_task.abort();
+    }
+}
+
+/// Contexto de navegador de un render (como una ventana de incógnito): cookies,
+/// caché y storage no se comparten con otros renders. Se descarta al soltarlo,
+/// también cuando el render se corta por timeout.
+struct RenderSession {
+    shared: Arc<SharedBrowser>,
+    context: BrowserContextId,
+}
+
+impl RenderSession {
+    async fn open(shared: Arc<SharedBrowser>) -> Result<Self> {
+        let context = shared
+            .browser
+            .create_browser_context(CreateBrowserContextParams::default())
+            .await
+            .context("Chromium no pudo crear un contexto para el render")?;
+        Ok(Self { shared, context })
+    }
+
+    /// Se abre en blanco para poder fijar cabeceras/cookies antes de navegar
+    async fn blank_page(&self) -> Result<Page> {
+        let params = CreateTargetParams::builder()
+            .url("about:blank")
+            .browser_context_id(self.context.clone())
+            .build()
+            .map_err(|e| anyhow!(e))?;
+        self.shared
+            .browser
+            .new_page(params)
+            .await
+            .context("Chromium no pudo abrir una pestaña")
+    }
+}
+
+impl Drop for RenderSession {
+    fn drop(&mut self) {
+        let shared = self.shared.clone();
+        let context = self.context.clone();
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(async move {
+                let _ = shared.browser.dispose_browser_context(context).await;
+            });
+        }
+    }
+}
+
+/// Navega a la página del request y espera a que esté lista. El interceptor
+/// retornado debe vivir mientras se use la página.
+async fn load_page(
+    page: &Page,
+    req: &PdfRequest,
+    files: &RenderFiles,
+) -> Result<RequestInterceptor> {
+    let url = match &req.source_url {
+        Some(url) => url.clone(),
+        None => format!("file://{}", files.html_path.display()),
+    };
+    let headers = match &req.source_options {
+        Some(options) => apply_source_options(page, options, req.source_url.as_deref()).await?,
+        None => Vec::new(),
+    };
+    let render_options = req.render_options.clone().unwrap_or_default();
+    let header_rule = match &req.source_url {
+        Some(url) if !headers.is_empty() => Some((url.as_str(), headers)),
+        _ => None,
+    };
+    let interceptor = RequestInterceptor::start(page, header_rule, file_root(files)?).await?;
+    if render_options.enable_javascript == Some(false) {
+        page.execute(SetScriptExecutionDisabledParams::new(true))
+            .await
+            .context("No se pudo deshabilitar JavaScript en Chromium")?;
+    }
+    page.goto(url.as_str())
+        .await
+        .with_context(|| format!("Chromium no pudo abrir {}", url))?;
+    page.wait_for_navigation()
+        .await
+        .context("Chromium no terminó de cargar la página")?;
+    if let Some(options) = &req.source_options {
+        wait_for_page(page, options).await?;
+    }
+    if let Some(delay) = render_options.javascript_delay_ms {
+        tokio::time::sleep(Duration::from_millis(delay)).await;
+    }
+    Ok(interceptor)
+}
+
+/// Directorio fuera del cual la página no puede leer `file://`: como con
+/// `--allow` en wkhtmltopdf, el HTML (inline o de un bundle) solo ve su
+/// directorio de trabajo. Las páginas remotas ya no pueden abrir `file://` en Chromium.
+fn file_root(files: &RenderFiles) -> Result<PathBuf> {
+    files
+        .work_dir
+        .canonicalize()
+        .with_context(|| format!("No se pudo resolver {:?}", files.work_dir))
+}
+
+/// Fija cookies antes de navegar y retorna las cabeceras (incluida la de
+/// Basic auth) a enviar. Las cabeceras solo van a las peticiones al origen de
+/// `source_url` (como en wkhtmltopdf, sin `source_url` no se envían): el resto
+/// de recursos de la página (CDNs, analítica, ...) nunca reciben las credenciales.
+async fn apply_source_options(
+    page: &Page,
+    options: &PdfSourceOptions,
+    source_url: Option<&str>,
+) -> Result<Vec<(String, String)>> {
+    let mut headers: Vec<(String, String)> = options
+        .headers
+        .iter()
+        .flatten()
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    if let Some(auth) = &options.basic_auth {
+        let token = base64::encode(format!("{}:{}", auth.username, auth.password));
+        headers.push(("Authorization".to_string(), format!("Basic {}", token)));
+    }
+    // Las cookies necesitan una URL http(s) a la que asociarse
+    if let (Some(cookies), Some(url)) = (&options.cookies, source_url) {
+        let params = cookies
+            .iter()
+            .map(|c| {
+                let mut cookie = CookieParam::new(c.name.clone(), c.value.clone());
+                cookie.url = Some(url.to_string());
+                cookie
+            })
+            .collect();
+        page.execute(SetCookiesParams::new(params))
+            .await
+            .context("No se pudieron fijar las cookies")?;
+    }
+    Ok(headers)
+}
+
+/// Intercepta (`Fetch.requestPaused`) las peticiones de una página para
+/// modificarlas o bloquearlas antes de que salgan. Deja de interceptar al soltarlo.
+struct RequestInterceptor {
+    task: JoinHandle<()>,
+}
+
+impl RequestInterceptor {
+    /// Agrega las cabeceras de `headers` a las peticiones a su origen y rechaza
+    /// los `file://` fuera de `file_root`. Chromium solo pausa las peticiones de
+    /// esos patrones y el resto sale sin cambios.
+    async fn start(
+        page: &Page,
+        headers: Option<(&str, Vec<(String, String)>)>,
+        file_root: PathBuf,
+    ) -> Result<Self> {
+        let headers = match headers {
+            Some((url, headers)) => {
+                let origin = reqwest::Url::parse(url)
+                    .with_context(|| format!("source_url inválida: {}", url))?
+                    .origin()
+                    .ascii_serialization();
+                Some((origin, headers))
+            }
+            None => None,
+        };
+        let mut patterns = vec!["file://*".to_string()];
+        if let Some((origin, _)) = &headers {
+            patterns.push(format!("{}/*", origin));
+        }
+
+        let mut paused = page
+            .event_listener::<EventRequestPaused>()
+            .await
+            .context("No se pudieron escuchar las peticiones de Chromium")?;
+        let patterns = patterns
+            .into_iter()
+            .map(|url_pattern| RequestPattern {
+                url_pattern: Some(url_pattern),
+                ..Default::default()
+            })
+            .collect();
+        page.execute(fetch::EnableParams {
+            patterns: Some(patterns),
+            handle_auth_requests: None,
+        })
+        .await
+        .context("No se pudo habilitar Fetch en Chromium")?;
+
+        let page = page.clone();
+        let task = tokio::spawn(async move {
+            while let Some(event) = paused.next().await {
+                // Toda petición pausada se continúa o se rechaza, si no la página
+                // se queda esperando
+                let request_url = reqwest::Url::parse(&event.request.url).ok();
+                let result = match &request_url {
+                    Some(url) if url.scheme() == "file" && !inside_root(url, &file_root) => {
+                        log::warn!("Chromium: acceso bloqueado a {}", event.request.url);
+                        page.execute(FailRequestParams::new(
+                            event.request_id.clone(),
+                            network::ErrorReason::AccessDenied,
+                        ))
+                        .await
+                        .map(|_| ())
+                    }
+                    _ => {
+                        let mut params = ContinueRequestParams::new(event.request_id.clone());
+                        if let (Some(url), Some((origin, headers))) = (&request_url, &headers) {
+                            params.headers =
+                                with_headers(url, origin, &event.request.headers, headers);
+                        }
+                        page.execute(params).await.map(|_| ())
+                    }
+                };
+                if let Err(e) = result {
+                    log::warn!("Chromium no pudo continuar {}: {}", event.request.url, e);
+                }
+            }
+        });
+        Ok(Self { task })
+    }
+}
+
+/// Si el `file://` apunta (resolviendo `..` y enlaces simbólicos) a `root` o a
+/// algo dentro de él. Un archivo que no existe no está permitido.
+fn inside_root(url: &reqwest::Url, root: &std::path::Path) -> bool {
+    url.to_file_path()
+        .ok()
+        .and_then(|path| path.canonicalize().ok())
+        .is_some_and(|path| path.starts_with(root))
+}
+
+impl Drop for RequestInterceptor {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Cabeceras de la petición a `url` con `extra` encima (reemplaza las del mismo
+/// nombre). `None` (sin cambios) si `url` no es del origen `origin`.
+fn with_headers(
+    url: &reqwest::Url,
+    origin: &str,
+    original: &Headers,
+    extra: &[(String, String)],
+) -> Option<Vec<HeaderEntry>> {
+    let url_origin = url.origin();
+    if !url_origin.is_tuple() || url_origin.ascii_serialization() != origin {
+        return None;
+    }
+    let mut entries: Vec<HeaderEntry> = original
+        .inner()
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(name, _)| {
+            !extra
+                .iter()
+                .any(|(extra_name, _)| extra_name.eq_ignore_ascii_case(name))
+        })
+        .filter_map(|(name, value)| Some(HeaderEntry::new(name.clone(), value.as_str()?)))
+        .collect();
+    entries.extend(
+        extra
+            .iter()
+            .map(|(name, value)| HeaderEntry::new(name.clone(), value.clone())),
+    );
+    Some(entries)
+}
+
+/// Alto (mm) del contenido maquetado a `printable_width` mm de ancho, con la
+/// escala del request. Deja la ventana como estaba.
+async fn content_height_mm(
+    page: &Page,
+    req: &PdfRequest,
+    printable_width: f64,
+    media: MediaTypeParams,
+) -> Result<f64> {
+    let scale = req.scale.unwrap_or(1.0);
+    let px_per_mm = CSS_PX_PER_INCH / MM_PER_INCH;
+    // Con ventana de 1 px de alto, scrollHeight es exactamente el alto del contenido
+    let width_px = (printable_width * px_per_mm / scale).round().max(1.0) as i64;
+    page.emulate_media_type(media)
+        .await
+        .context("No se pudo emular el medio de impresión en Chromium")?;
+    page.execute(SetDeviceMetricsOverrideParams::new(width_px, 1, 1.0, false))
+        .await
+        .context("No se pudo fijar el ancho de la ventana en Chromium")?;
+    let height_px: f64 = page
+        .evaluate(
+            "Math.max(document.documentElement.scrollHeight, \
+             document.body ? document.body.scrollHeight : 0)",
+        )
+        .await
+        .context("No se pudo medir el alto del contenido")?
+        .into_value()
+        .context("El alto del contenido no es un número")?;
+    page.execute(ClearDeviceMetricsOverrideParams::default())
+        .await
+        .context("No se pudo restaurar la ventana en Chromium")?;
+    Ok(height_px * scale / px_per_mm)
+}
+
+/// Espera fija para JavaScript y/o hasta que `window.status` tenga el valor pedido.
+async fn wait_for_page(page: &Page, options: &PdfSourceOptions) -> Result<()> {
+    if let Some(delay) = options.javascript_delay_ms {
+        tokio::time::sleep(Duration::from_millis(delay)).await;
+    }
+    if let Some(expected) = &options.window_status {
+        loop {
+            let status: String = page
+                .evaluate("String(window.status)")
+                .await
+                .context("No se pudo leer window.status")?
+                .into_value()
+                .context("window.status no es un string")?;
+            if &status == expected {
+                break;
+            }
+            tokio::time::sleep(WINDOW_STATUS_POLL).await;
+        }
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl PdfRenderer for ChromiumRenderer {
+    fn backend(&self) -> PdfBackend {
+        PdfBackend::Chromium
+    }
+
+    async fn render(&self, req: &PdfRequest, files: &RenderFiles) -> Result<RenderOutput> {
+        timeout(CHROMIUM_TIMEOUT, self.run_chromium(req, files))
+            .await
+            .context("Timeout ejecutando Chromium")?
+    }
+
+    async fn render_image(
+        &self,
+        req: &PdfRequest,
+        files: &RenderFiles,
+        options: &PdfImageOptions,
+    ) -> Result<RenderOutput> {
+        timeout(CHROMIUM_TIMEOUT, self.run_screenshot(req, files, options))
+            .await
+            .context("Timeout ejecutando Chromium")?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn file_url(path: &std::path::Path) -> reqwest::Url {
+        reqwest::Url::parse(&format!("file://{}", path.display())).unwrap()
+    }
+
+    #[test]
+    fn inside_root_resolves_dot_dot_and_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("work");
+        std::fs::create_dir_all(root.join("img")).unwrap();
+        std::fs::write(root.join("img/logo.png"), b"png").unwrap();
+        std::fs::write(dir.path().join("secreto.txt"), b"x").unwrap();
+        std::os::unix::fs::symlink(dir.path().join("secreto.txt"), root.join("enlace.txt"))
+            .unwrap();
+        let root = root.canonicalize().unwrap();
+
+        assert!(inside_root(&file_url(&root.join("img/logo.png")), &root));
+        assert!(inside_root(
+            &file_url(&root.join("img/../img/logo.png")),
+            &root
+        ));
+        assert!(!inside_root(&file_url(&root.join("../secreto.txt")), &root));
+        assert!(!inside_root(&file_url(&root.join("enlace.txt")), &root));
+        // Lo que no existe tampoco pasa
+        assert!(!inside_root(&file_url(&root.join("img/otro.png")), &root));
+    }
+
+    #[test]
+    fn with_headers_only_touches_the_source_origin() {
+        let original = Headers::new(json!({ "Accept": "*/*", "authorization": "viejo" }));
+        let extra = [("Authorization".to_string(), "Basic abc".to_string())];
+        let origin = "https://app.example.com";
+        let entries = |url: &str| {
+            with_headers(
+                &reqwest::Url::parse(url).unwrap(),
+                origin,
+                &original,
+                &extra,
+            )
+            .map(|entries| {
+                entries
+                    .into_iter()
+                    .map(|e| (e.name, e.value))
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        assert_eq!(
+            entries("https://app.example.com/informe?id=1"),
+            Some(vec![
+                ("Accept".to_string(), "*/*".to_string()),
+                ("Authorization".to_string(), "Basic abc".to_string())
+            ])
+        );
+        assert_eq!(entries("https://cdn.example.com/logo.png"), None);
+        assert_eq!(entries("http://app.example.com/informe"), None);
+        assert_eq!(entries("https://app.example.com:8443/informe"), None);
+        assert_eq!(entries("file:///etc/passwd"), None);
+    }
+
+    #[test]
+    fn file_root_is_the_work_dir_with_or_without_a_bundle() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut files = RenderFiles {
+            work_dir: dir.path().to_path_buf(),
+            html_path: dir.path().join("index.html"),
+            pdf_path: dir.path().join("output.pdf"),
+            bundle_dir: None,
+        };
+        let work_dir = dir.path().canonicalize().unwrap();
+        assert_eq!(file_root(&files).unwrap(), work_dir);
+
+        files.bundle_dir = Some(dir.path().join("bundle"));
+        assert_eq!(file_root(&files).unwrap(), work_dir);
+    }
+}
@@ -0,0 +1,101 @@
+//! services/renderer/header_footer.rs
+//! Traduce los encabezados/pies de página del request al formato de cada motor.
+//!
+//! Placeholders soportados (en HTML y en texto):
+//! `[page]`, `[total_pages]`, `[date]`, `[title]`.
+
+use crate::models::pdf_model::PdfTextHeaderFooter;
+
+/// (placeholder, variable de wkhtmltopdf, clase de Chromium)
+const PLACEHOLDERS: [(&str, &str, &str); 4] = [
+    ("[page]", "page", "pageNumber"),
+    ("[total_pages]", "topage", "totalPages"),
+    ("[date]", "date", "date"),
+    ("[title]", "title", "title"),
+];
+
+/// Script que wkhtmltopdf necesita para sustituir variables en `--header-html`:
+/// las recibe como query string de la página del encabezado.
+const WKHTMLTOPDF_SUBST_SCRIPT: &str = r#"<script>
+function substitutePdfVariables() {
+  var vars = {};
+  var query = document.location.search.substring(1).split('&');
+  for (var i = 0; i < query.length; i++) {
+    var pair = query[i].split('=', 2);
+    vars[pair[0]] = decodeURIComponent(pair[1] || '');
+  }
+  ['page', 'topage', 'date', 'title'].forEach(function (name) {
+    var els = document.getElementsByClassName('pdf-' + name);
+    for (var j = 0; j < els.length; j++) {
+      els[j].textContent = vars[name] || '';
+    }
+  });
+}
+</script>"#;
+
+/// Texto para `--header-left` y similares: wkhtmltopdf ya entiende
+/// `[page]`, `[date]` y `[title]`; solo cambia el total de páginas.
+pub fn wkhtmltopdf_text(text: &str) -> String {
+    text.replace("[total_pages]", "[topage]")
+}
+
+/// Documento HTML completo para `--header-html` / `--footer-html`.
+pub fn wkhtmltopdf_html(fragment: &str) -> String {
+    let mut body = fragment.to_string();
+    for (placeholder, var, _) in PLACEHOLDERS {
+        body = body.replace(
+            placeholder,
+            &format!(r#"<span class="pdf-{}"></span>"#, var),
+        );
+    }
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\">{}</head>\
+         <body style=\"margin:0\" onload=\"substitutePdfVariables()\">{}</body></html>",
+        WKHTMLTOPDF_SUBST_SCRIPT, body
+    )
+}
+
+/// Plantilla para `headerTemplate` / `footerTemplate` de Chromium.
+pub fn chromium_template(fragment: &str) -> String {
+    let mut body = fragment.to_string();
+    for (placeholder, _, class) in PLACEHOLDERS {
+        body = body.replace(placeholder, &format!(r#"<span class="{}"></span>"#, class));
+    }
+    // Chromium usa un font-size diminuto por defecto en estas plantillas
+    format!(
+        r#"<div style="font-size:10px; width:100%; margin:0 10mm;">{}</div>"#,
+        body
+    )
+}
+
+/// Plantilla de Chromium para un encabezado/pie: el HTML tiene prioridad sobre el texto.
+pub fn chromium_fragment(html: Option<&str>, text: Option<&PdfTextHeaderFooter>) -> Option<String> {
+    match (html, text) {
+        (Some(html), _) => Some(chromium_template(html)),
+        (None, Some(text)) => Some(chromium_template(&text_to_html(text))),
+        (None, None) => None,
+    }
+}
+
+/// Convierte la variante de texto (izquierda/centro/derecha) en un fragmento HTML.
+pub fn text_to_html(text: &PdfTextHeaderFooter) -> String {
+    let cell = |value: &Option<String>, align: &str| {
+        format!(
+            r#"<div style="flex:1; text-align:{};">{}</div>"#,
+            align,
+            escape_html(value.as_deref().unwrap_or(""))
+        )
+    };
+    format!(
+        r#"<div style="display:flex; width:100%;">{}{}{}</div>"#,
+        cell(&text.left, "left"),
+        cell(&text.center, "center"),
+        cell(&text.right, "right")
+    )
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
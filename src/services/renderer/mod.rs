@@ -0,0 +1,85 @@
+//! services/renderer/mod.rs
+//! Abstracción sobre los motores que convierten HTML a PDF (wkhtmltopdf, Chromium).
+
+pub mod asset_bundle;
+pub mod chromium_renderer;
+pub mod header_footer;
+pub mod pdf_rasterizer;
+pub mod toc;
+pub mod wkhtmltopdf_renderer;
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::config::pdf_config::{BUILTIN_MARGIN_MM, BUILTIN_PAGE_SIZE};
+use crate::models::pdf_model::{
+    PdfBackend, PdfImageOptions, PdfMargins, PdfOrientation, PdfRenderWarning, PdfRequest,
+};
+
+/// Archivos de trabajo de un render. Todos viven dentro de `work_dir`,
+/// un directorio exclusivo del request que se borra al terminar.
+#[derive(Clone)]
+pub struct RenderFiles {
+    pub work_dir: PathBuf,
+    pub html_path: PathBuf,
+    pub pdf_path: PathBuf,
+    /// Directorio con los recursos del bundle ZIP, si el request trae uno.
+    pub bundle_dir: Option<PathBuf>,
+}
+
+/// Resultado de un render: el archivo generado y los avisos del motor
+/// (recursos que no cargaron, errores de JavaScript, ...).
+pub struct RenderOutput {
+    pub bytes: Vec<u8>,
+    pub warnings: Vec<PdfRenderWarning>,
+}
+
+/// Motor de renderizado HTML -> PDF.
+#[async_trait]
+pub trait PdfRenderer: Send + Sync {
+    /// Backend que implementa este renderer.
+    fn backend(&self) -> PdfBackend;
+
+    /// Renderiza `files.html_path` y retorna el PDF resultante.
+    async fn render(&self, req: &PdfRequest, files: &RenderFiles) -> Result<RenderOutput>;
+
+    /// Captura `files.html_path` (o `source_url`) como imagen.
+    async fn render_image(
+        &self,
+        req: &PdfRequest,
+        files: &RenderFiles,
+        options: &PdfImageOptions,
+    ) -> Result<RenderOutput>;
+}
+
+/// Tamaño de página (ancho, alto) en mm, en orientación vertical.
+/// `page_size_preset` tiene prioridad sobre `custom_page_size`. PdfService ya
+/// completó el request con `PdfGlobalConfig`; el de fábrica es solo un resguardo.
+pub fn page_size_mm(req: &PdfRequest) -> (f64, f64) {
+    if let Some(preset) = &req.page_size_preset {
+        preset.dimensions_mm()
+    } else if let Some(custom) = &req.custom_page_size {
+        (custom.width, custom.height)
+    } else {
+        BUILTIN_PAGE_SIZE.dimensions_mm()
+    }
+}
+
+/// Márgenes del request (ya completados con los valores por defecto).
+pub fn margins_mm(req: &PdfRequest) -> PdfMargins {
+    req.margins.clone().unwrap_or(PdfMargins {
+        top: BUILTIN_MARGIN_MM,
+        bottom: BUILTIN_MARGIN_MM,
+        left: BUILTIN_MARGIN_MM,
+        right: BUILTIN_MARGIN_MM,
+    })
+}
+
+/// Profundidad del outline: todos los encabezados, `h1` a `h6`.
+pub const OUTLINE_DEPTH: u8 = 6;
+
+pub fn is_landscape(req: &PdfRequest) -> bool {
+    matches!(req.orientation, Some(PdfOrientation::Landscape))
+}
@@ -0,0 +1,89 @@
+//! services/renderer/pdf_rasterizer.rs
+//! Miniaturas de páginas de un PDF ya generado, con `pdftoppm` (poppler-utils).
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use tokio::{process::Command, time::timeout};
+
+use crate::models::pdf_model::ImageFormat;
+
+/// Tiempo máximo para rasterizar una página
+const PDFTOPPM_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub struct PdfRasterizer {
+    pdftoppm_path: PathBuf,
+}
+
+impl PdfRasterizer {
+    /// Localiza `pdftoppm`: primero `PDFTOPPM_PATH`, luego PATH.
+    pub fn find() -> Option<Self> {
+        let path = match std::env::var("PDFTOPPM_PATH") {
+            Ok(path) if !path.is_empty() => PathBuf::from(path),
+            _ => which::which("pdftoppm").ok()?,
+        };
+        Some(Self {
+            pdftoppm_path: path,
+        })
+    }
+
+    /// Rasteriza la página `page` (1-based) de `pdf_path` a `width` píxeles de
+    /// ancho, con el alto proporcional.
+    pub async fn thumbnail(
+        &self,
+        pdf_path: &Path,
+        page: u32,
+        width: u32,
+        format: ImageFormat,
+        quality: u8,
+    ) -> Result<Vec<u8>> {
+        let mut cmd = Command::new(&self.pdftoppm_path);
+        cmd.arg("-f")
+            .arg(page.to_string())
+            .arg("-l")
+            .arg(page.to_string())
+            .arg("-singlefile")
+            .arg("-scale-to-x")
+            .arg(width.to_string())
+            .arg("-scale-to-y")
+            .arg("-1");
+        match format {
+            ImageFormat::Png => {
+                cmd.arg("-png");
+            }
+            ImageFormat::Jpeg => {
+                cmd.arg("-jpeg")
+                    .arg("-jpegopt")
+                    .arg(format!("quality={}", quality));
+            }
+            ImageFormat::Webp => {
+                return Err(anyhow!("Las miniaturas solo se generan en PNG o JPEG"));
+            }
+        }
+
+        // pdftoppm agrega la extensión al prefijo de salida
+        let prefix = pdf_path.with_file_name("thumbnail");
+        cmd.arg(pdf_path).arg(&prefix);
+        cmd.stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+
+        let output = timeout(PDFTOPPM_TIMEOUT, cmd.output())
+            .await
+            .context("Timeout ejecutando pdftoppm")?
+            .context("No se pudo lanzar pdftoppm")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "pdftoppm falló: {}",
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+
+        let image_path = prefix.with_extension(format.extension());
+        fs::read(&image_path)
+            .with_context(|| format!("Error leyendo la miniatura en {:?}", image_path))
+    }
+}
@@ -0,0 +1,99 @@
+//! services/renderer/toc.rs
+//! Página de índice (tabla de contenidos) para cada motor.
+//!
+//! wkhtmltopdf la genera de forma nativa (objeto `toc`) con una hoja XSL;
+//! con Chromium se renderiza aparte a partir del outline del PDF principal.
+
+use crate::{
+    models::pdf_model::PdfTocOptions,
+    services::{pdf_tools::outline::OutlineEntry, renderer::header_footer::escape_html},
+};
+
+/// Estilos compartidos por ambos motores (el WebKit de wkhtmltopdf no soporta flexbox).
+const TOC_CSS: &str = "body { font-family: sans-serif; font-size: 13px; } \
+h1 { text-align: center; font-size: 20px; } \
+ul { list-style: none; padding-left: 0; margin: 0; } \
+ul ul { padding-left: 1.5em; } \
+li div { border-bottom: 1px dotted #bbb; margin: 6px 0 0 0; } \
+li span { float: right; background: #fff; padding-left: 4px; } \
+a { color: inherit; text-decoration: none; }";
+
+/// Hoja XSL para `toc --xsl-style-sheet`. wkhtmltopdf la aplica al outline
+/// del documento; el primer `item` es el propio documento, por eso se omite.
+pub fn wkhtmltopdf_xsl(options: &PdfTocOptions) -> String {
+    let title = escape_html(options.title());
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<xsl:stylesheet version="2.0" xmlns:xsl="http://www.w3.org/1999/XSL/Transform"
+                xmlns:outline="http://wkhtmltopdf.org/outline"
+                xmlns="http://www.w3.org/1999/xhtml">
+  <xsl:output doctype-public="-//W3C//DTD XHTML 1.0 Strict//EN"
+              doctype-system="http://www.w3.org/TR/xhtml1/DTD/xhtml1-strict.dtd"
+              indent="yes" />
+  <xsl:template match="outline:outline">
+    <html>
+      <head>
+        <title>{title}</title>
+        <meta http-equiv="Content-Type" content="text/html; charset=utf-8" />
+        <style>{css}</style>
+      </head>
+      <body>
+        <h1>{title}</h1>
+        <ul><xsl:apply-templates select="outline:item/outline:item"/></ul>
+      </body>
+    </html>
+  </xsl:template>
+  <xsl:template match="outline:item">
+    <li>
+      <xsl:if test="@title!=''">
+        <div>
+          <a>
+            <xsl:if test="@link"><xsl:attribute name="href"><xsl:value-of select="@link"/></xsl:attribute></xsl:if>
+            <xsl:if test="@backLink"><xsl:attribute name="name"><xsl:value-of select="@backLink"/></xsl:attribute></xsl:if>
+            <xsl:value-of select="@title" />
+          </a>
+          <span><xsl:value-of select="@page" /></span>
+        </div>
+      </xsl:if>
+      <xsl:if test="outline:item and count(ancestor::outline:item) &lt; {depth}">
+        <ul><xsl:apply-templates select="outline:item"/></ul>
+      </xsl:if>
+    </li>
+  </xsl:template>
+</xsl:stylesheet>
+"#,
+        title = title,
+        css = TOC_CSS,
+        depth = options.max_depth(),
+    )
+}
+
+/// HTML del índice para Chromium. `page_offset` es la cantidad de páginas
+/// que ocupa el propio índice, que se suma a la página de cada entrada.
+pub fn chromium_html(
+    options: &PdfTocOptions,
+    entries: &[OutlineEntry],
+    page_offset: u32,
+) -> String {
+    let max_depth = options.max_depth() as usize;
+    let items: String = entries
+        .iter()
+        .filter(|entry| entry.level <= max_depth)
+        .map(|entry| {
+            format!(
+                r#"<li style="padding-left:{}em"><div>{}<span>{}</span></div></li>"#,
+                (entry.level - 1) as f64 * 1.5,
+                escape_html(&entry.title),
+                entry.page + page_offset
+            )
+        })
+        .collect();
+    let title = escape_html(options.title());
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{title}</title>\
+         <style>{css}</style></head><body><h1>{title}</h1><ul>{items}</ul></body></html>",
+        title = title,
+        css = TOC_CSS,
+        items = items,
+    )
+}
@@ -0,0 +1,479 @@
+//! services/renderer/wkhtmltopdf_renderer.rs
+//! Renderer basado en el CLI de wkhtmltopdf (y wkhtmltoimage para imágenes).
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use tokio::{process::Command, time::timeout};
+
+use crate::{
+    models::pdf_model::{
+        ImageFormat, PdfBackend, PdfImageOptions, PdfMediaType, PdfRenderOptions, PdfRenderWarning,
+        PdfRenderWarningKind, PdfRequest, PdfSourceOptions, PdfTextHeaderFooter,
+    },
+    services::renderer::{
+        header_footer, is_landscape, margins_mm, page_size_mm, toc, PdfRenderer, RenderFiles,
+        RenderOutput, OUTLINE_DEPTH,
+    },
+};
+
+/// Tiempo máximo para que wkhtmltopdf genere un PDF
+const WKHTMLTOPDF_TIMEOUT: Duration = Duration::from_secs(300);
+
+pub struct WkhtmltopdfRenderer {
+    wkhtmltopdf_path: PathBuf,
+    /// Viene con wkhtmltopdf, pero algunos paquetes lo separan
+    wkhtmltoimage_path: Option<PathBuf>,
+}
+
+impl WkhtmltopdfRenderer {
+    pub fn new(wkhtmltopdf_path: PathBuf, wkhtmltoimage_path: Option<PathBuf>) -> Self {
+        Self {
+            wkhtmltopdf_path,
+            wkhtmltoimage_path,
+        }
+    }
+
+    async fn run_wkhtmltopdf(&self, req: &PdfRequest, paths: &RenderFiles) -> Result<RenderOutput> {
+        let mut cmd = Command::new(&self.wkhtmltopdf_path);
+
+        // ===== ORIENTACIÓN =====
+        let orientation_str = if is_landscape(req) {
+            "Landscape"
+        } else {
+            "Portrait"
+        };
+        cmd.arg("--orientation").arg(orientation_str);
+
+        // ===== TAMAÑO DE PÁGINA =====
+        // Los presets sin nombre en wkhtmltopdf (rollos térmicos) van por ancho y alto
+        let preset_name = req
+            .page_size_preset
+            .as_ref()
+            .and_then(|preset| preset.wkhtmltopdf_name());
+        if let Some(name) = preset_name {
+            cmd.arg("--page-size").arg(name);
+        } else {
+            let (width, height) = page_size_mm(req);
+            cmd.arg("--page-width").arg(mm_arg(width));
+            cmd.arg("--page-height").arg(mm_arg(height));
+        }
+
+        // ===== MÁRGENES =====
+        let margins = margins_mm(req);
+        cmd.arg("--margin-top").arg(mm_arg(margins.top));
+        cmd.arg("--margin-bottom").arg(mm_arg(margins.bottom));
+        cmd.arg("--margin-left").arg(mm_arg(margins.left));
+        cmd.arg("--margin-right").arg(mm_arg(margins.right));
+
+        // ===== ESCALA (zoom) =====
+        let scale = req.scale.unwrap_or(1.0);
+        if (scale - 1.0).abs() > f64::EPSILON {
+            cmd.arg("--zoom").arg(format!("{}", scale));
+        }
+
+        // ===== ENCABEZADO / PIE DE PÁGINA =====
+        add_header_footer(
+            &mut cmd,
+            "header",
+            req.header_html.as_deref(),
+            req.header_text.as_ref(),
+            paths,
+        )?;
+        add_header_footer(
+            &mut cmd,
+            "footer",
+            req.footer_html.as_deref(),
+            req.footer_text.as_ref(),
+            paths,
+        )?;
+
+        // ===== PÁGINA REMOTA / ESPERAS =====
+        if let Some(options) = &req.source_options {
+            add_source_options(&mut cmd, options);
+        }
+
+        // ===== METADATOS / OUTLINE =====
+        // `--title` también alimenta el placeholder `[title]` de encabezados y pies
+        if let Some(title) = req.metadata.as_ref().and_then(|m| m.title.as_ref()) {
+            cmd.arg("--title").arg(title);
+        }
+        match req.outline {
+            Some(true) => {
+                cmd.arg("--outline");
+            }
+            Some(false) => {
+                cmd.arg("--no-outline");
+            }
+            None => {}
+        }
+        if req.outline == Some(true) || req.toc.is_some() {
+            cmd.arg("--outline-depth").arg(OUTLINE_DEPTH.to_string());
+        }
+
+        // ===== OPCIONES DEL MOTOR =====
+        add_file_access(&mut cmd, req, paths);
+        add_render_options(&mut cmd, req.render_options.as_ref());
+
+        // ===== ÍNDICE =====
+        // Objeto `toc` antes de la página, con nuestra hoja XSL (título y profundidad)
+        if let Some(options) = &req.toc {
+            let xsl_path = paths.work_dir.join("toc.xsl");
+            fs::write(&xsl_path, toc::wkhtmltopdf_xsl(options))
+                .with_context(|| format!("Error escribiendo XSL del índice en {:?}", xsl_path))?;
+            cmd.arg("toc").arg("--xsl-style-sheet").arg(&xsl_path);
+        }
+
+        // Entradas/salidas
+        match &req.source_url {
+            Some(url) => cmd.arg(url),
+            None => cmd.arg(&paths.html_path),
+        };
+        cmd.arg(&paths.pdf_path);
+
+        execute(cmd, "wkhtmltopdf", &paths.pdf_path).await
+    }
+
+    /// Captura con wkhtmltoimage. Las medidas se escalan por la resolución pedida
+    /// y `--zoom` mantiene el layout en el ancho CSS original.
+    async fn run_wkhtmltoimage(
+        &self,
+        req: &PdfRequest,
+        paths: &RenderFiles,
+        options: &PdfImageOptions,
+    ) -> Result<RenderOutput> {
+        let wkhtmltoimage = self.wkhtmltoimage_path.as_ref().ok_or_else(|| {
+            anyhow!("wkhtmltoimage no está instalado; usar el backend chromium para imágenes")
+        })?;
+        let format = match options.format {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Webp => {
+                return Err(anyhow!(
+                    "wkhtmltoimage no genera WebP; usar el backend chromium"
+                ))
+            }
+        };
+
+        let mut cmd = Command::new(wkhtmltoimage);
+        let scale = options.scale();
+        let px = |value: u32| ((f64::from(value) * scale).round() as u32).to_string();
+
+        cmd.arg("--format").arg(format);
+        cmd.arg("--width").arg(px(options.width()));
+        if let Some(height) = options.height {
+            cmd.arg("--height").arg(px(height));
+        }
+        if (scale - 1.0).abs() > f64::EPSILON {
+            cmd.arg("--zoom").arg(scale.to_string());
+        }
+        if options.format == ImageFormat::Jpeg {
+            cmd.arg("--quality").arg(options.quality().to_string());
+        }
+        if let Some(crop) = &options.crop {
+            cmd.arg("--crop-x").arg(px(crop.x));
+            cmd.arg("--crop-y").arg(px(crop.y));
+            cmd.arg("--crop-w").arg(px(crop.width));
+            cmd.arg("--crop-h").arg(px(crop.height));
+        }
+        if let Some(source_options) = &req.source_options {
+            add_source_options(&mut cmd, source_options);
+        }
+        if let Some(render_options) = &req.render_options {
+            add_javascript_options(&mut cmd, render_options);
+        }
+        add_file_access(&mut cmd, req, paths);
+
+        let image_path = paths
+            .work_dir
+            .join(format!("output.{}", options.format.extension()));
+        match &req.source_url {
+            Some(url) => cmd.arg(url),
+            None => cmd.arg(&paths.html_path),
+        };
+        cmd.arg(&image_path);
+
+        execute(cmd, "wkhtmltoimage", &image_path).await
+    }
+}
+
+/// Ejecuta el comando y lee el archivo que generó, con los avisos de su stderr.
+async fn execute(mut cmd: Command, tool: &str, output_path: &Path) -> Result<RenderOutput> {
+    cmd.stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped());
+
+    let output = timeout(WKHTMLTOPDF_TIMEOUT, cmd.output())
+        .await
+        .with_context(|| format!("Timeout ejecutando {}", tool))?
+        .with_context(|| format!("No se pudo lanzar {}", tool))?;
+
+    if !output.status.success() {
+        let stderr_msg = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("{} falló: {}", tool, stderr_msg));
+    }
+
+    let bytes = fs::read(output_path)
+        .with_context(|| format!("Error leyendo la salida de {} en {:?}", tool, output_path))?;
+    Ok(RenderOutput {
+        bytes,
+        warnings: parse_warnings(&String::from_utf8_lossy(&output.stderr)),
+    })
+}
+
+/// Avisos del stderr de wkhtmltopdf/wkhtmltoimage. Solo cuentan las líneas
+/// `Warning:` y `Error:`; la barra de progreso se descarta. Sin repetidos.
+fn parse_warnings(stderr: &str) -> Vec<PdfRenderWarning> {
+    let mut warnings: Vec<PdfRenderWarning> = Vec::new();
+    for line in stderr.split(['\n', '\r']) {
+        let Some(message) = line
+            .trim()
+            .strip_prefix("Warning:")
+            .or_else(|| line.trim().strip_prefix("Error:"))
+            .map(str::trim)
+        else {
+            continue;
+        };
+        if message.is_empty() || warnings.iter().any(|w| w.message == message) {
+            continue;
+        }
+        warnings.push(classify_warning(message));
+    }
+    warnings
+}
+
+/// Categoría y recurso afectado según el texto del aviso
+fn classify_warning(message: &str) -> PdfRenderWarning {
+    let lower = message.to_lowercase();
+    let url_after = |prefix: &str| {
+        message.find(prefix).and_then(|start| {
+            message[start + prefix.len()..]
+                .split_whitespace()
+                .next()
+                .map(|url| url.trim_end_matches(',').to_string())
+        })
+    };
+    let (kind, url) = if lower.contains("blocked access to file") {
+        (
+            PdfRenderWarningKind::BlockedRequest,
+            url_after("Blocked access to file "),
+        )
+    } else if lower.contains("failed to load ") {
+        (
+            PdfRenderWarningKind::MissingResource,
+            url_after("Failed to load "),
+        )
+    } else if lower.contains("failed loading page ") {
+        (
+            PdfRenderWarningKind::MissingResource,
+            url_after("Failed loading page "),
+        )
+    } else if lower.contains("ssl") || lower.contains("network") {
+        (PdfRenderWarningKind::Network, None)
+    } else if lower.contains("font") {
+        (PdfRenderWarningKind::Font, None)
+    } else if ["referenceerror", "typeerror", "syntaxerror", "rangeerror"]
+        .iter()
+        .any(|error| lower.contains(error))
+    {
+        (PdfRenderWarningKind::Javascript, None)
+    } else {
+        (PdfRenderWarningKind::Other, None)
+    };
+    PdfRenderWarning {
+        kind,
+        message: message.to_string(),
+        url,
+    }
+}
+
+/// Longitud para los flags de wkhtmltopdf, sin el ruido de las conversiones de unidad
+/// (`8.5in` -> `215.9mm` y no `215.89999999999998mm`)
+fn mm_arg(value: f64) -> String {
+    format!("{}mm", (value * 1000.0).round() / 1000.0)
+}
+
+/// Ninguna página (inline, de un bundle o remota) puede leer archivos locales
+/// del servidor: solo su propio directorio de trabajo, y con
+/// `local_file_access: false` ni eso
+fn add_file_access(cmd: &mut Command, req: &PdfRequest, paths: &RenderFiles) {
+    cmd.arg("--disable-local-file-access");
+    let allowed = req
+        .render_options
+        .as_ref()
+        .and_then(|options| options.local_file_access)
+        .unwrap_or(true);
+    if allowed {
+        cmd.arg("--allow").arg(&paths.work_dir);
+    }
+}
+
+/// `render_options` como flags de wkhtmltopdf. Sin opciones se usan los
+/// estilos de impresión (`@media print`).
+fn add_render_options(cmd: &mut Command, options: Option<&PdfRenderOptions>) {
+    let default = PdfRenderOptions::default();
+    let options = options.unwrap_or(&default);
+
+    cmd.arg(match options.media_type.unwrap_or_default() {
+        PdfMediaType::Print => "--print-media-type",
+        PdfMediaType::Screen => "--no-print-media-type",
+    });
+    if let Some(background) = options.print_background {
+        cmd.arg(if background {
+            "--background"
+        } else {
+            "--no-background"
+        });
+    }
+    if options.grayscale == Some(true) {
+        cmd.arg("--grayscale");
+    }
+    if let Some(dpi) = options.dpi {
+        cmd.arg("--dpi").arg(dpi.to_string());
+    }
+    if let Some(quality) = options.image_quality {
+        cmd.arg("--image-quality").arg(quality.to_string());
+    }
+    if let Some(shrinking) = options.smart_shrinking {
+        cmd.arg(if shrinking {
+            "--enable-smart-shrinking"
+        } else {
+            "--disable-smart-shrinking"
+        });
+    }
+    if let Some(size) = options.minimum_font_size {
+        cmd.arg("--minimum-font-size").arg(size.to_string());
+    }
+    if let Some(disable) = options.disable_external_links {
+        cmd.arg(if disable {
+            "--disable-external-links"
+        } else {
+            "--enable-external-links"
+        });
+    }
+    add_javascript_options(cmd, options);
+}
+
+/// JavaScript habilitado y espera; las dos herramientas (pdf e image) las entienden
+fn add_javascript_options(cmd: &mut Command, options: &PdfRenderOptions) {
+    match options.enable_javascript {
+        Some(true) => {
+            cmd.arg("--enable-javascript");
+        }
+        Some(false) => {
+            cmd.arg("--disable-javascript");
+        }
+        None => {}
+    }
+    if let Some(delay) = options.javascript_delay_ms {
+        cmd.arg("--javascript-delay").arg(delay.to_string());
+    }
+}
+
+/// Agrega `--header-*` o `--footer-*` según `kind`. El HTML se escribe en un
+/// archivo del directorio de trabajo porque wkhtmltopdf lo carga como página.
+fn add_header_footer(
+    cmd: &mut Command,
+    kind: &str,
+    html: Option<&str>,
+    text: Option<&PdfTextHeaderFooter>,
+    paths: &RenderFiles,
+) -> Result<()> {
+    if let Some(html) = html {
+        let path = paths.work_dir.join(format!("{}.html", kind));
+        fs::write(&path, header_footer::wkhtmltopdf_html(html))
+            .with_context(|| format!("Error escribiendo {} HTML en {:?}", kind, path))?;
+        cmd.arg(format!("--{}-html", kind)).arg(&path);
+    } else if let Some(text) = text {
+        for (position, value) in [
+            ("left", &text.left),
+            ("center", &text.center),
+            ("right", &text.right),
+        ] {
+            if let Some(value) = value {
+                cmd.arg(format!("--{}-{}", kind, position))
+                    .arg(header_footer::wkhtmltopdf_text(value));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Cabeceras, cookies, auth y esperas de la página a renderizar.
+fn add_source_options(cmd: &mut Command, options: &PdfSourceOptions) {
+    if let Some(headers) = &options.headers {
+        for (name, value) in headers {
+            cmd.arg("--custom-header").arg(name).arg(value);
+        }
+    }
+    if let Some(cookies) = &options.cookies {
+        for cookie in cookies {
+            // wkhtmltopdf espera el valor url-encoded
+            cmd.arg("--cookie")
+                .arg(&cookie.name)
+                .arg(urlencoding::encode(&cookie.value).as_ref());
+        }
+    }
+    if let Some(auth) = &options.basic_auth {
+        cmd.arg("--username").arg(&auth.username);
+        cmd.arg("--password").arg(&auth.password);
+    }
+    if let Some(delay) = options.javascript_delay_ms {
+        cmd.arg("--javascript-delay").arg(delay.to_string());
+    }
+    if let Some(status) = &options.window_status {
+        cmd.arg("--window-status").arg(status);
+    }
+}
+
+#[async_trait]
+impl PdfRenderer for WkhtmltopdfRenderer {
+    fn backend(&self) -> PdfBackend {
+        PdfBackend::Wkhtmltopdf
+    }
+
+    async fn render(&self, req: &PdfRequest, files: &RenderFiles) -> Result<RenderOutput> {
+        self.run_wkhtmltopdf(req, files).await
+    }
+
+    async fn render_image(
+        &self,
+        req: &PdfRequest,
+        files: &RenderFiles,
+        options: &PdfImageOptions,
+    ) -> Result<RenderOutput> {
+        self.run_wkhtmltoimage(req, files, options).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_warnings_keeps_warnings_and_errors_once() {
+        let stderr = "Loading pages (1/6)\r[====>    ] 10%\r\
+            Warning: Failed to load http://cdn.example.com/logo.png, with network status code 203\n\
+            Warning: Failed to load http://cdn.example.com/logo.png, with network status code 203\n\
+            Warning: Blocked access to file /etc/passwd\n\
+            Error: ReferenceError: foo is not defined\n\
+            Warning: \n\
+            Done\n";
+        let warnings = parse_warnings(stderr);
+        assert_eq!(warnings.len(), 3);
+
+        assert_eq!(warnings[0].kind, PdfRenderWarningKind::MissingResource);
+        assert_eq!(
+            warnings[0].url.as_deref(),
+            Some("http://cdn.example.com/logo.png")
+        );
+        assert_eq!(warnings[1].kind, PdfRenderWarningKind::BlockedRequest);
+        assert_eq!(warnings[1].url.as_deref(), Some("/etc/passwd"));
+        assert_eq!(warnings[2].kind, PdfRenderWarningKind::Javascript);
+        assert_eq!(warnings[2].message, "ReferenceError: foo is not defined");
+    }
+}
@@ -0,0 +1,474 @@
+//! services/signing_service.rs
+//! Perfiles de firma (PKCS#12 guardados en SQLite), firma PAdES de PDFs
+//! generados, sellos de tiempo RFC 3161 y verificación de firmas.
+//! La contraseña de cada PKCS#12 se guarda cifrada (AES-256-GCM) con la clave
+//! de `SIGNING_PROFILE_KEY`.
+
+use std::{fs, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use openssl::{
+    asn1::{Asn1Time, Asn1TimeRef},
+    pkcs12::Pkcs12,
+    rand::rand_bytes,
+    sha::sha256,
+    symm::{decrypt_aead, encrypt_aead, Cipher},
+    x509::{store::X509StoreBuilder, X509},
+};
+use sqlx::{Pool, Sqlite};
+use uuid::Uuid;
+
+use crate::{
+    models::signing_model::{CreateSigningProfileRequest, SigningProfileRecord, VerifyPdfResponse},
+    services::{
+        error::ServiceError,
+        pdf_tools::signature::{self, ByteRange, Signer},
+    },
+};
+
+/// Tiempo máximo de espera de la TSA
+const TSA_TIMEOUT: Duration = Duration::from_secs(30);
+/// Prefijo de las contraseñas cifradas; sin él son texto plano guardado antes
+/// de `SIGNING_PROFILE_KEY` (`encrypt_stored_passwords` las cifra al arrancar)
+const SEALED_PASSWORD_PREFIX: &str = "aes256gcm:";
+const GCM_NONCE_LEN: usize = 12;
+const GCM_TAG_LEN: usize = 16;
+
+#[derive(Clone)]
+pub struct SigningService {
+    db_pool: Pool<Sqlite>,
+    /// Autoridad de sellado de tiempo (variable de entorno `TSA_URL`)
+    tsa_url: Option<String>,
+    http: reqwest::Client,
+    /// Clave de las contraseñas de los PKCS#12 (SHA-256 de `SIGNING_PROFILE_KEY`)
+    profile_key: Option<[u8; 32]>,
+    /// CA adicionales para verificar firmas (PEM de `SIGNING_TRUSTED_CERTS`)
+    trusted_certs: Vec<X509>,
+}
+
+impl SigningService {
+    pub fn new(db_pool: Pool<Sqlite>) -> Result<Self> {
+        let tsa_url = std::env::var("TSA_URL").ok().filter(|url| !url.is_empty());
+        match &tsa_url {
+            Some(url) => log::info!("TSA para sellos de tiempo: {}", url),
+            None => log::info!("Sin TSA_URL: las firmas con sello de tiempo están deshabilitadas"),
+        }
+        let profile_key = match std::env::var("SIGNING_PROFILE_KEY") {
+            Ok(secret) if !secret.is_empty() => Some(sha256(secret.as_bytes())),
+            _ => {
+                log::warn!(
+                    "Sin SIGNING_PROFILE_KEY: no se pueden crear perfiles de firma ni usar los \
+                     que tienen la contraseña cifrada"
+                );
+                None
+            }
+        };
+        let trusted_certs = match std::env::var("SIGNING_TRUSTED_CERTS") {
+            Ok(path) if !path.is_empty() => {
+                let pem = fs::read(&path)
+                    .with_context(|| format!("No se pudo leer SIGNING_TRUSTED_CERTS {}", path))?;
+                let certs = X509::stack_from_pem(&pem)
+                    .with_context(|| format!("SIGNING_TRUSTED_CERTS inválido: {}", path))?;
+                log::info!("{} CA adicionales para verificar firmas", certs.len());
+                certs
+            }
+            _ => Vec::new(),
+        };
+        Ok(SigningService {
+            db_pool,
+            tsa_url,
+            http: reqwest::Client::new(),
+            profile_key,
+            trusted_certs,
+        })
+    }
+
+    /// Cifra las contraseñas guardadas en texto plano (perfiles creados antes de
+    /// `SIGNING_PROFILE_KEY`). Retorna cuántas cifró.
+    pub async fn encrypt_stored_passwords(&self) -> Result<usize> {
+        let rows = sqlx::query!(r#"SELECT id, password FROM signing_profiles"#)
+            .fetch_all(&self.db_pool)
+            .await
+            .context("Error consultando perfiles de firma")?;
+        let plain: Vec<_> = rows
+            .into_iter()
+            .filter(|row| !row.password.starts_with(SEALED_PASSWORD_PREFIX))
+            .collect();
+        if plain.is_empty() {
+            return Ok(0);
+        }
+        let Some(key) = &self.profile_key else {
+            log::warn!(
+                "{} perfiles de firma tienen la contraseña en texto plano; define \
+                 SIGNING_PROFILE_KEY para cifrarlas",
+                plain.len()
+            );
+            return Ok(0);
+        };
+        for row in &plain {
+            let id = row.id.clone().unwrap_or_default();
+            let sealed = seal_password(key, &id, &row.password)?;
+            sqlx::query!(
+                r#"UPDATE signing_profiles SET password = ?1 WHERE id = ?2"#,
+                sealed,
+                id
+            )
+            .execute(&self.db_pool)
+            .await
+            .context("Fallo al cifrar la contraseña del perfil de firma")?;
+        }
+        log::info!(
+            "Cifradas las contraseñas de {} perfiles de firma",
+            plain.len()
+        );
+        Ok(plain.len())
+    }
+
+    /// Guarda un PKCS#12 como perfil. Falla si la contraseña no lo abre o si
+    /// no trae clave privada y certificado.
+    pub async fn create_profile(
+        &self,
+        req: CreateSigningProfileRequest,
+    ) -> Result<SigningProfileRecord> {
+        let key = self.profile_key.as_ref().ok_or_else(|| {
+            anyhow!("El servidor no tiene SIGNING_PROFILE_KEY para guardar la contraseña cifrada")
+        })?;
+        let signer = parse_pkcs12(&req.pkcs12, &req.password)?;
+        let cert = &signer.cert;
+
+        let existing = sqlx::query!(
+            r#"SELECT id FROM signing_profiles WHERE name = ?1"#,
+            req.name
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+        if existing.is_some() {
+            return Err(anyhow!(
+                "Ya existe un perfil de firma con el nombre '{}'",
+                req.name
+            ));
+        }
+
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now().to_rfc3339();
+        let password = seal_password(key, &id, &req.password)?;
+        let subject = signature::format_name(cert.subject_name());
+        let issuer = signature::format_name(cert.issuer_name());
+        let serial_number = cert.serial_number().to_bn()?.to_hex_str()?.to_string();
+        let not_before = asn1_to_utc(cert.not_before())?.to_rfc3339();
+        let not_after = asn1_to_utc(cert.not_after())?.to_rfc3339();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO signing_profiles
+                (id, name, pkcs12, password, subject, issuer, serial_number, not_before, not_after, created_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+            "#,
+            id,
+            req.name,
+            req.pkcs12,
+            password,
+            subject,
+            issuer,
+            serial_number,
+            not_before,
+            not_after,
+            now
+        )
+        .execute(&self.db_pool)
+        .await
+        .context("Fallo al insertar perfil de firma")?;
+
+        self.get_profile(&id)
+            .await?
+            .ok_or_else(|| anyhow!("Perfil de firma recién creado no encontrado"))
+    }
+
+    /// Busca un perfil por id o por nombre.
+    pub async fn get_profile(&self, id_or_name: &str) -> Result<Option<SigningProfileRecord>> {
+        let row = sqlx::query!(
+            r#"
+            SELECT id, name, subject, issuer, serial_number, not_before, not_after, created_at
+            FROM signing_profiles
+            WHERE id = ?1 OR name = ?1
+            "#,
+            id_or_name
+        )
+        .fetch_optional(&self.db_pool)
+        .await
+        .context("Error consultando perfil de firma")?;
+
+        match row {
+            Some(r) => Ok(Some(SigningProfileRecord {
+                id: r.id.unwrap_or_default(),
+                name: r.name,
+                subject: r.subject,
+                issuer: r.issuer,
+                serial_number: r.serial_number,
+                not_before: r.not_before.parse()?,
+                not_after: r.not_after.parse()?,
+                created_at: r.created_at.parse()?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    /// Lista los perfiles, ordenados por nombre
+    pub async fn list_profiles(&self) -> Result<Vec<SigningProfileRecord>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, name, subject, issuer, serial_number, not_before, not_after, created_at
+            FROM signing_profiles
+            ORDER BY name ASC
+            "#
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        let mut items = Vec::new();
+        for r in rows {
+            items.push(SigningProfileRecord {
+                id: r.id.unwrap_or_default(),
+                name: r.name,
+                subject: r.subject,
+                issuer: r.issuer,
+                serial_number: r.serial_number,
+                not_before: r.not_before.parse()?,
+                not_after: r.not_after.parse()?,
+                created_at: r.created_at.parse()?,
+            });
+        }
+        Ok(items)
+    }
+
+    /// Borra un perfil. Retorna `false` si no existía.
+    pub async fn delete_profile(&self, id_or_name: &str) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"DELETE FROM signing_profiles WHERE id = ?1 OR name = ?1"#,
+            id_or_name
+        )
+        .execute(&self.db_pool)
+        .await
+        .context("Fallo al borrar perfil de firma")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Clave y certificados de un perfil, listos para firmar.
+    pub async fn load_signer(&self, id_or_name: &str) -> Result<Signer> {
+        let row = sqlx::query!(
+            r#"SELECT id, pkcs12, password FROM signing_profiles WHERE id = ?1 OR name = ?1"#,
+            id_or_name
+        )
+        .fetch_optional(&self.db_pool)
+        .await
+        .context("Error consultando perfil de firma")?
+        .ok_or_else(|| ServiceError::not_found("Signing profile", id_or_name))?;
+
+        let password = self.open_password(row.id.as_deref().unwrap_or_default(), &row.password)?;
+        let signer = parse_pkcs12(&row.pkcs12, &password)?;
+        let now = Asn1Time::days_from_now(0)?;
+        if signer.cert.not_after() < now || signer.cert.not_before() > now {
+            return Err(anyhow!(
+                "El certificado del perfil de firma '{}' no está vigente",
+                id_or_name
+            ));
+        }
+        Ok(signer)
+    }
+
+    /// Firma un PDF ya serializado con el hueco reservado por
+    /// `signature::add_signature_field`. Con `timestamp`, agrega el sello de la TSA.
+    pub async fn sign_prepared(
+        &self,
+        mut pdf: Vec<u8>,
+        range: ByteRange,
+        signer: &Signer,
+        timestamp: bool,
+    ) -> Result<Vec<u8>> {
+        let data = signature::signed_bytes(&pdf, &range);
+        let mut cms = signature::sign_cms(&data, signer)?;
+        if timestamp {
+            let response = self
+                .request_timestamp(&signature::timestamp_request(&cms)?)
+                .await?;
+            cms = signature::add_timestamp(&cms, &response)?;
+        }
+        signature::embed_signature(&mut pdf, &range, &cms)?;
+        Ok(pdf)
+    }
+
+    /// Verifica las firmas de un PDF. Se confía en las CA del sistema y en las de
+    /// `SIGNING_TRUSTED_CERTS`; que el certificado sea de un perfil del servidor
+    /// se informa aparte (`server_profile`) y no lo hace de confianza.
+    pub async fn verify_pdf(&self, pdf: Vec<u8>) -> Result<VerifyPdfResponse> {
+        let trusted = self.trusted_certs.clone();
+        let mut signatures = tokio::task::spawn_blocking(move || {
+            let mut store = X509StoreBuilder::new()?;
+            store.set_default_paths()?;
+            for cert in trusted {
+                // Un certificado repetido no es un error
+                let _ = store.add_cert(cert);
+            }
+            signature::verify_signatures(&pdf, &store.build())
+        })
+        .await
+        .context("Falló la verificación de firmas")??;
+
+        let profiles = sqlx::query!(r#"SELECT name, issuer, serial_number FROM signing_profiles"#)
+            .fetch_all(&self.db_pool)
+            .await
+            .context("Error consultando perfiles de firma")?;
+        for result in &mut signatures {
+            result.server_profile = profiles
+                .iter()
+                .find(|profile| {
+                    result.issuer.as_deref() == Some(profile.issuer.as_str())
+                        && result.serial_number.as_deref() == Some(profile.serial_number.as_str())
+                })
+                .map(|profile| profile.name.clone());
+        }
+
+        let signed = !signatures.is_empty();
+        let integrity_valid = signed && signatures.iter().all(|s| s.integrity_valid);
+        let trusted = signed && signatures.iter().all(|s| s.certificate_trusted);
+        Ok(VerifyPdfResponse {
+            signed,
+            valid: integrity_valid && trusted,
+            integrity_valid,
+            trusted,
+            signatures,
+        })
+    }
+
+    /// Contraseña de un perfil: descifrada o, si es de antes del cifrado, tal cual
+    fn open_password(&self, id: &str, stored: &str) -> Result<String> {
+        if !stored.starts_with(SEALED_PASSWORD_PREFIX) {
+            return Ok(stored.to_string());
+        }
+        let key = self.profile_key.as_ref().ok_or_else(|| {
+            anyhow!("El servidor no tiene SIGNING_PROFILE_KEY para descifrar el perfil de firma")
+        })?;
+        open_password(key, id, stored)
+    }
+
+    /// Envía un `TimeStampReq` a la TSA y retorna el `TimeStampResp`.
+    async fn request_timestamp(&self, request: &[u8]) -> Result<Vec<u8>> {
+        let url = self.tsa_url.as_deref().ok_or_else(|| {
+            anyhow!("Se pidió sello de tiempo, pero el servidor no tiene TSA_URL configurada")
+        })?;
+        let response = self
+            .http
+            .post(url)
+            .header("Content-Type", "application/timestamp-query")
+            .header("Accept", "application/timestamp-reply")
+            .timeout(TSA_TIMEOUT)
+            .body(request.to_vec())
+            .send()
+            .await
+            .with_context(|| format!("No se pudo contactar la TSA {}", url))?;
+        if !response.status().is_success() {
+            return Err(anyhow!("La TSA respondió {}", response.status()));
+        }
+        Ok(response.bytes().await?.to_vec())
+    }
+}
+
+/// Cifra la contraseña con AES-256-GCM, atada al id del perfil (AAD) para que
+/// no sirva copiada a otro. Formato: prefijo + base64(nonce | cifrado | tag).
+fn seal_password(key: &[u8; 32], profile_id: &str, password: &str) -> Result<String> {
+    let mut nonce = [0u8; GCM_NONCE_LEN];
+    rand_bytes(&mut nonce)?;
+    let mut tag = [0u8; GCM_TAG_LEN];
+    let ciphertext = encrypt_aead(
+        Cipher::aes_256_gcm(),
+        key,
+        Some(&nonce),
+        profile_id.as_bytes(),
+        password.as_bytes(),
+        &mut tag,
+    )
+    .context("No se pudo cifrar la contraseña del perfil de firma")?;
+    let sealed = [&nonce[..], &ciphertext, &tag].concat();
+    Ok(format!(
+        "{}{}",
+        SEALED_PASSWORD_PREFIX,
+        base64::encode(sealed)
+    ))
+}
+
+fn open_password(key: &[u8; 32], profile_id: &str, stored: &str) -> Result<String> {
+    let invalid = || anyhow!("Contraseña cifrada del perfil de firma inválida");
+    let sealed = stored
+        .strip_prefix(SEALED_PASSWORD_PREFIX)
+        .and_then(|encoded| base64::decode(encoded).ok())
+        .filter(|sealed| sealed.len() >= GCM_NONCE_LEN + GCM_TAG_LEN)
+        .ok_or_else(invalid)?;
+    let (nonce, rest) = sealed.split_at(GCM_NONCE_LEN);
+    let (ciphertext, tag) = rest.split_at(rest.len() - GCM_TAG_LEN);
+    let password = decrypt_aead(
+        Cipher::aes_256_gcm(),
+        key,
+        Some(nonce),
+        profile_id.as_bytes(),
+        ciphertext,
+        tag,
+    )
+    .map_err(|_| {
+        anyhow!(
+            "No se pudo descifrar la contraseña del perfil de firma (¿cambió SIGNING_PROFILE_KEY?)"
+        )
+    })?;
+    String::from_utf8(password).map_err(|_| invalid())
+}
+
+/// Abre un PKCS#12. OpenSSL 3 no abre los cifrados con RC2/3DES antiguos
+/// sin el proveedor "legacy"; en ese caso hay que re-exportarlos con AES.
+fn parse_pkcs12(bytes: &[u8], password: &str) -> Result<Signer> {
+    let parsed = Pkcs12::from_der(bytes)
+        .context("El archivo no es un PKCS#12 válido")?
+        .parse2(password)
+        .context("No se pudo abrir el PKCS#12 (contraseña incorrecta o cifrado no soportado)")?;
+    let key = parsed
+        .pkey
+        .ok_or_else(|| anyhow!("El PKCS#12 no contiene una clave privada"))?;
+    let cert = parsed
+        .cert
+        .ok_or_else(|| anyhow!("El PKCS#12 no contiene un certificado"))?;
+    if !cert.public_key()?.public_eq(&key) {
+        return Err(anyhow!("La clave privada no corresponde al certificado"));
+    }
+    let chain = parsed
+        .ca
+        .map(|stack| stack.into_iter().collect())
+        .unwrap_or_default();
+    Ok(Signer { key, cert, chain })
+}
+
+fn asn1_to_utc(time: &Asn1TimeRef) -> Result<DateTime<Utc>> {
+    let diff = Asn1Time::from_unix(0)?.diff(time)?;
+    let seconds = diff.days as i64 * 86_400 + diff.secs as i64;
+    DateTime::from_timestamp(seconds, 0).ok_or_else(|| anyhow!("Fecha de certificado inválida"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_password_round_trip() {
+        let key = sha256(b"clave");
+        let sealed = seal_password(&key, "perfil-1", "secreto").unwrap();
+        assert!(sealed.starts_with(SEALED_PASSWORD_PREFIX));
+        assert!(!sealed.contains("secreto"));
+        assert_eq!(open_password(&key, "perfil-1", &sealed).unwrap(), "secreto");
+    }
+
+    #[test]
+    fn sealed_password_needs_same_key_and_profile() {
+        let key = sha256(b"clave");
+        let sealed = seal_password(&key, "perfil-1", "secreto").unwrap();
+        assert!(open_password(&sha256(b"otra"), "perfil-1", &sealed).is_err());
+        assert!(open_password(&key, "perfil-2", &sealed).is_err());
+    }
+}
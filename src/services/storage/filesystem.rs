@@ -0,0 +1,74 @@
+//! services/storage/filesystem.rs
+//! Archivos en disco local, bajo `STORAGE_DIR` (default `./files`).
+
+use std::{io::ErrorKind, path::PathBuf};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+
+use crate::services::storage::{validate_key, Storage};
+
+/// Raíz por defecto: la misma carpeta que se usaba antes de tener backends
+const DEFAULT_STORAGE_DIR: &str = "./files";
+
+pub struct FilesystemStorage {
+    root: PathBuf,
+}
+
+impl FilesystemStorage {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    pub fn from_env() -> Self {
+        let root = match std::env::var("STORAGE_DIR") {
+            Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(DEFAULT_STORAGE_DIR),
+        };
+        log::info!("Almacenamiento en disco: {:?}", root);
+        Self::new(root)
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        validate_key(key)?;
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl Storage for FilesystemStorage {
+    fn kind(&self) -> &'static str {
+        "filesystem"
+    }
+
+    async fn put(&self, key: &str, bytes: &[u8], _content_type: &str) -> Result<()> {
+        let path = self.path(key)?;
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir)
+                .await
+                .with_context(|| format!("No se pudo crear directorio {:?}", dir))?;
+        }
+        tokio::fs::write(&path, bytes)
+            .await
+            .with_context(|| format!("No se pudo guardar el archivo en {:?}", path))
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let path = self.path(key)?;
+        match tokio::fs::read(&path).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Error leyendo {:?}", path)),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        let path = self.path(key)?;
+        match tokio::fs::remove_file(&path).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => {
+                Err(e).with_context(|| format!("No se pudo borrar {:?}", path))
+            }
+            _ => Ok(()),
+        }
+    }
+}
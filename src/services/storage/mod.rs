@@ -0,0 +1,77 @@
+//! services/storage/mod.rs
+//! Dónde se guardan los archivos generados (documentos, PDFs de lotes):
+//! disco local o un bucket compatible con S3 (AWS, MinIO, ...), según `STORAGE_BACKEND`.
+
+pub mod filesystem;
+pub mod s3;
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+
+use self::{filesystem::FilesystemStorage, s3::S3Storage};
+
+/// Almacén de archivos por clave (`pdfs/<id>_factura.pdf`, `batches/<op>/0_a.pdf`, ...).
+/// Las claves usan `/` como separador en todos los backends.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Nombre del backend, para logs
+    fn kind(&self) -> &'static str;
+
+    /// Guarda (o reemplaza) el archivo `key`.
+    async fn put(&self, key: &str, bytes: &[u8], content_type: &str) -> Result<()>;
+
+    /// Lee el archivo `key`; `Ok(None)` si no existe.
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Borra el archivo `key`. No falla si ya no existía.
+    async fn delete(&self, key: &str) -> Result<()>;
+}
+
+/// Crea el backend configurado: `STORAGE_BACKEND=filesystem` (default, en `STORAGE_DIR`)
+/// o `STORAGE_BACKEND=s3` (variables `S3_*`).
+pub fn from_env() -> Result<Arc<dyn Storage>> {
+    let backend = std::env::var("STORAGE_BACKEND").unwrap_or_default();
+    let storage: Arc<dyn Storage> = match backend.to_lowercase().as_str() {
+        "" | "filesystem" | "fs" => Arc::new(FilesystemStorage::from_env()),
+        "s3" => Arc::new(S3Storage::from_env()?),
+        other => {
+            return Err(anyhow!(
+                "STORAGE_BACKEND inválido: {} (valores: filesystem, s3)",
+                other
+            ))
+        }
+    };
+    Ok(storage)
+}
+
+/// Rechaza claves vacías, absolutas o con `.`/`..`: ningún backend debe salir de su raíz.
+pub fn validate_key(key: &str) -> Result<()> {
+    let valid = !key.is_empty()
+        && !key.starts_with('/')
+        && !key.contains('\\')
+        && key
+            .split('/')
+            .all(|segment| !segment.is_empty() && segment != "." && segment != "..");
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow!("Clave de almacenamiento inválida: {:?}", key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_cannot_leave_the_root() {
+        for key in ["documento.pdf", "2026/10/abc.pdf", "a/.b/c"] {
+            assert!(validate_key(key).is_ok(), "{:?}", key);
+        }
+        for key in ["", "/etc/passwd", "a/../b", "..", ".", "a//b", "a/", "a\\b"] {
+            assert!(validate_key(key).is_err(), "{:?}", key);
+        }
+    }
+}
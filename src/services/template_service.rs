@@ -0,0 +1,481 @@
+//! services/template_service.rs
+//! Registro de plantillas HTML (Handlebars) guardadas en SQLite.
+//! Cada cambio de contenido crea una versión inmutable en `template_versions`.
+//! Borrar una plantilla la archiva: sus versiones se conservan.
+
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use handlebars::{Handlebars, Template};
+use openssl::sha::sha256;
+use serde_json::json;
+use sqlx::{Pool, Sqlite};
+use uuid::Uuid;
+
+use crate::models::pdf_model::PdfRequest;
+use crate::models::template_model::{
+    CreateTemplateRequest, ListTemplatesResponse, RenderedTemplate, TemplateRecord,
+    TemplateVersionRecord, UpdateTemplateRequest,
+};
+use crate::services::error::ServiceError;
+use crate::services::util::hex;
+
+#[derive(Clone, Debug)]
+pub struct TemplateService {
+    db_pool: Pool<Sqlite>,
+}
+
+impl TemplateService {
+    pub fn new(db_pool: Pool<Sqlite>) -> Self {
+        TemplateService { db_pool }
+    }
+
+    /// Crea una plantilla nueva (versión 1). Falla si el HTML no compila como Handlebars.
+    pub async fn create_template(&self, req: CreateTemplateRequest) -> Result<TemplateRecord> {
+        validate_content(&req.content)?;
+        self.ensure_name_available(&req.name, None).await?;
+
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now().to_rfc3339();
+        let sample_data = req.sample_data.as_ref().map(|v| v.to_string());
+
+        let mut tx = self.db_pool.begin().await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO templates (id, name, description, content, current_version, sample_data, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, 1, ?5, ?6, ?6)
+            "#,
+            id,
+            req.name,
+            req.description,
+            req.content,
+            sample_data,
+            now
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Fallo al insertar template")?;
+        insert_version(&mut tx, &id, 1, &req.content, &now).await?;
+        tx.commit().await?;
+
+        self.get_template(&id)
+            .await?
+            .ok_or_else(|| anyhow!("Template recién creado no encontrado"))
+    }
+
+    /// Busca una plantilla activa por id o por nombre.
+    pub async fn get_template(&self, id_or_name: &str) -> Result<Option<TemplateRecord>> {
+        self.find_template(id_or_name, false).await
+    }
+
+    /// Como `get_template`; con `include_archived` también encuentra las
+    /// archivadas, solo por id.
+    async fn find_template(
+        &self,
+        id_or_name: &str,
+        include_archived: bool,
+    ) -> Result<Option<TemplateRecord>> {
+        let row = sqlx::query!(
+            r#"
+            SELECT id, name, description, content, current_version, sample_data, created_at, updated_at
+            FROM templates
+            WHERE (id = ?1 OR name = ?1)
+              AND (archived_at IS NULL OR (?2 AND id = ?1))
+            "#,
+            id_or_name,
+            include_archived
+        )
+        .fetch_optional(&self.db_pool)
+        .await
+        .context("Error consultando template")?;
+
+        match row {
+            Some(r) => Ok(Some(TemplateRecord {
+                id: r.id.unwrap_or_default(),
+                name: r.name,
+                description: r.description,
+                content: r.content,
+                current_version: r.current_version,
+                sample_data: parse_sample_data(r.sample_data)?,
+                created_at: r.created_at.parse()?,
+                updated_at: r.updated_at.parse()?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    /// Lista las plantillas activas con paginación, ordenadas por nombre
+    pub async fn list_templates(&self, page: u64, page_size: u64) -> Result<ListTemplatesResponse> {
+        let offset = (page.max(1) - 1) * page_size;
+        let page_size_i64 = page_size as i64;
+        let offset_i64 = offset as i64;
+
+        let total_row =
+            sqlx::query!("SELECT COUNT(*) as cnt FROM templates WHERE archived_at IS NULL")
+                .fetch_one(&self.db_pool)
+                .await?;
+        let total = total_row.cnt as u64;
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, name, description, content, current_version, sample_data, created_at, updated_at
+            FROM templates
+            WHERE archived_at IS NULL
+            ORDER BY name ASC
+            LIMIT ?1 OFFSET ?2
+            "#,
+            page_size_i64,
+            offset_i64
+        )
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        let mut items = Vec::new();
+        for r in rows {
+            items.push(TemplateRecord {
+                id: r.id.unwrap_or_default(),
+                name: r.name,
+                description: r.description,
+                content: r.content,
+                current_version: r.current_version,
+                sample_data: parse_sample_data(r.sample_data)?,
+                created_at: r.created_at.parse()?,
+                updated_at: r.updated_at.parse()?,
+            });
+        }
+
+        Ok(ListTemplatesResponse {
+            total,
+            page,
+            page_size,
+            items,
+        })
+    }
+
+    /// Actualiza los campos presentes en el request. `Ok(None)` si no existe.
+    /// Si el contenido cambia se crea una versión nueva y pasa a ser la vigente.
+    pub async fn update_template(
+        &self,
+        id: &str,
+        req: UpdateTemplateRequest,
+    ) -> Result<Option<TemplateRecord>> {
+        let Some(current) = self.get_template(id).await? else {
+            return Ok(None);
+        };
+
+        if let Some(content) = &req.content {
+            validate_content(content)?;
+        }
+        if let Some(name) = &req.name {
+            self.ensure_name_available(name, Some(&current.id)).await?;
+        }
+
+        let name = req.name.unwrap_or(current.name);
+        let description = req.description.or(current.description);
+        let sample_data = req
+            .sample_data
+            .or(current.sample_data)
+            .map(|v| v.to_string());
+        let now = Utc::now().to_rfc3339();
+
+        let mut tx = self.db_pool.begin().await?;
+        let (content, version) = match req.content {
+            Some(content) if content != current.content => {
+                let version = current.current_version + 1;
+                insert_version(&mut tx, &current.id, version, &content, &now).await?;
+                (content, version)
+            }
+            _ => (current.content, current.current_version),
+        };
+
+        sqlx::query!(
+            r#"
+            UPDATE templates
+            SET name = ?1, description = ?2, content = ?3, current_version = ?4,
+                sample_data = ?5, updated_at = ?6
+            WHERE id = ?7
+            "#,
+            name,
+            description,
+            content,
+            version,
+            sample_data,
+            now,
+            current.id
+        )
+        .execute(&mut *tx)
+        .await
+        .context("Fallo al actualizar template")?;
+        tx.commit().await?;
+
+        self.get_template(&current.id).await
+    }
+
+    /// Archiva una plantilla: deja de listarse y de poder usarse sin versión,
+    /// pero sus versiones se conservan y siguen disponibles por id para repetir
+    /// renders ya hechos. El nombre queda libre (la archivada pasa a llamarse
+    /// `{nombre}~{id}`). Retorna `false` si no existía.
+    pub async fn delete_template(&self, id: &str) -> Result<bool> {
+        let Some(current) = self.get_template(id).await? else {
+            return Ok(false);
+        };
+
+        let now = Utc::now().to_rfc3339();
+        let archived_name = format!("{}~{}", current.name, current.id);
+        let result = sqlx::query!(
+            r#"
+            UPDATE templates
+            SET archived_at = ?1, name = ?2, updated_at = ?1
+            WHERE id = ?3 AND archived_at IS NULL
+            "#,
+            now,
+            archived_name,
+            current.id
+        )
+        .execute(&self.db_pool)
+        .await
+        .context("Fallo al archivar template")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Lista las versiones de una plantilla (la más reciente primero), también
+    /// de una archivada si se pide por id. `Ok(None)` si la plantilla no existe.
+    pub async fn list_versions(
+        &self,
+        id_or_name: &str,
+    ) -> Result<Option<Vec<TemplateVersionRecord>>> {
+        let Some(template) = self.find_template(id_or_name, true).await? else {
+            return Ok(None);
+        };
+
+        let rows = sqlx::query!(
+            r#"
+            SELECT version, content, created_at
+            FROM template_versions
+            WHERE template_id = ?1
+            ORDER BY version DESC
+            "#,
+            template.id
+        )
+        .fetch_all(&self.db_pool)
+        .await
+        .context("Error consultando versiones del template")?;
+
+        let mut versions = Vec::new();
+        for r in rows {
+            versions.push(TemplateVersionRecord {
+                template_id: template.id.clone(),
+                template_name: template.name.clone(),
+                version: r.version,
+                content: r.content,
+                created_at: r.created_at.parse()?,
+            });
+        }
+        Ok(Some(versions))
+    }
+
+    /// Obtiene una versión concreta; si `version` es None, la vigente.
+    /// Una versión concreta de una plantilla archivada se obtiene por id.
+    pub async fn get_version(
+        &self,
+        id_or_name: &str,
+        version: Option<i64>,
+    ) -> Result<Option<TemplateVersionRecord>> {
+        let Some(template) = self.find_template(id_or_name, version.is_some()).await? else {
+            return Ok(None);
+        };
+        let version = version.unwrap_or(template.current_version);
+
+        let row = sqlx::query!(
+            r#"
+            SELECT version, content, created_at
+            FROM template_versions
+            WHERE template_id = ?1 AND version = ?2
+            "#,
+            template.id,
+            version
+        )
+        .fetch_optional(&self.db_pool)
+        .await
+        .context("Error consultando versión del template")?;
+
+        match row {
+            Some(r) => Ok(Some(TemplateVersionRecord {
+                template_id: template.id,
+                template_name: template.name,
+                version: r.version,
+                content: r.content,
+                created_at: r.created_at.parse()?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    /// Fija la versión a usar (la vigente si `version` es None) para que una
+    /// operación se renderice siempre con el mismo contenido aunque luego cambie.
+    pub async fn pin_version(
+        &self,
+        id_or_name: &str,
+        version: Option<i64>,
+    ) -> Result<TemplateVersionRecord> {
+        self.get_version(id_or_name, version)
+            .await?
+            .ok_or_else(|| match version {
+                Some(v) => {
+                    ServiceError::not_found("Template version", format!("{} v{}", id_or_name, v))
+                }
+                None => ServiceError::not_found("Template", id_or_name),
+            })
+    }
+
+    /// Vuelve al contenido de `version` creando una versión nueva con ese contenido,
+    /// así el historial nunca se reescribe.
+    pub async fn rollback(&self, id: &str, version: i64) -> Result<Option<TemplateRecord>> {
+        let Some(target) = self.get_version(id, Some(version)).await? else {
+            if self.get_template(id).await?.is_none() {
+                return Ok(None);
+            }
+            return Err(ServiceError::not_found(
+                "Template version",
+                format!("{} v{}", id, version),
+            ));
+        };
+
+        let req = UpdateTemplateRequest {
+            name: None,
+            description: None,
+            content: Some(target.content),
+            sample_data: None,
+        };
+        self.update_template(&target.template_id, req).await
+    }
+
+    /// Renderiza la plantilla (por id o nombre) con los datos JSON dados.
+    /// Si `version` es None se usa la vigente.
+    pub async fn render_template(
+        &self,
+        id_or_name: &str,
+        version: Option<i64>,
+        data: &serde_json::Value,
+    ) -> Result<RenderedTemplate> {
+        let template = self.pin_version(id_or_name, version).await?;
+        Ok(RenderedTemplate {
+            html: render_content(&template.content, data)?,
+            template_id: template.template_id,
+            version: template.version,
+        })
+    }
+
+    /// Resuelve el HTML de un PDF: si hay `template_id` se renderiza la plantilla,
+    /// si no, se usa el HTML recibido tal cual.
+    pub async fn resolve_html(
+        &self,
+        template_id: Option<&str>,
+        version: Option<i64>,
+        data: Option<&serde_json::Value>,
+        html: Option<String>,
+    ) -> Result<Option<String>> {
+        match template_id {
+            Some(id) => {
+                let empty = serde_json::Value::Object(Default::default());
+                let rendered = self
+                    .render_template(id, version, data.unwrap_or(&empty))
+                    .await?;
+                Ok(Some(rendered.html))
+            }
+            None => Ok(html),
+        }
+    }
+
+    /// Si el PdfRequest trae `template_id`, reemplaza su `html` por el render
+    /// de la plantilla, fija en el request la versión usada y la retorna.
+    pub async fn apply_to_request(&self, req: &mut PdfRequest) -> Result<Option<(String, i64)>> {
+        let Some(template_id) = req.template_id.clone() else {
+            return Ok(None);
+        };
+        let empty = serde_json::Value::Object(Default::default());
+        let data = req.data.as_ref().unwrap_or(&empty);
+        let rendered = self
+            .render_template(&template_id, req.template_version, data)
+            .await?;
+        req.html = rendered.html;
+        req.template_id = Some(rendered.template_id.clone());
+        req.template_version = Some(rendered.version);
+        Ok(Some((rendered.template_id, rendered.version)))
+    }
+
+    async fn ensure_name_available(&self, name: &str, current_id: Option<&str>) -> Result<()> {
+        let existing = sqlx::query!(
+            r#"SELECT id FROM templates WHERE name = ?1 AND archived_at IS NULL"#,
+            name
+        )
+        .fetch_optional(&self.db_pool)
+        .await?;
+        match existing {
+            Some(row) if row.id.as_deref() != current_id => {
+                Err(anyhow!("Ya existe una plantilla con el nombre '{}'", name))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+async fn insert_version(
+    tx: &mut sqlx::Transaction<'_, Sqlite>,
+    template_id: &str,
+    version: i64,
+    content: &str,
+    now: &str,
+) -> Result<()> {
+    let id = Uuid::new_v4().to_string();
+    sqlx::query!(
+        r#"
+        INSERT INTO template_versions (id, template_id, version, content, created_at)
+        VALUES (?1, ?2, ?3, ?4, ?5)
+        "#,
+        id,
+        template_id,
+        version,
+        content,
+        now
+    )
+    .execute(&mut **tx)
+    .await
+    .context("Fallo al insertar versión del template")?;
+    Ok(())
+}
+
+/// Lo que se guarda en la metadata de una operación renderizada con plantilla:
+/// la versión fijada y una copia de los datos con su SHA-256, para poder
+/// repetir el render tal cual.
+pub fn render_metadata(
+    template_id: &str,
+    version: i64,
+    data: Option<&serde_json::Value>,
+) -> serde_json::Value {
+    json!({
+        "template_id": template_id,
+        "version": version,
+        "data": data,
+        "data_sha256": data.map(|d| hex(&sha256(d.to_string().as_bytes()))),
+    })
+}
+
+fn parse_sample_data(raw: Option<String>) -> Result<Option<serde_json::Value>> {
+    raw.map(|s| serde_json::from_str(&s))
+        .transpose()
+        .context("sample_data inválido en la base de datos")
+}
+
+/// Verifica que el contenido sea Handlebars válido.
+fn validate_content(content: &str) -> Result<()> {
+    Template::compile(content)
+        .map(|_| ())
+        .map_err(|e| anyhow!("Plantilla Handlebars inválida: {}", e))
+}
+
+fn render_content(content: &str, data: &serde_json::Value) -> Result<String> {
+    let handlebars = Handlebars::new();
+    handlebars
+        .render_template(content, data)
+        .map_err(|e| anyhow!("Error renderizando plantilla: {}", e))
+}
@@ -0,0 +1,130 @@
+//! services/util.rs
+//! Utilidades pequeñas que comparten varios servicios.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::Url;
+
+/// Bytes en hexadecimal en minúsculas (hashes, firmas HMAC, ids aleatorios)
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Resuelve el host de `url` y falla si alguna de sus direcciones no es pública
+/// (`is_public_ip`). Retorna las direcciones, para fijarlas en un cliente.
+pub async fn resolve_public_host(url: &Url) -> Result<Vec<SocketAddr>> {
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| anyhow!("URL sin puerto: {}", url))?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("URL sin host: {}", url))?;
+    // Las IPv6 literales vienen entre corchetes
+    let literal = host.trim_start_matches('[').trim_end_matches(']');
+    let addrs: Vec<SocketAddr> = match literal.parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => tokio::net::lookup_host((host, port))
+            .await
+            .with_context(|| format!("No se pudo resolver {}", host))?
+            .collect(),
+    };
+    if addrs.is_empty() {
+        return Err(anyhow!("{} no resuelve a ninguna dirección", url));
+    }
+    if let Some(addr) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+        return Err(anyhow!(
+            "El destino apunta a una dirección interna ({})",
+            addr.ip()
+        ));
+    }
+    Ok(addrs)
+}
+
+/// Si la IP es alcanzable desde internet: fuera quedan loopback, redes privadas,
+/// link-local (donde viven los metadatos de las nubes, 169.254.169.254), CGNAT,
+/// multicast y rangos reservados
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public_ipv4(mapped),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        // 100.64.0.0/10 (CGNAT) y 198.18.0.0/15 (pruebas de red)
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 198 && (18..20).contains(&b))
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // fc00::/7 (locales únicas) y fe80::/10 (link-local)
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn internal_addresses_are_not_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.10",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00:ec2::254",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{} es interna", ip);
+        }
+        for ip in ["8.8.8.8", "1.1.1.1", "2606:4700:4700::1111"] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{} es pública", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn resolve_public_host_rejects_internal_targets() {
+        for url in [
+            "http://127.0.0.1:8080/",
+            "http://localhost/",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://[::1]/",
+            "https://10.0.0.5/informe",
+        ] {
+            let url = Url::parse(url).unwrap();
+            assert!(resolve_public_host(&url).await.is_err(), "{}", url);
+        }
+        let url = Url::parse("https://8.8.8.8/informe").unwrap();
+        assert_eq!(
+            resolve_public_host(&url).await.unwrap(),
+            ["8.8.8.8:443".parse::<SocketAddr>().unwrap()]
+        );
+    }
+}